@@ -118,10 +118,24 @@ pub fn shape_aabb(shape: &Shape) -> Aabb {
             let extent = Vec3::new(shape.radius, shape.height * 0.5, shape.radius);
             Aabb::new(pos - extent, pos + extent)
         }
-        ShapeType::Cone | ShapeType::Paraboloid | ShapeType::Pyramid => {
+        ShapeType::Cone => {
+            // Base disc around `normal` plus the apex.
+            let axis = Vec3::from(shape.normal).normalize_or(Vec3::Y);
+            let disc = shape.radius * (Vec3::ONE - axis * axis).max(Vec3::ZERO).powf(0.5);
+            Aabb::new(pos - disc, pos + disc)
+                .expand(pos + axis * shape.height)
+                .pad()
+        }
+        ShapeType::Pyramid => {
             let (r, h) = (shape.radius, shape.height);
             Aabb::new(pos - Vec3::new(r, 0.0, r), pos + Vec3::new(r, h, r))
         }
+        ShapeType::Paraboloid => {
+            // x² + z² = radius·y, so the rim at the cap is sqrt(radius·height) wide.
+            let h = shape.height;
+            let rim = (shape.radius * h).max(0.0).sqrt();
+            Aabb::new(pos - Vec3::new(rim, 0.0, rim), pos + Vec3::new(rim, h, rim))
+        }
         ShapeType::Torus => {
             let extent = shape.radius + shape.radius2;
             Aabb::new(
@@ -143,12 +157,7 @@ pub fn shape_aabb(shape: &Shape) -> Aabb {
             Aabb::new(pos - r, pos + r)
         }
         ShapeType::Ellipsoid => {
-            // radius = x-radius, radius2 = z-radius, height = y-radius
-            let extent = Vec3::new(
-                shape.radius,
-                shape.height.max(shape.radius),
-                shape.radius2.max(shape.radius),
-            );
+            let extent = shape.ellipsoid_radii();
             Aabb::new(pos - extent, pos + extent)
         }
         ShapeType::Hyperboloid => {
@@ -164,6 +173,17 @@ pub fn shape_aabb(shape: &Shape) -> Aabb {
             let extent = Vec3::splat(shape.radius);
             Aabb::new(pos - extent, pos + extent)
         }
+        // Delta light: never intersected, a small box for gizmos/picking proxies.
+        ShapeType::SpotLight => {
+            let extent = Vec3::splat(0.1);
+            Aabb::new(pos - extent, pos + extent)
+        }
+        // The real bounds come from the model (`InstancedModel::instance_aabb`);
+        // a box of the instance's scale stands in where it isn't at hand.
+        ShapeType::Instance => {
+            let extent = Vec3::splat(shape.radius.abs());
+            Aabb::new(pos - extent, pos + extent).pad()
+        }
         // Infinite primitives — given a large finite box so the BVH builder
         // can still include them; the shader handles their true intersection.
         ShapeType::Plane | ShapeType::Skybox => {
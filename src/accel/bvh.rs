@@ -2,9 +2,45 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
 
 use super::aabb::Aabb;
-use crate::constants::{BVH_LEAF_MAX_PRIMS, BVH_NUM_BINS};
+use crate::constants::{DEFAULT_BVH_LEAF_PRIMS, DEFAULT_BVH_NUM_BINS};
+
+/// Build-quality knobs of the SAH builder. Smaller leaves and more bins give
+/// tighter trees at the cost of build time and node count; which traces
+/// fastest depends on the scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BvhParams {
+    /// Nodes with at most this many primitives become leaves.
+    pub max_leaf_prims: usize,
+    /// Centroid bins per axis when searching for the best split.
+    pub num_bins: usize,
+    /// Skip the build: one leaf holds every primitive and traversal tests
+    /// them all. For checking BVH bugs against, and tiny scenes.
+    pub brute_force: bool,
+}
+
+impl Default for BvhParams {
+    fn default() -> Self {
+        Self {
+            max_leaf_prims: DEFAULT_BVH_LEAF_PRIMS,
+            num_bins: DEFAULT_BVH_NUM_BINS,
+            brute_force: false,
+        }
+    }
+}
+
+/// Shape of a built tree, for comparing `BvhParams` on a scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BvhStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    /// Levels on the longest root-to-leaf path; a lone root is depth 1.
+    pub max_depth: usize,
+    /// Most primitives in any one leaf.
+    pub max_leaf_prims: usize,
+}
 
 /// GPU BVH node. The left child is always stored at `index + 1` in the flat
 /// array; `left_or_prim` holds the right child index for inner nodes and the
@@ -27,24 +63,48 @@ struct BvhBuildNode {
 }
 
 /// Flat BVH built over a primitive AABB list, ready for GPU upload.
+#[derive(Clone)]
 pub struct Bvh {
     pub nodes: Vec<GpuBvhNode>,
     pub prim_indices: Vec<u32>,
 }
 
 impl Bvh {
-    /// Build a BVH over `aabbs` using the Surface Area Heuristic.
-    pub fn build(aabbs: &[Aabb]) -> Self {
+    /// Build a BVH over `aabbs` using the binned Surface Area Heuristic.
+    pub fn build(aabbs: &[Aabb], params: BvhParams) -> Self {
         if aabbs.is_empty() {
             return Self {
                 nodes: vec![GpuBvhNode::zeroed()],
                 prim_indices: vec![],
             };
         }
+        if params.brute_force {
+            let bounds = aabbs.iter().fold(Aabb::EMPTY, |acc, &b| acc.union(b));
+            return Self {
+                nodes: vec![GpuBvhNode {
+                    aabb_min: bounds.min.into(),
+                    left_or_prim: 0,
+                    aabb_max: bounds.max.into(),
+                    prim_count: aabbs.len() as u32,
+                }],
+                prim_indices: (0..aabbs.len() as u32).collect(),
+            };
+        }
 
         let mut indices: Vec<usize> = (0..aabbs.len()).collect();
         let mut build_nodes: Vec<BvhBuildNode> = Vec::with_capacity(2 * aabbs.len());
-        Self::build_recursive(aabbs, &mut indices, 0, aabbs.len(), &mut build_nodes);
+        Self::build_recursive(
+            aabbs,
+            &mut indices,
+            0,
+            aabbs.len(),
+            BvhParams {
+                max_leaf_prims: params.max_leaf_prims.max(1),
+                num_bins: params.num_bins.max(2),
+                ..params
+            },
+            &mut build_nodes,
+        );
 
         let mut nodes = Vec::with_capacity(build_nodes.len());
         Self::flatten(&build_nodes, 0, &mut nodes);
@@ -56,11 +116,80 @@ impl Bvh {
         }
     }
 
+    /// Node count, depth and leaf occupancy of the tree.
+    pub fn stats(&self) -> BvhStats {
+        let mut stats = BvhStats::default();
+        if self.prim_indices.is_empty() {
+            return stats;
+        }
+        let mut stack = vec![(0usize, 1usize)];
+        while let Some((idx, depth)) = stack.pop() {
+            let node = self.nodes[idx];
+            stats.nodes += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            if node.prim_count > 0 {
+                stats.leaves += 1;
+                stats.max_leaf_prims = stats.max_leaf_prims.max(node.prim_count as usize);
+            } else {
+                stack.push((idx + 1, depth + 1));
+                stack.push((node.left_or_prim as usize, depth + 1));
+            }
+        }
+        stats
+    }
+
+    /// Recompute every node's bounds from the primitives' current `aabbs`,
+    /// keeping the tree's shape. Exact, and much cheaper than a rebuild when
+    /// the primitives moved together.
+    pub fn refit(&mut self, aabbs: &[Aabb]) {
+        // Children always come after their parent, so a reverse sweep sees
+        // them first.
+        for idx in (0..self.nodes.len()).rev() {
+            let node = self.nodes[idx];
+            let bounds = if node.prim_count > 0 {
+                let first = node.left_or_prim as usize;
+                self.prim_indices[first..first + node.prim_count as usize]
+                    .iter()
+                    .fold(Aabb::EMPTY, |acc, &i| acc.union(aabbs[i as usize]))
+            } else {
+                let left = &self.nodes[idx + 1];
+                let right = &self.nodes[node.left_or_prim as usize];
+                Aabb::new(Vec3::from(left.aabb_min), Vec3::from(left.aabb_max)).union(Aabb::new(
+                    Vec3::from(right.aabb_min),
+                    Vec3::from(right.aabb_max),
+                ))
+            };
+            self.nodes[idx].aabb_min = bounds.min.into();
+            self.nodes[idx].aabb_max = bounds.max.into();
+        }
+    }
+
+    /// Append `other` after this BVH's nodes as a separate tree and return
+    /// its root node index. Its primitive indices are offset by `prim_base`.
+    pub fn append(&mut self, other: &Self, prim_base: u32) -> u32 {
+        let node_base = self.nodes.len() as u32;
+        let first_prim = self.prim_indices.len() as u32;
+        self.nodes.extend(other.nodes.iter().map(|node| GpuBvhNode {
+            // Inner nodes point at their right child, leaves at their prims.
+            left_or_prim: node.left_or_prim
+                + if node.prim_count > 0 {
+                    first_prim
+                } else {
+                    node_base
+                },
+            ..*node
+        }));
+        self.prim_indices
+            .extend(other.prim_indices.iter().map(|&i| i + prim_base));
+        node_base
+    }
+
     fn build_recursive(
         aabbs: &[Aabb],
         indices: &mut [usize],
         start: usize,
         end: usize,
+        params: BvhParams,
         nodes: &mut Vec<BvhBuildNode>,
     ) -> usize {
         let count = end - start;
@@ -69,7 +198,7 @@ impl Bvh {
             .fold(Aabb::EMPTY, |acc, &i| acc.union(aabbs[i]));
         let node_idx = nodes.len();
 
-        if count <= BVH_LEAF_MAX_PRIMS {
+        if count <= params.max_leaf_prims {
             nodes.push(BvhBuildNode {
                 bounds,
                 left: None,
@@ -80,7 +209,8 @@ impl Bvh {
             return node_idx;
         }
 
-        let (best_axis, best_split) = Self::find_best_split(aabbs, &indices[start..end], &bounds);
+        let (best_axis, best_split) =
+            Self::find_best_split(aabbs, &indices[start..end], &bounds, params.num_bins);
         let raw_mid =
             Self::partition(aabbs, &mut indices[start..end], best_axis, best_split) + start;
 
@@ -100,15 +230,20 @@ impl Bvh {
             prim_count: 0,
         });
 
-        let left = Self::build_recursive(aabbs, indices, start, mid, nodes);
-        let right = Self::build_recursive(aabbs, indices, mid, end, nodes);
+        let left = Self::build_recursive(aabbs, indices, start, mid, params, nodes);
+        let right = Self::build_recursive(aabbs, indices, mid, end, params, nodes);
         nodes[node_idx].left = Some(left);
         nodes[node_idx].right = Some(right);
 
         node_idx
     }
 
-    fn find_best_split(aabbs: &[Aabb], indices: &[usize], parent_bounds: &Aabb) -> (usize, f32) {
+    fn find_best_split(
+        aabbs: &[Aabb],
+        indices: &[usize],
+        parent_bounds: &Aabb,
+        num_bins: usize,
+    ) -> (usize, f32) {
         let mut best_cost = f32::INFINITY;
         let mut best_axis = 0;
         let mut best_split = 0.0f32;
@@ -122,24 +257,24 @@ impl Bvh {
             }
 
             // Phase 1: Bin all primitives by centroid — O(N) per axis.
-            let mut bin_bounds = [Aabb::EMPTY; BVH_NUM_BINS];
-            let mut bin_counts = [0u32; BVH_NUM_BINS];
-            let inv_extent = BVH_NUM_BINS as f32 / extent;
+            let mut bin_bounds = vec![Aabb::EMPTY; num_bins];
+            let mut bin_counts = vec![0u32; num_bins];
+            let inv_extent = num_bins as f32 / extent;
             for &idx in indices {
                 let centroid = aabbs[idx].center()[axis];
                 let b = ((centroid - min) * inv_extent) as usize;
-                let b = b.min(BVH_NUM_BINS - 1);
+                let b = b.min(num_bins - 1);
                 bin_bounds[b] = bin_bounds[b].union(aabbs[idx]);
                 bin_counts[b] += 1;
             }
 
             // Phase 2: Right-to-left sweep — accumulate right-side bounds/counts.
-            let mut right_area = [0.0f32; BVH_NUM_BINS - 1];
-            let mut right_count = [0u32; BVH_NUM_BINS - 1];
+            let mut right_area = vec![0.0f32; num_bins - 1];
+            let mut right_count = vec![0u32; num_bins - 1];
             {
                 let mut rb = Aabb::EMPTY;
                 let mut rc = 0u32;
-                for i in (1..BVH_NUM_BINS).rev() {
+                for i in (1..num_bins).rev() {
                     rb = rb.union(bin_bounds[i]);
                     rc += bin_counts[i];
                     right_area[i - 1] = rb.surface_area();
@@ -150,8 +285,8 @@ impl Bvh {
             // Phase 3: Left-to-right sweep — evaluate SAH cost at each split.
             let mut lb = Aabb::EMPTY;
             let mut lc = 0u32;
-            let bin_width = extent / BVH_NUM_BINS as f32;
-            for i in 0..(BVH_NUM_BINS - 1) {
+            let bin_width = extent / num_bins as f32;
+            for i in 0..(num_bins - 1) {
                 lb = lb.union(bin_bounds[i]);
                 lc += bin_counts[i];
                 if lc == 0 || right_count[i] == 0 {
@@ -212,3 +347,50 @@ impl Bvh {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_shape_the_tree() {
+        // A 16x16 grid of unit boxes.
+        let aabbs: Vec<_> = (0..256)
+            .map(|i| {
+                let p = Vec3::new((i % 16) as f32 * 2.0, (i / 16) as f32 * 2.0, 0.0);
+                Aabb::new(p, p + Vec3::ONE)
+            })
+            .collect();
+
+        let stats = Bvh::build(&aabbs, BvhParams::default()).stats();
+        assert!(stats.max_leaf_prims <= DEFAULT_BVH_LEAF_PRIMS);
+        assert_eq!(stats.nodes, 2 * stats.leaves - 1);
+
+        let single = BvhParams {
+            max_leaf_prims: 1,
+            ..Default::default()
+        };
+        let stats = Bvh::build(&aabbs, single).stats();
+        assert_eq!((stats.leaves, stats.max_leaf_prims), (256, 1));
+        // Binned SAH on a regular grid splits evenly: 256 leaves, 9 levels.
+        assert_eq!(stats.max_depth, 9);
+
+        // Two bins still split; out-of-range values are clamped.
+        let coarse = BvhParams {
+            max_leaf_prims: 0,
+            num_bins: 0,
+            ..Default::default()
+        };
+        assert_eq!(Bvh::build(&aabbs, coarse).stats().leaves, 256);
+        assert_eq!(Bvh::build(&[], single).stats(), BvhStats::default());
+
+        // Brute force: every primitive in the root leaf.
+        let brute = BvhParams {
+            brute_force: true,
+            ..Default::default()
+        };
+        let bvh = Bvh::build(&aabbs, brute);
+        assert_eq!((bvh.stats().nodes, bvh.stats().max_leaf_prims), (1, 256));
+        assert_eq!(bvh.prim_indices, (0..256).collect::<Vec<_>>());
+    }
+}
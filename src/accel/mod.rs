@@ -3,3 +3,4 @@
 
 pub mod aabb;
 pub mod bvh;
+pub mod tlas;
@@ -0,0 +1,285 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::{BTreeMap, HashMap};
+
+use super::aabb::Aabb;
+use super::bvh::{Bvh, BvhParams, GpuBvhNode};
+use crate::constants::BLAS_REFIT_TOLERANCE;
+
+/// Bottom-level BVH of one triangle group and the triangle bounds it was
+/// last fitted to.
+struct CachedBlas {
+    bvh: Bvh,
+    aabbs: Vec<Aabb>,
+}
+
+/// A named triangle group (one imported mesh) for `BlasCache::build`.
+#[derive(Default)]
+pub struct TriangleGroup {
+    /// Global shape indices of the group's triangles.
+    pub members: Vec<u32>,
+    /// Current bounds of each member, in the same order.
+    pub aabbs: Vec<Aabb>,
+}
+
+/// Bottom-level BVHs of named triangle groups, kept across scene rebuilds.
+///
+/// A group that was only moved or uniformly scaled keeps its tree and just
+/// refits the bounds; the full SAH build runs when a group first appears or
+/// its triangles change otherwise, or when the build parameters change.
+#[derive(Default)]
+pub struct BlasCache {
+    groups: HashMap<String, CachedBlas>,
+    params: BvhParams,
+}
+
+impl BlasCache {
+    /// Two-level BVH over `singles` (global shape index and bounds) and the
+    /// triangle `groups`, flattened into one tree: the top level is built
+    /// over objects each rebuild, and every group's cached BLAS is grafted in
+    /// place of its top-level leaf. Groups no longer present are evicted.
+    pub fn build(
+        &mut self,
+        singles: &[(u32, Aabb)],
+        groups: &BTreeMap<String, TriangleGroup>,
+        params: BvhParams,
+    ) -> Bvh {
+        if params != self.params {
+            self.groups.clear();
+            self.params = params;
+        }
+        self.groups.retain(|name, _| groups.contains_key(name));
+        if groups.is_empty() || params.brute_force {
+            // A single-level tree; brute force lists grouped triangles too.
+            let prims: Vec<(u32, Aabb)> = singles
+                .iter()
+                .copied()
+                .chain(groups.values().flat_map(|group| {
+                    group
+                        .members
+                        .iter()
+                        .copied()
+                        .zip(group.aabbs.iter().copied())
+                }))
+                .collect();
+            let aabbs: Vec<_> = prims.iter().map(|&(_, aabb)| aabb).collect();
+            let mut bvh = Bvh::build(&aabbs, params);
+            for idx in &mut bvh.prim_indices {
+                *idx = prims[*idx as usize].0;
+            }
+            return bvh;
+        }
+
+        for (name, group) in groups {
+            self.update(name, &group.aabbs);
+        }
+
+        // Objects are singles first, then groups in name order.
+        let blases: Vec<_> = groups
+            .iter()
+            .map(|(name, group)| (&self.groups[name].bvh, group.members.as_slice()))
+            .collect();
+        let object_aabbs: Vec<_> = singles
+            .iter()
+            .map(|&(_, aabb)| aabb)
+            .chain(blases.iter().map(|(bvh, _)| node_bounds(&bvh.nodes[0])))
+            .collect();
+        // One object per leaf, so every group leaf can be swapped for its BLAS.
+        let tlas = Bvh::build(
+            &object_aabbs,
+            BvhParams {
+                max_leaf_prims: 1,
+                ..params
+            },
+        );
+
+        let mut out = Bvh {
+            nodes: Vec::with_capacity(
+                tlas.nodes.len() + blases.iter().map(|(b, _)| b.nodes.len()).sum::<usize>(),
+            ),
+            prim_indices: Vec::new(),
+        };
+        graft(&tlas, 0, singles, &blases, &mut out);
+        out
+    }
+
+    /// Refit the group's cached BLAS to `aabbs` if they are the cached ones
+    /// moved or uniformly scaled, otherwise build it anew.
+    fn update(&mut self, name: &str, aabbs: &[Aabb]) {
+        if let Some(cached) = self.groups.get_mut(name)
+            && same_shape(&cached.aabbs, aabbs)
+        {
+            cached.bvh.refit(aabbs);
+            cached.aabbs = aabbs.to_vec();
+            return;
+        }
+        log::debug!("Building BLAS for '{name}' ({} triangles)", aabbs.len());
+        self.groups.insert(
+            name.to_string(),
+            CachedBlas {
+                bvh: Bvh::build(aabbs, self.params),
+                aabbs: aabbs.to_vec(),
+            },
+        );
+    }
+}
+
+fn node_bounds(node: &GpuBvhNode) -> Aabb {
+    Aabb::new(node.aabb_min.into(), node.aabb_max.into())
+}
+
+/// Whether `current` is `cached` up to translation and uniform scale, i.e.
+/// the tree built for `cached` is still a good one.
+fn same_shape(cached: &[Aabb], current: &[Aabb]) -> bool {
+    if cached.len() != current.len() || cached.is_empty() {
+        return false;
+    }
+    let normalize = |aabbs: &[Aabb]| {
+        let bounds = aabbs.iter().fold(Aabb::EMPTY, |acc, &b| acc.union(b));
+        let extent = (bounds.max - bounds.min)
+            .max_element()
+            .max(f32::MIN_POSITIVE);
+        (bounds.min, extent.recip())
+    };
+    let (cached_min, cached_scale) = normalize(cached);
+    let (current_min, current_scale) = normalize(current);
+    cached.iter().zip(current).all(|(a, b)| {
+        let a_center = (a.center() - cached_min) * cached_scale;
+        let b_center = (b.center() - current_min) * current_scale;
+        a_center.abs_diff_eq(b_center, BLAS_REFIT_TOLERANCE)
+    })
+}
+
+/// Copy the subtree of `tlas` at `idx` into `out`, replacing each group leaf
+/// with that group's BLAS. `out` keeps the flat layout: left child at
+/// `index + 1`, right child index in `left_or_prim`.
+fn graft(
+    tlas: &Bvh,
+    idx: usize,
+    singles: &[(u32, Aabb)],
+    blases: &[(&Bvh, &[u32])],
+    out: &mut Bvh,
+) {
+    let node = tlas.nodes[idx];
+    if node.prim_count == 0 {
+        let out_idx = out.nodes.len();
+        out.nodes.push(node);
+        graft(tlas, idx + 1, singles, blases, out);
+        out.nodes[out_idx].left_or_prim = out.nodes.len() as u32;
+        graft(tlas, node.left_or_prim as usize, singles, blases, out);
+        return;
+    }
+
+    let object = tlas.prim_indices[node.left_or_prim as usize] as usize;
+    if let Some(&(shape_idx, _)) = singles.get(object) {
+        out.nodes.push(GpuBvhNode {
+            left_or_prim: out.prim_indices.len() as u32,
+            prim_count: 1,
+            ..node
+        });
+        out.prim_indices.push(shape_idx);
+        return;
+    }
+
+    let (blas, members) = blases[object - singles.len()];
+    let node_base = out.nodes.len() as u32;
+    let first_prim = out.prim_indices.len() as u32;
+    out.nodes.extend(blas.nodes.iter().map(|n| GpuBvhNode {
+        left_or_prim: n.left_or_prim
+            + if n.prim_count > 0 {
+                first_prim
+            } else {
+                node_base
+            },
+        ..*n
+    }));
+    out.prim_indices
+        .extend(blas.prim_indices.iter().map(|&i| members[i as usize]));
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    /// Every primitive appears once, and every node contains what is below it.
+    fn assert_valid(bvh: &Bvh, aabb_of: impl Fn(u32) -> Aabb, prim_count: usize) {
+        let mut seen = Vec::new();
+        let mut stack = vec![(0usize, Aabb::new(Vec3::splat(-1e9), Vec3::splat(1e9)))];
+        while let Some((idx, parent)) = stack.pop() {
+            let node = bvh.nodes[idx];
+            let bounds = node_bounds(&node);
+            assert!(bounds.min.cmpge(parent.min).all() && bounds.max.cmple(parent.max).all());
+            if node.prim_count > 0 {
+                let first = node.left_or_prim as usize;
+                for &prim in &bvh.prim_indices[first..first + node.prim_count as usize] {
+                    let aabb = aabb_of(prim);
+                    assert!(aabb.min.cmpge(bounds.min).all() && aabb.max.cmple(bounds.max).all());
+                    seen.push(prim);
+                }
+            } else {
+                stack.push((idx + 1, bounds));
+                stack.push((node.left_or_prim as usize, bounds));
+            }
+        }
+        seen.sort_unstable();
+        assert_eq!(seen, (0..prim_count as u32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_grafted_groups_follow_moves() {
+        let unit = |p: Vec3| Aabb::new(p, p + Vec3::ONE);
+        // Shapes 0-1 stand alone; shapes 2-41 form a row-shaped mesh.
+        let mut all: Vec<Aabb> = vec![unit(Vec3::new(-5.0, 0.0, 0.0)), unit(Vec3::Y * 5.0)];
+        all.extend((0..40).map(|i| unit(Vec3::new(i as f32 * 0.5, 0.0, 3.0))));
+        let singles = [(0, all[0]), (1, all[1])];
+        let group = |all: &[Aabb]| {
+            BTreeMap::from([(
+                "mesh".to_string(),
+                TriangleGroup {
+                    members: (2..42).collect(),
+                    aabbs: all[2..].to_vec(),
+                },
+            )])
+        };
+
+        let mut cache = BlasCache::default();
+        let bvh = cache.build(&singles, &group(&all), BvhParams::default());
+        assert_valid(&bvh, |i| all[i as usize], all.len());
+
+        // Move and grow the mesh: the tree is refit, not rebuilt.
+        let before = all.clone();
+        for aabb in &mut all[2..] {
+            *aabb = Aabb::new(
+                aabb.min * 2.0 + Vec3::Z * 10.0,
+                aabb.max * 2.0 + Vec3::Z * 10.0,
+            );
+        }
+        assert!(same_shape(&before[2..], &all[2..]));
+        let bvh = cache.build(&singles, &group(&all), BvhParams::default());
+        assert_valid(&bvh, |i| all[i as usize], all.len());
+
+        // Bending it is a different mesh as far as the tree is concerned.
+        let mut bent = all.clone();
+        bent[41] = unit(Vec3::new(0.0, 30.0, 0.0));
+        assert!(!same_shape(&all[2..], &bent[2..]));
+        let bvh = cache.build(&singles, &group(&bent), BvhParams::default());
+        assert_valid(&bvh, |i| bent[i as usize], bent.len());
+
+        // Brute force puts the mesh's triangles next to the singles.
+        let brute = BvhParams {
+            brute_force: true,
+            ..Default::default()
+        };
+        let bvh = cache.build(&singles, &group(&all), brute);
+        assert_eq!(bvh.nodes.len(), 1);
+        assert_valid(&bvh, |i| all[i as usize], all.len());
+
+        // Gone from the scene, gone from the cache.
+        let bvh = cache.build(&singles, &BTreeMap::new(), BvhParams::default());
+        assert_valid(&bvh, |i| all[i as usize], 2);
+        assert!(cache.groups.is_empty());
+    }
+}
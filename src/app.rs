@@ -5,41 +5,68 @@
 //   app/state.rs       — AppState struct, initialization, GPU state management
 //   app/rendering.rs   — Render dispatch, frame loop, UI actions
 //   app/scene_ops.rs   — Scene loading/saving, shape management, OBJ import
+//   app/camera_path.rs — Camera path keyframes and playback
+//   app/solo.rs        — Solo mode: render only the selected shape
+//   app/light_view.rs  — Looking through the selected light and back
+//   app/device_loss.rs — Recreating the GPU device and resources after a loss
+//   app/shader_console.rs — --dev shader console: live user BSDF edits
 //   app/interaction.rs — Object picking, dragging, window/mouse event handling
+//   app/headless.rs    — Windowless renderer, --benchmark and --batch modes
 
+#[path = "app/camera_path.rs"]
+mod camera_path;
+#[path = "app/device_loss.rs"]
+mod device_loss;
+#[path = "app/headless.rs"]
+mod headless;
 #[path = "app/interaction.rs"]
 mod interaction;
+#[path = "app/light_view.rs"]
+mod light_view;
 #[path = "app/rendering.rs"]
 mod rendering;
 #[path = "app/scene_ops.rs"]
 mod scene_ops;
+#[path = "app/shader_console.rs"]
+mod shader_console;
+#[path = "app/solo.rs"]
+mod solo;
 #[path = "app/state.rs"]
 mod state;
 
+use std::time::Instant;
+
 use anyhow::Result;
 use winit::application::ApplicationHandler;
 use winit::event::{DeviceEvent, DeviceId, WindowEvent};
-use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::window::WindowId;
 
+pub use headless::{run_batch, run_benchmark};
 pub use state::AppState;
 
-pub fn run(scene_path: Option<String>) -> Result<()> {
+use crate::cli::CliArgs;
+use crate::settings::Settings;
+
+pub fn run(cli: CliArgs) -> Result<()> {
+    let settings = Settings::load();
     let event_loop = EventLoop::new()?;
-    let mut app = App::new(scene_path);
+    let mut app = App::new(cli, settings);
     event_loop.run_app(&mut app)?;
     Ok(())
 }
 
 struct App {
-    scene_path: Option<String>,
+    cli: CliArgs,
+    settings: Settings,
     state: Option<AppState>,
 }
 
 impl App {
-    fn new(scene_path: Option<String>) -> Self {
+    fn new(cli: CliArgs, settings: Settings) -> Self {
         Self {
-            scene_path,
+            cli,
+            settings,
             state: None,
         }
     }
@@ -51,7 +78,7 @@ impl ApplicationHandler for App {
             return;
         }
 
-        match AppState::new(event_loop, &self.scene_path) {
+        match AppState::new(event_loop, &self.cli, &self.settings) {
             Ok(state) => self.state = Some(state),
             Err(e) => {
                 log::error!("Failed to initialize: {e:#}");
@@ -79,9 +106,34 @@ impl ApplicationHandler for App {
         }
     }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        if let Some(state) = &self.state {
-            state.window.request_redraw();
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(state) = &self.state
+            && let Err(e) = state.current_settings().save()
+        {
+            log::warn!("Failed to save settings: {e:#}");
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        // While hidden, stop requesting redraws so the loop sleeps until the
+        // next window event instead of spinning the GPU.
+        if !state.is_visible() {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            return;
+        }
+        // With an FPS cap, sleep until the next frame is due. Input events still
+        // wake the loop in between, so they are handled without extra latency.
+        match state.next_frame_deadline() {
+            Some(deadline) if Instant::now() < deadline => {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(deadline));
+            }
+            _ => {
+                event_loop.set_control_flow(ControlFlow::Wait);
+                state.window.request_redraw();
+            }
         }
     }
 }
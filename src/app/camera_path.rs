@@ -0,0 +1,101 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::time::Instant;
+
+use crate::camera::path;
+use crate::constants::CAMERA_KEYFRAME_SPACING_SECS;
+use crate::scene::scene::CameraKeyframe;
+
+use super::state::AppState;
+
+impl AppState {
+    /// Append the current view as a keyframe after the last one.
+    pub fn add_keyframe(&mut self) {
+        let time = self
+            .camera_path
+            .last()
+            .map_or(0.0, |key| key.time + CAMERA_KEYFRAME_SPACING_SECS);
+        self.camera_path.push(CameraKeyframe {
+            time,
+            camera: self.camera.to_config(),
+        });
+        self.sync_keyframe_times();
+    }
+
+    pub fn remove_keyframe(&mut self, idx: usize) {
+        if idx < self.camera_path.len() {
+            self.camera_path.remove(idx);
+            self.sync_keyframe_times();
+        }
+    }
+
+    /// Move the camera to keyframe `idx`.
+    pub fn goto_keyframe(&mut self, idx: usize) {
+        let Some(key) = self.camera_path.get(idx) else {
+            return;
+        };
+        if let Some(pose) = path::pose_at(std::slice::from_ref(key), key.time) {
+            pose.apply(&mut self.camera);
+            self.accumulator.reset();
+        }
+    }
+
+    /// Take keyframe times edited in the UI and restore time order.
+    pub fn apply_keyframe_times(&mut self) {
+        for (key, &time) in self
+            .camera_path
+            .iter_mut()
+            .zip(&self.ui_state.keyframe_times)
+        {
+            key.time = time.max(0.0);
+        }
+        self.camera_path.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.sync_keyframe_times();
+    }
+
+    /// Mirror keyframe times into the UI after the path changed.
+    pub fn sync_keyframe_times(&mut self) {
+        self.ui_state.keyframe_times = self.camera_path.iter().map(|key| key.time).collect();
+    }
+
+    /// Start playback from the first keyframe, or stop it if running.
+    pub fn toggle_path_playback(&mut self) {
+        self.path_playback_start = match self.path_playback_start {
+            None if self.camera_path.len() >= 2 => Some(Instant::now()),
+            _ => None,
+        };
+        self.ui_state.path_playing = self.path_playback_start.is_some();
+    }
+
+    /// Move the camera along the path while playback runs. The whole path
+    /// takes `path_duration` seconds, or its own length when that is 0.
+    /// Returns whether the camera moved.
+    pub fn advance_path_playback(&mut self) -> bool {
+        let Some(start) = self.path_playback_start else {
+            return false;
+        };
+        let Some((first, last)) = path::time_range(&self.camera_path) else {
+            self.toggle_path_playback();
+            return false;
+        };
+        let span = last - first;
+        let duration = if self.ui_state.path_duration > 0.0 {
+            self.ui_state.path_duration
+        } else {
+            span
+        };
+        let progress = if duration > 0.0 {
+            start.elapsed().as_secs_f32() / duration
+        } else {
+            1.0
+        };
+        if let Some(pose) = path::pose_at(&self.camera_path, first + span * progress.min(1.0)) {
+            pose.apply(&mut self.camera);
+        }
+        if progress >= 1.0 {
+            self.toggle_path_playback();
+        }
+        true
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::time::Instant;
+
+use anyhow::Result;
+
+use crate::constants::{BLUE_NOISE_SIZE, DEVICE_RECOVERY_RETRY_SECS};
+use crate::gpu::buffers;
+use crate::gpu::context::GpuContext;
+use crate::render::blue_noise::blue_noise;
+use crate::render::frame::BlitParams;
+use crate::render::pixel_probe::PixelProbe;
+
+use super::state::AppState;
+
+impl AppState {
+    /// Replace a lost GPU device (driver reset or update, GPU out of memory)
+    /// and rebuild every GPU resource from the scene held on the CPU, so no
+    /// edits are lost. Called every frame while the device is lost; failed
+    /// attempts are retried every few seconds, and the first failure saves
+    /// the scene next to its file in case the GPU never comes back.
+    pub fn recover_lost_device(&mut self) {
+        if self
+            .device_recovery_failed_at
+            .is_some_and(|at| at.elapsed().as_secs_f32() < DEVICE_RECOVERY_RETRY_SECS)
+        {
+            return;
+        }
+        log::warn!("Recreating the lost GPU device");
+        match self.recreate_gpu() {
+            Ok(()) => {
+                log::info!("GPU device recreated");
+                self.device_recovery_failed_at = None;
+                self.window.set_title("PathTracer");
+                let mut message = String::from(
+                    "The GPU device was lost and has been recreated. \
+                     The scene is intact; rendering restarted from the first sample.",
+                );
+                if let Some(path) = self.recovery_scene_path.take() {
+                    message += &format!("\nA copy of the scene was saved to {}.", path.display());
+                }
+                self.ui_state.error_message = Some(message);
+            }
+            Err(e) => {
+                log::error!("Failed to recreate the GPU device: {e:#}");
+                if self.device_recovery_failed_at.is_none() {
+                    self.save_recovery_scene();
+                }
+                self.device_recovery_failed_at = Some(Instant::now());
+                // The window can't draw without a device; its title still shows.
+                self.window.set_title(&match &self.recovery_scene_path {
+                    Some(path) => format!(
+                        "PathTracer — GPU lost, retrying (scene saved to {})",
+                        path.display()
+                    ),
+                    None => "PathTracer — GPU lost, retrying".to_string(),
+                });
+            }
+        }
+    }
+
+    fn save_recovery_scene(&mut self) {
+        let name = self.scene_name.as_deref().unwrap_or("untitled");
+        let path = self.scene.dir.join(format!("{name}.recovered.yaml"));
+        match crate::scene::exporter::save_scene(&self.scene_snapshot(), &path) {
+            Ok(()) => {
+                log::info!("Saved the scene to {}", path.display());
+                self.recovery_scene_path = Some(path);
+            }
+            Err(e) => log::error!("Failed to save the scene: {e:#}"),
+        }
+    }
+
+    /// New device and surface, then pipelines, buffers and bind groups
+    /// recreated the way `AppState::new` and the rebuild paths make them.
+    fn recreate_gpu(&mut self) -> Result<()> {
+        // Everything that can fail comes before the lost context is replaced,
+        // so a failed attempt leaves the app as it was for the next retry.
+        let composer = self.shader_composer()?;
+        let trace_source = composer.compose("path_trace")?;
+        let blit_source = composer.compose("blit")?;
+        let post_source = composer.compose("post_process")?;
+        let gpu = GpuContext::unconfigured(self.window.clone(), self.gpu.present_mode)?;
+        let device = &gpu.device;
+
+        let compute_bg_layout_0 = Self::create_compute_bg0_layout(device);
+        let compute_bg_layout_1 = Self::create_compute_bg1_layout(device);
+        let blit_bg_layout = Self::create_blit_bg_layout(device);
+        let post_bg_layout = Self::create_post_bg_layout(device);
+        let compute_pipeline = crate::gpu::pipeline::create_compute_pipeline(
+            device,
+            &trace_source,
+            &[&compute_bg_layout_0, &compute_bg_layout_1],
+            "path trace",
+        )?;
+        let blit_pipeline = crate::gpu::pipeline::create_blit_pipeline(
+            device,
+            &blit_source,
+            gpu.surface_format(),
+            &blit_bg_layout,
+        )?;
+        let post_process_pipeline = crate::gpu::pipeline::create_compute_pipeline(
+            device,
+            &post_source,
+            &[&post_bg_layout],
+            "post process",
+        )?;
+        let texture_buffers = Self::create_texture_buffers(device, &self.texture_atlas)?;
+
+        // Dropping the lost context releases the window's old swapchain, which
+        // must happen before the new surface is configured.
+        self.gpu = gpu;
+        self.gpu.configure_surface();
+        self.compute_bg_layout_0 = compute_bg_layout_0;
+        self.compute_bg_layout_1 = compute_bg_layout_1;
+        self.blit_bg_layout = blit_bg_layout;
+        self.post_bg_layout = post_bg_layout;
+        self.compute_pipeline = compute_pipeline;
+        self.blit_pipeline = blit_pipeline;
+        self.post_process_pipeline = post_process_pipeline;
+        (
+            self.tex_pixels_buffer,
+            self.tex_infos_buffer,
+            self.tex_float_pixels_buffer,
+        ) = texture_buffers;
+
+        let device = &self.gpu.device;
+        let (width, height) = self.render_size();
+        self.camera_buffer = buffers::create_uniform_buffer(
+            device,
+            &self.camera.to_gpu(width, height, 0, 0),
+            "camera",
+        );
+        // Filled in by `recreate_size_dependent_resources`.
+        self.post_params_buffer = buffers::create_uniform_buffer(
+            device,
+            &Self::build_post_params(width, height, &[], 0, 0, None),
+            "post_params",
+        );
+        self.blit_sampler = Self::create_blit_sampler(device);
+        self.blit_params_buffer =
+            buffers::create_uniform_buffer(device, &BlitParams::default(), "blit_params");
+        self.blue_noise_buffer = buffers::create_storage_buffer(
+            device,
+            &blue_noise(BLUE_NOISE_SIZE),
+            "blue_noise",
+            true,
+        );
+        // A fresh egui context uploads its font atlas to the new renderer.
+        self.egui_renderer =
+            egui_wgpu::Renderer::new(device, self.gpu.surface_format(), None, 1, false);
+        self.egui_ctx = egui::Context::default();
+        self.egui_state = egui_winit::State::new(
+            self.egui_ctx.clone(),
+            self.egui_ctx.viewport_id(),
+            &self.window,
+            Some(self.window.scale_factor() as f32),
+            None,
+            None,
+        );
+
+        self.recreate_size_dependent_resources();
+        self.rebuild_scene_buffers();
+        self.auto_exposure.reset();
+        self.pixel_probe = PixelProbe::default();
+        self.accumulator.reset();
+        Ok(())
+    }
+}
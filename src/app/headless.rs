@@ -0,0 +1,417 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+
+use crate::accel::bvh::{BvhParams, BvhStats};
+use crate::accel::tlas::BlasCache;
+use crate::camera::camera::Camera;
+use crate::camera::path::{self, CameraPose};
+use crate::cli::CliArgs;
+use crate::constants::*;
+use crate::gpu::buffers;
+use crate::io::batch::{BatchJob, BatchManifest};
+use crate::io::screenshot::ScreenshotMetadata;
+use crate::model::library::ModelLibrary;
+use crate::render::blue_noise::blue_noise;
+use crate::render::frame::{
+    ColorSpace, RenderRegion, ShaderFeatures, WorkgroupSize, define_shared_constants,
+};
+use crate::scene::lights;
+use crate::scene::scene::Scene;
+use crate::shaders::composer::ShaderComposer;
+
+use super::state::{AppState, scene_stem};
+
+/// Path tracer without a window, surface or egui: renders a scene at a fixed
+/// resolution into its own output texture.
+///
+/// Sample `n` always uses frame index `n`, so the same scene and sample count
+/// produce the same image.
+pub struct HeadlessRenderer {
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub width: u32,
+    pub height: u32,
+    pub sample_count: u32,
+    pub bvh_stats: BvhStats,
+    pub color_space: ColorSpace,
+    brute_force: bool,
+    camera: Camera,
+    workgroup_size: WorkgroupSize,
+    compute_pipeline: wgpu::ComputePipeline,
+    camera_buffer: wgpu::Buffer,
+    accumulation_buffer: wgpu::Buffer,
+    output_texture: wgpu::Texture,
+    compute_bind_group_0: wgpu::BindGroup,
+    compute_bind_group_1: wgpu::BindGroup,
+    // Referenced by the bind groups; kept alive for the renderer's lifetime.
+    _buffers: Vec<wgpu::Buffer>,
+}
+
+impl HeadlessRenderer {
+    pub fn new(
+        scene: &Scene,
+        width: u32,
+        height: u32,
+        workgroup: Option<WorkgroupSize>,
+        bvh_params: BvhParams,
+    ) -> Result<Self> {
+        let (adapter, device, queue) = crate::gpu::context::create_headless_device()?;
+        let workgroup_size = WorkgroupSize::resolve(workgroup, &device.limits());
+
+        let camera = Camera::from_config(&scene.camera);
+        let (shapes, _) = AppState::load_scene_shapes(scene);
+        let mut models = ModelLibrary::default();
+        models.set_bvh_params(bvh_params);
+        models.sync(&shapes, &scene.dir, scene.up_axis);
+        let (texture_atlas, tex_path_cache) =
+            AppState::build_texture_atlas(shapes.iter().chain(models.triangles()), &scene.dir);
+        let light_groups = lights::collect_light_groups(&shapes, &[]);
+        let (bvh, infinite_indices, blas_roots) = AppState::build_bvh(
+            &shapes,
+            &models,
+            &mut BlasCache::default(),
+            bvh_params,
+            None,
+        );
+        let (gpu_shapes, gpu_materials, light_indices, delta_lights) = AppState::build_gpu_data(
+            &shapes,
+            &models,
+            &blas_roots,
+            &tex_path_cache,
+            &light_groups,
+            None,
+        );
+
+        let mut composer = ShaderComposer::from_directory(&ShaderComposer::shader_dir())?;
+        workgroup_size.define_constants(&mut composer);
+        define_shared_constants(&mut composer);
+        ShaderFeatures::default().define(&mut composer);
+        let trace_source = composer.compose("path_trace")?;
+
+        let camera_buffer =
+            buffers::create_uniform_buffer(&device, &camera.to_gpu(width, height, 0, 0), "camera");
+        let accumulation_buffer = buffers::create_empty_storage_buffer(
+            &device,
+            (width * height) as u64 * ACCUM_BYTES_PER_PIXEL,
+            "accumulation",
+        );
+        // Only the windowed blit reads object ids; the bind group keeps them alive.
+        let object_id_buffer = buffers::create_empty_storage_buffer(
+            &device,
+            (width * height) as u64 * OBJECT_ID_BYTES_PER_PIXEL,
+            "object ids",
+        );
+        let (output_texture, output_view) =
+            buffers::create_output_texture(&device, width, height, "output");
+
+        let (
+            shape_buffer,
+            material_buffer,
+            bvh_node_buffer,
+            bvh_prim_buffer,
+            light_index_buffer,
+            infinite_index_buffer,
+            light_buffer,
+        ) = AppState::create_geometry_buffers(
+            &device,
+            &gpu_shapes,
+            &gpu_materials,
+            &bvh,
+            &light_indices,
+            &infinite_indices,
+            &delta_lights,
+        )?;
+        let (tex_pixels_buffer, tex_infos_buffer, tex_float_pixels_buffer) =
+            AppState::create_texture_buffers(&device, &texture_atlas)?;
+        let blue_noise_buffer = buffers::create_storage_buffer(
+            &device,
+            &blue_noise(BLUE_NOISE_SIZE),
+            "blue_noise",
+            true,
+        );
+
+        let compute_bg_layout_0 = AppState::create_compute_bg0_layout(&device);
+        let compute_bg_layout_1 = AppState::create_compute_bg1_layout(&device);
+        let compute_pipeline = crate::gpu::pipeline::create_compute_pipeline(
+            &device,
+            &trace_source,
+            &[&compute_bg_layout_0, &compute_bg_layout_1],
+            "path trace",
+        )?;
+        let compute_bind_group_0 = AppState::create_compute_bg0(
+            &device,
+            &compute_bg_layout_0,
+            &camera_buffer,
+            &accumulation_buffer,
+            &object_id_buffer,
+            &output_view,
+        );
+        let compute_bind_group_1 = AppState::create_compute_bg1(
+            &device,
+            &compute_bg_layout_1,
+            &shape_buffer,
+            &material_buffer,
+            &bvh_node_buffer,
+            &bvh_prim_buffer,
+            &light_index_buffer,
+            &tex_pixels_buffer,
+            &tex_infos_buffer,
+            &infinite_index_buffer,
+            &light_buffer,
+            &blue_noise_buffer,
+            &tex_float_pixels_buffer,
+        );
+
+        Ok(Self {
+            adapter,
+            device,
+            queue,
+            width,
+            height,
+            sample_count: 0,
+            bvh_stats: bvh.stats(),
+            color_space: ColorSpace::Srgb,
+            brute_force: bvh_params.brute_force,
+            camera,
+            workgroup_size,
+            compute_pipeline,
+            camera_buffer,
+            accumulation_buffer,
+            output_texture,
+            compute_bind_group_0,
+            compute_bind_group_1,
+            _buffers: vec![
+                shape_buffer,
+                material_buffer,
+                bvh_node_buffer,
+                bvh_prim_buffer,
+                light_index_buffer,
+                infinite_index_buffer,
+                light_buffer,
+                tex_pixels_buffer,
+                tex_infos_buffer,
+                tex_float_pixels_buffer,
+                blue_noise_buffer,
+            ],
+        })
+    }
+
+    /// Accumulate `samples` more samples per pixel and wait for the GPU to finish.
+    pub fn render_samples(&mut self, samples: u32) {
+        for _ in 0..samples {
+            let frame_index = self.sample_count;
+            self.sample_count += 1;
+            let mut gpu_camera =
+                self.camera
+                    .to_gpu(self.width, self.height, frame_index, self.sample_count);
+            gpu_camera.color_space = self.color_space.as_u32();
+            gpu_camera.brute_force = u32::from(self.brute_force);
+            // Camera writes are staged until the next submit, so each sample
+            // needs its own submission.
+            buffers::update_uniform_buffer(&self.queue, &self.camera_buffer, &gpu_camera);
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("headless encoder"),
+                });
+            crate::render::frame::dispatch_path_trace(
+                &mut encoder,
+                &self.compute_pipeline,
+                &[&self.compute_bind_group_0, &self.compute_bind_group_1],
+                RenderRegion::full(self.width, self.height),
+                self.workgroup_size,
+            );
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+
+    /// Move the camera to `pose` and start accumulating from scratch.
+    pub fn set_pose(&mut self, pose: &CameraPose) {
+        pose.apply(&mut self.camera);
+        self.sample_count = 0;
+    }
+
+    /// Screenshot metadata describing the current render.
+    pub fn metadata(&self, scene: Option<String>) -> ScreenshotMetadata {
+        let mut metadata = ScreenshotMetadata::new(scene, self.sample_count, &self.camera);
+        metadata.color_space = self.color_space;
+        metadata
+    }
+
+    /// Tone-mapped RGBA8 pixels of the current accumulation.
+    pub fn read_pixels(&self) -> Result<Vec<u8>> {
+        crate::io::screenshot::check_accumulation(
+            &self.device,
+            &self.queue,
+            &self.accumulation_buffer,
+            self.width,
+            self.height,
+        );
+        buffers::read_texture_rgba8(
+            &self.device,
+            &self.queue,
+            &self.output_texture,
+            self.width,
+            self.height,
+        )
+    }
+}
+
+/// `--benchmark` result, printed as a single JSON object on stdout.
+#[derive(Debug, Serialize)]
+struct BenchmarkReport {
+    scene: String,
+    adapter: String,
+    width: u32,
+    height: u32,
+    workgroup: String,
+    bvh_leaf_size: usize,
+    bvh_bins: usize,
+    bvh_brute_force: bool,
+    bvh_nodes: usize,
+    bvh_max_depth: usize,
+    samples: u32,
+    total_seconds: f64,
+    samples_per_second: f64,
+}
+
+/// Render `scene_path` headlessly at a fixed resolution and sample count and
+/// print timing as JSON. Setup time (scene load, BVH build, shader compile)
+/// is excluded.
+pub fn run_benchmark(scene_path: &str, cli: &CliArgs) -> Result<()> {
+    let scene = crate::scene::loader::load_scene(Path::new(scene_path))?;
+    let mut renderer = HeadlessRenderer::new(
+        &scene,
+        BENCHMARK_WIDTH,
+        BENCHMARK_HEIGHT,
+        cli.workgroup,
+        cli.bvh_params(),
+    )?;
+
+    // One untimed sample absorbs pipeline warm-up.
+    renderer.render_samples(1);
+    let start = Instant::now();
+    renderer.render_samples(BENCHMARK_SAMPLES);
+    let total_seconds = start.elapsed().as_secs_f64();
+
+    let report = BenchmarkReport {
+        scene: scene_path.to_string(),
+        adapter: renderer.adapter.get_info().name,
+        width: renderer.width,
+        height: renderer.height,
+        workgroup: renderer.workgroup_size.to_string(),
+        bvh_leaf_size: cli.bvh_params().max_leaf_prims,
+        bvh_bins: cli.bvh_params().num_bins,
+        bvh_brute_force: cli.brute_force,
+        bvh_nodes: renderer.bvh_stats.nodes,
+        bvh_max_depth: renderer.bvh_stats.max_depth,
+        samples: BENCHMARK_SAMPLES,
+        total_seconds,
+        samples_per_second: BENCHMARK_SAMPLES as f64 / total_seconds,
+    };
+    println!("{}", serde_json::to_string(&report)?);
+    Ok(())
+}
+
+/// Render every job in the `--batch` manifest in order. A failing job is
+/// logged and skipped; the run fails at the end if any job did.
+pub fn run_batch(manifest_path: &str, cli: &CliArgs) -> Result<()> {
+    let manifest = BatchManifest::load(Path::new(manifest_path))?;
+    let total = manifest.jobs.len();
+    let mut failed = 0;
+    for (i, job) in manifest.jobs.iter().enumerate() {
+        log::info!(
+            "[{}/{total}] {} -> {} ({}x{}, {} spp)",
+            i + 1,
+            job.scene.display(),
+            job.output.display(),
+            job.width,
+            job.height,
+            job.samples
+        );
+        let start = Instant::now();
+        match render_job(job, cli) {
+            Ok(()) => log::info!(
+                "[{}/{total}] Done in {:.1}s",
+                i + 1,
+                start.elapsed().as_secs_f32()
+            ),
+            Err(e) => {
+                failed += 1;
+                log::error!("[{}/{total}] {}: {e:#}", i + 1, job.scene.display());
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{failed} of {total} batch jobs failed");
+    }
+    Ok(())
+}
+
+fn render_job(job: &BatchJob, cli: &CliArgs) -> Result<()> {
+    if job.width == 0 || job.height == 0 || job.samples == 0 {
+        bail!("width, height and samples must be positive");
+    }
+    let scene = crate::scene::loader::load_scene(&job.scene)?;
+    let mut renderer = HeadlessRenderer::new(
+        &scene,
+        job.width,
+        job.height,
+        cli.workgroup,
+        cli.bvh_params(),
+    )?;
+    renderer.color_space = job.color_space;
+    if let Some(dir) = job.output.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    if job.frames == 0 {
+        renderer.render_samples(job.samples);
+        let pixels = renderer.read_pixels()?;
+        return crate::io::screenshot::save_screenshot(
+            &pixels,
+            job.width,
+            job.height,
+            &job.output,
+            &renderer.metadata(scene_stem(&job.scene)),
+        );
+    }
+
+    let Some((first, last)) = path::time_range(&scene.camera_path) else {
+        bail!("frames is set but the scene has no camera_path");
+    };
+    let step = if job.frames > 1 {
+        (last - first) / (job.frames - 1) as f32
+    } else {
+        0.0
+    };
+    for frame in 0..job.frames {
+        if let Some(pose) = path::pose_at(&scene.camera_path, first + step * frame as f32) {
+            renderer.set_pose(&pose);
+        }
+        renderer.render_samples(job.samples);
+        let pixels = renderer.read_pixels()?;
+        let output = job.frame_output(frame);
+        crate::io::screenshot::save_screenshot(
+            &pixels,
+            job.width,
+            job.height,
+            &output,
+            &renderer
+                .metadata(scene_stem(&job.scene))
+                .with("Frame", frame),
+        )?;
+        log::info!("  frame {}/{}: {}", frame + 1, job.frames, output.display());
+    }
+    Ok(())
+}
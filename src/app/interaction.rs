@@ -1,11 +1,13 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::path::Path;
+
 use winit::event::{ElementState, MouseButton, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
 use winit::keyboard::{KeyCode, PhysicalKey};
 
-use crate::constants::DRAG_THRESHOLD_PX;
+use crate::constants::{DRAG_THRESHOLD_PX, RESIZE_SETTLE_SECS, TEXTURE_EXTENSIONS};
 use crate::input::handler;
 use crate::scene::shape::ShapeType;
 
@@ -66,6 +68,58 @@ pub fn move_shape_or_group(
     }
 }
 
+/// Round each component to the nearest multiple of `step`; a step of 0 disables snapping.
+fn snap_to_grid(pos: glam::Vec3, step: f32) -> glam::Vec3 {
+    if step > 0.0 {
+        (pos / step).round() * step
+    } else {
+        pos
+    }
+}
+
+/// What a file dropped onto the window does, decided by its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DroppedFileKind {
+    /// Replace the current scene.
+    Scene,
+    /// Import an OBJ model in front of the camera.
+    Model,
+    /// Texture for the selected shape.
+    Image,
+}
+
+impl DroppedFileKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "yaml" | "yml" | "json" => Some(Self::Scene),
+            "obj" => Some(Self::Model),
+            ext if TEXTURE_EXTENSIONS.contains(&ext) => Some(Self::Image),
+            _ => None,
+        }
+    }
+
+    /// Hint shown while a file of this kind hovers over the window.
+    fn hint(kind: Option<Self>, has_selection: bool) -> &'static str {
+        match kind {
+            Some(Self::Scene) => "Drop to open scene",
+            Some(Self::Model) => "Drop to import model",
+            Some(Self::Image) if has_selection => "Drop to texture the selected shape",
+            Some(Self::Image) => "Select a shape to drop a texture on it",
+            None => "Unsupported file type",
+        }
+    }
+}
+
+fn handle_dropped_file(state: &mut AppState, path: &Path) {
+    match DroppedFileKind::from_path(path) {
+        Some(DroppedFileKind::Scene) => state.open_scene(path),
+        Some(DroppedFileKind::Model) => state.import_model(path),
+        Some(DroppedFileKind::Image) => state.set_selected_texture(path),
+        None => log::warn!("Ignoring dropped file {}: unsupported type", path.display()),
+    }
+}
+
 pub fn handle_window_event(state: &mut AppState, event_loop: &ActiveEventLoop, event: WindowEvent) {
     let is_keyboard = matches!(&event, WindowEvent::KeyboardInput { .. });
     let egui_wants_kb = state.egui_ctx.wants_keyboard_input();
@@ -96,9 +150,7 @@ pub fn handle_window_event(state: &mut AppState, event_loop: &ActiveEventLoop, e
                 && key_event.state == ElementState::Pressed
             {
                 let tx = state.file_dialog_tx.clone();
-                let default_name = crate::io::screenshot::default_screenshot_path()
-                    .to_string_lossy()
-                    .to_string();
+                let default_name = state.default_screenshot_name();
                 std::thread::spawn(move || {
                     if let Some(path) = rfd::FileDialog::new()
                         .add_filter("PNG image", &["png"])
@@ -109,6 +161,26 @@ pub fn handle_window_event(state: &mut AppState, event_loop: &ActiveEventLoop, e
                     }
                 });
             }
+            if let WindowEvent::KeyboardInput {
+                event: ref key_event,
+                ..
+            } = event
+                && key_event.physical_key == PhysicalKey::Code(KeyCode::Slash)
+                && key_event.state == ElementState::Pressed
+                && !key_event.repeat
+            {
+                state.toggle_solo();
+            }
+            if let WindowEvent::KeyboardInput {
+                event: ref key_event,
+                ..
+            } = event
+                && key_event.physical_key == PhysicalKey::Code(KeyCode::F1)
+                && key_event.state == ElementState::Pressed
+                && !key_event.repeat
+            {
+                state.ui_state.ui_hidden = !state.ui_state.ui_hidden;
+            }
 
             let was_mouse_look = state.controller.mouse_look_key;
             handler::handle_window_event(&event, &mut state.controller);
@@ -142,8 +214,33 @@ pub fn handle_window_event(state: &mut AppState, event_loop: &ActiveEventLoop, e
         WindowEvent::Resized(size) => {
             state.handle_resize(*size);
         }
+        WindowEvent::Occluded(occluded) => {
+            state.set_occluded(*occluded);
+        }
+        WindowEvent::HoveredFile(path) => {
+            let kind = DroppedFileKind::from_path(path);
+            let hint = DroppedFileKind::hint(kind, state.ui_state.selected_shape.is_some());
+            state.ui_state.drop_hint = Some(hint.to_string());
+        }
+        WindowEvent::HoveredFileCancelled => {
+            state.ui_state.drop_hint = None;
+        }
+        WindowEvent::DroppedFile(path) => {
+            state.ui_state.drop_hint = None;
+            handle_dropped_file(state, path);
+        }
         WindowEvent::RedrawRequested => {
-            state.update_and_render();
+            if state.is_visible() {
+                // Coalesce the stream of resizes from dragging a window edge
+                // into one, instead of restarting accumulation on each.
+                if state
+                    .resized_at
+                    .is_some_and(|at| at.elapsed().as_secs_f32() >= RESIZE_SETTLE_SECS)
+                {
+                    state.finish_resize();
+                }
+                state.update_and_render();
+            }
             return;
         }
         WindowEvent::MouseInput {
@@ -165,6 +262,8 @@ pub fn handle_window_event(state: &mut AppState, event_loop: &ActiveEventLoop, e
                     &state.bvh,
                     &state.shapes,
                     &state.infinite_indices,
+                    &state.models,
+                    state.camera.clip_range(),
                 ) {
                     let shape_pos = shape_centroid(&state.shapes[idx]);
                     state.drag_shape = Some(idx);
@@ -172,6 +271,7 @@ pub fn handle_window_event(state: &mut AppState, event_loop: &ActiveEventLoop, e
                     state.drag_offset = hit_point - shape_pos;
                     state.drag_moved = false;
                     state.drag_start_pos = (cx, cy);
+                    state.last_click_point = Some(hit_point);
                 } else {
                     state.ui_state.selected_shape = None;
                     state.drag_shape = None;
@@ -211,7 +311,10 @@ pub fn handle_window_event(state: &mut AppState, event_loop: &ActiveEventLoop, e
                     state.gpu.width(),
                     state.gpu.height(),
                 );
-                let new_pos = origin + dir * state.drag_depth - state.drag_offset;
+                let new_pos = snap_to_grid(
+                    origin + dir * state.drag_depth - state.drag_offset,
+                    state.ui_state.grid_snap,
+                );
                 move_shape_or_group(&mut state.shapes, idx, new_pos);
                 state.rebuild_scene_buffers_in_place();
                 state.accumulator.reset();
@@ -237,3 +340,28 @@ pub fn handle_window_event(state: &mut AppState, event_loop: &ActiveEventLoop, e
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dropped_file_kind_by_extension() {
+        let cases = [
+            ("scenes/demo.yaml", Some(DroppedFileKind::Scene)),
+            ("old.JSON", Some(DroppedFileKind::Scene)),
+            ("models/teddy.obj", Some(DroppedFileKind::Model)),
+            ("wood.JPG", Some(DroppedFileKind::Image)),
+            ("brick.jpeg", Some(DroppedFileKind::Image)),
+            ("model.mtl", None),
+            ("README", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                DroppedFileKind::from_path(Path::new(path)),
+                expected,
+                "{path}"
+            );
+        }
+    }
+}
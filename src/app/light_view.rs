@@ -0,0 +1,119 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use glam::{Quat, Vec3};
+
+use crate::accel::aabb::shape_aabb;
+use crate::camera::camera::Camera;
+use crate::camera::path::CameraPose;
+use crate::constants::MAX_LIGHT_VIEW_FOV;
+use crate::scene::lights::can_look_through;
+use crate::scene::shape::{Shape, ShapeType};
+
+use super::interaction::shape_centroid;
+use super::state::AppState;
+
+impl AppState {
+    /// Look from the selected light along its direction, or go back to the
+    /// camera used before.
+    pub fn toggle_light_view(&mut self) {
+        if let Some(pose) = self.light_view_return.take() {
+            pose.apply(&mut self.camera);
+        } else {
+            let Some(pose) = self
+                .ui_state
+                .selected_shape
+                .and_then(|idx| self.shapes.get(idx))
+                .and_then(|shape| light_view_pose(shape, self.camera.fov))
+            else {
+                return;
+            };
+            self.light_view_return = Some(CameraPose::of_camera(&self.camera));
+            pose.apply(&mut self.camera);
+        }
+        self.ui_state.light_view = self.light_view_return.is_some();
+        self.ui_state.sync_from_camera(&self.camera);
+        self.accumulator.reset();
+    }
+}
+
+/// Camera pose looking from `shape` along its normal (a triangle's face
+/// normal), with the field of view of a spot light's cone or `fov`.
+/// Solid shapes are viewed from just outside their bounds so the camera
+/// doesn't see their inside.
+fn light_view_pose(shape: &Shape, fov: f32) -> Option<CameraPose> {
+    if !can_look_through(shape) {
+        return None;
+    }
+    let direction = match shape.shape_type {
+        ShapeType::Triangle => (Vec3::from(shape.v1) - Vec3::from(shape.v0))
+            .cross(Vec3::from(shape.v2) - Vec3::from(shape.v0)),
+        _ => Vec3::from(shape.normal),
+    }
+    .try_normalize()?;
+    let mut position = shape_centroid(shape);
+    let flat = matches!(
+        shape.shape_type,
+        ShapeType::SpotLight | ShapeType::Plane | ShapeType::Disc | ShapeType::Triangle
+    );
+    if !flat {
+        let bounds = shape_aabb(shape);
+        let exit = (0..3)
+            .filter(|&axis| direction[axis].abs() > 1e-6)
+            .map(|axis| {
+                let side = if direction[axis] > 0.0 {
+                    bounds.max[axis]
+                } else {
+                    bounds.min[axis]
+                };
+                (side - position[axis]) / direction[axis]
+            })
+            .fold(f32::INFINITY, f32::min);
+        position += direction * exit.max(0.0);
+    }
+    let [pitch, yaw, _] = Camera::look_at_rotation(position, position + direction)?;
+    Some(CameraPose {
+        position,
+        orientation: Quat::from_euler(
+            glam::EulerRot::YXZ,
+            yaw.to_radians(),
+            pitch.to_radians(),
+            0.0,
+        ),
+        fov: match shape.shape_type {
+            ShapeType::SpotLight => (shape.radius2 * 2.0).clamp(1.0, MAX_LIGHT_VIEW_FOV),
+            _ => fov,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_view_looks_along_the_light() {
+        let mut camera = Camera::new(Vec3::ZERO, [0.0, 0.0, 0.0], 60.0, 1.0);
+
+        let spot = ShapeType::SpotLight.new_default_shape([0.0, 4.0, 0.0]);
+        light_view_pose(&spot, 60.0).unwrap().apply(&mut camera);
+        let (_, _, forward) = camera.basis_vectors();
+        assert!(forward.abs_diff_eq(Vec3::NEG_Y, 1e-4));
+        assert!(camera.position.abs_diff_eq(Vec3::new(0.0, 4.0, 0.0), 1e-5));
+        assert_eq!(camera.fov, 60.0);
+
+        // A sphere is looked out of from its surface, not its center.
+        let mut sphere = ShapeType::Sphere.new_default_shape([0.0, 0.0, 0.0]);
+        sphere.normal = [1.0, 0.0, 0.0];
+        sphere.material.emission = [1.0, 1.0, 1.0];
+        sphere.material.emission_strength = 5.0;
+        light_view_pose(&sphere, 45.0).unwrap().apply(&mut camera);
+        let (_, _, forward) = camera.basis_vectors();
+        assert!(forward.abs_diff_eq(Vec3::X, 1e-4));
+        assert!(camera.position.x >= sphere.radius);
+        assert_eq!(camera.fov, 45.0);
+
+        sphere.material.emission_strength = 0.0;
+        assert!(light_view_pose(&sphere, 45.0).is_none());
+    }
+}
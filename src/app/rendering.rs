@@ -4,26 +4,77 @@
 use std::path::Path;
 use std::time::Instant;
 
+use crate::constants::{EMPTY_SCENE_SAMPLES, TEXTURE_EXTENSIONS, UPSCALE_SHARPNESS};
 use crate::gpu::buffers;
+use crate::io::screenshot::ScreenshotMetadata;
+use crate::render::accumulator::{Accumulator, adapt_frame_samples};
+use crate::render::frame::{BlitParams, RenderRegion, StereoMode, ViewMode};
+use crate::scene::diagnostics::MissingAsset;
+use crate::scene::lights;
 use crate::ui;
 
 use super::state::{AppState, FileDialogResult};
 
 impl AppState {
     pub fn update_and_render(&mut self) {
+        if self.gpu.is_lost() {
+            self.recover_lost_device();
+            if self.gpu.is_lost() {
+                return;
+            }
+        }
         let now = Instant::now();
         let dt = (now - self.last_frame).as_secs_f32();
         self.last_frame = now;
+        self.frame_samples =
+            adapt_frame_samples(self.frame_samples, self.ui_state.samples_per_frame, dt);
 
         self.ui_state.sample_count = self.accumulator.sample_count;
-        self.ui_state.render_elapsed_secs = self.accumulator.render_start.elapsed().as_secs_f32();
+        // Move speed also changes via keyboard shortcuts.
+        self.ui_state.move_speed = self.controller.move_speed;
+        // The clock stops once the target sample count is reached.
+        if !self.target_reached() {
+            self.ui_state.render_elapsed_secs =
+                self.accumulator.render_start.elapsed().as_secs_f32();
+        }
 
         let moved = self.controller.update(&mut self.camera, dt);
         let rotated = self.controller.apply_mouse_look(&mut self.camera);
+        let moved = self.advance_path_playback() || moved;
+        self.update_solo();
         if moved || rotated {
             self.accumulator.reset();
         }
 
+        // Dynamic resolution: trace the low-res preview target while moving and
+        // restart full-res accumulation once the camera comes to rest.
+        let use_preview =
+            self.ui_state.dynamic_resolution && !self.ui_state.paused && (moved || rotated);
+        if self.preview_active && !use_preview {
+            self.accumulator.reset();
+        }
+        self.preview_active = use_preview;
+
+        // Auto-exposure only changes the tone mapping, which every sample
+        // reapplies, so adapting it never restarts accumulation.
+        if self.ui_state.auto_exposure {
+            self.auto_exposure.update(dt);
+        } else if self.auto_exposure.scale != 1.0 {
+            self.auto_exposure.reset();
+        }
+        self.ui_state.auto_exposure_scale = self.auto_exposure.scale;
+        self.poll_bake();
+
+        // egui's zoom factor scales on top of the display's scale factor.
+        let native = self.window.scale_factor() as f32;
+        self.egui_ctx
+            .set_zoom_factor(self.ui_scale.map_or(1.0, |scale| scale / native));
+        if self.ui_state.crosshair {
+            self.update_crosshair();
+        }
+        if self.ui_state.light_gizmos {
+            self.update_light_gizmos();
+        }
         let raw_input = self.egui_state.take_egui_input(&self.window);
         let mut ui_actions = ui::UiActions::default();
         let full_output = self.egui_ctx.run(raw_input, |ctx| {
@@ -45,7 +96,7 @@ impl AppState {
 
         let screen_descriptor = egui_wgpu::ScreenDescriptor {
             size_in_pixels: [self.gpu.width(), self.gpu.height()],
-            pixels_per_point: self.window.scale_factor() as f32,
+            pixels_per_point: full_output.pixels_per_point,
         };
 
         for (id, delta) in &full_output.textures_delta.set {
@@ -68,18 +119,24 @@ impl AppState {
             &screen_descriptor,
         );
 
+        // The flat preview and debug views don't accumulate: they render one
+        // frame after each change and then idle until the next one. So does
+        // an empty scene once its sky has a few samples.
+        let sky_settled =
+            self.shapes.is_empty() && self.accumulator.sample_count >= EMPTY_SCENE_SAMPLES;
+        let sampling = !self.ui_state.paused
+            && if self.ui_state.view_mode.accumulates() && !sky_settled {
+                !self.target_reached()
+            } else {
+                self.accumulator.needs_reset()
+            };
         let mut needs_accum_clear = false;
-        if !self.ui_state.paused {
+        if sampling {
+            if !use_preview && self.ui_state.view_mode.accumulates() {
+                self.trace_extra_samples();
+            }
             needs_accum_clear = self.accumulator.advance();
-
-            let gpu_camera = self.camera.to_gpu(
-                self.gpu.width(),
-                self.gpu.height(),
-                self.frame_index,
-                self.accumulator.sample_count,
-            );
-            buffers::update_uniform_buffer(&self.gpu.queue, &self.camera_buffer, &gpu_camera);
-            self.frame_index = self.frame_index.wrapping_add(1);
+            self.upload_sample_camera(use_preview);
         }
 
         let output = match self.gpu.surface.get_current_texture() {
@@ -88,6 +145,11 @@ impl AppState {
                 self.gpu.resize(self.gpu.width(), self.gpu.height());
                 return;
             }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                log::error!("Surface out of memory");
+                self.gpu.mark_lost();
+                return;
+            }
             Err(e) => {
                 log::error!("Surface error: {e}");
                 return;
@@ -100,14 +162,34 @@ impl AppState {
         let frame_dt = (after_acquire - self.last_acquire_time).as_secs_f32();
         self.last_acquire_time = after_acquire;
         self.ui_state.fps = if frame_dt > 0.0 { 1.0 / frame_dt } else { 0.0 };
+        self.ui_state.gpu_memory = self.gpu_memory_usage();
 
         let surface_view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
-        if !self.ui_state.paused {
-            // Clear on GPU to avoid a large CPU allocation per reset.
+        if sampling && use_preview {
+            // Post effects are skipped at preview resolution; they come back
+            // with the first full-res frame.
             if needs_accum_clear {
+                encoder.clear_buffer(&self.preview_target.accumulation_buffer, 0, None);
+            }
+            let (w, h) = (self.preview_target.width, self.preview_target.height);
+            crate::render::frame::dispatch_path_trace(
+                &mut encoder,
+                &self.compute_pipeline,
+                &[
+                    &self.preview_target.compute_bind_group_0,
+                    &self.compute_bind_group_1,
+                ],
+                RenderRegion::full(w, h),
+                self.workgroup_size,
+            );
+        } else if sampling {
+            // Clear on GPU to avoid a large CPU allocation per reset. With a render
+            // region the first sample overwrites the region anyway, and clearing
+            // would wipe the accumulation kept outside it.
+            if needs_accum_clear && self.ui_state.render_region.is_none() {
                 encoder.clear_buffer(&self.accumulation_buffer, 0, None);
             }
 
@@ -115,21 +197,74 @@ impl AppState {
                 &mut encoder,
                 &self.compute_pipeline,
                 &[&self.compute_bind_group_0, &self.compute_bind_group_1],
-                self.gpu.width(),
-                self.gpu.height(),
+                self.render_region(),
+                self.workgroup_size,
             );
 
-            if !self.active_effects.is_empty() {
+            if self.ui_state.auto_exposure && self.ui_state.view_mode == ViewMode::PathTraced {
+                let (width, height) = self.render_size();
+                self.auto_exposure.record_readback(
+                    &self.gpu.device,
+                    &mut encoder,
+                    &self.accumulation_buffer,
+                    width,
+                    height,
+                );
+            }
+        }
+
+        // Also rerun on a converged or paused image whose effect settings
+        // changed; the accumulation it reads is left as is.
+        if !use_preview && (sampling || self.post_pending) {
+            if self.post_process_active() {
                 crate::render::frame::dispatch_post_process(
                     &mut encoder,
                     &self.post_process_pipeline,
                     &self.post_bind_group,
-                    self.gpu.width(),
-                    self.gpu.height(),
+                    self.workgroup_size,
+                    self.render_width,
+                    self.render_height,
                 );
             }
+            self.post_pending = false;
         }
 
+        // The preview target is a different size and gets no readback.
+        if self.ui_state.crosshair && !use_preview {
+            let (width, height) = self.render_size();
+            self.pixel_probe.record_readback(
+                &self.gpu.device,
+                &mut encoder,
+                &self.accumulation_buffer,
+                width,
+                (width / 2, height / 2),
+            );
+        }
+
+        let source_width = if use_preview {
+            self.preview_target.width
+        } else {
+            self.render_width
+        };
+        let scale = source_width as f32 / self.gpu.width().max(1) as f32;
+        let upscaling = scale < 1.0;
+        let sharpness = if self.ui_state.sharpen_upscale && upscaling {
+            UPSCALE_SHARPNESS
+        } else {
+            0.0
+        };
+        let selection = self.selection_outline();
+        buffers::update_uniform_buffer(
+            &self.gpu.queue,
+            &self.blit_params_buffer,
+            &BlitParams {
+                sharpness,
+                selection_start: selection.start,
+                selection_end: selection.end,
+                downsample: scale,
+            },
+        );
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("blit pass"),
@@ -146,7 +281,12 @@ impl AppState {
                 occlusion_query_set: None,
             });
             render_pass.set_pipeline(&self.blit_pipeline);
-            render_pass.set_bind_group(0, Some(&self.blit_bind_group), &[]);
+            let blit_bind_group = if use_preview {
+                &self.preview_target.blit_bind_group
+            } else {
+                &self.blit_bind_group
+            };
+            render_pass.set_bind_group(0, Some(blit_bind_group), &[]);
             render_pass.draw(0..3, 0..1);
         }
 
@@ -172,9 +312,11 @@ impl AppState {
 
         self.gpu.queue.submit(std::iter::once(encoder.finish()));
         output.present();
+        self.auto_exposure.map_pending();
+        self.pixel_probe.map_pending();
 
         // Non-blocking poll: reclaim completed staging buffers without stalling the CPU.
-        // VSync (PresentMode::AutoVsync) provides frame pacing.
+        // The present mode (AutoVsync by default) and optional FPS cap provide frame pacing.
         self.gpu.device.poll(wgpu::Maintain::Poll);
 
         for id in &full_output.textures_delta.free {
@@ -182,33 +324,180 @@ impl AppState {
         }
     }
 
+    /// Write the camera uniform for the sample `advance` just started.
+    fn upload_sample_camera(&mut self, use_preview: bool) {
+        let (width, height, region) = if use_preview {
+            let (w, h) = (self.preview_target.width, self.preview_target.height);
+            (w, h, RenderRegion::full(w, h))
+        } else {
+            let (w, h) = self.render_size();
+            (w, h, self.render_region())
+        };
+        let mut gpu_camera = self.camera.to_gpu(
+            width,
+            height,
+            self.frame_index,
+            self.accumulator.sample_count,
+        );
+        gpu_camera.region_origin = [region.x, region.y];
+        gpu_camera.region_size = [region.width, region.height];
+        gpu_camera.view_mode = self.ui_state.view_mode.as_u32();
+        gpu_camera.jitter_pattern = self.ui_state.jitter_pattern.as_u32();
+        gpu_camera.color_space = self.ui_state.color_space.as_u32();
+        gpu_camera.stereo_mode = self.ui_state.stereo_mode.as_u32();
+        gpu_camera.eye_separation = self.ui_state.stereo_ipd;
+        gpu_camera.brute_force = u32::from(self.ui_state.bvh_params.brute_force);
+        let sky_hidden = self.ui_state.solo && self.ui_state.solo_hides_sky;
+        if sky_hidden {
+            gpu_camera.skybox_brightness = 0.0;
+        }
+        // Solo leaves the scene's lights out; only the sky may still light it.
+        let lit = match (self.ui_state.solo, sky_hidden) {
+            (false, _) => lights::scene_is_lit(&self.ui_state.light_groups, &self.camera),
+            (true, false) => lights::scene_is_lit(&[], &self.camera),
+            (true, true) => false,
+        };
+        self.ui_state.headlight_active = self.ui_state.headlight && !lit;
+        if self.ui_state.headlight_active {
+            gpu_camera.headlight = self.ui_state.headlight_strength;
+        }
+        gpu_camera.exposure *= self.auto_exposure.scale;
+        buffers::update_uniform_buffer(&self.gpu.queue, &self.camera_buffer, &gpu_camera);
+        self.frame_index = self.frame_index.wrapping_add(1);
+    }
+
+    /// Trace all but the last of this frame's samples, each in its own
+    /// submission: every sample needs its own camera uniform, and no single
+    /// submission runs long enough to trip the GPU watchdog. The frame's own
+    /// dispatch then traces the last one and feeds the post effects.
+    fn trace_extra_samples(&mut self) {
+        let mut extra = self.frame_samples.saturating_sub(1);
+        if self.ui_state.target_samples > 0 {
+            let remaining = self.ui_state.target_samples - self.accumulator.sample_count;
+            extra = extra.min(remaining.saturating_sub(1));
+        }
+        for _ in 0..extra {
+            let needs_clear = self.accumulator.advance();
+            self.upload_sample_camera(false);
+            let mut encoder =
+                self.gpu
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("extra sample encoder"),
+                    });
+            // Same clearing rule as the frame's own sample.
+            if needs_clear && self.ui_state.render_region.is_none() {
+                encoder.clear_buffer(&self.accumulation_buffer, 0, None);
+            }
+            crate::render::frame::dispatch_path_trace(
+                &mut encoder,
+                &self.compute_pipeline,
+                &[&self.compute_bind_group_0, &self.compute_bind_group_1],
+                self.render_region(),
+                self.workgroup_size,
+            );
+            self.gpu.queue.submit(std::iter::once(encoder.finish()));
+        }
+    }
+
+    /// Project the lights into the window for their gizmos.
+    fn update_light_gizmos(&mut self) {
+        let (width, height) = (self.gpu.width(), self.gpu.height());
+        self.ui_state.light_gizmo_points = lights::light_anchors(&self.shapes)
+            .into_iter()
+            .filter_map(|(idx, anchor)| {
+                crate::picking::project_to_screen(&self.camera, anchor, width, height)
+                    .filter(|&[x, y]| {
+                        (0.0..width as f32).contains(&x) && (0.0..height as f32).contains(&y)
+                    })
+                    .map(|point| (idx, point))
+            })
+            .collect();
+    }
+
+    /// Pick along the center ray and collect the center pixel's color for
+    /// the crosshair inspector.
+    fn update_crosshair(&mut self) {
+        self.pixel_probe.update();
+        let (width, height) = (self.gpu.width(), self.gpu.height());
+        let (origin, dir) = crate::picking::picking_ray(
+            &self.camera,
+            width as f32 * 0.5,
+            height as f32 * 0.5,
+            width,
+            height,
+        );
+        self.ui_state.crosshair_hit = crate::picking::pick(
+            origin,
+            dir,
+            &self.bvh,
+            &self.shapes,
+            &self.infinite_indices,
+            &self.models,
+            self.camera.clip_range(),
+        )
+        .map(|(idx, t, point)| (idx, point.into(), t));
+        self.ui_state.crosshair_color = self.pixel_probe.color;
+    }
+
+    /// Restart `accumulator` if applying `actions` invalidates its samples.
+    pub(super) fn reset_accumulation_for(accumulator: &mut Accumulator, actions: &ui::UiActions) {
+        if actions.resets_accumulation() {
+            accumulator.reset();
+        }
+    }
+
     fn apply_ui_actions(&mut self, ui_actions: ui::UiActions) {
+        Self::reset_accumulation_for(&mut self.accumulator, &ui_actions);
         if let Some(exp) = ui_actions.exposure_changed {
             self.camera.exposure = exp;
-            self.accumulator.reset();
         }
         if let Some(bounces) = ui_actions.max_bounces_changed {
             self.camera.max_bounces = bounces;
-            self.accumulator.reset();
         }
         if ui_actions.render_settings_changed {
             self.sync_render_settings_to_camera();
-            self.accumulator.reset();
+            self.refresh_diagnostics();
         }
-        let mut rebuild_post = ui_actions.post_effect_params_changed;
-        if let Some(effects) = ui_actions.effects_changed {
-            self.active_effects = effects;
-            rebuild_post = true;
-        }
-        if rebuild_post {
-            let params = AppState::build_post_params(
-                self.gpu.width(),
-                self.gpu.height(),
-                &self.active_effects,
-                self.ui_state.oil_radius,
-                self.ui_state.comic_levels,
-            );
-            buffers::update_uniform_buffer(&self.gpu.queue, &self.post_params_buffer, &params);
+        if let Some(mode) = ui_actions.present_mode_changed {
+            if self.gpu.set_present_mode(mode) {
+                log::info!("Present mode switched to {mode:?}");
+                self.settings.present_mode = mode;
+            }
+            self.ui_state.present_mode = self.gpu.present_mode;
+        }
+        if ui_actions.shader_features_changed {
+            self.rebuild_path_trace_pipeline();
+        }
+        if ui_actions.render_size_changed {
+            self.recreate_size_dependent_resources();
+        }
+        if ui_actions.controls_changed {
+            self.controller.look_sensitivity = self.ui_state.mouse_sensitivity;
+            self.controller.move_speed = self.ui_state.move_speed;
+            self.controller.invert_y = self.ui_state.invert_y;
+        }
+        if ui_actions.ui_scale_changed {
+            self.ui_scale = self.ui_state.ui_scale;
+            self.settings.ui_scale = self.ui_state.ui_scale;
+        }
+        if ui_actions.recheck_diagnostics {
+            self.refresh_diagnostics();
+        }
+        if let Some(idx) = ui_actions.camera_selected {
+            self.select_camera(idx);
+        }
+        if ui_actions.reset_camera {
+            self.reset_camera();
+        }
+        if ui_actions.reset_render_settings {
+            self.reset_render_settings();
+        }
+        if ui_actions.updates_post_params() {
+            if let Some(effects) = &ui_actions.effects_changed {
+                self.active_effects = effects.clone();
+            }
+            self.upload_post_params();
         }
         if let Some(shape_type) = ui_actions.shape_to_add {
             self.add_shape(shape_type);
@@ -216,13 +505,69 @@ impl AppState {
         if let Some(idx) = ui_actions.shape_to_delete {
             self.delete_shape(idx);
         }
+        if ui_actions.align_selected_to_camera
+            && let Some(shape) = self
+                .ui_state
+                .selected_shape
+                .and_then(|idx| self.shapes.get_mut(idx))
+        {
+            shape.set_rotation_quat(self.camera.orientation());
+            self.rebuild_scene_buffers();
+        }
+        if ui_actions.bvh_params_changed {
+            self.apply_bvh_params();
+        }
+        if ui_actions.add_keyframe {
+            self.add_keyframe();
+        }
+        if let Some(idx) = ui_actions.remove_keyframe {
+            self.remove_keyframe(idx);
+        }
+        if ui_actions.keyframe_times_changed {
+            self.apply_keyframe_times();
+        }
+        if let Some(idx) = ui_actions.goto_keyframe {
+            self.goto_keyframe(idx);
+        }
+        if ui_actions.toggle_path_playback {
+            self.toggle_path_playback();
+        }
+        if ui_actions.toggle_solo {
+            self.toggle_solo();
+        }
+        if ui_actions.toggle_light_view {
+            self.toggle_light_view();
+        }
+        if let Some(idx) = ui_actions.fly_to_shape {
+            self.fly_to_shape(idx);
+        }
+        if ui_actions.apply_user_bsdf {
+            self.apply_user_bsdf();
+        }
+        if ui_actions.revert_user_bsdf {
+            self.revert_user_bsdf();
+        }
+        if let Some(shape_type) = ui_actions.material_to_type {
+            self.apply_selected_material_to_type(shape_type);
+        }
+        if ui_actions.bake_selected_mesh {
+            self.bake_selected_mesh();
+        }
+        if ui_actions.clear_selected_bake {
+            self.clear_selected_bake();
+        }
+        if ui_actions.flip_selected_normals {
+            self.flip_selected_normals();
+        }
+        if ui_actions.light_groups_changed && !ui_actions.scene_dirty {
+            self.rebuild_scene_buffers();
+        }
         if ui_actions.scene_dirty {
             if ui_actions.textures_dirty {
                 self.rebuild_scene_buffers_with_textures();
             } else {
                 self.rebuild_scene_buffers();
             }
-            self.accumulator.reset();
         }
         if ui_actions.save_requested {
             self.save_scene(&self.ui_state.save_filename.clone());
@@ -270,11 +615,22 @@ impl AppState {
                 }
             });
         }
+        if let Some(asset) = ui_actions.relocate_asset {
+            let tx = self.file_dialog_tx.clone();
+            let mut dialog = rfd::FileDialog::new().set_title(format!("Locate {}", asset.path()));
+            dialog = match asset {
+                MissingAsset::Texture(_) => dialog.add_filter("Images", TEXTURE_EXTENSIONS),
+                MissingAsset::Model(_) => dialog.add_filter("OBJ model", &["obj"]),
+            };
+            std::thread::spawn(move || {
+                if let Some(path) = dialog.pick_file() {
+                    let _ = tx.send(FileDialogResult::RelocateAsset(asset, path));
+                }
+            });
+        }
         if ui_actions.open_screenshot_dialog {
             let tx = self.file_dialog_tx.clone();
-            let default_name = crate::io::screenshot::default_screenshot_path()
-                .to_string_lossy()
-                .to_string();
+            let default_name = self.default_screenshot_name();
             std::thread::spawn(move || {
                 if let Some(path) = rfd::FileDialog::new()
                     .add_filter("PNG image", &["png"])
@@ -291,6 +647,7 @@ impl AppState {
                 FileDialogResult::OpenScene(path) => self.open_scene(&path),
                 FileDialogResult::ImportScene(path) => self.import_scene(&path),
                 FileDialogResult::ImportModel(path) => self.import_model(&path),
+                FileDialogResult::RelocateAsset(asset, path) => self.relocate_asset(asset, &path),
                 FileDialogResult::Screenshot(mut path) => {
                     if path.extension().is_none() {
                         path.set_extension("png");
@@ -305,83 +662,78 @@ impl AppState {
     /// through dedicated actions) from `ui_state` into the camera uniform.
     fn sync_render_settings_to_camera(&mut self) {
         self.camera.firefly_clamp = self.ui_state.firefly_clamp;
+        self.camera.skybox_mode = self.ui_state.skybox_mode;
         self.camera.skybox_color = self.ui_state.skybox_color;
+        self.camera.skybox_horizon_color = self.ui_state.skybox_horizon_color;
         self.camera.skybox_brightness = self.ui_state.skybox_brightness;
+        self.camera.transparent_background = self.ui_state.transparent_background;
+        self.camera.sun_azimuth = self.ui_state.sun_azimuth;
+        self.camera.sun_elevation = self.ui_state.sun_elevation;
+        self.camera.sky_turbidity = self.ui_state.sky_turbidity;
         self.camera.tone_mapper = self.ui_state.tone_mapper;
+        self.camera.white_point = self.ui_state.white_point;
         self.camera.fractal_march_steps = self.ui_state.fractal_march_steps;
+        self.camera.near_clip = self.ui_state.near_clip;
+        self.camera.far_clip = self.ui_state.far_clip;
     }
 
-    pub fn take_screenshot(&self, path: &Path) {
-        let width = self.gpu.width();
-        let height = self.gpu.height();
-        let bytes_per_row_unpadded = width * 4;
-        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-        let bytes_per_row_padded = bytes_per_row_unpadded.div_ceil(align) * align;
-
-        let staging_buffer = self.gpu.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("screenshot staging"),
-            size: (bytes_per_row_padded * height) as u64,
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
+    /// Suggested file name for a screenshot of the current render.
+    pub fn default_screenshot_name(&self) -> String {
+        crate::io::screenshot::default_screenshot_path(
+            self.scene_name.as_deref(),
+            self.accumulator.sample_count,
+        )
+        .to_string_lossy()
+        .to_string()
+    }
 
-        let mut encoder = self
-            .gpu
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("screenshot encoder"),
-            });
+    /// The render settings stored in screenshots.
+    fn screenshot_metadata(&self) -> ScreenshotMetadata {
+        let features = self.ui_state.shader_features;
+        let mut metadata = ScreenshotMetadata::new(
+            self.scene_name.clone(),
+            self.accumulator.sample_count,
+            &self.camera,
+        )
+        .with("Pixel Jitter", self.ui_state.jitter_pattern.label())
+        .with("Next Event Estimation", features.nee)
+        .with("Russian Roulette", features.russian_roulette);
+        if self.ui_state.stereo_mode != StereoMode::Off {
+            metadata = metadata
+                .with("Stereo", self.ui_state.stereo_mode.label())
+                .with("Eye Separation", self.ui_state.stereo_ipd);
+        }
+        metadata.color_space = self.ui_state.color_space;
+        metadata
+    }
 
-        encoder.copy_texture_to_buffer(
-            wgpu::ImageCopyTexture {
-                texture: &self.output_texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            wgpu::ImageCopyBuffer {
-                buffer: &staging_buffer,
-                layout: wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(bytes_per_row_padded),
-                    rows_per_image: None,
-                },
-            },
-            wgpu::Extent3d {
+    pub fn take_screenshot(&self, path: &Path) {
+        let (width, height) = self.render_size();
+        crate::io::screenshot::check_accumulation(
+            &self.gpu.device,
+            &self.gpu.queue,
+            &self.accumulation_buffer,
+            width,
+            height,
+        );
+        let result = buffers::read_texture_rgba8(
+            &self.gpu.device,
+            &self.gpu.queue,
+            &self.output_texture,
+            width,
+            height,
+        )
+        .and_then(|pixels| {
+            crate::io::screenshot::save_screenshot(
+                &pixels,
                 width,
                 height,
-                depth_or_array_layers: 1,
-            },
-        );
-
-        self.gpu.queue.submit(std::iter::once(encoder.finish()));
-
-        let buffer_slice = staging_buffer.slice(..);
-        let (sender, receiver) = std::sync::mpsc::channel();
-        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
-            let _ = sender.send(result);
+                path,
+                &self.screenshot_metadata(),
+            )
         });
-        self.gpu.device.poll(wgpu::Maintain::Wait);
-
-        if let Ok(Ok(())) = receiver.recv() {
-            let data = buffer_slice.get_mapped_range();
-            // Remove row padding if necessary.
-            let mut pixels = Vec::with_capacity((width * height * 4) as usize);
-            for row in 0..height {
-                let start = (row * bytes_per_row_padded) as usize;
-                let end = start + bytes_per_row_unpadded as usize;
-                pixels.extend_from_slice(&data[start..end]);
-            }
-            drop(data);
-            staging_buffer.unmap();
-
-            if let Err(e) =
-                crate::io::screenshot::save_screenshot(&pixels, width, height, path)
-            {
-                log::error!("Screenshot failed: {e:#}");
-            }
-        } else {
-            log::error!("Failed to map screenshot buffer");
+        if let Err(e) = result {
+            log::error!("Screenshot failed: {e:#}");
         }
     }
 }
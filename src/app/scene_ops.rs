@@ -1,40 +1,115 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Instant;
 
+use glam::Vec3;
+
+use crate::accel::aabb::{Aabb, shape_aabb};
 use crate::camera::camera::Camera;
-use crate::constants::MODEL_AUTO_SCALE_TARGET;
+use crate::constants::{
+    BAKE_SAMPLES, MIN_FRAME_RADIUS, MODEL_AUTO_SCALE_TARGET, UNBOUNDED_FRAME_RADIUS,
+    resolve_resource_path,
+};
+use crate::io::texture_atlas::TextureAtlas;
+use crate::render::bake;
+use crate::scene::diagnostics::MissingAsset;
 use crate::scene::material::Material;
-use crate::scene::scene::Scene;
+use crate::scene::scene::{ModelRef, PostEffectsConfig, Scene};
 use crate::scene::shape::{Shape, ShapeType};
+use crate::settings::SpawnPlacement;
 
 use super::state::AppState;
 
+/// Lighting bake of a mesh running on a worker thread.
+pub struct BakeJob {
+    /// Triangles being baked, by shape index.
+    members: Vec<usize>,
+    /// Vertices of `members` when the bake started, to tell whether the
+    /// indices still name the same triangles once it finishes.
+    vertices: Vec<[[f32; 3]; 3]>,
+    start: Instant,
+    colors: mpsc::Receiver<Vec<[[f32; 3]; 3]>>,
+}
+
+impl BakeJob {
+    fn new(
+        members: Vec<usize>,
+        shapes: &[Shape],
+        colors: mpsc::Receiver<Vec<[[f32; 3]; 3]>>,
+    ) -> Self {
+        let vertices = members
+            .iter()
+            .map(|&idx| triangle_vertices(&shapes[idx]))
+            .collect();
+        Self {
+            members,
+            vertices,
+            start: Instant::now(),
+            colors,
+        }
+    }
+
+    /// Whether every baked index still holds the triangle it held when the
+    /// bake started, so the colors land where they were computed for.
+    fn matches(&self, shapes: &[Shape]) -> bool {
+        self.members
+            .iter()
+            .zip(&self.vertices)
+            .all(|(&idx, vertices)| {
+                shapes.get(idx).is_some_and(|shape| {
+                    shape.shape_type == ShapeType::Triangle && triangle_vertices(shape) == *vertices
+                })
+            })
+    }
+}
+
+fn triangle_vertices(shape: &Shape) -> [[f32; 3]; 3] {
+    [shape.v0, shape.v1, shape.v2]
+}
+
 impl AppState {
     pub fn open_scene(&mut self, path: &Path) {
         match crate::scene::loader::load_scene(path) {
             Ok(scene) => {
                 self.camera = Camera::from_config(&scene.camera);
+                self.initial_camera = scene.camera.clone();
+                self.ui_state.scene_camera = scene.camera.clone();
                 self.ui_state.sync_from_camera(&self.camera);
-                self.shapes = scene.shapes;
-
-                for model_ref in &scene.models {
-                    match crate::model::obj_loader::load_obj(
-                        &model_ref.path,
-                        model_ref.position,
-                        model_ref.scale,
-                        &model_ref.material,
-                    ) {
-                        Ok(triangles) => self.shapes.extend(triangles),
-                        Err(e) => {
-                            log::error!("Failed to load model '{}': {e:#}", model_ref.path)
-                        }
-                    }
-                }
+                self.ui_state.camera_names = scene.camera_names();
+                self.ui_state.active_camera = 0;
+                self.scene.camera = scene.camera.clone();
+                self.scene.cameras = scene.cameras.clone();
+                self.camera_path = scene.camera_path.clone();
+                self.path_playback_start = None;
+                self.solo = None;
+                self.ui_state.solo = false;
+                self.light_view_return = None;
+                self.ui_state.light_view = false;
+                // A bake of the previous scene no longer applies.
+                self.bake_job = None;
+                self.ui_state.baking = false;
+                self.scene_name = super::state::scene_stem(path);
+                self.scene_path = Some(path.to_path_buf());
+                self.ui_state.path_playing = false;
+                self.sync_keyframe_times();
+                let (shapes, missing_models) = Self::load_scene_shapes(&scene);
+                self.shapes = shapes;
+                self.ui_state.missing_assets = missing_models;
+                self.scene.dir = scene.dir;
+                self.scene.up_axis = scene.up_axis;
+                self.ui_state.model_up_axis = scene.up_axis;
+                self.apply_post_effects(&scene.post_effects);
 
                 self.ui_state.selected_shape = None;
+                self.last_click_point = None;
                 self.ui_state.paused = false;
+                self.ui_state.light_groups.clear();
+                // Decode every texture again so edited image files show up.
+                self.texture_atlas = TextureAtlas::default();
                 self.rebuild_scene_buffers_with_textures();
                 self.accumulator.reset();
                 log::info!("Opened scene: {}", path.display());
@@ -43,51 +118,268 @@ impl AppState {
         }
     }
 
-    pub fn add_shape(&mut self, shape_type: ShapeType) {
-        let mut shape = Shape {
-            name: None,
-            shape_type,
-            negative: false,
-            position: self.camera.position.into(),
-            normal: [0.0, 1.0, 0.0],
-            radius: 1.0,
-            radius2: 0.3,
-            height: 2.0,
-            rotation: [0.0, 0.0, 0.0],
-            v0: [0.0, 0.0, 0.0],
-            v1: [1.0, 0.0, 0.0],
-            v2: [0.0, 1.0, 0.0],
-            power: 8.0,
-            max_iterations: 12,
-            texture: None,
-            texture_scale: None,
-            uv0: [0.0, 0.0],
-            uv1: [0.0, 0.0],
-            uv2: [0.0, 0.0],
-            material: Material::default(),
+    /// Move the camera back to where the scene file put it.
+    pub fn reset_camera(&mut self) {
+        let initial = Camera::from_config(&self.initial_camera);
+        self.camera.position = initial.position;
+        self.camera.yaw = initial.yaw;
+        self.camera.pitch = initial.pitch;
+        self.camera.fov = initial.fov;
+        self.accumulator.reset();
+    }
+
+    /// Switch to the scene's camera `idx` (see `Scene::camera_config`). The
+    /// reset actions then return to this camera's settings.
+    pub fn select_camera(&mut self, idx: usize) {
+        let Some(config) = self.scene.camera_config(idx).cloned() else {
+            return;
         };
+        self.camera = Camera::from_config(&config);
+        self.ui_state.scene_camera = config.clone();
+        self.initial_camera = config;
+        self.ui_state.active_camera = idx;
+        self.ui_state.sync_from_camera(&self.camera);
+        self.refresh_diagnostics();
+        self.accumulator.reset();
+    }
 
-        let (_, _, forward) = self.camera.basis_vectors();
-        let spawn_pos = self.camera.position + forward * 5.0;
-        shape.position = spawn_pos.into();
+    /// Restore bounces, clamp, sky and tone mapping from the scene file.
+    pub fn reset_render_settings(&mut self) {
+        self.camera.apply_render_settings(&self.initial_camera);
+        self.ui_state.sync_from_camera(&self.camera);
+        self.refresh_diagnostics();
+        self.accumulator.reset();
+    }
 
-        match shape_type {
-            ShapeType::Plane => {
-                shape.position = [0.0, 0.0, 0.0];
+    /// Switch to the scene file's post-effect chain and parameters.
+    fn apply_post_effects(&mut self, config: &PostEffectsConfig) {
+        self.active_effects = config.effects.clone();
+        self.ui_state.active_effects = config.effects.clone();
+        self.ui_state.oil_radius = config.oil_radius;
+        self.ui_state.comic_levels = config.comic_levels;
+        self.upload_post_params();
+    }
+
+    /// Where a new object goes under the current spawn placement setting;
+    /// `min_distance` keeps large objects from enclosing the camera.
+    fn spawn_position(&self, min_distance: f32) -> Vec3 {
+        match (self.ui_state.spawn_placement, self.last_click_point) {
+            (SpawnPlacement::Origin, _) => Vec3::ZERO,
+            (SpawnPlacement::ClickedPoint, Some(point)) => point,
+            _ => {
+                let (_, _, forward) = self.camera.basis_vectors();
+                let distance = self.ui_state.spawn_distance.max(min_distance);
+                self.camera.position + forward * distance
             }
-            ShapeType::Mandelbulb => {
-                shape.radius = 1.5;
-                shape.power = 8.0;
-                shape.max_iterations = 12;
+        }
+    }
+
+    /// Indices of the selected triangle's mesh: every triangle with its name,
+    /// or just the triangle itself when it has none.
+    pub fn selected_mesh(&self) -> Vec<usize> {
+        self.ui_state
+            .selected_shape
+            .map_or_else(Vec::new, |idx| self.mesh_of(idx))
+    }
+
+    /// `selected_mesh` for the triangle at `selected`; empty for other shapes.
+    pub fn mesh_of(&self, selected: usize) -> Vec<usize> {
+        if self.shapes.get(selected).map(|s| s.shape_type) != Some(ShapeType::Triangle) {
+            return Vec::new();
+        }
+        match self.shapes[selected].name.as_deref() {
+            Some(name) if !name.is_empty() => (0..self.shapes.len())
+                .filter(|&idx| {
+                    let shape = &self.shapes[idx];
+                    shape.shape_type == ShapeType::Triangle && shape.name.as_deref() == Some(name)
+                })
+                .collect(),
+            _ => vec![selected],
+        }
+    }
+
+    /// Shapes outlined in the viewport: the selected one, or its whole mesh
+    /// when the mesh's triangles are stored together, as imports leave them.
+    /// Nothing is outlined while the UI is hidden.
+    pub fn selection_outline(&self) -> Range<u32> {
+        let Some(selected) = self
+            .ui_state
+            .selected_shape
+            .filter(|&idx| idx < self.shapes.len() && !self.ui_state.ui_hidden)
+        else {
+            return 0..0;
+        };
+        let mesh = self.selected_mesh();
+        if let (Some(&first), Some(&last)) = (mesh.first(), mesh.last())
+            && last - first + 1 == mesh.len()
+        {
+            return first as u32..last as u32 + 1;
+        }
+        selected as u32..selected as u32 + 1
+    }
+
+    /// Frame shape `idx` with the camera, keeping the view direction. A
+    /// named triangle is framed with its whole mesh and an instance with
+    /// its model; planes and skyboxes only bring the camera near their
+    /// position.
+    pub fn fly_to_shape(&mut self, idx: usize) {
+        let Some(shape) = self.shapes.get(idx) else {
+            return;
+        };
+        let bounds = match shape.shape_type {
+            ShapeType::Plane | ShapeType::Skybox => None,
+            ShapeType::Triangle => Some(
+                self.mesh_of(idx)
+                    .into_iter()
+                    .map(|i| shape_aabb(&self.shapes[i]))
+                    .fold(Aabb::EMPTY, Aabb::union),
+            ),
+            ShapeType::Instance => Some(
+                self.models
+                    .model_of(shape)
+                    .map_or_else(|| shape_aabb(shape), |model| model.instance_aabb(shape)),
+            ),
+            _ => Some(shape_aabb(shape)),
+        };
+        let (center, radius) = match bounds {
+            Some(aabb) => (
+                aabb.center(),
+                ((aabb.max - aabb.min).length() * 0.5).max(MIN_FRAME_RADIUS),
+            ),
+            None => (Vec3::from(shape.position), UNBOUNDED_FRAME_RADIUS),
+        };
+        self.camera.frame(center, radius);
+        self.accumulator.reset();
+    }
+
+    /// Copy the selected shape's material to every shape of `shape_type`.
+    pub fn apply_selected_material_to_type(&mut self, shape_type: ShapeType) {
+        let Some(material) = self
+            .ui_state
+            .selected_shape
+            .and_then(|idx| self.shapes.get(idx))
+            .map(|shape| shape.material.clone())
+        else {
+            return;
+        };
+        let mut count = 0;
+        for shape in self
+            .shapes
+            .iter_mut()
+            .filter(|s| s.shape_type == shape_type)
+        {
+            shape.material = material.clone();
+            count += 1;
+        }
+        self.rebuild_scene_buffers();
+        log::info!(
+            "Applied the selected material to {count} {}",
+            shape_type.label()
+        );
+    }
+
+    /// Bake single-bounce lighting into the vertex colors of the selected
+    /// mesh, which then renders them unlit (see `render::bake`). The bake
+    /// traces a copy of the scene on a worker thread; `poll_bake` applies it.
+    pub fn bake_selected_mesh(&mut self) {
+        if self.bake_job.is_some() {
+            log::warn!("A lighting bake is already running");
+            return;
+        }
+        let members = self.selected_mesh();
+        if members.is_empty() {
+            return;
+        }
+        let shapes = self.shapes.clone();
+        let bvh = self.bvh.clone();
+        let infinite_indices = self.infinite_indices.clone();
+        let models = self.models.clone();
+        let camera = self.camera.clone();
+        let baked = members.clone();
+        let (sender, receiver) = mpsc::channel();
+        rayon::spawn(move || {
+            let trace = |origin: Vec3, dir: Vec3| {
+                crate::picking::pick(
+                    origin,
+                    dir,
+                    &bvh,
+                    &shapes,
+                    &infinite_indices,
+                    &models,
+                    0.0..f32::INFINITY,
+                )
+                .map(|(idx, _, _)| idx)
+            };
+            let sky = |dir: Vec3| bake::sky_radiance(&camera, dir);
+            let colors = bake::bake_vertex_colors(&shapes, &baked, BAKE_SAMPLES, trace, sky);
+            let _ = sender.send(colors);
+        });
+        self.bake_job = Some(BakeJob::new(members, &self.shapes, receiver));
+        self.ui_state.baking = true;
+    }
+
+    /// Apply the colors of a finished lighting bake.
+    pub fn poll_bake(&mut self) {
+        let Some(job) = self.bake_job.take() else {
+            return;
+        };
+        let colors = match job.colors.try_recv() {
+            Ok(colors) => colors,
+            Err(mpsc::TryRecvError::Empty) => {
+                self.bake_job = Some(job);
+                return;
             }
-            ShapeType::Julia => {
-                shape.radius = 1.5;
-                shape.rotation = [-0.8, 0.156, 0.0]; // Julia C.xyz
-                shape.radius2 = -0.046; // Julia C.w
-                shape.max_iterations = 14;
+            Err(mpsc::TryRecvError::Disconnected) => {
+                log::error!("Lighting bake failed");
+                self.ui_state.baking = false;
+                return;
             }
-            _ => {}
+        };
+        self.ui_state.baking = false;
+        if !job.matches(&self.shapes) {
+            log::warn!("The mesh changed while baking; bake discarded");
+            return;
+        }
+        for (idx, colors) in job.members.iter().zip(colors) {
+            self.shapes[*idx].vertex_colors = Some(colors);
         }
+        self.rebuild_scene_buffers();
+        self.accumulator.reset();
+        log::info!(
+            "Baked lighting into {} triangles in {:.1?}",
+            job.members.len(),
+            job.start.elapsed()
+        );
+    }
+
+    pub fn clear_selected_bake(&mut self) {
+        for idx in self.selected_mesh() {
+            self.shapes[idx].vertex_colors = None;
+        }
+        self.rebuild_scene_buffers();
+        self.accumulator.reset();
+    }
+
+    /// Flip the selected shape, or every triangle of the selected mesh, to
+    /// face the other way.
+    pub fn flip_selected_normals(&mut self) {
+        let Some(selected) = self.ui_state.selected_shape else {
+            return;
+        };
+        let mut members = self.mesh_of(selected);
+        if members.is_empty() && selected < self.shapes.len() {
+            members.push(selected);
+        }
+        for &idx in &members {
+            self.shapes[idx].flip_normal();
+        }
+        self.rebuild_scene_buffers();
+        self.accumulator.reset();
+        log::info!("Flipped the normals of {} shapes", members.len());
+    }
+
+    pub fn add_shape(&mut self, shape_type: ShapeType) {
+        let shape = shape_type.new_default_shape(self.spawn_position(0.0).into());
 
         self.shapes.push(shape);
         self.ui_state.paused = false;
@@ -112,38 +404,70 @@ impl AppState {
         }
     }
 
-    pub fn save_scene(&self, filename: &str) {
-        let scene = Scene {
-            camera: self.camera.to_config(),
+    pub fn save_scene(&mut self, filename: &str) {
+        let path = Path::new(filename);
+        let mut scene = self.scene_snapshot();
+        // Asset paths are relative to the scene file, so follow it to its new home.
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        scene.rebase_asset_paths(dir.unwrap_or(Path::new(".")));
+        match crate::scene::exporter::save_scene(&scene, path) {
+            Ok(()) => {
+                self.scene_path = Some(PathBuf::from(filename));
+                self.scene.dir = scene.dir;
+                let renamed = self
+                    .shapes
+                    .iter()
+                    .zip(&scene.shapes)
+                    .any(|(old, new)| old.texture != new.texture || old.model != new.model);
+                if renamed {
+                    // Same files under new names: re-key the texture cache.
+                    self.shapes = scene.shapes;
+                    self.rebuild_scene_buffers_with_textures();
+                }
+            }
+            Err(e) => log::error!("Failed to save scene: {e:#}"),
+        }
+    }
+
+    /// The scene as edited, with the current view as its active camera.
+    pub fn scene_snapshot(&self) -> Scene {
+        let mut scene = Scene {
+            camera: self.scene.camera.clone(),
+            cameras: self.scene.cameras.clone(),
+            camera_path: self.camera_path.clone(),
             shapes: self.shapes.clone(),
             models: vec![],
+            up_axis: self.ui_state.model_up_axis,
+            post_effects: PostEffectsConfig {
+                effects: self.active_effects.clone(),
+                oil_radius: self.ui_state.oil_radius,
+                comic_levels: self.ui_state.comic_levels,
+            },
+            dir: self.scene.dir.clone(),
         };
-        if let Err(e) = crate::scene::exporter::save_scene(&scene, Path::new(filename)) {
-            log::error!("Failed to save scene: {e:#}");
+        if let Some(active) = scene.camera_config_mut(self.ui_state.active_camera) {
+            *active = self.camera.to_config();
         }
+        scene
     }
 
     pub fn import_scene(&mut self, path: &Path) {
         match crate::scene::loader::load_scene(path) {
-            Ok(scene) => {
-                let mut count = scene.shapes.len();
-                self.shapes.extend(scene.shapes);
-                for model_ref in &scene.models {
-                    match crate::model::obj_loader::load_obj(
-                        &model_ref.path,
-                        model_ref.position,
-                        model_ref.scale,
-                        &model_ref.material,
-                    ) {
-                        Ok(triangles) => {
-                            count += triangles.len();
-                            self.shapes.extend(triangles);
-                        }
-                        Err(e) => {
-                            log::error!("Failed to load model '{}': {e:#}", model_ref.path)
-                        }
+            Ok(mut scene) => {
+                // Imported textures are relative to the imported file, not the open scene.
+                for shape in &mut scene.shapes {
+                    if let Some(tex) = &shape.texture {
+                        shape.texture = Some(resolve_resource_path(&scene.dir, tex));
                     }
                 }
+                let (shapes, missing_models) = Self::load_scene_shapes(&scene);
+                self.ui_state.missing_assets.extend(missing_models);
+                if self.ui_state.import_scene_camera {
+                    self.camera = Camera::from_config(&scene.camera);
+                    self.ui_state.sync_from_camera(&self.camera);
+                }
+                let count = shapes.len();
+                self.shapes.extend(shapes);
                 self.ui_state.paused = false;
                 self.rebuild_scene_buffers_with_textures();
                 self.accumulator.reset();
@@ -153,22 +477,97 @@ impl AppState {
         }
     }
 
+    /// Use the image at `path` as the selected shape's texture. Like the
+    /// editor, the texture applies to the whole group of a named mesh.
+    pub fn set_selected_texture(&mut self, path: &Path) {
+        let Some(idx) = self
+            .ui_state
+            .selected_shape
+            .filter(|&i| i < self.shapes.len())
+        else {
+            log::warn!("Select a shape before dropping a texture on it");
+            return;
+        };
+        let texture = Some(path.to_string_lossy().into_owned());
+        let group = self.shapes[idx]
+            .name
+            .clone()
+            .filter(|n| !n.is_empty() && self.shapes[idx].shape_type == ShapeType::Triangle);
+        for (i, shape) in self.shapes.iter_mut().enumerate() {
+            let in_group =
+                group.is_some() && shape.shape_type == ShapeType::Triangle && shape.name == group;
+            if i == idx || in_group {
+                shape.texture = texture.clone();
+            }
+        }
+        self.rebuild_scene_buffers_with_textures();
+        self.accumulator.reset();
+        log::info!("Set texture {} on shape {idx}", path.display());
+    }
+
+    /// Point a missing texture or model at the file the user picked and load it.
+    pub fn relocate_asset(&mut self, asset: MissingAsset, path: &Path) {
+        let new_path = path.to_string_lossy().into_owned();
+        match asset {
+            MissingAsset::Texture(old_path) => {
+                for shape in &mut self.shapes {
+                    if shape.texture.as_deref() == Some(old_path.as_str()) {
+                        shape.texture = Some(new_path.clone());
+                    }
+                }
+                log::info!("Relocated texture '{old_path}' to {}", path.display());
+            }
+            MissingAsset::Model(model_ref) => {
+                let relocated = ModelRef {
+                    path: new_path,
+                    ..model_ref.clone()
+                };
+                match Self::load_model_ref(&relocated, &self.scene.dir, self.scene.up_axis) {
+                    Ok(triangles) => {
+                        self.shapes.extend(triangles);
+                        self.ui_state
+                            .missing_assets
+                            .retain(|a| *a != MissingAsset::Model(model_ref.clone()));
+                        log::info!("Relocated model '{}' to {}", model_ref.path, path.display());
+                    }
+                    Err(e) => {
+                        log::error!("{e:#}");
+                        self.ui_state.error_message = Some(format!("{e:#}"));
+                        return;
+                    }
+                }
+            }
+        }
+        self.ui_state.paused = false;
+        self.rebuild_scene_buffers_with_textures();
+        self.accumulator.reset();
+    }
+
     pub fn import_model(&mut self, path: &Path) {
         let path_str = path.to_string_lossy();
 
-        let (_, _, forward) = self.camera.basis_vectors();
-        let spawn_distance = MODEL_AUTO_SCALE_TARGET * 2.0;
-        let position: [f32; 3] = (self.camera.position + forward * spawn_distance).into();
+        let position: [f32; 3] = self.spawn_position(MODEL_AUTO_SCALE_TARGET).into();
 
         match crate::model::obj_loader::load_obj_auto_scaled(
             &path_str,
             position,
             MODEL_AUTO_SCALE_TARGET,
+            self.ui_state.model_up_axis,
             &Material::default(),
         ) {
-            Ok(triangles) => {
+            Ok(mut triangles) => {
+                if self.ui_state.merge_coplanar_on_import {
+                    let merged = crate::model::cleanup::merge_coplanar(&mut triangles);
+                    log::info!("Merged away {merged} split coplanar triangles");
+                }
                 let count = triangles.len();
+                let ground = self
+                    .ui_state
+                    .ground_plane_on_import
+                    .then(|| crate::model::obj_loader::ground_plane_under(&triangles))
+                    .flatten();
                 self.shapes.extend(triangles);
+                self.shapes.extend(ground);
                 self.ui_state.paused = false;
                 self.rebuild_scene_buffers_with_textures();
                 self.accumulator.reset();
@@ -178,3 +577,48 @@ impl AppState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bake_discarded_after_scene_edits() {
+        let triangle = |x: f32| Shape {
+            v0: [x, 0.0, 0.0],
+            v1: [x + 1.0, 0.0, 0.0],
+            v2: [x, 1.0, 0.0],
+            ..ShapeType::Triangle.new_default_shape([0.0; 3])
+        };
+        let shapes = vec![
+            ShapeType::Sphere.new_default_shape([0.0; 3]),
+            triangle(0.0),
+            triangle(1.0),
+        ];
+        let (_sender, receiver) = mpsc::channel();
+        let job = BakeJob::new(vec![1, 2], &shapes, receiver);
+        assert!(job.matches(&shapes));
+
+        // Editing a shape outside the mesh keeps the bake.
+        let mut edited = shapes.clone();
+        edited[0].radius = 3.0;
+        assert!(job.matches(&edited));
+
+        // One shape deleted and another added: same count, shifted indices.
+        let mut replaced = shapes.clone();
+        replaced.remove(0);
+        replaced.push(ShapeType::Cube.new_default_shape([0.0; 3]));
+        assert_eq!(replaced.len(), shapes.len());
+        assert!(!job.matches(&replaced));
+
+        let mut reordered = shapes.clone();
+        reordered.swap(0, 1);
+        assert!(!job.matches(&reordered));
+
+        let mut moved = shapes.clone();
+        moved[2].v0 = [5.0, 0.0, 0.0];
+        assert!(!job.matches(&moved));
+
+        assert!(!job.matches(&shapes[..2]));
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::constants::USER_BSDF_MODULE;
+
+use super::state::AppState;
+
+impl AppState {
+    /// Recompile the path tracer with the user BSDF module edited in the
+    /// shader console. If it fails to compile, the running pipeline and the
+    /// previously applied module stay and the error is shown in the console.
+    pub fn apply_user_bsdf(&mut self) {
+        let previous = self
+            .user_bsdf
+            .replace(self.ui_state.user_bsdf_source.clone());
+        match self.compile_path_trace_pipeline() {
+            Ok(()) => {
+                log::info!("Path tracer recompiled with the edited {USER_BSDF_MODULE} module");
+                self.ui_state.shader_console_error = None;
+                self.accumulator.reset();
+            }
+            Err(e) => {
+                log::warn!("Edited {USER_BSDF_MODULE} module rejected: {e:#}");
+                self.user_bsdf = previous;
+                self.ui_state.shader_console_error = Some(format!("{e:#}"));
+            }
+        }
+    }
+
+    /// Drop the edits and recompile with the module on disk.
+    pub fn revert_user_bsdf(&mut self) {
+        self.user_bsdf = None;
+        self.ui_state.shader_console_error = None;
+        match self.shader_composer() {
+            Ok(composer) => {
+                self.ui_state.user_bsdf_source = composer
+                    .module(USER_BSDF_MODULE)
+                    .unwrap_or_default()
+                    .to_string();
+            }
+            Err(e) => log::error!("Failed to reload shaders: {e:#}"),
+        }
+        self.rebuild_path_trace_pipeline();
+        self.accumulator.reset();
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use super::state::AppState;
+
+impl AppState {
+    /// Render only the selected shape (its whole mesh for a named triangle),
+    /// or the full scene again. The shape list is untouched; solo only
+    /// leaves the other shapes out of the GPU scene.
+    pub fn toggle_solo(&mut self) {
+        self.solo = match self.solo {
+            Some(_) => None,
+            None if self.ui_state.selected_shape.is_some() => self.ui_state.selected_shape,
+            None => return,
+        };
+        self.apply_solo();
+    }
+
+    /// Follow the selection while solo is on, ending solo when nothing is
+    /// selected any more.
+    pub fn update_solo(&mut self) {
+        if self.solo.is_some() && self.solo != self.ui_state.selected_shape {
+            self.solo = self.ui_state.selected_shape;
+            self.apply_solo();
+        }
+    }
+
+    /// Indices of the shapes solo mode shows, sorted, or `None` when it is off.
+    pub fn solo_members(&self) -> Option<Vec<usize>> {
+        let anchor = self.solo.filter(|&idx| idx < self.shapes.len())?;
+        let mesh = self.selected_mesh();
+        Some(if mesh.contains(&anchor) {
+            mesh
+        } else {
+            vec![anchor]
+        })
+    }
+
+    fn apply_solo(&mut self) {
+        self.ui_state.solo = self.solo.is_some();
+        self.rebuild_scene_buffers();
+        self.accumulator.reset();
+    }
+}
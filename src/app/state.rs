@@ -1,10 +1,10 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, mpsc};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use bytemuck::Zeroable;
@@ -12,27 +12,44 @@ use winit::dpi::PhysicalSize;
 use winit::event_loop::ActiveEventLoop;
 use winit::window::{Icon, Window};
 
-use crate::accel::aabb::shape_aabb;
-use crate::accel::bvh::Bvh;
+use crate::accel::aabb::{Aabb, shape_aabb};
+use crate::accel::bvh::{Bvh, BvhParams};
+use crate::accel::tlas::{BlasCache, TriangleGroup};
 use crate::camera::camera::Camera;
 use crate::camera::controller::CameraController;
+use crate::camera::path::CameraPose;
+use crate::cli::CliArgs;
 use crate::constants::*;
-use crate::gpu::buffers;
+use crate::gpu::buffers::{self, GpuMemoryUsage};
 use crate::gpu::context::GpuContext;
 use crate::io::texture_atlas::TextureAtlas;
+use crate::model::library::ModelLibrary;
 use crate::render::accumulator::Accumulator;
+use crate::render::auto_exposure::AutoExposure;
+use crate::render::blue_noise::blue_noise;
+use crate::render::frame::{
+    BlitParams, RenderRegion, ShaderFeatures, WorkgroupSize, define_shared_constants,
+};
+use crate::render::pixel_probe::PixelProbe;
 use crate::render::post_process::PostEffect;
-use crate::scene::material::GpuMaterial;
-use crate::scene::scene::Scene;
+use crate::scene::diagnostics::MissingAsset;
+use crate::scene::lights::{self, GpuAreaLight, GpuLight, LightGroup};
+use crate::scene::material::{GpuMaterial, MaterialTable};
+use crate::scene::scene::{CameraConfig, CameraKeyframe, ModelRef, Scene, UpAxis};
 use crate::scene::shape::{GpuShape, Shape, ShapeType};
+use crate::settings::Settings;
 use crate::shaders::composer::ShaderComposer;
 use crate::ui;
 
+use super::scene_ops::BakeJob;
+
 pub enum FileDialogResult {
     OpenScene(PathBuf),
     ImportScene(PathBuf),
     ImportModel(PathBuf),
     Screenshot(PathBuf),
+    /// New location picked for a file the scene could not load.
+    RelocateAsset(MissingAsset, PathBuf),
 }
 
 pub struct AppState {
@@ -41,23 +58,55 @@ pub struct AppState {
     pub file_dialog_tx: mpsc::Sender<FileDialogResult>,
     pub gpu: GpuContext,
     pub scene: Scene,
+    /// File stem of the open scene, for screenshot names and metadata.
+    pub scene_name: Option<String>,
+    /// File the open scene was loaded from or last saved to.
+    pub scene_path: Option<PathBuf>,
+    /// Camera and render settings as the open scene file gave them, for the
+    /// reset actions.
+    pub initial_camera: CameraConfig,
+    /// Fly-through keyframes, sorted by time.
+    pub camera_path: Vec<CameraKeyframe>,
+    /// When camera path playback started, while it runs.
+    pub path_playback_start: Option<Instant>,
+    /// Selected shape whose mesh alone is rendered, while solo mode is on.
+    pub solo: Option<usize>,
+    /// Camera to go back to while looking through a light.
+    pub light_view_return: Option<CameraPose>,
+    /// User BSDF module applied from the shader console, replacing the one
+    /// on disk in every path tracer rebuild.
+    pub user_bsdf: Option<String>,
+    /// Lighting bake running on a worker thread, applied by `poll_bake`.
+    pub bake_job: Option<BakeJob>,
     pub shapes: Vec<Shape>,
     pub compute_pipeline: wgpu::ComputePipeline,
     pub blit_pipeline: wgpu::RenderPipeline,
     pub post_process_pipeline: wgpu::ComputePipeline,
     pub camera_buffer: wgpu::Buffer,
     pub accumulation_buffer: wgpu::Buffer,
+    /// Shape seen through each pixel, for the selection outline.
+    pub object_id_buffer: wgpu::Buffer,
     pub shape_buffer: wgpu::Buffer,
     pub material_buffer: wgpu::Buffer,
     pub bvh_node_buffer: wgpu::Buffer,
     pub bvh_prim_buffer: wgpu::Buffer,
     pub light_index_buffer: wgpu::Buffer,
+    /// Delta lights (spot lights), see `scene::lights::GpuLight`.
+    pub light_buffer: wgpu::Buffer,
     pub infinite_index_buffer: wgpu::Buffer,
     pub infinite_indices: Vec<u32>,
     pub tex_pixels_buffer: wgpu::Buffer,
     pub tex_infos_buffer: wgpu::Buffer,
+    /// Texels of HDR textures, see `TextureAtlas::float_pixels`.
+    pub tex_float_pixels_buffer: wgpu::Buffer,
+    /// `render::blue_noise` mask for blue-noise pixel jitter; never changes.
+    pub blue_noise_buffer: wgpu::Buffer,
     pub texture_atlas: TextureAtlas,
     pub tex_path_cache: HashMap<String, i32>,
+    /// Internal resolution of `output_texture` and the accumulation buffer;
+    /// equals the window size unless capped by `max_render_size`.
+    pub render_width: u32,
+    pub render_height: u32,
     pub output_texture: wgpu::Texture,
     pub output_view: wgpu::TextureView,
     pub compute_bind_group_0: wgpu::BindGroup,
@@ -70,15 +119,26 @@ pub struct AppState {
     pub post_bg_layout: wgpu::BindGroupLayout,
     pub post_params_buffer: wgpu::Buffer,
     pub blit_sampler: wgpu::Sampler,
+    /// `[sharpness, 0, 0, 0]` for the blit pass; sharpness 0 = plain bilinear.
+    pub blit_params_buffer: wgpu::Buffer,
     pub bvh: Bvh,
+    /// Models shared by the scene's `Instance` shapes.
+    pub models: ModelLibrary,
+    /// Per-mesh BVHs reused across rebuilds; see `build_bvh`.
+    pub blas_cache: BlasCache,
     pub camera: Camera,
     pub controller: CameraController,
     pub accumulator: Accumulator,
+    pub auto_exposure: AutoExposure,
+    /// Readback of the pixel under the crosshair inspector.
+    pub pixel_probe: PixelProbe,
     pub drag_shape: Option<usize>,
     pub drag_depth: f32,
     pub drag_offset: glam::Vec3,
     pub drag_moved: bool,
     pub drag_start_pos: (f32, f32),
+    /// World-space surface point of the last click that hit a shape.
+    pub last_click_point: Option<glam::Vec3>,
     pub egui_ctx: egui::Context,
     pub egui_state: egui_winit::State,
     pub egui_renderer: egui_wgpu::Renderer,
@@ -86,11 +146,108 @@ pub struct AppState {
     pub last_frame: Instant,
     pub last_acquire_time: Instant,
     pub frame_index: u32,
+    /// Samples this frame runs, adapted to the frame time up to
+    /// `ui_state.samples_per_frame`.
+    pub frame_samples: u32,
     pub active_effects: Vec<PostEffect>,
+    pub preview_target: PreviewTarget,
+    pub workgroup_size: WorkgroupSize,
+    /// The last frame was rendered into `preview_target` (camera moving with
+    /// dynamic resolution on).
+    pub preview_active: bool,
+    /// The post-process uniform changed since the post pass last ran, so it
+    /// must run again even if no new sample is traced.
+    pub post_pending: bool,
+    /// Settings loaded at startup; updated by explicit preference changes.
+    pub settings: Settings,
+    /// UI pixels per point in effect, `None` following the display.
+    pub ui_scale: Option<f32>,
+    /// Window reported `Occluded(true)` (fully covered or on another workspace).
+    pub occluded: bool,
+    /// Window was resized to zero, which is how minimizing shows up on most platforms.
+    pub minimized: bool,
+    /// When the window last changed size, while the render targets still
+    /// have the previous one (see `finish_resize`).
+    pub resized_at: Option<Instant>,
+    /// Last failed attempt to replace a lost GPU device, while it stays lost.
+    pub device_recovery_failed_at: Option<Instant>,
+    /// Where the scene was saved when the device could not be recreated.
+    pub recovery_scene_path: Option<PathBuf>,
+}
+
+/// Reduced-resolution accumulation/output pair rendered into while the camera
+/// moves (dynamic resolution). The blit pass upscales it to the surface.
+pub struct PreviewTarget {
+    pub width: u32,
+    pub height: u32,
+    pub accumulation_buffer: wgpu::Buffer,
+    pub object_id_buffer: wgpu::Buffer,
+    pub output_texture: wgpu::Texture,
+    pub output_view: wgpu::TextureView,
+    pub compute_bind_group_0: wgpu::BindGroup,
+    pub blit_bind_group: wgpu::BindGroup,
+}
+
+impl PreviewTarget {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        full_width: u32,
+        full_height: u32,
+        compute_bg_layout_0: &wgpu::BindGroupLayout,
+        blit_bg_layout: &wgpu::BindGroupLayout,
+        camera_buf: &wgpu::Buffer,
+        sampler: &wgpu::Sampler,
+        blit_params_buf: &wgpu::Buffer,
+    ) -> Self {
+        let width = (full_width / DYNAMIC_RESOLUTION_DIVISOR).max(1);
+        let height = (full_height / DYNAMIC_RESOLUTION_DIVISOR).max(1);
+        let accum_size = (width * height) as u64 * ACCUM_BYTES_PER_PIXEL;
+        let accumulation_buffer =
+            buffers::create_empty_storage_buffer(device, accum_size, "preview accumulation");
+        let object_id_buffer = buffers::create_empty_storage_buffer(
+            device,
+            (width * height) as u64 * OBJECT_ID_BYTES_PER_PIXEL,
+            "preview object ids",
+        );
+        let (output_texture, output_view) =
+            buffers::create_output_texture(device, width, height, "preview output");
+        let compute_bind_group_0 = AppState::create_compute_bg0(
+            device,
+            compute_bg_layout_0,
+            camera_buf,
+            &accumulation_buffer,
+            &object_id_buffer,
+            &output_view,
+        );
+        let blit_bind_group = AppState::create_blit_bind_group(
+            device,
+            blit_bg_layout,
+            &output_view,
+            sampler,
+            blit_params_buf,
+            &object_id_buffer,
+        );
+        Self {
+            width,
+            height,
+            accumulation_buffer,
+            object_id_buffer,
+            output_texture,
+            output_view,
+            compute_bind_group_0,
+            blit_bind_group,
+        }
+    }
+}
+
+/// File stem of a scene path, e.g. "demo" for `resources/scenes/demo.yaml`.
+pub fn scene_stem(path: impl AsRef<Path>) -> Option<String> {
+    Some(path.as_ref().file_stem()?.to_string_lossy().into_owned())
 }
 
 impl AppState {
-    pub fn new(event_loop: &ActiveEventLoop, scene_path: &Option<String>) -> Result<Self> {
+    pub fn new(event_loop: &ActiveEventLoop, cli: &CliArgs, settings: &Settings) -> Result<Self> {
         let mut attrs = Window::default_attributes()
             .with_title("PathTracer")
             .with_inner_size(PhysicalSize::new(
@@ -107,45 +264,75 @@ impl AppState {
         }
 
         let window = Arc::new(event_loop.create_window(attrs)?);
-        let gpu = GpuContext::new(window.clone())?;
-        let width = gpu.width();
-        let height = gpu.height();
+        // A --present-mode flag applies to this run only and is not persisted.
+        let gpu = GpuContext::new(
+            window.clone(),
+            cli.present_mode.unwrap_or(settings.present_mode),
+        )?;
+        let target_size = Self::supersampled_size(&gpu, settings.supersampling);
+        let (width, height) =
+            Self::compute_render_size(&gpu, settings.max_render_size, settings.supersampling);
+        if (width, height) != target_size {
+            log::warn!(
+                "Window is {}x{}, rendering at {width}x{height} and upscaling",
+                gpu.width(),
+                gpu.height()
+            );
+        }
 
-        let scene = if let Some(path) = scene_path {
-            crate::scene::loader::load_scene(Path::new(path))?
+        let mut scene_path = cli.scene_path.as_ref().map(PathBuf::from);
+        let scene = if let Some(path) = &scene_path {
+            crate::scene::loader::load_scene(path)?
+        } else if let Some(path) = settings.scene_to_reopen() {
+            // Unlike a scene given on the command line, a broken last scene
+            // mustn't keep the app from starting.
+            match crate::scene::loader::load_scene(path) {
+                Ok(scene) => {
+                    log::info!("Reopened the last scene {}", path.display());
+                    scene_path = Some(path.to_path_buf());
+                    scene
+                }
+                Err(e) => {
+                    log::warn!("Failed to reopen the last scene: {e:#}");
+                    Scene::empty()
+                }
+            }
         } else {
             Scene::empty()
         };
 
         let camera = Camera::from_config(&scene.camera);
 
-        let mut shapes = scene.shapes.clone();
-        for model_ref in &scene.models {
-            match crate::model::obj_loader::load_obj(
-                &model_ref.path,
-                model_ref.position,
-                model_ref.scale,
-                &model_ref.material,
-            ) {
-                Ok(triangles) => {
-                    log::info!(
-                        "Loaded model '{}': {} triangles",
-                        model_ref.path,
-                        triangles.len()
-                    );
-                    shapes.extend(triangles);
-                }
-                Err(e) => log::error!("Failed to load model '{}': {e:#}", model_ref.path),
-            }
-        }
-
-        let (texture_atlas, tex_path_cache) = Self::build_texture_atlas(&shapes);
-        let (gpu_shapes, gpu_materials, light_indices) =
-            Self::build_gpu_data(&shapes, &tex_path_cache);
+        let (shapes, missing_models) = Self::load_scene_shapes(&scene);
+        let bvh_params = cli.bvh_params();
+        let mut models = ModelLibrary::default();
+        models.set_bvh_params(bvh_params);
+        models.sync(&shapes, &scene.dir, scene.up_axis);
+
+        let (texture_atlas, tex_path_cache) =
+            Self::build_texture_atlas(shapes.iter().chain(models.triangles()), &scene.dir);
+        let light_groups = lights::collect_light_groups(&shapes, &[]);
+        let mut blas_cache = BlasCache::default();
+        let bvh_start = Instant::now();
+        let (bvh, infinite_indices, blas_roots) =
+            Self::build_bvh(&shapes, &models, &mut blas_cache, bvh_params, None);
+        let bvh_build_ms = bvh_start.elapsed().as_secs_f32() * 1000.0;
+        let (gpu_shapes, gpu_materials, light_indices, delta_lights) = Self::build_gpu_data(
+            &shapes,
+            &models,
+            &blas_roots,
+            &tex_path_cache,
+            &light_groups,
+            None,
+        );
 
-        let (bvh, infinite_indices) = Self::build_bvh(&shapes);
+        let workgroup_size = WorkgroupSize::resolve(cli.workgroup, &gpu.device.limits());
+        log::info!("Compute workgroup size: {workgroup_size}");
 
-        let composer = ShaderComposer::from_directory(&ShaderComposer::shader_dir())?;
+        let mut composer = ShaderComposer::from_directory(&ShaderComposer::shader_dir())?;
+        workgroup_size.define_constants(&mut composer);
+        define_shared_constants(&mut composer);
+        ShaderFeatures::default().define(&mut composer);
         let trace_source = composer.compose("path_trace")?;
         let blit_source = composer.compose("blit")?;
         let post_source = composer.compose("post_process")?;
@@ -156,6 +343,11 @@ impl AppState {
         let accum_size = (width * height) as u64 * ACCUM_BYTES_PER_PIXEL;
         let accumulation_buffer =
             buffers::create_empty_storage_buffer(&gpu.device, accum_size, "accumulation");
+        let object_id_buffer = buffers::create_empty_storage_buffer(
+            &gpu.device,
+            (width * height) as u64 * OBJECT_ID_BYTES_PER_PIXEL,
+            "object ids",
+        );
 
         let (output_texture, output_view) =
             buffers::create_output_texture(&gpu.device, width, height, "output");
@@ -167,6 +359,7 @@ impl AppState {
             bvh_prim_buffer,
             light_index_buffer,
             infinite_index_buffer,
+            light_buffer,
         ) = Self::create_geometry_buffers(
             &gpu.device,
             &gpu_shapes,
@@ -174,15 +367,26 @@ impl AppState {
             &bvh,
             &light_indices,
             &infinite_indices,
-        );
+            &delta_lights,
+        )?;
 
-        let tex_pixels_buffer =
-            buffers::create_storage_buffer(&gpu.device, &texture_atlas.pixels, "tex_pixels", true);
-        let tex_infos_buffer =
-            buffers::create_storage_buffer(&gpu.device, &texture_atlas.infos, "tex_infos", true);
+        let (tex_pixels_buffer, tex_infos_buffer, tex_float_pixels_buffer) =
+            Self::create_texture_buffers(&gpu.device, &texture_atlas)?;
+        let blue_noise_buffer = buffers::create_storage_buffer(
+            &gpu.device,
+            &blue_noise(BLUE_NOISE_SIZE),
+            "blue_noise",
+            true,
+        );
 
-        let post_params =
-            Self::build_post_params(width, height, &[], DEFAULT_OIL_RADIUS, DEFAULT_COMIC_LEVELS);
+        let post_params = Self::build_post_params(
+            width,
+            height,
+            &scene.post_effects.effects,
+            scene.post_effects.oil_radius,
+            scene.post_effects.comic_levels,
+            None,
+        );
         let post_params_buffer =
             buffers::create_uniform_buffer(&gpu.device, &post_params, "post_params");
 
@@ -217,6 +421,7 @@ impl AppState {
             &compute_bg_layout_0,
             &camera_buffer,
             &accumulation_buffer,
+            &object_id_buffer,
             &output_view,
         );
 
@@ -231,16 +436,23 @@ impl AppState {
             &tex_pixels_buffer,
             &tex_infos_buffer,
             &infinite_index_buffer,
+            &light_buffer,
+            &blue_noise_buffer,
+            &tex_float_pixels_buffer,
         );
 
-        let blit_sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor {
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        });
+        let blit_sampler = Self::create_blit_sampler(&gpu.device);
 
-        let blit_bind_group =
-            Self::create_blit_bind_group(&gpu.device, &blit_bg_layout, &output_view, &blit_sampler);
+        let blit_params_buffer =
+            buffers::create_uniform_buffer(&gpu.device, &BlitParams::default(), "blit_params");
+        let blit_bind_group = Self::create_blit_bind_group(
+            &gpu.device,
+            &blit_bg_layout,
+            &output_view,
+            &blit_sampler,
+            &blit_params_buffer,
+            &object_id_buffer,
+        );
         let post_bind_group = Self::create_post_bind_group(
             &gpu.device,
             &post_bg_layout,
@@ -248,6 +460,16 @@ impl AppState {
             &accumulation_buffer,
             &output_view,
         );
+        let preview_target = PreviewTarget::new(
+            &gpu.device,
+            width,
+            height,
+            &compute_bg_layout_0,
+            &blit_bg_layout,
+            &camera_buffer,
+            &blit_sampler,
+            &blit_params_buffer,
+        );
 
         let egui_ctx = egui::Context::default();
         let egui_state = egui_winit::State::new(
@@ -262,11 +484,61 @@ impl AppState {
             egui_wgpu::Renderer::new(&gpu.device, gpu.surface_format(), None, 1, false);
 
         let mut ui_state = ui::UiState {
-            paused: shapes.is_empty(),
+            paused: false,
             example_scenes: crate::constants::discover_example_scenes(),
+            present_mode: gpu.present_mode,
+            supported_present_modes: gpu.supported_present_modes.clone(),
+            fps_cap: settings.fps_cap,
+            samples_per_frame: settings.samples_per_frame.clamp(1, MAX_SAMPLES_PER_FRAME),
+            dynamic_resolution: settings.dynamic_resolution,
+            auto_exposure: settings.auto_exposure,
+            sharpen_upscale: settings.sharpen_upscale,
+            keep_samples_on_resize: settings.keep_samples_on_resize,
+            max_render_size: settings.max_render_size,
+            supersampling: settings.supersampling,
+            clamped_render_size: ((width, height) != target_size).then_some((width, height)),
+            mouse_sensitivity: settings.effective_mouse_sensitivity(),
+            move_speed: settings.move_speed,
+            invert_y: settings.invert_y,
+            grid_snap: settings.grid_snap,
+            spawn_placement: settings.spawn_placement,
+            spawn_distance: settings.spawn_distance,
+            jitter_pattern: settings.jitter_pattern,
+            ui_scale: cli.ui_scale.or(settings.ui_scale),
+            color_space: settings.color_space,
+            headlight: settings.headlight,
+            reopen_last_scene: settings.reopen_last_scene,
+            bvh_params,
+            bvh_stats: bvh.stats(),
+            bvh_build_ms,
             ..Default::default()
         };
         ui_state.sync_from_camera(&camera);
+        ui_state.camera_names = scene.camera_names();
+        ui_state.scene_camera = scene.camera.clone();
+        ui_state.keyframe_times = scene.camera_path.iter().map(|key| key.time).collect();
+        ui_state.model_up_axis = scene.up_axis;
+        let active_effects = scene.post_effects.effects.clone();
+        ui_state.active_effects = active_effects.clone();
+        ui_state.oil_radius = scene.post_effects.oil_radius;
+        ui_state.comic_levels = scene.post_effects.comic_levels;
+        ui_state.scene_issues = crate::scene::diagnostics::diagnose(&shapes, &camera);
+        ui_state.light_groups = light_groups;
+        ui_state.missing_assets = missing_models;
+        if cli.dev {
+            ui_state.dev_mode = true;
+            ui_state.composed_trace_source = trace_source.clone();
+            ui_state.user_bsdf_source = composer
+                .module(USER_BSDF_MODULE)
+                .unwrap_or_default()
+                .to_string();
+        }
+        ui_state
+            .missing_assets
+            .extend(crate::scene::diagnostics::missing_textures(
+                &shapes,
+                &tex_path_cache,
+            ));
 
         let (file_dialog_tx, file_dialog_rx) = mpsc::channel();
 
@@ -275,6 +547,15 @@ impl AppState {
             file_dialog_rx,
             file_dialog_tx,
             gpu,
+            initial_camera: scene.camera.clone(),
+            camera_path: scene.camera_path.clone(),
+            path_playback_start: None,
+            solo: None,
+            light_view_return: None,
+            user_bsdf: None,
+            bake_job: None,
+            scene_name: scene_path.as_deref().and_then(scene_stem),
+            scene_path,
             scene,
             shapes,
             compute_pipeline,
@@ -282,15 +563,19 @@ impl AppState {
             post_process_pipeline,
             camera_buffer,
             accumulation_buffer,
+            object_id_buffer,
             shape_buffer,
             material_buffer,
             bvh_node_buffer,
             bvh_prim_buffer,
             light_index_buffer,
+            light_buffer,
             infinite_index_buffer,
             infinite_indices,
             tex_pixels_buffer,
             tex_infos_buffer,
+            tex_float_pixels_buffer,
+            blue_noise_buffer,
             texture_atlas,
             tex_path_cache,
             output_texture,
@@ -305,15 +590,29 @@ impl AppState {
             post_bg_layout,
             post_params_buffer,
             blit_sampler,
+            blit_params_buffer,
             bvh,
+            models,
+            blas_cache,
             camera,
-            controller: CameraController::new(),
+            controller: CameraController::new(settings),
+            settings: settings.clone(),
+            ui_scale: cli.ui_scale.or(settings.ui_scale),
+            preview_target,
+            render_width: width,
+            render_height: height,
+            workgroup_size,
+            preview_active: false,
+            post_pending: false,
             accumulator: Accumulator::default(),
+            auto_exposure: AutoExposure::default(),
+            pixel_probe: PixelProbe::default(),
             drag_shape: None,
             drag_depth: 0.0,
             drag_offset: glam::Vec3::ZERO,
             drag_moved: false,
             drag_start_pos: (0.0, 0.0),
+            last_click_point: None,
             egui_ctx,
             egui_state,
             egui_renderer,
@@ -321,59 +620,182 @@ impl AppState {
             last_frame: Instant::now(),
             last_acquire_time: Instant::now(),
             frame_index: 0,
-            active_effects: Vec::new(),
+            frame_samples: 1,
+            active_effects,
+            occluded: false,
+            minimized: false,
+            resized_at: None,
+            device_recovery_failed_at: None,
+            recovery_scene_path: None,
         })
     }
 
-    pub fn build_texture_atlas(shapes: &[Shape]) -> (TextureAtlas, HashMap<String, i32>) {
-        let mut atlas = TextureAtlas::new();
-        let mut cache: HashMap<String, i32> = HashMap::new();
-
-        for shape in shapes {
-            if let Some(ref tex_path) = shape.texture
-                && !cache.contains_key(tex_path)
-            {
-                match atlas.load_texture(Path::new(tex_path)) {
-                    Ok(id) => {
-                        cache.insert(tex_path.clone(), id as i32);
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to load texture '{}': {e:#}", tex_path);
-                    }
+    /// Scene shapes plus the triangles of every referenced model. Model paths
+    /// are resolved against the scene's directory; models that fail to load
+    /// are logged, skipped and returned as missing assets.
+    pub fn load_scene_shapes(scene: &Scene) -> (Vec<Shape>, Vec<MissingAsset>) {
+        let mut shapes = scene.shapes.clone();
+        let mut missing = Vec::new();
+        for model_ref in &scene.models {
+            match Self::load_model_ref(model_ref, &scene.dir, scene.up_axis) {
+                Ok(triangles) => shapes.extend(triangles),
+                Err(e) => {
+                    log::error!("Failed to load model '{}': {e:#}", model_ref.path);
+                    missing.push(MissingAsset::Model(model_ref.clone()));
                 }
             }
         }
+        (shapes, missing)
+    }
+
+    /// Triangles of one referenced model, its path resolved against `scene_dir`.
+    pub fn load_model_ref(
+        model_ref: &ModelRef,
+        scene_dir: &Path,
+        scene_up_axis: UpAxis,
+    ) -> Result<Vec<Shape>> {
+        let triangles = crate::model::obj_loader::load_obj(
+            &resolve_resource_path(scene_dir, &model_ref.path),
+            model_ref.position,
+            model_ref.scale,
+            model_ref.up_axis.unwrap_or(scene_up_axis),
+            &model_ref.material,
+        )?;
+        log::info!(
+            "Loaded model '{}': {} triangles",
+            model_ref.path,
+            triangles.len()
+        );
+        Ok(triangles)
+    }
+
+    /// Replace the texture entries of the missing-asset list with the shape
+    /// textures that failed to load this time.
+    pub fn refresh_missing_textures(&mut self) {
+        let missing = &mut self.ui_state.missing_assets;
+        missing.retain(|asset| matches!(asset, MissingAsset::Model(_)));
+        missing.extend(crate::scene::diagnostics::missing_textures(
+            &self.shapes,
+            &self.tex_path_cache,
+        ));
+    }
+
+    /// Load every shape texture into one atlas. Paths are resolved against
+    /// `scene_dir`; the returned cache is keyed by the path as written on the shape.
+    pub fn build_texture_atlas<'a>(
+        shapes: impl IntoIterator<Item = &'a Shape>,
+        scene_dir: &Path,
+    ) -> (TextureAtlas, HashMap<String, i32>) {
+        Self::update_texture_atlas(&TextureAtlas::new(), shapes, scene_dir)
+    }
+
+    /// Like `build_texture_atlas`, but textures already in `previous` are
+    /// reused instead of decoded again.
+    pub fn update_texture_atlas<'a>(
+        previous: &TextureAtlas,
+        shapes: impl IntoIterator<Item = &'a Shape>,
+        scene_dir: &Path,
+    ) -> (TextureAtlas, HashMap<String, i32>) {
+        let mut resolved: HashMap<&str, PathBuf> = HashMap::new();
+        let mut paths = Vec::new();
+        for tex_path in shapes.into_iter().filter_map(|s| s.texture.as_deref()) {
+            if !resolved.contains_key(tex_path) {
+                let path = PathBuf::from(resolve_resource_path(scene_dir, tex_path));
+                paths.push(path.clone());
+                resolved.insert(tex_path, path);
+            }
+        }
 
+        let (atlas, ids) = previous.rebuilt_with(&paths);
+        let cache = resolved
+            .into_iter()
+            .filter_map(|(tex_path, path)| Some((tex_path.to_string(), *ids.get(&path)? as i32)))
+            .collect();
         (atlas, cache)
     }
 
+    /// Convert shapes to GPU structs. Emission of each light is scaled by its
+    /// light group's multiplier; lights switched off are left out of `light_indices`,
+    /// and the rest are weighted by their power for NEE.
+    /// Spot lights go to the dedicated delta-light list instead.
+    /// Shapes with identical materials share one material entry.
+    ///
+    /// The triangles of instanced models follow the scene shapes, with
+    /// `blas_roots` as returned by `build_bvh`. Emissive model triangles glow
+    /// when hit but are not sampled as lights.
+    ///
+    /// With `solo` (sorted shape indices), lights outside it are dark, to
+    /// match a tree built by `build_bvh` with the same shapes.
     pub fn build_gpu_data(
         shapes: &[Shape],
+        models: &ModelLibrary,
+        blas_roots: &[u32],
         tex_cache: &HashMap<String, i32>,
-    ) -> (Vec<GpuShape>, Vec<GpuMaterial>, Vec<u32>) {
+        light_groups: &[LightGroup],
+        solo: Option<&[usize]>,
+    ) -> (
+        Vec<GpuShape>,
+        Vec<GpuMaterial>,
+        Vec<GpuAreaLight>,
+        Vec<GpuLight>,
+    ) {
         let mut gpu_shapes = Vec::with_capacity(shapes.len());
-        let mut gpu_materials = Vec::with_capacity(shapes.len());
-        let mut light_indices = Vec::new();
-
-        for (i, shape) in shapes.iter().enumerate() {
+        let mut gpu_materials = MaterialTable::default();
+        let mut delta_lights = Vec::new();
+        let mut multipliers = lights::emission_multipliers(shapes, light_groups);
+        if let Some(members) = solo {
+            for (i, multiplier) in multipliers.iter_mut().enumerate() {
+                if members.binary_search(&i).is_err() {
+                    *multiplier = 0.0;
+                }
+            }
+        }
+        let roots: HashMap<&str, u32> = models
+            .iter()
+            .map(|(path, _)| path)
+            .zip(blas_roots.iter().copied())
+            .collect();
+        let material_of = |shape: &Shape| {
             let mut mat = GpuMaterial::from(&shape.material);
-
             if let Some(ref tex_path) = shape.texture
                 && let Some(&id) = tex_cache.get(tex_path)
             {
                 mat.texture_id = id;
             }
+            mat
+        };
 
-            let mat_idx = gpu_materials.len() as u32;
-            gpu_materials.push(mat);
-            gpu_shapes.push(GpuShape::from_shape(shape, mat_idx));
+        for (i, shape) in shapes.iter().enumerate() {
+            let mut mat = material_of(shape);
+            mat.emission_strength *= multipliers[i];
+
+            let mat_idx = gpu_materials.insert(mat);
+            let root = shape.model.as_deref().and_then(|path| roots.get(path));
+            gpu_shapes.push(match root {
+                Some(&root) if shape.shape_type == ShapeType::Instance => {
+                    GpuShape::instance(shape, root, mat_idx)
+                }
+                _ => GpuShape::from_shape(shape, mat_idx),
+            });
 
-            if shape.material.is_emissive() {
-                light_indices.push(i as u32);
+            if shape.shape_type == ShapeType::SpotLight && mat.emission_strength > 0.0 {
+                delta_lights.push(GpuLight::spot(shape, multipliers[i]));
             }
         }
 
-        (gpu_shapes, gpu_materials, light_indices)
+        for triangle in models.triangles() {
+            let mat_idx = gpu_materials.insert(material_of(triangle));
+            gpu_shapes.push(GpuShape::from_shape(triangle, mat_idx));
+        }
+
+        let light_indices =
+            lights::build_area_light_table(&lights::area_light_candidates(shapes, &multipliers));
+        (
+            gpu_shapes,
+            gpu_materials.into_vec(),
+            light_indices,
+            delta_lights,
+        )
     }
 
     /// wgpu requires non-empty buffers. When the list is empty, a single
@@ -386,21 +808,55 @@ impl AppState {
         }
     }
 
+    fn nonempty_area_lights(lights: &[GpuAreaLight]) -> &[GpuAreaLight] {
+        if lights.is_empty() {
+            &lights::NO_AREA_LIGHTS
+        } else {
+            lights
+        }
+    }
+
+    /// Create the scene storage buffers, or fail if any exceeds the device's
+    /// storage buffer limits (e.g. a huge imported model).
     pub fn create_geometry_buffers(
         device: &wgpu::Device,
         gpu_shapes: &[GpuShape],
         gpu_materials: &[GpuMaterial],
         bvh: &Bvh,
-        light_indices: &[u32],
+        light_indices: &[GpuAreaLight],
         infinite_indices: &[u32],
-    ) -> (
+        delta_lights: &[GpuLight],
+    ) -> Result<(
         wgpu::Buffer,
         wgpu::Buffer,
         wgpu::Buffer,
         wgpu::Buffer,
         wgpu::Buffer,
         wgpu::Buffer,
-    ) {
+        wgpu::Buffer,
+    )> {
+        buffers::check_storage_sizes(
+            &device.limits(),
+            &[
+                ("shapes", std::mem::size_of_val(gpu_shapes) as u64),
+                ("materials", std::mem::size_of_val(gpu_materials) as u64),
+                (
+                    "BVH nodes",
+                    std::mem::size_of_val(bvh.nodes.as_slice()) as u64,
+                ),
+                (
+                    "BVH primitive indices",
+                    std::mem::size_of_val(bvh.prim_indices.as_slice()) as u64,
+                ),
+                ("light indices", std::mem::size_of_val(light_indices) as u64),
+                (
+                    "infinite shape indices",
+                    std::mem::size_of_val(infinite_indices) as u64,
+                ),
+                ("lights", std::mem::size_of_val(delta_lights) as u64),
+            ],
+        )?;
+
         let shape_buffer = if gpu_shapes.is_empty() {
             buffers::create_storage_buffer(device, &[GpuShape::zeroed()], "shapes", true)
         } else {
@@ -423,7 +879,7 @@ impl AppState {
 
         let light_index_buffer = buffers::create_storage_buffer(
             device,
-            Self::nonempty_index_buffer(light_indices),
+            Self::nonempty_area_lights(light_indices),
             "light_indices",
             true,
         );
@@ -435,14 +891,52 @@ impl AppState {
             true,
         );
 
-        (
+        // A zeroed entry has type LIGHT_NONE and is skipped by the shader.
+        let light_buffer = if delta_lights.is_empty() {
+            buffers::create_storage_buffer(device, &[GpuLight::zeroed()], "lights", true)
+        } else {
+            buffers::create_storage_buffer(device, delta_lights, "lights", true)
+        };
+
+        Ok((
             shape_buffer,
             material_buffer,
             bvh_node_buffer,
             bvh_prim_buffer,
             light_index_buffer,
             infinite_index_buffer,
-        )
+            light_buffer,
+        ))
+    }
+
+    /// Upload the texture atlas, or fail if it exceeds the device's storage
+    /// buffer limits. Returns the pixel, info and float pixel buffers.
+    pub fn create_texture_buffers(
+        device: &wgpu::Device,
+        atlas: &TextureAtlas,
+    ) -> Result<(wgpu::Buffer, wgpu::Buffer, wgpu::Buffer)> {
+        buffers::check_storage_sizes(
+            &device.limits(),
+            &[
+                (
+                    "texture pixels",
+                    std::mem::size_of_val(atlas.pixels.as_slice()) as u64,
+                ),
+                (
+                    "texture infos",
+                    std::mem::size_of_val(atlas.infos.as_slice()) as u64,
+                ),
+                (
+                    "HDR texture pixels",
+                    std::mem::size_of_val(atlas.float_pixels.as_slice()) as u64,
+                ),
+            ],
+        )?;
+        Ok((
+            buffers::create_storage_buffer(device, &atlas.pixels, "tex_pixels", true),
+            buffers::create_storage_buffer(device, &atlas.infos, "tex_infos", true),
+            buffers::create_storage_buffer(device, &atlas.float_pixels, "tex_float_pixels", true),
+        ))
     }
 
     pub fn build_post_params(
@@ -451,6 +945,7 @@ impl AppState {
         effects: &[PostEffect],
         oil_radius: u32,
         comic_levels: u32,
+        compare: Option<(&[PostEffect], u32)>,
     ) -> [u32; POST_PARAMS_SIZE] {
         let mut params = [0u32; POST_PARAMS_SIZE];
         params[0] = width;
@@ -462,9 +957,67 @@ impl AppState {
             params[4 + i] = effect.as_u32();
         }
         params[12] = comic_levels;
+        // A/B compare: pixels at or right of `split_x` use the second chain.
+        let (effects_b, split_x) = compare.unwrap_or((&[], u32::MAX));
+        params[13] = split_x;
+        params[14] = effects_b.len().min(POST_PARAMS_MAX_EFFECTS) as u32;
+        for (i, effect) in effects_b.iter().take(POST_PARAMS_MAX_EFFECTS).enumerate() {
+            params[16 + i] = effect.as_u32();
+        }
         params
     }
 
+    /// Whether the post-process pass has anything to do this frame.
+    pub fn post_process_active(&self) -> bool {
+        !self.active_effects.is_empty()
+            || (self.ui_state.compare_enabled && !self.ui_state.compare_effects.is_empty())
+    }
+
+    /// Sizes of the buffers and textures that grow with the scene and the
+    /// render resolution; small uniforms are left out.
+    pub fn gpu_memory_usage(&self) -> GpuMemoryUsage {
+        let preview = &self.preview_target;
+        GpuMemoryUsage {
+            shapes: self.shape_buffer.size()
+                + self.light_index_buffer.size()
+                + self.light_buffer.size()
+                + self.infinite_index_buffer.size(),
+            materials: self.material_buffer.size(),
+            bvh: self.bvh_node_buffer.size() + self.bvh_prim_buffer.size(),
+            textures: self.tex_pixels_buffer.size()
+                + self.tex_infos_buffer.size()
+                + self.tex_float_pixels_buffer.size(),
+            render_targets: self.accumulation_buffer.size()
+                + self.object_id_buffer.size()
+                + buffers::texture_size(&self.output_texture)
+                + preview.accumulation_buffer.size()
+                + preview.object_id_buffer.size()
+                + buffers::texture_size(&preview.output_texture),
+        }
+    }
+
+    /// Rebuild the post-process uniform from the current effects and UI
+    /// settings. The post pass reruns on the next frame, so a converged or
+    /// paused image takes new effect settings without restarting accumulation.
+    pub fn upload_post_params(&mut self) {
+        let (width, height) = self.render_size();
+        let split_x = (self.ui_state.compare_split.clamp(0.0, 1.0) * width as f32) as u32;
+        let compare = self
+            .ui_state
+            .compare_enabled
+            .then_some((self.ui_state.compare_effects.as_slice(), split_x));
+        let params = Self::build_post_params(
+            width,
+            height,
+            &self.active_effects,
+            self.ui_state.oil_radius,
+            self.ui_state.comic_levels,
+            compare,
+        );
+        buffers::update_uniform_buffer(&self.gpu.queue, &self.post_params_buffer, &params);
+        self.post_pending = true;
+    }
+
     pub fn set_cursor_grabbed(&self, grabbed: bool) {
         use winit::window::CursorGrabMode;
         self.window.set_cursor_visible(!grabbed);
@@ -480,22 +1033,271 @@ impl AppState {
         }
     }
 
+    /// Auto-stop: a target sample count is set and accumulation has reached it.
+    pub fn target_reached(&self) -> bool {
+        self.ui_state.target_samples > 0
+            && self.accumulator.sample_count >= self.ui_state.target_samples
+    }
+
+    /// Area to path trace this frame: the user's render region clipped to the
+    /// frame, or the whole frame. The region is picked in window pixels and
+    /// mapped to the internal render resolution.
+    pub fn render_region(&self) -> RenderRegion {
+        let (width, height) = self.render_size();
+        let sx = width as f32 / self.gpu.width().max(1) as f32;
+        let sy = height as f32 / self.gpu.height().max(1) as f32;
+        self.ui_state
+            .render_region
+            .and_then(|r| r.scaled(sx, sy).clamped(width, height))
+            .unwrap_or(RenderRegion::full(width, height))
+    }
+
+    /// Snapshot of the current preferences for saving on exit.
+    pub fn current_settings(&self) -> Settings {
+        // Don't persist a PATHTRACER_MOUSE_SENS override unless the user changed it.
+        let mouse_sensitivity =
+            if self.controller.look_sensitivity == self.settings.effective_mouse_sensitivity() {
+                self.settings.mouse_sensitivity
+            } else {
+                self.controller.look_sensitivity
+            };
+        Settings {
+            mouse_sensitivity,
+            move_speed: self.controller.move_speed,
+            invert_y: self.controller.invert_y,
+            grid_snap: self.ui_state.grid_snap,
+            spawn_placement: self.ui_state.spawn_placement,
+            spawn_distance: self.ui_state.spawn_distance,
+            fps_cap: self.ui_state.fps_cap,
+            samples_per_frame: self.ui_state.samples_per_frame,
+            dynamic_resolution: self.ui_state.dynamic_resolution,
+            auto_exposure: self.ui_state.auto_exposure,
+            max_render_size: self.ui_state.max_render_size,
+            supersampling: self.ui_state.supersampling,
+            sharpen_upscale: self.ui_state.sharpen_upscale,
+            keep_samples_on_resize: self.ui_state.keep_samples_on_resize,
+            jitter_pattern: self.ui_state.jitter_pattern,
+            color_space: self.ui_state.color_space,
+            headlight: self.ui_state.headlight,
+            reopen_last_scene: self.ui_state.reopen_last_scene,
+            last_scene: self
+                .scene_path
+                .as_deref()
+                .and_then(|path| std::path::absolute(path).ok())
+                .or_else(|| self.settings.last_scene.clone()),
+            ..self.settings.clone()
+        }
+    }
+
+    /// Earliest time the next frame may start when an FPS cap is set, or `None`
+    /// to render as fast as presentation allows.
+    pub fn next_frame_deadline(&self) -> Option<Instant> {
+        match self.ui_state.fps_cap {
+            0 => None,
+            cap => Some(self.last_frame + Duration::from_secs_f64(1.0 / cap as f64)),
+        }
+    }
+
+    /// Whether frames should be rendered at all. Rendering is skipped entirely
+    /// while the window is minimized or occluded.
+    pub fn is_visible(&self) -> bool {
+        !self.occluded && !self.minimized
+    }
+
+    pub fn set_occluded(&mut self, occluded: bool) {
+        let was_visible = self.is_visible();
+        self.occluded = occluded;
+        self.on_visibility_changed(was_visible);
+    }
+
+    /// Restart frame timers when becoming visible again, so the time spent hidden
+    /// doesn't show up as one huge camera step or a bogus FPS reading.
+    fn on_visibility_changed(&mut self, was_visible: bool) {
+        if !was_visible && self.is_visible() {
+            let now = Instant::now();
+            self.last_frame = now;
+            self.last_acquire_time = now;
+            log::debug!("Window visible again, resuming rendering");
+        } else if was_visible && !self.is_visible() {
+            log::debug!("Window hidden, pausing rendering");
+        }
+    }
+
     pub fn handle_resize(&mut self, size: PhysicalSize<u32>) {
-        if size.width == 0 || size.height == 0 {
+        let was_visible = self.is_visible();
+        self.minimized = size.width == 0 || size.height == 0;
+        self.on_visibility_changed(was_visible);
+        if self.minimized {
+            return;
+        }
+        // Restoring from minimize re-sends the old size; keep the accumulated samples.
+        if size.width == self.gpu.width() && size.height == self.gpu.height() {
             return;
         }
+        // Only the surface follows right away; the render targets, and with
+        // them the accumulated samples, wait until the size settles.
         self.gpu.resize(size.width, size.height);
+        self.resized_at = Some(Instant::now());
+    }
+
+    /// Resize the render targets to the window after a resize. Accumulated
+    /// samples are rescaled to the new size when `keep_samples_on_resize` is
+    /// on, and discarded otherwise; a resize that ends at the current render
+    /// size keeps everything.
+    pub fn finish_resize(&mut self) {
+        self.resized_at = None;
+        let old_size = self.render_size();
+        let new_size = Self::compute_render_size(
+            &self.gpu,
+            self.ui_state.max_render_size,
+            self.ui_state.supersampling,
+        );
+        if new_size == old_size {
+            return;
+        }
+        let samples = self.accumulator.sample_count;
+        let kept = (self.ui_state.keep_samples_on_resize && samples > 0)
+            .then(|| {
+                let size = (old_size.0 * old_size.1) as u64 * ACCUM_BYTES_PER_PIXEL;
+                buffers::read_buffer_ranges(
+                    &self.gpu.device,
+                    &self.gpu.queue,
+                    &self.accumulation_buffer,
+                    std::slice::from_ref(&(0..size)),
+                )
+                .inspect_err(|e| log::warn!("Could not keep samples across resize: {e:#}"))
+                .ok()
+            })
+            .flatten();
         self.recreate_size_dependent_resources();
-        self.accumulator.reset();
+        match kept {
+            Some(bytes) => {
+                let texels: &[[f32; 4]] = bytemuck::cast_slice(&bytes);
+                let rescaled = crate::render::frame::rescale_nearest(texels, old_size, new_size);
+                self.gpu.queue.write_buffer(
+                    &self.accumulation_buffer,
+                    0,
+                    bytemuck::cast_slice(&rescaled),
+                );
+                self.post_pending = true;
+            }
+            None => {
+                if samples > 0 {
+                    log::info!(
+                        "Resized to {}x{}, discarding {samples} accumulated samples",
+                        new_size.0,
+                        new_size.1
+                    );
+                }
+                self.accumulator.reset();
+            }
+        }
+    }
+
+    /// Recompile the path-trace pipeline with the shader features selected in
+    /// the UI. On failure the previous pipeline stays in use.
+    pub fn rebuild_path_trace_pipeline(&mut self) {
+        match self.compile_path_trace_pipeline() {
+            Ok(()) => {
+                let features = self.ui_state.shader_features;
+                log::info!("Path tracer recompiled with {features:?}");
+            }
+            Err(e) => {
+                log::error!("Failed to recompile path tracer: {e:#}");
+                self.ui_state.error_message = Some(format!("{e:#}"));
+            }
+        }
+    }
+
+    /// Compose `path_trace` and replace the running pipeline with it. The
+    /// running one stays if that fails.
+    pub fn compile_path_trace_pipeline(&mut self) -> Result<()> {
+        let source = self.shader_composer()?.compose("path_trace")?;
+        self.compute_pipeline = crate::gpu::pipeline::create_compute_pipeline(
+            &self.gpu.device,
+            &source,
+            &[&self.compute_bg_layout_0, &self.compute_bg_layout_1],
+            "path trace",
+        )?;
+        if self.ui_state.dev_mode {
+            self.ui_state.composed_trace_source = source;
+        }
+        Ok(())
+    }
+
+    /// Shader modules from disk, set up for the workgroup size and shader
+    /// features in use and with the user BSDF module, if one was applied.
+    pub fn shader_composer(&self) -> Result<ShaderComposer> {
+        let mut composer = ShaderComposer::from_directory(&ShaderComposer::shader_dir())?;
+        self.workgroup_size.define_constants(&mut composer);
+        define_shared_constants(&mut composer);
+        self.ui_state.shader_features.define(&mut composer);
+        if let Some(source) = &self.user_bsdf {
+            composer.register(USER_BSDF_MODULE, source);
+        }
+        Ok(composer)
+    }
+
+    /// Internal render resolution (accumulation buffer and output texture size).
+    pub fn render_size(&self) -> (u32, u32) {
+        (self.render_width, self.render_height)
+    }
+
+    /// The window size times the supersampling factor.
+    fn supersampled_size(gpu: &GpuContext, supersampling: f32) -> (u32, u32) {
+        let scaled = |v: u32| ((v as f32 * supersampling.max(1.0)).round() as u32).max(1);
+        (scaled(gpu.width()), scaled(gpu.height()))
+    }
+
+    /// Render resolution for the current window and supersampling factor:
+    /// capped by the user's maximum size (0 = the largest texture the device
+    /// allows) and by how large an accumulation buffer the device can bind.
+    fn compute_render_size(
+        gpu: &GpuContext,
+        max_render_size: u32,
+        supersampling: f32,
+    ) -> (u32, u32) {
+        let limits = gpu.device.limits();
+        let max_side = match max_render_size {
+            0 => limits.max_texture_dimension_2d,
+            size => size.min(limits.max_texture_dimension_2d),
+        };
+        let max_buffer =
+            (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+        let (width, height) = Self::supersampled_size(gpu, supersampling);
+        crate::render::frame::clamp_render_size(
+            width,
+            height,
+            max_side,
+            max_buffer / ACCUM_BYTES_PER_PIXEL,
+        )
     }
 
     pub fn recreate_size_dependent_resources(&mut self) {
-        let width = self.gpu.width();
-        let height = self.gpu.height();
+        let supersampling = self.ui_state.supersampling;
+        let (width, height) =
+            Self::compute_render_size(&self.gpu, self.ui_state.max_render_size, supersampling);
+        let clamped = ((width, height) != Self::supersampled_size(&self.gpu, supersampling))
+            .then_some((width, height));
+        if clamped.is_some() && self.ui_state.clamped_render_size.is_none() {
+            log::warn!(
+                "Window is {}x{}, rendering at {width}x{height} and upscaling",
+                self.gpu.width(),
+                self.gpu.height()
+            );
+        }
+        self.ui_state.clamped_render_size = clamped;
+        self.render_width = width;
+        self.render_height = height;
 
         let accum_size = (width * height) as u64 * ACCUM_BYTES_PER_PIXEL;
         self.accumulation_buffer =
             buffers::create_empty_storage_buffer(&self.gpu.device, accum_size, "accumulation");
+        self.object_id_buffer = buffers::create_empty_storage_buffer(
+            &self.gpu.device,
+            (width * height) as u64 * OBJECT_ID_BYTES_PER_PIXEL,
+            "object ids",
+        );
 
         let (tex, view) = buffers::create_output_texture(&self.gpu.device, width, height, "output");
         self.output_texture = tex;
@@ -506,6 +1308,7 @@ impl AppState {
             &self.compute_bg_layout_0,
             &self.camera_buffer,
             &self.accumulation_buffer,
+            &self.object_id_buffer,
             &self.output_view,
         );
 
@@ -514,6 +1317,8 @@ impl AppState {
             &self.blit_bg_layout,
             &self.output_view,
             &self.blit_sampler,
+            &self.blit_params_buffer,
+            &self.object_id_buffer,
         );
 
         self.post_bind_group = Self::create_post_bind_group(
@@ -524,14 +1329,18 @@ impl AppState {
             &self.output_view,
         );
 
-        let post_params = Self::build_post_params(
+        self.preview_target = PreviewTarget::new(
+            &self.gpu.device,
             width,
             height,
-            &self.active_effects,
-            self.ui_state.oil_radius,
-            self.ui_state.comic_levels,
+            &self.compute_bg_layout_0,
+            &self.blit_bg_layout,
+            &self.camera_buffer,
+            &self.blit_sampler,
+            &self.blit_params_buffer,
         );
-        buffers::update_uniform_buffer(&self.gpu.queue, &self.post_params_buffer, &post_params);
+
+        self.upload_post_params();
     }
 
     /// Partition `shapes` into a BVH over finite shapes and a flat list of
@@ -540,60 +1349,147 @@ impl AppState {
     /// Planes are infinite and would produce degenerate AABBs that corrupt the
     /// BVH tree, so they are excluded from it and tested separately each frame.
     /// Skybox shapes are excluded entirely — they are sampled via `sample_skybox`.
-    pub fn build_bvh(shapes: &[Shape]) -> (Bvh, Vec<u32>) {
-        let mut finite_to_global: Vec<usize> = Vec::new();
+    /// Spot lights are delta lights living only in the light buffer.
+    ///
+    /// Named triangle groups (imported meshes) get their own BVH from
+    /// `blas_cache`, built once and refit as the mesh moves; only the tree
+    /// above them is rebuilt each time.
+    ///
+    /// Instances enter this top-level tree with their world bounds. Each
+    /// model's own BVH is appended after it, its primitives pointing at the
+    /// model triangles that follow the scene shapes; the third list holds
+    /// those root nodes in `models` order.
+    ///
+    /// With `solo` (sorted shape indices), only those shapes are traced.
+    pub fn build_bvh(
+        shapes: &[Shape],
+        models: &ModelLibrary,
+        blas_cache: &mut BlasCache,
+        params: BvhParams,
+        solo: Option<&[usize]>,
+    ) -> (Bvh, Vec<u32>, Vec<u32>) {
+        let mut singles: Vec<(u32, Aabb)> = Vec::new();
+        let mut groups: BTreeMap<String, TriangleGroup> = BTreeMap::new();
         let mut infinite_indices: Vec<u32> = Vec::new();
 
         for (i, shape) in shapes.iter().enumerate() {
+            if solo.is_some_and(|members| members.binary_search(&i).is_err()) {
+                continue;
+            }
             match shape.shape_type {
                 ShapeType::Plane => infinite_indices.push(i as u32),
-                ShapeType::Skybox => {}
-                _ => finite_to_global.push(i),
+                ShapeType::Skybox | ShapeType::SpotLight => {}
+                ShapeType::Instance => {
+                    // Nothing to trace until the model loads.
+                    if let Some(model) = models.model_of(shape) {
+                        singles.push((i as u32, model.instance_aabb(shape)));
+                    }
+                }
+                ShapeType::Triangle if shape.name.as_ref().is_some_and(|n| !n.is_empty()) => {
+                    let group: &mut TriangleGroup = groups
+                        .entry(shape.name.clone().unwrap_or_default())
+                        .or_default();
+                    group.members.push(i as u32);
+                    group.aabbs.push(shape_aabb(shape));
+                }
+                _ => singles.push((i as u32, shape_aabb(shape))),
             }
         }
 
-        let finite_aabbs: Vec<_> = finite_to_global
+        let mut bvh = blas_cache.build(&singles, &groups, params);
+
+        let mut first_figure = shapes.len() as u32;
+        let blas_roots = models
             .iter()
-            .map(|&i| shape_aabb(&shapes[i]))
+            .map(|(_, model)| {
+                let root = bvh.append(&model.blas, first_figure);
+                first_figure += model.triangles.len() as u32;
+                root
+            })
             .collect();
-        let mut bvh = Bvh::build(&finite_aabbs);
-
-        // Remap leaf prim_indices from finite-local back to global shape indices.
-        for idx in &mut bvh.prim_indices {
-            *idx = finite_to_global[*idx as usize] as u32;
-        }
 
-        (bvh, infinite_indices)
+        (bvh, infinite_indices, blas_roots)
     }
 
-    fn compute_scene_gpu_data(&self) -> (Vec<GpuShape>, Vec<GpuMaterial>, Vec<u32>, Bvh, Vec<u32>) {
-        let (gpu_shapes, gpu_materials, light_indices) =
-            Self::build_gpu_data(&self.shapes, &self.tex_path_cache);
-        let (bvh, infinite_indices) = Self::build_bvh(&self.shapes);
+    #[allow(clippy::type_complexity)]
+    fn compute_scene_gpu_data(
+        &mut self,
+    ) -> (
+        Vec<GpuShape>,
+        Vec<GpuMaterial>,
+        Vec<GpuAreaLight>,
+        Bvh,
+        Vec<u32>,
+        Vec<GpuLight>,
+    ) {
+        // Shapes may have become lights or stopped being ones since the
+        // groups were collected.
+        self.refresh_light_groups();
+        let solo = self.solo_members();
+        let start = Instant::now();
+        let (bvh, infinite_indices, blas_roots) = Self::build_bvh(
+            &self.shapes,
+            &self.models,
+            &mut self.blas_cache,
+            self.ui_state.bvh_params,
+            solo.as_deref(),
+        );
+        self.ui_state.bvh_build_ms = start.elapsed().as_secs_f32() * 1000.0;
+        self.ui_state.bvh_stats = bvh.stats();
+        let (gpu_shapes, gpu_materials, light_indices, delta_lights) = Self::build_gpu_data(
+            &self.shapes,
+            &self.models,
+            &blas_roots,
+            &self.tex_path_cache,
+            &self.ui_state.light_groups,
+            solo.as_deref(),
+        );
         (
             gpu_shapes,
             gpu_materials,
             light_indices,
             bvh,
             infinite_indices,
+            delta_lights,
         )
     }
 
     /// Write updated scene data to existing GPU buffers in-place when they fit.
-    /// Falls back to a full rebuild if the BVH grew beyond the current buffer.
+    /// Falls back to a full rebuild if any of them grew beyond its current buffer.
     pub fn rebuild_scene_buffers_in_place(&mut self) {
-        let (gpu_shapes, gpu_materials, light_indices, bvh, infinite_indices) =
+        self.refresh_diagnostics();
+        let (gpu_shapes, gpu_materials, light_indices, bvh, infinite_indices, delta_lights) =
             self.compute_scene_gpu_data();
-        self.bvh = bvh;
-        self.infinite_indices = infinite_indices;
 
-        let new_node_bytes = std::mem::size_of_val(self.bvh.nodes.as_slice()) as u64;
-        if new_node_bytes > self.bvh_node_buffer.size() {
-            // BVH grew beyond the current buffer — reallocate so future
-            // in-place writes fit without overflow.
+        let grew = |data_bytes: usize, buffer: &wgpu::Buffer| data_bytes as u64 > buffer.size();
+        if grew(
+            std::mem::size_of_val(bvh.nodes.as_slice()),
+            &self.bvh_node_buffer,
+        ) || grew(
+            std::mem::size_of_val(bvh.prim_indices.as_slice()),
+            &self.bvh_prim_buffer,
+        ) || grew(
+            std::mem::size_of_val(gpu_shapes.as_slice()),
+            &self.shape_buffer,
+        ) || grew(
+            std::mem::size_of_val(gpu_materials.as_slice()),
+            &self.material_buffer,
+        ) || grew(
+            std::mem::size_of_val(light_indices.as_slice()),
+            &self.light_index_buffer,
+        ) || grew(
+            std::mem::size_of_val(infinite_indices.as_slice()),
+            &self.infinite_index_buffer,
+        ) || grew(
+            std::mem::size_of_val(delta_lights.as_slice()),
+            &self.light_buffer,
+        ) {
+            // Reallocate so future in-place writes fit without overflow.
             self.rebuild_scene_buffers();
             return;
         }
+        self.bvh = bvh;
+        self.infinite_indices = infinite_indices;
 
         buffers::update_storage_buffer(&self.gpu.queue, &self.shape_buffer, &gpu_shapes);
         buffers::update_storage_buffer(&self.gpu.queue, &self.material_buffer, &gpu_materials);
@@ -606,20 +1502,61 @@ impl AppState {
         buffers::update_storage_buffer(
             &self.gpu.queue,
             &self.light_index_buffer,
-            Self::nonempty_index_buffer(&light_indices),
+            Self::nonempty_area_lights(&light_indices),
         );
         buffers::update_storage_buffer(
             &self.gpu.queue,
             &self.infinite_index_buffer,
             Self::nonempty_index_buffer(&self.infinite_indices),
         );
+        // Pad with LIGHT_NONE entries so lights removed since the last write
+        // don't linger in the buffer's tail.
+        let capacity = self.light_buffer.size() as usize / std::mem::size_of::<GpuLight>();
+        let mut padded = delta_lights;
+        padded.resize(capacity, GpuLight::zeroed());
+        buffers::update_storage_buffer(&self.gpu.queue, &self.light_buffer, &padded);
+    }
+
+    /// Rebuild every BVH with the debug panel's leaf size and bin count.
+    pub fn apply_bvh_params(&mut self) {
+        let params = self.ui_state.bvh_params;
+        self.models.set_bvh_params(params);
+        self.rebuild_scene_buffers();
+        let stats = self.ui_state.bvh_stats;
+        if params.brute_force {
+            log::info!(
+                "BVH off: brute force over {} shapes and {} model triangles",
+                self.shapes.len(),
+                self.models.triangles().count()
+            );
+            return;
+        }
+        log::info!(
+            "BVH rebuilt (leaf size {}, {} bins): {} nodes, max depth {}, {:.1} ms",
+            params.max_leaf_prims,
+            params.num_bins,
+            stats.nodes,
+            stats.max_depth,
+            self.ui_state.bvh_build_ms
+        );
+    }
+
+    /// Re-run scene diagnostics and publish the result to the UI.
+    pub fn refresh_diagnostics(&mut self) {
+        self.ui_state.scene_issues =
+            crate::scene::diagnostics::diagnose(&self.shapes, &self.camera);
+    }
+
+    /// Re-derive light groups from the current shapes, keeping per-group settings.
+    pub fn refresh_light_groups(&mut self) {
+        self.ui_state.light_groups =
+            lights::collect_light_groups(&self.shapes, &self.ui_state.light_groups);
     }
 
     pub fn rebuild_scene_buffers(&mut self) {
-        let (gpu_shapes, gpu_materials, light_indices, bvh, infinite_indices) =
+        self.refresh_diagnostics();
+        let (gpu_shapes, gpu_materials, light_indices, bvh, infinite_indices, delta_lights) =
             self.compute_scene_gpu_data();
-        self.bvh = bvh;
-        self.infinite_indices = infinite_indices;
 
         let (
             shape_buffer,
@@ -628,20 +1565,34 @@ impl AppState {
             bvh_prim_buffer,
             light_index_buffer,
             infinite_index_buffer,
-        ) = Self::create_geometry_buffers(
+            light_buffer,
+        ) = match Self::create_geometry_buffers(
             &self.gpu.device,
             &gpu_shapes,
             &gpu_materials,
-            &self.bvh,
+            &bvh,
             &light_indices,
-            &self.infinite_indices,
-        );
+            &infinite_indices,
+            &delta_lights,
+        ) {
+            Ok(buffers) => buffers,
+            Err(e) => {
+                // Keep rendering the previous GPU scene rather than binding
+                // buffers the device would reject.
+                log::error!("{e:#}");
+                self.ui_state.error_message = Some(format!("{e:#}"));
+                return;
+            }
+        };
+        self.bvh = bvh;
+        self.infinite_indices = infinite_indices;
         self.shape_buffer = shape_buffer;
         self.material_buffer = material_buffer;
         self.bvh_node_buffer = bvh_node_buffer;
         self.bvh_prim_buffer = bvh_prim_buffer;
         self.light_index_buffer = light_index_buffer;
         self.infinite_index_buffer = infinite_index_buffer;
+        self.light_buffer = light_buffer;
 
         self.compute_bind_group_1 = Self::create_compute_bg1(
             &self.gpu.device,
@@ -654,29 +1605,46 @@ impl AppState {
             &self.tex_pixels_buffer,
             &self.tex_infos_buffer,
             &self.infinite_index_buffer,
+            &self.light_buffer,
+            &self.blue_noise_buffer,
+            &self.tex_float_pixels_buffer,
         );
     }
 
+    /// Bring the texture atlas in line with the shapes' textures, decoding
+    /// only new images, then rebuild the scene buffers. The texture buffers
+    /// are re-uploaded only if the atlas contents changed.
     pub fn rebuild_scene_buffers_with_textures(&mut self) {
-        (self.texture_atlas, self.tex_path_cache) = Self::build_texture_atlas(&self.shapes);
-
-        self.tex_pixels_buffer = buffers::create_storage_buffer(
-            &self.gpu.device,
-            &self.texture_atlas.pixels,
-            "tex_pixels",
-            true,
-        );
-        self.tex_infos_buffer = buffers::create_storage_buffer(
-            &self.gpu.device,
-            &self.texture_atlas.infos,
-            "tex_infos",
-            true,
+        self.models
+            .sync(&self.shapes, &self.scene.dir, self.scene.up_axis);
+        let (atlas, cache) = Self::update_texture_atlas(
+            &self.texture_atlas,
+            self.shapes.iter().chain(self.models.triangles()),
+            &self.scene.dir,
         );
+        if !atlas.same_layout(&self.texture_atlas) {
+            match Self::create_texture_buffers(&self.gpu.device, &atlas) {
+                Ok((pixels, infos, float_pixels)) => {
+                    self.tex_pixels_buffer = pixels;
+                    self.tex_infos_buffer = infos;
+                    self.tex_float_pixels_buffer = float_pixels;
+                }
+                Err(e) => {
+                    // Keep rendering the previous GPU scene, as for geometry.
+                    log::error!("{e:#}");
+                    self.ui_state.error_message = Some(format!("{e:#}"));
+                    return;
+                }
+            }
+        }
+        self.texture_atlas = atlas;
+        self.tex_path_cache = cache;
+        self.refresh_missing_textures();
 
         self.rebuild_scene_buffers();
     }
 
-    fn create_compute_bg0_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    pub fn create_compute_bg0_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("compute bg0 layout"),
             entries: &[
@@ -710,11 +1678,21 @@ impl AppState {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         })
     }
 
-    fn create_compute_bg1_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    pub fn create_compute_bg1_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         let ro_storage = |binding: u32| wgpu::BindGroupLayoutEntry {
             binding,
             visibility: wgpu::ShaderStages::COMPUTE,
@@ -736,11 +1714,14 @@ impl AppState {
                 ro_storage(5),
                 ro_storage(6),
                 ro_storage(7),
+                ro_storage(8),
+                ro_storage(9),
+                ro_storage(10),
             ],
         })
     }
 
-    fn create_blit_bg_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    pub fn create_blit_bg_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("blit bg layout"),
             entries: &[
@@ -760,11 +1741,31 @@ impl AppState {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         })
     }
 
-    fn create_post_bg_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    pub fn create_post_bg_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("post bg layout"),
             entries: &[
@@ -807,6 +1808,7 @@ impl AppState {
         layout: &wgpu::BindGroupLayout,
         camera_buf: &wgpu::Buffer,
         accum_buf: &wgpu::Buffer,
+        object_id_buf: &wgpu::Buffer,
         output_view: &wgpu::TextureView,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -825,6 +1827,10 @@ impl AppState {
                     binding: 2,
                     resource: wgpu::BindingResource::TextureView(output_view),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: object_id_buf.as_entire_binding(),
+                },
             ],
         })
     }
@@ -841,6 +1847,9 @@ impl AppState {
         tex_pixels_buf: &wgpu::Buffer,
         tex_infos_buf: &wgpu::Buffer,
         infinite_idx_buf: &wgpu::Buffer,
+        light_buf: &wgpu::Buffer,
+        blue_noise_buf: &wgpu::Buffer,
+        tex_float_pixels_buf: &wgpu::Buffer,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("compute bg1"),
@@ -878,15 +1887,37 @@ impl AppState {
                     binding: 7,
                     resource: infinite_idx_buf.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: light_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: blue_noise_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: tex_float_pixels_buf.as_entire_binding(),
+                },
             ],
         })
     }
 
+    pub fn create_blit_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        })
+    }
+
     pub fn create_blit_bind_group(
         device: &wgpu::Device,
         layout: &wgpu::BindGroupLayout,
         output_view: &wgpu::TextureView,
         sampler: &wgpu::Sampler,
+        params_buf: &wgpu::Buffer,
+        object_id_buf: &wgpu::Buffer,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("blit bg"),
@@ -900,6 +1931,14 @@ impl AppState {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: object_id_buf.as_entire_binding(),
+                },
             ],
         })
     }
@@ -931,3 +1970,43 @@ impl AppState {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_params_follow_effect_settings() {
+        let effects = [PostEffect::OilPainting, PostEffect::Comic];
+        let before = AppState::build_post_params(64, 32, &effects, 3, 4, None);
+        // Oil radius and comic levels dragged in the post-effects panel.
+        let after = AppState::build_post_params(64, 32, &effects, 5, 6, None);
+        assert_eq!(after[..3], [64, 32, 2]);
+        assert_eq!(after[3], 5, "oil_radius");
+        assert_eq!(
+            after[4..6],
+            [PostEffect::OilPainting.as_u32(), PostEffect::Comic.as_u32()]
+        );
+        assert_eq!(after[12], 6, "comic_levels");
+        assert_eq!(after[13..15], [u32::MAX, 0], "no A/B compare");
+        let changed: Vec<usize> = (0..POST_PARAMS_SIZE)
+            .filter(|&i| before[i] != after[i])
+            .collect();
+        assert_eq!(changed, [3, 12]);
+
+        // Applying the tweak keeps the accumulated samples and their timing.
+        let actions = ui::UiActions {
+            post_effect_params_changed: true,
+            ..Default::default()
+        };
+        let mut accumulator = Accumulator::default();
+        for _ in 0..16 {
+            accumulator.advance();
+        }
+        let render_start = accumulator.render_start;
+        AppState::reset_accumulation_for(&mut accumulator, &actions);
+        assert!(actions.updates_post_params());
+        assert_eq!(accumulator.sample_count, 16);
+        assert_eq!(accumulator.render_start, render_start);
+    }
+}
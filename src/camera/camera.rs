@@ -5,12 +5,21 @@ use bytemuck::{Pod, Zeroable};
 use glam::{Quat, Vec3};
 
 use crate::constants::{
-    DEFAULT_CAMERA_POSITION, DEFAULT_EXPOSURE, DEFAULT_FIREFLY_CLAMP, DEFAULT_FOV,
-    DEFAULT_FRACTAL_MARCH_STEPS, DEFAULT_MAX_BOUNCES, DEFAULT_SKYBOX_BRIGHTNESS,
-    DEFAULT_SKYBOX_COLOR, DEFAULT_TONE_MAPPER,
+    DEFAULT_CAMERA_POSITION, DEFAULT_EXPOSURE, DEFAULT_FAR_CLIP, DEFAULT_FIREFLY_CLAMP,
+    DEFAULT_FOV, DEFAULT_FRACTAL_MARCH_STEPS, DEFAULT_MAX_BOUNCES, DEFAULT_NEAR_CLIP,
+    DEFAULT_SKY_TURBIDITY, DEFAULT_SKYBOX_BRIGHTNESS, DEFAULT_SKYBOX_COLOR,
+    DEFAULT_SKYBOX_HORIZON_COLOR, DEFAULT_SUN_AZIMUTH, DEFAULT_SUN_ELEVATION, DEFAULT_TONE_MAPPER,
+    DEFAULT_WHITE_POINT,
 };
-use crate::scene::scene::CameraConfig;
+use crate::scene::scene::{CameraConfig, SkyboxMode};
 
+/// Free-look camera.
+///
+/// World space has +Y up and is left-handed: at `yaw = pitch = 0` the camera
+/// looks down +Z with +X to its right. Positive yaw turns right (towards +X),
+/// positive pitch tilts the view down. The CPU picking ray and the shader both
+/// use `basis_vectors`, so they share this convention.
+#[derive(Clone)]
 pub struct Camera {
     pub position: Vec3,
     pub yaw: f32,   // degrees
@@ -19,10 +28,20 @@ pub struct Camera {
     pub exposure: f32,
     pub max_bounces: u32,
     pub tone_mapper: u32,
+    pub white_point: f32,
     pub fractal_march_steps: u32,
     pub firefly_clamp: f32,
+    pub skybox_mode: SkyboxMode,
     pub skybox_color: [f32; 3],
+    pub skybox_horizon_color: [f32; 3],
     pub skybox_brightness: f32,
+    pub sun_azimuth: f32,   // degrees
+    pub sun_elevation: f32, // degrees
+    pub sky_turbidity: f32,
+    pub transparent_background: bool,
+    pub near_clip: f32,
+    /// 0 = no far clip.
+    pub far_clip: f32,
 }
 
 impl Camera {
@@ -35,17 +54,30 @@ impl Camera {
             exposure,
             max_bounces: DEFAULT_MAX_BOUNCES,
             tone_mapper: DEFAULT_TONE_MAPPER,
+            white_point: DEFAULT_WHITE_POINT,
             fractal_march_steps: DEFAULT_FRACTAL_MARCH_STEPS,
             firefly_clamp: DEFAULT_FIREFLY_CLAMP,
+            skybox_mode: SkyboxMode::HdrMap,
             skybox_color: DEFAULT_SKYBOX_COLOR,
+            skybox_horizon_color: DEFAULT_SKYBOX_HORIZON_COLOR,
             skybox_brightness: DEFAULT_SKYBOX_BRIGHTNESS,
+            sun_azimuth: DEFAULT_SUN_AZIMUTH,
+            sun_elevation: DEFAULT_SUN_ELEVATION,
+            sky_turbidity: DEFAULT_SKY_TURBIDITY,
+            transparent_background: false,
+            near_clip: DEFAULT_NEAR_CLIP,
+            far_clip: DEFAULT_FAR_CLIP,
         }
     }
 
     /// Construct a camera fully from a scene's camera config (position, orientation, and all
     /// render settings). Prefer this over `new()` followed by manual field assignments.
     pub fn from_config(cfg: &CameraConfig) -> Self {
-        let mut cam = Self::new(cfg.position.into(), cfg.rotation, cfg.fov, cfg.exposure);
+        let rotation = cfg
+            .look_at
+            .and_then(|target| Self::look_at_rotation(cfg.position.into(), target.into()))
+            .unwrap_or(cfg.rotation);
+        let mut cam = Self::new(cfg.position.into(), rotation, cfg.fov, cfg.exposure);
         cam.apply_render_settings(cfg);
         cam
     }
@@ -55,14 +87,24 @@ impl Camera {
         CameraConfig {
             position: self.position.into(),
             rotation: [self.pitch, self.yaw, 0.0],
+            look_at: None,
             fov: self.fov,
             exposure: self.exposure,
             max_bounces: self.max_bounces,
             firefly_clamp: self.firefly_clamp,
+            skybox_mode: self.skybox_mode,
             skybox_color: self.skybox_color,
+            skybox_horizon_color: self.skybox_horizon_color,
             skybox_brightness: self.skybox_brightness,
+            sun_azimuth: self.sun_azimuth,
+            sun_elevation: self.sun_elevation,
+            sky_turbidity: self.sky_turbidity,
             tone_mapper: self.tone_mapper,
+            white_point: self.white_point,
             fractal_march_steps: self.fractal_march_steps,
+            transparent_background: self.transparent_background,
+            near_clip: self.near_clip,
+            far_clip: self.far_clip,
         }
     }
 
@@ -71,10 +113,40 @@ impl Camera {
     pub fn apply_render_settings(&mut self, cfg: &CameraConfig) {
         self.max_bounces = cfg.max_bounces;
         self.firefly_clamp = cfg.firefly_clamp;
+        self.skybox_mode = cfg.skybox_mode;
         self.skybox_color = cfg.skybox_color;
+        self.skybox_horizon_color = cfg.skybox_horizon_color;
         self.skybox_brightness = cfg.skybox_brightness;
+        self.sun_azimuth = cfg.sun_azimuth;
+        self.sun_elevation = cfg.sun_elevation;
+        self.sky_turbidity = cfg.sky_turbidity;
         self.tone_mapper = cfg.tone_mapper;
+        self.white_point = cfg.white_point;
         self.fractal_march_steps = cfg.fractal_march_steps;
+        self.transparent_background = cfg.transparent_background;
+        self.near_clip = cfg.near_clip;
+        self.far_clip = cfg.far_clip;
+    }
+
+    /// Distances along a camera ray that can be hit, from the near to the far
+    /// clip (unbounded when the far clip is off).
+    pub fn clip_range(&self) -> std::ops::Range<f32> {
+        let near = self.near_clip.max(0.0);
+        let far = if self.far_clip > 0.0 {
+            self.far_clip.max(near)
+        } else {
+            f32::INFINITY
+        };
+        near..far
+    }
+
+    /// `[pitch, yaw, 0]` that makes a camera at `position` face `target`, or
+    /// `None` if the two coincide.
+    pub fn look_at_rotation(position: Vec3, target: Vec3) -> Option<[f32; 3]> {
+        let dir = (target - position).try_normalize()?;
+        let yaw = dir.x.atan2(dir.z).to_degrees();
+        let pitch = (-dir.y).atan2(dir.x.hypot(dir.z)).to_degrees();
+        Some([pitch, yaw, 0.0])
     }
 
     pub fn orientation(&self) -> Quat {
@@ -86,6 +158,14 @@ impl Camera {
         )
     }
 
+    /// Keep the view direction and move back from `center` until a sphere
+    /// of `radius` around it just fills the vertical field of view.
+    pub fn frame(&mut self, center: Vec3, radius: f32) {
+        let half_fov = (self.fov.to_radians() * 0.5).clamp(0.01, std::f32::consts::FRAC_PI_2);
+        let (_, _, forward) = self.basis_vectors();
+        self.position = center - forward * (radius / half_fov.sin());
+    }
+
     pub fn basis_vectors(&self) -> (Vec3, Vec3, Vec3) {
         let rot = self.orientation();
         let forward = rot * Vec3::Z;
@@ -94,6 +174,15 @@ impl Camera {
         (right, up, forward)
     }
 
+    /// Unit vector pointing towards the sun (azimuth clockwise from +Z towards +X).
+    pub fn sun_direction(&self) -> Vec3 {
+        let (az, el) = (
+            self.sun_azimuth.to_radians(),
+            self.sun_elevation.to_radians(),
+        );
+        Vec3::new(el.cos() * az.sin(), el.sin(), el.cos() * az.cos())
+    }
+
     pub fn to_gpu(
         &self,
         width: u32,
@@ -104,6 +193,7 @@ impl Camera {
         let (right, up, forward) = self.basis_vectors();
         let aspect = width as f32 / height as f32;
         let focal_length = 1.0 / (self.fov.to_radians() * 0.5).tan();
+        let clip = self.clip_range();
 
         GpuCamera {
             position: self.position.into(),
@@ -123,7 +213,24 @@ impl Camera {
             firefly_clamp: self.firefly_clamp,
             skybox_brightness: self.skybox_brightness,
             skybox_color: self.skybox_color,
-            _pad2: 0.0,
+            skybox_mode: self.skybox_mode.as_u32(),
+            skybox_horizon_color: self.skybox_horizon_color,
+            view_mode: 0,
+            sun_direction: self.sun_direction().into(),
+            sky_turbidity: self.sky_turbidity,
+            region_origin: [0, 0],
+            region_size: [width, height],
+            transparent_background: u32::from(self.transparent_background),
+            white_point: self.white_point,
+            near_clip: clip.start,
+            far_clip: if clip.end.is_finite() { clip.end } else { 0.0 },
+            jitter_pattern: 0,
+            headlight: 0.0,
+            color_space: 0,
+            stereo_mode: 0,
+            eye_separation: 0.0,
+            brute_force: 0,
+            _pad: [0; 2],
         }
     }
 }
@@ -138,10 +245,19 @@ impl Default for Camera {
             exposure: DEFAULT_EXPOSURE,
             max_bounces: DEFAULT_MAX_BOUNCES,
             tone_mapper: DEFAULT_TONE_MAPPER,
+            white_point: DEFAULT_WHITE_POINT,
             fractal_march_steps: DEFAULT_FRACTAL_MARCH_STEPS,
             firefly_clamp: DEFAULT_FIREFLY_CLAMP,
+            skybox_mode: SkyboxMode::HdrMap,
             skybox_color: DEFAULT_SKYBOX_COLOR,
+            skybox_horizon_color: DEFAULT_SKYBOX_HORIZON_COLOR,
             skybox_brightness: DEFAULT_SKYBOX_BRIGHTNESS,
+            sun_azimuth: DEFAULT_SUN_AZIMUTH,
+            sun_elevation: DEFAULT_SUN_ELEVATION,
+            sky_turbidity: DEFAULT_SKY_TURBIDITY,
+            transparent_background: false,
+            near_clip: DEFAULT_NEAR_CLIP,
+            far_clip: DEFAULT_FAR_CLIP,
         }
     }
 }
@@ -167,5 +283,118 @@ pub struct GpuCamera {
     pub firefly_clamp: f32,
     pub skybox_brightness: f32,
     pub skybox_color: [f32; 3],
-    pub _pad2: f32,
+    pub skybox_mode: u32,
+    pub skybox_horizon_color: [f32; 3],
+    /// `ViewMode::as_u32`; `to_gpu` leaves it at path tracing.
+    pub view_mode: u32,
+    pub sun_direction: [f32; 3],
+    pub sky_turbidity: f32,
+    /// Top-left pixel of the path-traced region.
+    pub region_origin: [u32; 2],
+    /// Size of the path-traced region; the full frame unless a render region is set.
+    pub region_size: [u32; 2],
+    /// 1 = camera rays that escape write alpha 0 instead of the sky.
+    pub transparent_background: u32,
+    /// Exposed radiance that maps to white, 0 = the tone mapper's own curve.
+    pub white_point: f32,
+    /// Camera rays start this far along and ignore hits beyond `far_clip`
+    /// (0 = no limit).
+    pub near_clip: f32,
+    pub far_clip: f32,
+    /// `JitterPattern::as_u32`; `to_gpu` leaves it at random.
+    pub jitter_pattern: u32,
+    /// Strength of the editor headlight, 0 = off (the default from `to_gpu`).
+    pub headlight: f32,
+    /// `ColorSpace::as_u32`; `to_gpu` leaves it at sRGB.
+    pub color_space: u32,
+    /// `StereoMode::as_u32`; `to_gpu` leaves stereo off.
+    pub stereo_mode: u32,
+    pub eye_separation: f32,
+    /// 1 when the BVH is a single brute-force leaf (`BvhParams::brute_force`).
+    pub brute_force: u32,
+    pub _pad: [u32; 2],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{a} != {b}");
+    }
+
+    #[test]
+    fn test_default_camera_looks_at_origin() {
+        let cam = Camera::from_config(&CameraConfig::default());
+        let (right, up, forward) = cam.basis_vectors();
+        assert_near(forward, Vec3::Z);
+        assert_near(right, Vec3::X);
+        assert_near(up, Vec3::Y);
+        // Left-handed: right x up points forward, not backward.
+        assert_near(right.cross(up), forward);
+
+        let to_origin = (-Vec3::from(DEFAULT_CAMERA_POSITION)).normalize();
+        assert!(forward.dot(to_origin) > 0.95, "{forward} vs {to_origin}");
+    }
+
+    #[test]
+    fn test_yaw_and_pitch_directions() {
+        let mut cam = Camera {
+            yaw: 90.0,
+            ..Default::default()
+        };
+        assert_near(cam.basis_vectors().2, Vec3::X);
+
+        cam.yaw = 0.0;
+        cam.pitch = 30.0;
+        let forward = cam.basis_vectors().2;
+        assert!(forward.y < 0.0 && forward.z > 0.0, "{forward}");
+    }
+
+    #[test]
+    fn test_gpu_forward_matches_picking_ray() {
+        let cam = Camera {
+            yaw: 35.0,
+            pitch: -20.0,
+            ..Default::default()
+        };
+        let gpu = cam.to_gpu(1280, 720, 0, 0);
+        let (_, dir) = crate::picking::picking_ray(&cam, 640.0, 360.0, 1280, 720);
+        assert_near(Vec3::from(gpu.forward), cam.basis_vectors().2);
+        assert_near(dir, Vec3::from(gpu.forward));
+    }
+
+    #[test]
+    fn test_frame_keeps_direction_and_fits_sphere() {
+        let mut cam = Camera::new(Vec3::ZERO, [20.0, -30.0, 0.0], 90.0, 1.0);
+        let forward = cam.basis_vectors().2;
+        let center = Vec3::new(100.0, 5.0, -40.0);
+        cam.frame(center, 2.0);
+        assert_near(cam.basis_vectors().2, forward);
+        let to_center = center - cam.position;
+        assert_near(to_center.normalize(), forward);
+        // Tangent to the sphere at the edge of a 45° half field of view.
+        assert!((to_center.length() - 2.0 * 2f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_look_at_overrides_rotation() {
+        let target = Vec3::new(3.0, -1.0, 4.0);
+        let cfg = CameraConfig {
+            position: [1.0, 2.0, -3.0],
+            rotation: [45.0, 45.0, 0.0],
+            look_at: Some(target.into()),
+            ..Default::default()
+        };
+        let cam = Camera::from_config(&cfg);
+        let expected = (target - Vec3::from(cfg.position)).normalize();
+        assert_near(cam.basis_vectors().2, expected);
+        assert_eq!(cam.to_config().look_at, None);
+
+        let degenerate = CameraConfig {
+            look_at: Some(cfg.position),
+            ..cfg
+        };
+        assert_eq!(Camera::from_config(&degenerate).pitch, 45.0);
+    }
 }
@@ -5,15 +5,16 @@ use glam::Vec3;
 
 use super::camera::Camera;
 use crate::constants::{
-    CAMERA_DEFAULT_MOVE_SPEED, CAMERA_DEFAULT_SENSITIVITY, CAMERA_PITCH_CLAMP,
-    CAMERA_RAW_ABSOLUTE_THRESHOLD, CAMERA_RAW_JUMP_THRESHOLD, CAMERA_RAW_SCALE, CAMERA_SPEED_MAX,
-    CAMERA_SPEED_MIN, CAMERA_SPEED_STEP, CAMERA_SPRINT_MULTIPLIER,
+    CAMERA_PITCH_CLAMP, CAMERA_RAW_ABSOLUTE_THRESHOLD, CAMERA_RAW_JUMP_THRESHOLD, CAMERA_RAW_SCALE,
+    CAMERA_SPEED_MAX, CAMERA_SPEED_MIN, CAMERA_SPEED_STEP, CAMERA_SPRINT_MULTIPLIER,
 };
+use crate::settings::Settings;
 
 /// FPS-style camera controller (WASD + mouse look).
 pub struct CameraController {
     pub move_speed: f32,
     pub look_sensitivity: f32,
+    pub invert_y: bool,
     pub sprint_multiplier: f32,
     pub forward: bool,
     pub backward: bool,
@@ -33,12 +34,13 @@ pub struct CameraController {
 }
 
 impl CameraController {
-    pub fn new() -> Self {
-        let look_sensitivity = Self::resolve_sensitivity();
-
+    pub fn new(settings: &Settings) -> Self {
         Self {
-            move_speed: CAMERA_DEFAULT_MOVE_SPEED,
-            look_sensitivity,
+            move_speed: settings
+                .move_speed
+                .clamp(CAMERA_SPEED_MIN, CAMERA_SPEED_MAX),
+            look_sensitivity: settings.effective_mouse_sensitivity(),
+            invert_y: settings.invert_y,
             sprint_multiplier: CAMERA_SPRINT_MULTIPLIER,
             forward: false,
             backward: false,
@@ -57,22 +59,6 @@ impl CameraController {
         }
     }
 
-    fn resolve_sensitivity() -> f32 {
-        let Ok(val) = std::env::var("PATHTRACER_MOUSE_SENS") else {
-            return CAMERA_DEFAULT_SENSITIVITY;
-        };
-        match val.parse::<f32>() {
-            Ok(sens) if sens > 0.0 && sens.is_finite() => {
-                log::info!("PATHTRACER_MOUSE_SENS={sens}");
-                sens
-            }
-            _ => {
-                log::warn!("PATHTRACER_MOUSE_SENS={val:?} invalid, using default");
-                CAMERA_DEFAULT_SENSITIVITY
-            }
-        }
-    }
-
     /// Returns true if the camera moved (signals accumulation reset).
     pub fn update(&mut self, camera: &mut Camera, dt: f32) -> bool {
         if self.speed_up {
@@ -163,8 +149,11 @@ impl CameraController {
     /// Apply accumulated mouse delta to camera rotation (called once per frame).
     /// Returns true if camera rotated (signals accumulation reset).
     pub fn apply_mouse_look(&mut self, camera: &mut Camera) -> bool {
-        let (dx, dy) = self.mouse_delta;
+        let (dx, mut dy) = self.mouse_delta;
         self.mouse_delta = (0.0, 0.0);
+        if self.invert_y {
+            dy = -dy;
+        }
         if dx == 0.0 && dy == 0.0 {
             return false;
         }
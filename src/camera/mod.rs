@@ -4,5 +4,6 @@
 #[allow(clippy::module_inception)]
 pub mod camera;
 pub mod controller;
+pub mod path;
 
 pub use controller::CameraController;
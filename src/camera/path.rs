@@ -0,0 +1,139 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use glam::{EulerRot, Quat, Vec3};
+
+use super::camera::Camera;
+use crate::scene::scene::CameraKeyframe;
+
+/// The animated part of a camera: where it is, where it looks and its FOV.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub position: Vec3,
+    pub orientation: Quat,
+    pub fov: f32,
+}
+
+impl CameraPose {
+    fn of_keyframe(key: &CameraKeyframe) -> Self {
+        Self::of_camera(&Camera::from_config(&key.camera))
+    }
+
+    pub fn of_camera(camera: &Camera) -> Self {
+        Self {
+            position: camera.position,
+            orientation: camera.orientation(),
+            fov: camera.fov,
+        }
+    }
+
+    /// Move `camera` to this pose. Roll picked up by interpolating between
+    /// orientations is dropped, since the camera has none.
+    pub fn apply(&self, camera: &mut Camera) {
+        let (yaw, pitch, _) = self.orientation.to_euler(EulerRot::YXZ);
+        camera.position = self.position;
+        camera.yaw = yaw.to_degrees();
+        camera.pitch = pitch.to_degrees();
+        camera.fov = self.fov;
+    }
+}
+
+/// Time of the first and last keyframe, or `None` without keyframes.
+pub fn time_range(keys: &[CameraKeyframe]) -> Option<(f32, f32)> {
+    Some((keys.first()?.time, keys.last()?.time))
+}
+
+/// Pose along the path through `keys` (sorted by time) at `time`, held at
+/// the first and last keyframe outside their range. Positions follow a
+/// Catmull-Rom spline through the keyframes, orientations are slerped along
+/// the shorter arc and the FOV is interpolated linearly.
+pub fn pose_at(keys: &[CameraKeyframe], time: f32) -> Option<CameraPose> {
+    let (first, last) = time_range(keys)?;
+    if keys.len() == 1 || time <= first {
+        return Some(CameraPose::of_keyframe(&keys[0]));
+    }
+    if time >= last {
+        return Some(CameraPose::of_keyframe(&keys[keys.len() - 1]));
+    }
+
+    // Segment keys[i]..keys[i + 1] contains `time`; its neighbours shape the
+    // tangents, repeating the end keys at the path's ends.
+    let i = keys.partition_point(|key| key.time <= time).max(1) - 1;
+    let pose = |idx: usize| CameraPose::of_keyframe(&keys[idx.min(keys.len() - 1)]);
+    let (p0, p1, p2, p3) = (pose(i.saturating_sub(1)), pose(i), pose(i + 1), pose(i + 2));
+    let span = keys[i + 1].time - keys[i].time;
+    let t = if span > 0.0 {
+        (time - keys[i].time) / span
+    } else {
+        1.0
+    };
+
+    Some(CameraPose {
+        position: catmull_rom(p0.position, p1.position, p2.position, p3.position, t),
+        orientation: p1.orientation.slerp(p2.orientation, t),
+        fov: p1.fov + (p2.fov - p1.fov) * t,
+    })
+}
+
+/// Uniform Catmull-Rom spline between `p1` and `p2` at `t` in [0, 1].
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::scene::CameraConfig;
+
+    fn key(time: f32, position: [f32; 3], yaw: f32, fov: f32) -> CameraKeyframe {
+        CameraKeyframe {
+            time,
+            camera: CameraConfig {
+                position,
+                rotation: [0.0, yaw, 0.0],
+                fov,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_path_passes_through_keyframes() {
+        let keys = [
+            key(0.0, [0.0, 0.0, 0.0], 0.0, 40.0),
+            key(1.0, [1.0, 0.0, 0.0], 90.0, 60.0),
+            key(3.0, [1.0, 2.0, 0.0], 90.0, 60.0),
+        ];
+        for key in &keys {
+            let pose = pose_at(&keys, key.time).unwrap();
+            assert!(pose.position.abs_diff_eq(key.camera.position.into(), 1e-5));
+            assert_eq!(pose.fov, key.camera.fov);
+        }
+        // Held at the ends.
+        assert_eq!(pose_at(&keys, -1.0), pose_at(&keys, 0.0));
+        assert_eq!(pose_at(&keys, 10.0), pose_at(&keys, 3.0));
+        assert_eq!(pose_at(&[], 0.0), None);
+
+        let mut camera = Camera::default();
+        pose_at(&keys, 0.5).unwrap().apply(&mut camera);
+        assert!((camera.yaw - 45.0).abs() < 1e-3, "{}", camera.yaw);
+        assert!(camera.pitch.abs() < 1e-3);
+        assert_eq!(camera.fov, 50.0);
+    }
+
+    #[test]
+    fn test_orientation_takes_the_short_way() {
+        let keys = [
+            key(0.0, [0.0; 3], 350.0, 60.0),
+            key(1.0, [0.0; 3], 10.0, 60.0),
+        ];
+        let mut camera = Camera::default();
+        pose_at(&keys, 0.5).unwrap().apply(&mut camera);
+        assert!(camera.yaw.abs() < 1e-3, "{}", camera.yaw);
+    }
+}
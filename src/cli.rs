@@ -0,0 +1,220 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use anyhow::{Result, bail};
+
+use crate::accel::bvh::BvhParams;
+use crate::constants::{MAX_BVH_LEAF_PRIMS, MAX_BVH_NUM_BINS, MAX_UI_SCALE, MIN_UI_SCALE};
+use crate::gpu::context::PresentModeChoice;
+use crate::render::frame::WorkgroupSize;
+
+pub const USAGE: &str = "\
+Usage: path-tracer [OPTIONS] [SCENE]
+
+Arguments:
+  [SCENE]                  Scene file to open (YAML or JSON)
+
+Options:
+  --present-mode <MODE>    auto-vsync (default), immediate or mailbox
+  --workgroup <WxH>        Compute workgroup size, e.g. 16x16 or 8x4 (default 8x8)
+  --bvh-leaf-size <N>      Most primitives per BVH leaf, 1-32 (default 4)
+  --bvh-bins <N>           SAH split candidates per axis, 2-64 (default 12)
+  --brute-force            Skip the BVH and test every shape per ray (debugging)
+  --ui-scale <SCALE>       UI size in pixels per point, 0.5-4 (default: display scale)
+  --dev                    Enable developer tools (WGSL shader console)
+  --benchmark <SCENE>      Render SCENE headlessly and print samples/sec as JSON
+  --batch <MANIFEST>       Render every job in a YAML manifest headlessly
+  -h, --help               Print this help";
+
+/// Parsed command-line arguments.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CliArgs {
+    pub scene_path: Option<String>,
+    pub present_mode: Option<PresentModeChoice>,
+    pub workgroup: Option<WorkgroupSize>,
+    pub bvh_leaf_size: Option<usize>,
+    pub bvh_bins: Option<usize>,
+    /// Trace every shape without a BVH.
+    pub brute_force: bool,
+    /// UI pixels per point, overriding the display's scale factor.
+    pub ui_scale: Option<f32>,
+    /// Show developer tools such as the shader console.
+    pub dev: bool,
+    /// Scene to benchmark without opening a window.
+    pub benchmark: Option<String>,
+    /// Batch manifest to render without opening a window.
+    pub batch: Option<String>,
+    pub help: bool,
+}
+
+impl CliArgs {
+    /// Parse arguments, excluding the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut cli = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => cli.help = true,
+                "--present-mode" => {
+                    let Some(value) = args.next() else {
+                        bail!("--present-mode requires a value");
+                    };
+                    cli.present_mode = Some(parse_present_mode(&value)?);
+                }
+                _ if arg.starts_with("--present-mode=") => {
+                    cli.present_mode = Some(parse_present_mode(&arg["--present-mode=".len()..])?);
+                }
+                "--workgroup" => {
+                    let Some(value) = args.next() else {
+                        bail!("--workgroup requires a value");
+                    };
+                    cli.workgroup = Some(parse_workgroup(&value)?);
+                }
+                "--benchmark" => {
+                    let Some(value) = args.next() else {
+                        bail!("--benchmark requires a scene path");
+                    };
+                    cli.benchmark = Some(value);
+                }
+                "--batch" => {
+                    let Some(value) = args.next() else {
+                        bail!("--batch requires a manifest path");
+                    };
+                    cli.batch = Some(value);
+                }
+                "--bvh-leaf-size" => {
+                    let Some(value) = args.next() else {
+                        bail!("--bvh-leaf-size requires a value");
+                    };
+                    cli.bvh_leaf_size = Some(parse_count(
+                        "--bvh-leaf-size",
+                        &value,
+                        1,
+                        MAX_BVH_LEAF_PRIMS,
+                    )?);
+                }
+                "--bvh-bins" => {
+                    let Some(value) = args.next() else {
+                        bail!("--bvh-bins requires a value");
+                    };
+                    cli.bvh_bins = Some(parse_count("--bvh-bins", &value, 2, MAX_BVH_NUM_BINS)?);
+                }
+                "--brute-force" => cli.brute_force = true,
+                "--dev" => cli.dev = true,
+                "--ui-scale" => {
+                    let Some(value) = args.next() else {
+                        bail!("--ui-scale requires a value");
+                    };
+                    cli.ui_scale = Some(parse_ui_scale(&value)?);
+                }
+                _ if arg.starts_with("--workgroup=") => {
+                    cli.workgroup = Some(parse_workgroup(&arg["--workgroup=".len()..])?);
+                }
+                _ if arg.starts_with('-') => bail!("Unknown option '{arg}'\n\n{USAGE}"),
+                _ if cli.scene_path.is_none() => cli.scene_path = Some(arg),
+                _ => bail!("Unexpected argument '{arg}'\n\n{USAGE}"),
+            }
+        }
+        Ok(cli)
+    }
+
+    /// BVH build parameters with the command-line overrides applied.
+    pub fn bvh_params(&self) -> BvhParams {
+        let defaults = BvhParams::default();
+        BvhParams {
+            max_leaf_prims: self.bvh_leaf_size.unwrap_or(defaults.max_leaf_prims),
+            num_bins: self.bvh_bins.unwrap_or(defaults.num_bins),
+            brute_force: self.brute_force,
+        }
+    }
+}
+
+fn parse_present_mode(value: &str) -> Result<PresentModeChoice> {
+    PresentModeChoice::from_name(value).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Invalid present mode '{value}' (expected auto-vsync, immediate or mailbox)"
+        )
+    })
+}
+
+fn parse_count(flag: &str, value: &str, min: usize, max: usize) -> Result<usize> {
+    match value.parse::<usize>() {
+        Ok(n) if (min..=max).contains(&n) => Ok(n),
+        _ => bail!("{flag} expects a number from {min} to {max}, got '{value}'"),
+    }
+}
+
+fn parse_ui_scale(value: &str) -> Result<f32> {
+    match value.parse::<f32>() {
+        Ok(scale) if (MIN_UI_SCALE..=MAX_UI_SCALE).contains(&scale) => Ok(scale),
+        _ => bail!(
+            "--ui-scale expects a number from {MIN_UI_SCALE} to {MAX_UI_SCALE}, got '{value}'"
+        ),
+    }
+}
+
+fn parse_workgroup(value: &str) -> Result<WorkgroupSize> {
+    WorkgroupSize::parse(value).ok_or_else(|| {
+        anyhow::anyhow!("Invalid workgroup size '{value}' (expected e.g. 16x16 or 8)")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliArgs> {
+        CliArgs::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_scene_and_present_mode() {
+        let cli = parse(&["scene.yaml", "--present-mode", "immediate"]).unwrap();
+        assert_eq!(cli.scene_path.as_deref(), Some("scene.yaml"));
+        assert_eq!(cli.present_mode, Some(PresentModeChoice::Immediate));
+
+        let cli = parse(&["--present-mode=mailbox"]).unwrap();
+        assert_eq!(cli.scene_path, None);
+        assert_eq!(cli.present_mode, Some(PresentModeChoice::Mailbox));
+
+        let cli = parse(&["--workgroup=16x4"]).unwrap();
+        assert_eq!(cli.workgroup, Some(WorkgroupSize { x: 16, y: 4 }));
+
+        let cli = parse(&["--bvh-leaf-size", "1", "--bvh-bins", "32"]).unwrap();
+        assert_eq!(
+            cli.bvh_params(),
+            BvhParams {
+                max_leaf_prims: 1,
+                num_bins: 32,
+                brute_force: false,
+            }
+        );
+        assert!(parse(&["--brute-force"]).unwrap().bvh_params().brute_force);
+        assert_eq!(CliArgs::default().bvh_params(), BvhParams::default());
+
+        assert_eq!(parse(&["--ui-scale", "1.5"]).unwrap().ui_scale, Some(1.5));
+        assert!(parse(&["--dev"]).unwrap().dev);
+        assert!(!CliArgs::default().dev);
+
+        let cli = parse(&["--benchmark", "demo.yaml"]).unwrap();
+        assert_eq!(cli.benchmark.as_deref(), Some("demo.yaml"));
+        assert_eq!(cli.scene_path, None);
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(parse(&["--present-mode"]).is_err());
+        assert!(parse(&["--present-mode", "fast"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&["--benchmark"]).is_err());
+        assert!(parse(&["--batch"]).is_err());
+        assert!(parse(&["--workgroup", "0x8"]).is_err());
+        assert!(parse(&["--bvh-leaf-size"]).is_err());
+        assert!(parse(&["--bvh-leaf-size", "0"]).is_err());
+        assert!(parse(&["--bvh-bins", "65"]).is_err());
+        assert!(parse(&["--ui-scale"]).is_err());
+        assert!(parse(&["--ui-scale", "big"]).is_err());
+        assert!(parse(&["--ui-scale", "10"]).is_err());
+        assert!(parse(&["a.yaml", "b.yaml"]).is_err());
+    }
+}
@@ -6,9 +6,15 @@ use std::path::PathBuf;
 // GPU / compute
 pub const WORKGROUP_SIZE: u32 = 8;
 
-// BVH construction
-pub const BVH_NUM_BINS: usize = 12;
-pub const BVH_LEAF_MAX_PRIMS: usize = 4;
+// BVH construction defaults (see `BvhParams`) and the largest values the
+// CLI and debug panel accept
+pub const DEFAULT_BVH_NUM_BINS: usize = 12;
+pub const DEFAULT_BVH_LEAF_PRIMS: usize = 4;
+pub const MAX_BVH_NUM_BINS: usize = 64;
+pub const MAX_BVH_LEAF_PRIMS: usize = 32;
+/// How far (relative to the mesh size) triangles may drift from a rigid move
+/// or uniform scale before a cached mesh BVH is rebuilt instead of refit.
+pub const BLAS_REFIT_TOLERANCE: f32 = 1e-3;
 
 // AABB padding
 pub const AABB_EPS: f32 = 0.0001;
@@ -17,14 +23,28 @@ pub const AABB_EPS: f32 = 0.0001;
 pub const DEFAULT_FOV: f32 = 60.0;
 pub const DEFAULT_EXPOSURE: f32 = 1.0;
 pub const DEFAULT_MAX_BOUNCES: u32 = 16;
+/// Upper limit of a material's own `max_bounces`; substituted into
+/// `path_trace.wgsl` as `{{MAX_MATERIAL_BOUNCES}}`.
+pub const MAX_MATERIAL_BOUNCES: u32 = 32;
 pub const DEFAULT_CAMERA_POSITION: [f32; 3] = [0.0, 2.0, -10.0];
 
 // Render settings defaults
 pub const DEFAULT_FIREFLY_CLAMP: f32 = 100.0;
 pub const DEFAULT_SKYBOX_COLOR: [f32; 3] = [0.5, 0.7, 1.0];
+pub const DEFAULT_SKYBOX_HORIZON_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
 pub const DEFAULT_SKYBOX_BRIGHTNESS: f32 = 0.3;
+pub const DEFAULT_SUN_AZIMUTH: f32 = 45.0; // degrees, clockwise from +Z towards +X
+pub const DEFAULT_SUN_ELEVATION: f32 = 35.0; // degrees above the horizon
+pub const DEFAULT_SKY_TURBIDITY: f32 = 3.0;
 pub const DEFAULT_TONE_MAPPER: u32 = 0; // 0=ACES, 1=Reinhard, 2=None
+pub const TONE_MAPPER_NAMES: [&str; 3] = ["ACES", "Reinhard", "None"];
+pub const DEFAULT_WHITE_POINT: f32 = 0.0; // 0 = the tone mapper's own curve
+pub const MAX_WHITE_POINT: f32 = 32.0;
 pub const DEFAULT_FRACTAL_MARCH_STEPS: u32 = 256;
+pub const DEFAULT_NEAR_CLIP: f32 = 0.0;
+pub const DEFAULT_FAR_CLIP: f32 = 0.0; // 0 = no far clip
+pub const MAX_NEAR_CLIP: f32 = 100.0;
+pub const MAX_FAR_CLIP: f32 = 100_000.0;
 pub const DEFAULT_OIL_RADIUS: u32 = 3;
 pub const DEFAULT_COMIC_LEVELS: u32 = 4;
 
@@ -48,22 +68,129 @@ pub const DRAG_THRESHOLD_PX: f32 = 5.0;
 // OBJ import / model scaling
 pub const MODEL_AUTO_SCALE_TARGET: f32 = 3.0;
 
+// Image files accepted as textures; the HDR ones keep their float range
+pub const TEXTURE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "tga", "hdr", "exr"];
+pub const HDR_TEXTURE_EXTENSIONS: &[&str] = &["hdr", "exr"];
+
+// Editor headlight: default strength, and the sky brightness (radiance of its
+// brightest color) below which a scene without lights counts as unlit
+pub const DEFAULT_HEADLIGHT_STRENGTH: f32 = 1.0;
+pub const HEADLIGHT_SKY_THRESHOLD: f32 = 0.05;
+
+// Seconds between attempts to recreate a lost GPU device
+pub const DEVICE_RECOVERY_RETRY_SECS: f32 = 2.0;
+
+// Shader module the `--dev` shader console edits: the BRDF that path_trace samples
+pub const USER_BSDF_MODULE: &str = "materials";
+
+// UI scale override range, in physical pixels per egui point
+pub const MIN_UI_SCALE: f32 = 0.5;
+pub const MAX_UI_SCALE: f32 = 4.0;
+
+// Stereo rendering: default and largest eye separation, in scene units
+pub const DEFAULT_STEREO_IPD: f32 = 0.065;
+pub const MAX_STEREO_IPD: f32 = 1.0;
+
+// Imported triangles whose edges are closer to parallel than this (sine of
+// the angle between them) have no area and are dropped; merged triangles may
+// bend UVs or straight edges by at most the same relative amount
+pub const DEGENERATE_TRIANGLE_SIN: f32 = 1e-6;
+pub const MESH_MERGE_TOLERANCE: f32 = 1e-5;
+
+// Distance in front of the camera at which new shapes and models appear,
+// and the largest distance the setting allows
+pub const DEFAULT_SPAWN_DISTANCE: f32 = 5.0;
+pub const MAX_SPAWN_DISTANCE: f32 = 100.0;
+
+// Smallest bounding radius the camera frames when flying to a shape, and
+// the radius used for shapes without finite bounds (planes, skyboxes)
+pub const MIN_FRAME_RADIUS: f32 = 0.25;
+pub const UNBOUNDED_FRAME_RADIUS: f32 = 2.0;
+// Widest field of view (degrees) when looking through a spot light's cone
+pub const MAX_LIGHT_VIEW_FOV: f32 = 150.0;
+
+// Vertex lighting bake: cosine-weighted rays per vertex side, and how far
+// they start off the surface
+pub const BAKE_SAMPLES: u32 = 128;
+pub const BAKE_RAY_OFFSET: f32 = 1e-3;
+
+// Blue-noise mask for primary-ray jitter: side of the tiled square
+// (substituted into camera.wgsl as `{{BLUE_NOISE_SIZE}}`) and the
+// void-and-cluster filter width in pixels
+pub const BLUE_NOISE_SIZE: usize = 64;
+pub const BLUE_NOISE_SIGMA: f32 = 1.5;
+
+// Smallest radius (or instance scale) a shape can be given in the editor;
+// loaded non-positive radii are raised to it so the shape stays visible.
+pub const MIN_SHAPE_RADIUS: f32 = 0.01;
+
+// Dynamic resolution: render at 1/N width and height while the camera moves
+pub const DYNAMIC_RESOLUTION_DIVISOR: u32 = 2;
+
 // Accumulation buffer: vec4<f32> = 16 bytes per pixel
 pub const ACCUM_BYTES_PER_PIXEL: u64 = 16;
+// Object id buffer: u32 = 4 bytes per pixel
+pub const OBJECT_ID_BYTES_PER_PIXEL: u64 = 4;
+
+// Auto-exposure: accumulation rows read back per measurement, how often to
+// measure, the mid-gray the average luminance is mapped to, how fast the
+// exposure eases towards it (per second) and the allowed multiplier range
+pub const AUTO_EXPOSURE_SAMPLE_ROWS: u32 = 32;
+pub const AUTO_EXPOSURE_INTERVAL_SECS: f32 = 0.25;
+pub const AUTO_EXPOSURE_KEY: f32 = 0.18;
+pub const AUTO_EXPOSURE_ADAPT_SPEED: f32 = 3.0;
+pub const AUTO_EXPOSURE_MIN: f32 = 0.01;
+pub const AUTO_EXPOSURE_MAX: f32 = 100.0;
+
+// Longest side of the internal render resolution; larger windows are upscaled by the blit
+pub const DEFAULT_MAX_RENDER_SIZE: u32 = 3840;
+// Choices offered in Settings (0 = match the window)
+pub const MAX_RENDER_SIZE_OPTIONS: &[u32] = &[1280, 1920, 2560, 3840, 5120, 0];
+// Supersampling factors offered in Settings: render at this multiple of the
+// window size and average down in the blit
+pub const SUPERSAMPLING_OPTIONS: &[f32] = &[1.0, 1.5, 2.0];
+// Contrast-adaptive sharpening strength (0..1) applied by the blit when upscaling
+pub const UPSCALE_SHARPNESS: f32 = 0.6;
+
+// Frame-rate cap choices offered in Settings (0 = uncapped, VSync only)
+pub const FPS_CAP_OPTIONS: &[u32] = &[0, 30, 60, 120];
+// Samples an empty scene accumulates; with only the sky to trace, more
+// would just keep the GPU busy
+pub const EMPTY_SCENE_SAMPLES: u32 = 16;
+// Most path-trace samples one frame may run
+pub const MAX_SAMPLES_PER_FRAME: u32 = 16;
+// Frame time above which a frame runs fewer samples, keeping the UI responsive
+pub const SAMPLES_PER_FRAME_BUDGET_SECS: f32 = 0.05;
 
 // Window defaults
 pub const DEFAULT_WINDOW_WIDTH: u32 = 1280;
 pub const DEFAULT_WINDOW_HEIGHT: u32 = 720;
+// Seconds the window size must hold before the render targets follow it
+pub const RESIZE_SETTLE_SECS: f32 = 0.25;
+
+// --benchmark: fixed resolution and samples per pixel
+pub const BENCHMARK_WIDTH: u32 = 1280;
+pub const BENCHMARK_HEIGHT: u32 = 720;
+pub const BENCHMARK_SAMPLES: u32 = 64;
+
+// --batch: samples per pixel for manifest jobs that don't set `samples`
+pub const BATCH_DEFAULT_SAMPLES: u32 = 256;
+
+// Camera path: time between a new keyframe and the previous last one, and
+// the longest playback duration offered in the UI (seconds)
+pub const CAMERA_KEYFRAME_SPACING_SECS: f32 = 2.0;
+pub const MAX_CAMERA_PATH_DURATION_SECS: f32 = 120.0;
 
 // Default paths
 pub const WINDOW_ICON_PATH: &str = "resources/icon.png";
 pub const EXAMPLE_SCENES_DIR: &str = "resources/scenes";
 
 // Post-process params slot counts
-pub const POST_PARAMS_SIZE: usize = 16;
+pub const POST_PARAMS_SIZE: usize = 24;
 pub const POST_PARAMS_MAX_EFFECTS: usize = 8;
 
 /// Resolve a data-file path: check next to the executable first, then macOS bundle, then CWD.
+#[allow(clippy::collapsible_if)]
 pub fn resolve_data_path(relative: &str) -> PathBuf {
     if let Ok(exe) = std::env::current_exe() {
         if let Some(dir) = exe.parent() {
@@ -83,31 +210,41 @@ pub fn resolve_data_path(relative: &str) -> PathBuf {
     PathBuf::from(relative)
 }
 
-/// Resolve a relative resource path using multiple strategies:
-/// 1. Return as-is if the path already exists (e.g. `cargo run` from project root)
-/// 2. Try relative to the scene file's directory
+/// Resolve a resource path referenced by a scene using multiple strategies:
+/// 1. Try relative to the scene file's directory (an existing absolute path wins here)
+/// 2. Return as-is if reachable from CWD (e.g. `cargo run` from project root)
 /// 3. Try via `resolve_data_path()` (next to executable / macOS bundle)
-/// 4. Fall back to the original path unchanged
+/// 4. Try the bare file name next to the scene, for absolute paths from another machine
+/// 5. Fall back to the original path unchanged
 pub fn resolve_resource_path(scene_dir: &std::path::Path, relative: &str) -> String {
-    // 1. Already reachable from CWD
-    if std::path::Path::new(relative).exists() {
-        return relative.to_string();
-    }
-    // 2. Relative to the scene file's parent directory
+    // 1. Relative to the scene file's parent directory. Checked before CWD so
+    //    the result doesn't depend on where the app was started.
     let scene_relative = scene_dir.join(relative);
     if scene_relative.exists() {
         return scene_relative.to_string_lossy().into_owned();
     }
+    // 2. Reachable from CWD
+    if std::path::Path::new(relative).exists() {
+        return relative.to_string();
+    }
     // 3. Next to the executable / inside bundle
     let data = resolve_data_path(relative);
     if data.exists() {
         return data.to_string_lossy().into_owned();
     }
-    // 4. Return unchanged — let the caller handle the missing file
+    // 4. Same file name next to the scene
+    if let Some(name) = std::path::Path::new(relative).file_name() {
+        let sibling = scene_dir.join(name);
+        if sibling.exists() {
+            return sibling.to_string_lossy().into_owned();
+        }
+    }
+    // 5. Return unchanged — let the caller handle the missing file
     relative.to_string()
 }
 
 /// Scan the bundled example scenes directory and return sorted stem names.
+#[allow(clippy::collapsible_if)]
 pub fn discover_example_scenes() -> Vec<String> {
     let dir = resolve_data_path(EXAMPLE_SCENES_DIR);
     let mut names = Vec::new();
@@ -1,6 +1,9 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::ops::Range;
+
+use anyhow::{Result, bail};
 use wgpu::util::DeviceExt;
 
 pub fn create_storage_buffer<T: bytemuck::Pod>(
@@ -90,3 +93,181 @@ pub fn create_output_texture(
     let view = texture.create_view(&Default::default());
     (texture, view)
 }
+
+/// Copy an `Rgba8Unorm` texture back to the CPU as tightly packed rows.
+pub fn read_texture_rgba8(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>> {
+    let bytes_per_row_unpadded = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let bytes_per_row_padded = bytes_per_row_unpadded.div_ceil(align) * align;
+
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback staging"),
+        size: (bytes_per_row_padded * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("readback encoder"),
+    });
+
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &staging_buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row_padded),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let buffer_slice = staging_buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+
+    if !matches!(receiver.recv(), Ok(Ok(()))) {
+        bail!("Failed to map readback buffer");
+    }
+    let data = buffer_slice.get_mapped_range();
+    // Remove row padding if necessary.
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for row in 0..height {
+        let start = (row * bytes_per_row_padded) as usize;
+        let end = start + bytes_per_row_unpadded as usize;
+        pixels.extend_from_slice(&data[start..end]);
+    }
+    drop(data);
+    staging_buffer.unmap();
+    Ok(pixels)
+}
+
+/// Copy the byte `ranges` of a `COPY_SRC` buffer back to the CPU, one after
+/// another. Range bounds must be multiples of `COPY_BUFFER_ALIGNMENT`.
+pub fn read_buffer_ranges(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    ranges: &[Range<u64>],
+) -> Result<Vec<u8>> {
+    let total: u64 = ranges.iter().map(|r| r.end - r.start).sum();
+    if total == 0 {
+        return Ok(Vec::new());
+    }
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("buffer readback staging"),
+        size: total,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("buffer readback encoder"),
+    });
+    let mut offset = 0;
+    for range in ranges {
+        let size = range.end - range.start;
+        encoder.copy_buffer_to_buffer(buffer, range.start, &staging_buffer, offset, size);
+        offset += size;
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let buffer_slice = staging_buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+
+    if !matches!(receiver.recv(), Ok(Ok(()))) {
+        bail!("Failed to map readback buffer");
+    }
+    let data = buffer_slice.get_mapped_range().to_vec();
+    staging_buffer.unmap();
+    Ok(data)
+}
+
+/// Fail with a readable message if any `(label, bytes)` storage buffer exceeds
+/// what the device can bind, instead of letting buffer creation fail later.
+pub fn check_storage_sizes(limits: &wgpu::Limits, sizes: &[(&str, u64)]) -> Result<()> {
+    let max = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+    for &(label, bytes) in sizes {
+        if bytes > max {
+            bail!(
+                "Scene is too large for this GPU: {label} need {:.1} MiB, \
+                 but the device allows at most {:.1} MiB per buffer",
+                bytes as f64 / MIB,
+                max as f64 / MIB
+            );
+        }
+    }
+    Ok(())
+}
+
+pub const MIB: f64 = 1024.0 * 1024.0;
+
+/// Approximate VRAM held by the renderer's major resources, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GpuMemoryUsage {
+    /// Shape and light buffers.
+    pub shapes: u64,
+    pub materials: u64,
+    /// BVH nodes and primitive indices.
+    pub bvh: u64,
+    /// Texture atlas pixels and per-texture infos.
+    pub textures: u64,
+    /// Accumulation buffers and output textures, preview target included.
+    pub render_targets: u64,
+}
+
+impl GpuMemoryUsage {
+    pub fn total(&self) -> u64 {
+        self.shapes + self.materials + self.bvh + self.textures + self.render_targets
+    }
+}
+
+/// Bytes taken by a single-mip 2D texture.
+pub fn texture_size(texture: &wgpu::Texture) -> u64 {
+    let texel = texture.format().block_copy_size(None).unwrap_or(4);
+    texture.width() as u64 * texture.height() as u64 * texel as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_storage_sizes() {
+        let limits = wgpu::Limits {
+            max_storage_buffer_binding_size: 1024,
+            ..Default::default()
+        };
+        assert!(check_storage_sizes(&limits, &[("shapes", 1024), ("materials", 16)]).is_ok());
+
+        let err = check_storage_sizes(&limits, &[("shapes", 16), ("materials", 2048)])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("materials"));
+    }
+}
@@ -2,52 +2,83 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use winit::window::Window;
 
+/// Surface present modes selectable from the command line and the Debug menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PresentModeChoice {
+    /// VSync; always supported.
+    #[default]
+    AutoVsync,
+    /// No VSync, may tear. Best for measuring uncapped throughput.
+    Immediate,
+    /// Low-latency triple buffering without tearing.
+    Mailbox,
+}
+
+impl PresentModeChoice {
+    pub const ALL: &[Self] = &[Self::AutoVsync, Self::Immediate, Self::Mailbox];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::AutoVsync => "Auto VSync",
+            Self::Immediate => "Immediate (no VSync)",
+            Self::Mailbox => "Mailbox",
+        }
+    }
+
+    /// Parse the command-line spelling (`auto-vsync`, `immediate`, `mailbox`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('_', "-").as_str() {
+            "auto-vsync" | "autovsync" | "vsync" => Some(Self::AutoVsync),
+            "immediate" => Some(Self::Immediate),
+            "mailbox" => Some(Self::Mailbox),
+            _ => None,
+        }
+    }
+
+    pub fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            Self::AutoVsync => wgpu::PresentMode::AutoVsync,
+            Self::Immediate => wgpu::PresentMode::Immediate,
+            Self::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
+
 pub struct GpuContext {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub surface: wgpu::Surface<'static>,
     pub surface_config: wgpu::SurfaceConfiguration,
     pub adapter: wgpu::Adapter,
+    /// Present modes the surface reports as supported.
+    pub supported_present_modes: Vec<PresentModeChoice>,
+    pub present_mode: PresentModeChoice,
+    /// Set once the driver reports the device lost (GPU reset, driver
+    /// update) or out of memory; see `AppState::recover_lost_device`.
+    lost: Arc<AtomicBool>,
 }
 
 impl GpuContext {
-    pub fn new(window: Arc<Window>) -> Result<Self> {
-        // Prefer Vulkan/Metal/DX12 — these support compute shaders.
-        // OpenGL fallback lacks storage buffers needed for path tracing.
-        let backends = wgpu::Backends::VULKAN | wgpu::Backends::METAL | wgpu::Backends::DX12;
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends,
-            ..Default::default()
-        });
+    pub fn new(window: Arc<Window>, present_mode: PresentModeChoice) -> Result<Self> {
+        let gpu = Self::unconfigured(window, present_mode)?;
+        gpu.configure_surface();
+        Ok(gpu)
+    }
 
+    /// Like `new`, but the surface is left unconfigured. A window can only
+    /// have one swapchain (Vulkan reports the window as in use otherwise),
+    /// so a context replacing another must drop the old one before calling
+    /// `configure_surface`.
+    pub fn unconfigured(window: Arc<Window>, present_mode: PresentModeChoice) -> Result<Self> {
+        let instance = create_instance();
         let surface = instance.create_surface(window.clone())?;
-
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        }))
-        .ok_or_else(|| {
-            anyhow::anyhow!(
-                "No suitable GPU adapter found. PathTracer requires Vulkan, Metal, or DX12."
-            )
-        })?;
-
-        let info = adapter.get_info();
-        log::info!("Using GPU: {} (backend: {:?})", info.name, info.backend);
-
-        let (device, queue) = pollster::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: Some("PathTracer Device"),
-                required_features: wgpu::Features::empty(),
-                required_limits: adapter.limits(),
-                ..Default::default()
-            },
-            None,
-        ))?;
+        let (adapter, device, queue) = request_device(&instance, Some(&surface))?;
 
         let size = window.inner_size();
         let surface_caps = surface.get_capabilities(&adapter);
@@ -60,17 +91,57 @@ impl GpuContext {
             .copied()
             .unwrap_or(surface_caps.formats[0]);
 
+        // AutoVsync falls back to Fifo, which every surface supports.
+        let supported_present_modes: Vec<PresentModeChoice> = PresentModeChoice::ALL
+            .iter()
+            .copied()
+            .filter(|m| {
+                *m == PresentModeChoice::AutoVsync
+                    || surface_caps.present_modes.contains(&m.to_wgpu())
+            })
+            .collect();
+        let present_mode = if supported_present_modes.contains(&present_mode) {
+            present_mode
+        } else {
+            log::warn!(
+                "Present mode {present_mode:?} is not supported by this surface, using AutoVsync"
+            );
+            PresentModeChoice::AutoVsync
+        };
+
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width.max(1),
             height: size.height.max(1),
-            present_mode: wgpu::PresentMode::AutoVsync,
+            present_mode: present_mode.to_wgpu(),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
-        surface.configure(&device, &surface_config);
+        let lost = Arc::new(AtomicBool::new(false));
+        let flag = lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Dropping the device on purpose reports a loss too.
+            if matches!(
+                reason,
+                wgpu::DeviceLostReason::Unknown | wgpu::DeviceLostReason::Destroyed
+            ) {
+                log::error!("GPU device lost ({reason:?}): {message}");
+                flag.store(true, Ordering::Relaxed);
+            }
+        });
+        let flag = lost.clone();
+        device.on_uncaptured_error(Box::new(move |error| match error {
+            wgpu::Error::OutOfMemory { .. } => {
+                log::error!("GPU out of memory: {error}");
+                flag.store(true, Ordering::Relaxed);
+            }
+            // Calls on a lost device fail until it is replaced.
+            _ if flag.load(Ordering::Relaxed) => log::warn!("GPU error after device loss: {error}"),
+            // Anything else is a bug, fatal as with wgpu's default handler.
+            _ => panic!("wgpu error: {error}"),
+        }));
 
         Ok(Self {
             device,
@@ -78,17 +149,47 @@ impl GpuContext {
             surface,
             surface_config,
             adapter,
+            supported_present_modes,
+            present_mode,
+            lost,
         })
     }
 
+    /// Whether the device is gone and the context must be created anew.
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// Treat the device as lost, e.g. after the surface ran out of memory.
+    pub fn mark_lost(&self) {
+        self.lost.store(true, Ordering::Relaxed);
+    }
+
+    /// Switch the present mode and reconfigure the surface. Returns false if
+    /// the surface doesn't support `mode`.
+    pub fn set_present_mode(&mut self, mode: PresentModeChoice) -> bool {
+        if !self.supported_present_modes.contains(&mode) {
+            return false;
+        }
+        self.present_mode = mode;
+        self.surface_config.present_mode = mode.to_wgpu();
+        self.configure_surface();
+        true
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.surface_config.width = width;
             self.surface_config.height = height;
-            self.surface.configure(&self.device, &self.surface_config);
+            self.configure_surface();
         }
     }
 
+    /// (Re)create the swapchain from `surface_config`.
+    pub fn configure_surface(&self) {
+        self.surface.configure(&self.device, &self.surface_config);
+    }
+
     pub fn surface_format(&self) -> wgpu::TextureFormat {
         self.surface_config.format
     }
@@ -101,3 +202,48 @@ impl GpuContext {
         self.surface_config.height
     }
 }
+
+fn create_instance() -> wgpu::Instance {
+    // Prefer Vulkan/Metal/DX12 — these support compute shaders.
+    // OpenGL fallback lacks storage buffers needed for path tracing.
+    let backends = wgpu::Backends::VULKAN | wgpu::Backends::METAL | wgpu::Backends::DX12;
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    })
+}
+
+fn request_device(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue)> {
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::HighPerformance,
+        compatible_surface,
+        force_fallback_adapter: false,
+    }))
+    .ok_or_else(|| {
+        anyhow::anyhow!(
+            "No suitable GPU adapter found. PathTracer requires Vulkan, Metal, or DX12."
+        )
+    })?;
+
+    let info = adapter.get_info();
+    log::info!("Using GPU: {} (backend: {:?})", info.name, info.backend);
+
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("PathTracer Device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: adapter.limits(),
+            ..Default::default()
+        },
+        None,
+    ))?;
+    Ok((adapter, device, queue))
+}
+
+/// Adapter, device and queue without a window or surface, for offline rendering.
+pub fn create_headless_device() -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue)> {
+    request_device(&create_instance(), None)
+}
@@ -1,14 +1,17 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 
+/// Compile a compute shader with entry point `main`. WGSL and layout errors
+/// are returned rather than raised on the device.
 pub fn create_compute_pipeline(
     device: &wgpu::Device,
     shader_source: &str,
     bind_group_layouts: &[&wgpu::BindGroupLayout],
     label: &str,
 ) -> Result<wgpu::ComputePipeline> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
     let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
         label: Some(label),
         source: wgpu::ShaderSource::Wgsl(shader_source.into()),
@@ -29,6 +32,9 @@ pub fn create_compute_pipeline(
         cache: None,
     });
 
+    if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+        bail!("Failed to compile {label} shader: {error}");
+    }
     Ok(pipeline)
 }
 
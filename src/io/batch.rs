@@ -0,0 +1,151 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::constants::{BATCH_DEFAULT_SAMPLES, DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+use crate::render::frame::ColorSpace;
+
+/// `--batch` manifest: a list of headless renders to run in order.
+///
+/// ```yaml
+/// jobs:
+///   - scene: scenes/demo.yaml
+///     output: renders/demo.png
+///     width: 1920
+///     height: 1080
+///     samples: 512
+///   - scene: scenes/flythrough.yaml
+///     output: renders/fly.png   # fly_0000.png, fly_0001.png, ...
+///     frames: 120
+/// ```
+///
+/// Relative `scene` and `output` paths are resolved against the manifest's directory.
+/// A job with `frames` renders that many images evenly spaced along the
+/// scene's `camera_path` instead of a single still.
+#[derive(Debug, Deserialize)]
+pub struct BatchManifest {
+    pub jobs: Vec<BatchJob>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatchJob {
+    pub scene: PathBuf,
+    pub output: PathBuf,
+    #[serde(default = "default_width")]
+    pub width: u32,
+    #[serde(default = "default_height")]
+    pub height: u32,
+    #[serde(default = "default_samples")]
+    pub samples: u32,
+    /// Frames to render along the scene's camera path, 0 for a still.
+    #[serde(default)]
+    pub frames: u32,
+    /// Encoding of the written images.
+    #[serde(default)]
+    pub color_space: ColorSpace,
+}
+
+impl BatchJob {
+    /// Output path of animation frame `frame`: the number goes between the
+    /// file stem and the extension.
+    pub fn frame_output(&self, frame: u32) -> PathBuf {
+        let stem = self
+            .output
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let name = match self.output.extension() {
+            Some(ext) => format!("{stem}_{frame:04}.{}", ext.to_string_lossy()),
+            None => format!("{stem}_{frame:04}"),
+        };
+        self.output.with_file_name(name)
+    }
+}
+
+fn default_width() -> u32 {
+    DEFAULT_WINDOW_WIDTH
+}
+
+fn default_height() -> u32 {
+    DEFAULT_WINDOW_HEIGHT
+}
+
+fn default_samples() -> u32 {
+    BATCH_DEFAULT_SAMPLES
+}
+
+impl BatchManifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&text, base_dir).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    fn parse(text: &str, base_dir: &Path) -> Result<Self> {
+        let mut manifest: Self = serde_yml::from_str(text)?;
+        for job in &mut manifest.jobs {
+            job.scene = base_dir.join(&job.scene);
+            job.output = base_dir.join(&job.output);
+        }
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolves_paths_and_defaults() {
+        let yaml = "\
+jobs:
+  - scene: demo.yaml
+    output: out/demo.png
+    samples: 16
+  - scene: /abs/cornell.yaml
+    output: cornell.png
+    width: 640
+    height: 480
+    color_space: rec709
+";
+        let manifest = BatchManifest::parse(yaml, Path::new("batch")).unwrap();
+        assert_eq!(
+            manifest.jobs[0],
+            BatchJob {
+                scene: PathBuf::from("batch/demo.yaml"),
+                output: PathBuf::from("batch/out/demo.png"),
+                width: DEFAULT_WINDOW_WIDTH,
+                height: DEFAULT_WINDOW_HEIGHT,
+                samples: 16,
+                frames: 0,
+                color_space: ColorSpace::Srgb,
+            }
+        );
+        assert_eq!(manifest.jobs[1].scene, PathBuf::from("/abs/cornell.yaml"));
+        assert_eq!(
+            (manifest.jobs[1].width, manifest.jobs[1].height),
+            (640, 480)
+        );
+        assert_eq!(manifest.jobs[1].samples, BATCH_DEFAULT_SAMPLES);
+        assert_eq!(manifest.jobs[1].color_space, ColorSpace::Rec709);
+    }
+
+    #[test]
+    fn test_frame_output_numbers_before_the_extension() {
+        let yaml = "\
+jobs:
+  - scene: fly.yaml
+    output: out/fly.png
+    frames: 24
+";
+        let manifest = BatchManifest::parse(yaml, Path::new("batch")).unwrap();
+        let job = &manifest.jobs[0];
+        assert_eq!(job.frames, 24);
+        assert_eq!(job.frame_output(7), PathBuf::from("batch/out/fly_0007.png"));
+    }
+}
@@ -1,5 +1,6 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod batch;
 pub mod screenshot;
 pub mod texture_atlas;
@@ -1,24 +1,330 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result};
 
-pub fn save_screenshot(pixels: &[u8], width: u32, height: u32, path: &Path) -> Result<()> {
-    let img = image::RgbaImage::from_raw(width, height, pixels.to_vec())
-        .context("Failed to create image from pixel data")?;
-    img.save(path)
-        .with_context(|| format!("Failed to save screenshot to {}", path.display()))?;
+use crate::camera::camera::Camera;
+use crate::constants::{ACCUM_BYTES_PER_PIXEL, TONE_MAPPER_NAMES};
+use crate::render::frame::ColorSpace;
+
+/// What a screenshot shows and how it was rendered, written into PNGs as
+/// text chunks so a folder of renders documents itself.
+#[derive(Debug, Clone, Default)]
+pub struct ScreenshotMetadata {
+    /// File stem of the rendered scene, if it came from a file.
+    pub scene: Option<String>,
+    pub samples: u32,
+    /// Encoding of the pixels; also picks the PNG color chunks.
+    pub color_space: ColorSpace,
+    /// Further render settings as (name, value), one text chunk each.
+    pub settings: Vec<(String, String)>,
+}
+
+impl ScreenshotMetadata {
+    /// Metadata with the camera's render settings filled in.
+    pub fn new(scene: Option<String>, samples: u32, camera: &Camera) -> Self {
+        let tone_mapper = TONE_MAPPER_NAMES
+            .get(camera.tone_mapper as usize)
+            .copied()
+            .unwrap_or("Unknown");
+        Self {
+            scene,
+            samples,
+            color_space: ColorSpace::default(),
+            settings: vec![
+                ("FOV".to_string(), format!("{}", camera.fov)),
+                ("Max Bounces".to_string(), camera.max_bounces.to_string()),
+                ("Exposure".to_string(), format!("{}", camera.exposure)),
+                ("Tone Mapper".to_string(), tone_mapper.to_string()),
+                ("White Point".to_string(), format!("{}", camera.white_point)),
+                (
+                    "Firefly Clamp".to_string(),
+                    format!("{}", camera.firefly_clamp),
+                ),
+            ],
+        }
+    }
+
+    /// Add one more setting.
+    pub fn with(mut self, name: &str, value: impl ToString) -> Self {
+        self.settings.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// PNG text chunks: the registered Title, Software and Creation Time
+    /// keywords, then the sample count and the settings.
+    fn text_chunks(&self, unix_secs: u64) -> Vec<(String, String)> {
+        let mut chunks = Vec::new();
+        if let Some(scene) = &self.scene {
+            chunks.push(("Title".to_string(), scene.clone()));
+        }
+        chunks.push((
+            "Software".to_string(),
+            format!("PathTracer {}", env!("CARGO_PKG_VERSION")),
+        ));
+        let (date, time) = utc_date_time(unix_secs);
+        chunks.push((
+            "Creation Time".to_string(),
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                date.0, date.1, date.2, time.0, time.1, time.2
+            ),
+        ));
+        chunks.push(("Samples".to_string(), self.samples.to_string()));
+        chunks.push((
+            "Color Space".to_string(),
+            self.color_space.label().to_string(),
+        ));
+        chunks.extend(self.settings.iter().cloned());
+        chunks
+    }
+}
+
+/// Save RGBA8 `pixels`. PNGs carry the color chunks for the metadata's
+/// color space and its text chunks; other formats are written untagged.
+pub fn save_screenshot(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    path: &Path,
+    metadata: &ScreenshotMetadata,
+) -> Result<()> {
+    let is_png = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    let result = if is_png {
+        File::create(path)
+            .map_err(Into::into)
+            .and_then(|file| write_png(BufWriter::new(file), pixels, width, height, metadata))
+    } else {
+        image::RgbaImage::from_raw(width, height, pixels.to_vec())
+            .context("Failed to create image from pixel data")
+            .and_then(|img| img.save(path).map_err(Into::into))
+    };
+    result.with_context(|| format!("Failed to save screenshot to {}", path.display()))?;
     log::info!("Screenshot saved to {}", path.display());
     Ok(())
 }
 
-pub fn default_screenshot_path() -> PathBuf {
-    let timestamp = SystemTime::now()
+/// Encode RGBA8 `pixels` as a PNG with `metadata` in text chunks, tagged for
+/// its color space: an sRGB chunk, or gAMA and cHRM with the Rec.709
+/// primaries (shared by all three) and the transfer curve's exponent.
+pub fn write_png(
+    out: impl Write,
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    metadata: &ScreenshotMetadata,
+) -> Result<()> {
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // tEXt is Latin-1 only; other text (a scene named in Cyrillic) goes in iTXt.
+    for (keyword, text) in metadata.text_chunks(unix_now()) {
+        if text.is_ascii() {
+            encoder.add_text_chunk(keyword, text)?;
+        } else {
+            encoder.add_itxt_chunk(keyword, text)?;
+        }
+    }
+    let color_space = metadata.color_space;
+    match color_space {
+        ColorSpace::Srgb => encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual),
+        ColorSpace::Linear | ColorSpace::Rec709 => {
+            let gamma = if color_space == ColorSpace::Linear {
+                1.0
+            } else {
+                0.45
+            };
+            encoder.set_source_gamma(png::ScaledFloat::new(gamma));
+            encoder.set_source_chromaticities(png::SourceChromaticities::new(
+                (0.3127, 0.3290),
+                (0.64, 0.33),
+                (0.30, 0.60),
+                (0.15, 0.06),
+            ));
+        }
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Number of accumulation texels holding NaN or infinity.
+pub fn count_non_finite(texels: &[[f32; 4]]) -> usize {
+    texels
+        .iter()
+        .filter(|texel| !texel.iter().all(|c| c.is_finite()))
+        .count()
+}
+
+/// Rows of the accumulation buffer `check_accumulation` reads back.
+const CHECKED_ROWS: u32 = 64;
+
+/// Warn if the accumulation buffer behind a `width`x`height` screenshot holds
+/// NaN or infinite texels. The shader drops such samples, so this points at a
+/// bug rather than a bad pixel that will go away. Only `CHECKED_ROWS` evenly
+/// spaced rows are read back, which keeps batch renders from copying the
+/// whole float buffer every frame.
+pub fn check_accumulation(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    accumulation: &wgpu::Buffer,
+    width: u32,
+    height: u32,
+) {
+    let row_bytes = u64::from(width) * ACCUM_BYTES_PER_PIXEL;
+    let ranges: Vec<_> = checked_rows(height)
+        .map(|y| u64::from(y) * row_bytes..u64::from(y + 1) * row_bytes)
+        .collect();
+    match crate::gpu::buffers::read_buffer_ranges(device, queue, accumulation, &ranges) {
+        Ok(bytes) => {
+            let count = count_non_finite(bytemuck::cast_slice(&bytes));
+            if count > 0 {
+                log::warn!("Screenshot: {count} accumulated pixels are NaN or infinite");
+            }
+        }
+        Err(e) => log::warn!("Could not check the accumulation buffer: {e:#}"),
+    }
+}
+
+/// Every row of a short image, otherwise the middle rows of `CHECKED_ROWS`
+/// equal bands.
+fn checked_rows(height: u32) -> impl Iterator<Item = u32> {
+    let bands = height.min(CHECKED_ROWS);
+    (0..bands).map(move |i| ((2 * i + 1) * height) / (2 * bands))
+}
+
+/// File name for a screenshot of `scene` (or "screenshot") taken now, e.g.
+/// `demo_256spp_20261016-142301.png`, so names sort by scene and then time.
+pub fn default_screenshot_path(scene: Option<&str>, samples: u32) -> PathBuf {
+    screenshot_name(scene, samples, unix_now())
+}
+
+fn screenshot_name(scene: Option<&str>, samples: u32, unix_secs: u64) -> PathBuf {
+    let ((year, month, day), (hour, minute, second)) = utc_date_time(unix_secs);
+    PathBuf::from(format!(
+        "{}_{samples}spp_{year:04}{month:02}{day:02}-{hour:02}{minute:02}{second:02}.png",
+        scene.unwrap_or("screenshot")
+    ))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
-        .as_secs();
-    PathBuf::from(format!("screenshot_{timestamp}.png"))
+        .as_secs()
+}
+
+/// UTC (year, month, day) and (hour, minute, second) of a Unix time, using
+/// Howard Hinnant's days-to-civil algorithm.
+fn utc_date_time(unix_secs: u64) -> ((i64, u32, u32), (u32, u32, u32)) {
+    let days = (unix_secs / 86_400) as i64;
+    let secs = (unix_secs % 86_400) as u32;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    ((year, month, day), (secs / 3600, secs / 60 % 60, secs % 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_non_finite() {
+        let texels = [
+            [0.5, 0.25, 0.0, 1.0],
+            [f32::NAN, 0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0, 0.0],
+            [1.0, f32::INFINITY, 1.0, 1.0],
+            [1.0, 1.0, 1.0, f32::NEG_INFINITY],
+        ];
+        assert_eq!(count_non_finite(&texels), 3);
+        assert_eq!(count_non_finite(&[]), 0);
+    }
+
+    #[test]
+    fn test_checked_rows_span_the_image() {
+        assert_eq!(checked_rows(3).collect::<Vec<_>>(), [0, 1, 2]);
+        let rows: Vec<_> = checked_rows(1080).collect();
+        assert_eq!(rows.len(), CHECKED_ROWS as usize);
+        assert!(rows.windows(2).all(|w| w[0] < w[1]));
+        assert!(rows[0] < 20 && rows[rows.len() - 1] > 1060);
+    }
+
+    #[test]
+    fn test_png_is_tagged_with_the_color_space() {
+        let pixels = [10, 20, 30, 255, 40, 50, 60, 255];
+        let info = |color_space| {
+            let metadata = ScreenshotMetadata {
+                color_space,
+                ..Default::default()
+            };
+            let mut bytes = Vec::new();
+            write_png(&mut bytes, &pixels, 2, 1, &metadata).unwrap();
+            let mut reader = png::Decoder::new(std::io::Cursor::new(bytes))
+                .read_info()
+                .unwrap();
+            let mut decoded = vec![0; reader.output_buffer_size().unwrap()];
+            reader.next_frame(&mut decoded).unwrap();
+            assert_eq!(decoded, pixels);
+            let info = reader.info();
+            (info.srgb, info.gama_chunk.map(|g| g.into_value()))
+        };
+        assert_eq!(
+            info(ColorSpace::Srgb).0,
+            Some(png::SrgbRenderingIntent::Perceptual)
+        );
+        assert_eq!(info(ColorSpace::Linear), (None, Some(1.0)));
+        assert_eq!(info(ColorSpace::Rec709), (None, Some(0.45)));
+    }
+
+    #[test]
+    fn test_metadata_lands_in_text_chunks() {
+        let camera = Camera::default();
+        let metadata = ScreenshotMetadata::new(Some("cornell".to_string()), 512, &camera)
+            .with("Next Event Estimation", true);
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, &[0; 4], 1, 1, &metadata).unwrap();
+        let reader = png::Decoder::new(std::io::Cursor::new(bytes))
+            .read_info()
+            .unwrap();
+        let text: Vec<_> = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.as_str(), chunk.text.as_str()))
+            .collect();
+        assert!(text.contains(&("Title", "cornell")));
+        assert!(text.contains(&("Samples", "512")));
+        assert!(text.contains(&("Tone Mapper", "ACES")));
+        assert!(text.contains(&("Next Event Estimation", "true")));
+    }
+
+    #[test]
+    fn test_screenshot_name_encodes_samples_and_time() {
+        // 2024-02-29 23:59:58 UTC
+        let secs = 1_709_251_198;
+        assert_eq!(
+            screenshot_name(Some("demo"), 256, secs),
+            PathBuf::from("demo_256spp_20240229-235958.png")
+        );
+        assert_eq!(
+            screenshot_name(None, 1, 0),
+            PathBuf::from("screenshot_1spp_19700101-000000.png")
+        );
+    }
 }
@@ -1,38 +1,49 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use bytemuck::{Pod, Zeroable};
 
+use crate::constants::HDR_TEXTURE_EXTENSIONS;
+
 /// Metadata for a single texture in the atlas.
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
 pub struct TextureInfo {
     pub width: u32,
     pub height: u32,
     /// Byte offset into the pixel buffer.
     pub offset: u32,
-    pub _pad: u32,
+    /// 1 if the texels are in `float_pixels` rather than `pixels`.
+    pub hdr: u32,
 }
 
 /// A flat texture atlas: all textures packed into a single RGBA u32 pixel buffer (0xAABBGGRR).
+/// HDR images (`.hdr`, `.exr`) keep their range in a parallel RGBA f32 buffer.
 pub struct TextureAtlas {
     pub pixels: Vec<u32>,
+    /// Never empty, as GPU buffers can't be: slot 0 is unused.
+    pub float_pixels: Vec<[f32; 4]>,
     pub infos: Vec<TextureInfo>,
+    /// File of each texture after the fallback slot, in ID order.
+    sources: Vec<PathBuf>,
 }
 
 impl Default for TextureAtlas {
     fn default() -> Self {
         Self {
             pixels: vec![0xFF808080], // slot 0: 1x1 gray fallback
+            float_pixels: vec![[0.0; 4]],
             infos: vec![TextureInfo {
                 width: 1,
                 height: 1,
                 offset: 0,
-                _pad: 0,
+                hdr: 0,
             }],
+            sources: Vec::new(),
         }
     }
 }
@@ -45,40 +56,190 @@ impl TextureAtlas {
     /// Load a texture from disk, append it to the atlas, and return its ID.
     pub fn load_texture(&mut self, path: &Path) -> Result<usize> {
         let img = image::open(path)
-            .with_context(|| format!("Failed to load texture: {}", path.display()))?
-            .to_rgba8();
+            .with_context(|| format!("Failed to load texture: {}", path.display()))?;
 
-        let width = img.width();
-        let height = img.height();
+        let (width, height) = (img.width(), img.height());
+        let id = if is_hdr_texture(path) {
+            let img = img.to_rgba32f();
+            self.push_float(
+                path,
+                width,
+                height,
+                img.as_raw()
+                    .chunks_exact(4)
+                    .map(|c| [c[0], c[1], c[2], c[3]]),
+            )
+        } else {
+            let img = img.to_rgba8();
+            self.push(
+                path,
+                width,
+                height,
+                img.as_raw()
+                    .chunks_exact(4)
+                    .map(|c| pack_rgba(c[0], c[1], c[2], c[3])),
+            )
+        };
+        log::info!(
+            "Loaded texture '{}' ({}x{}) as ID {id}",
+            path.display(),
+            width,
+            height
+        );
+        Ok(id)
+    }
+
+    /// Atlas holding the textures at `paths`, in order and without duplicates,
+    /// laid out exactly as if each were loaded into a new atlas. Textures this
+    /// atlas already holds are copied rather than decoded again. Returns the
+    /// atlas and the ID of every path that loaded.
+    pub fn rebuilt_with(&self, paths: &[PathBuf]) -> (Self, HashMap<PathBuf, usize>) {
+        let mut atlas = Self::new();
+        let mut ids = HashMap::new();
+        for path in paths {
+            if ids.contains_key(path) {
+                continue;
+            }
+            let result = match self.id_of(path) {
+                Some(id) => {
+                    let info = self.infos[id];
+                    let start = info.offset as usize;
+                    let end = start + (info.width * info.height) as usize;
+                    Ok(if info.hdr != 0 {
+                        atlas.push_float(
+                            path,
+                            info.width,
+                            info.height,
+                            self.float_pixels[start..end].iter().copied(),
+                        )
+                    } else {
+                        atlas.push(
+                            path,
+                            info.width,
+                            info.height,
+                            self.pixels[start..end].iter().copied(),
+                        )
+                    })
+                }
+                None => atlas.load_texture(path),
+            };
+            match result {
+                Ok(id) => {
+                    ids.insert(path.clone(), id);
+                }
+                Err(e) => log::warn!("{e:#}"),
+            }
+        }
+        (atlas, ids)
+    }
+
+    /// Whether both atlases hold the same files under the same IDs, i.e. the
+    /// GPU copy of one is still valid for the other.
+    pub fn same_layout(&self, other: &Self) -> bool {
+        self.sources == other.sources
+    }
+
+    fn id_of(&self, path: &Path) -> Option<usize> {
+        self.sources.iter().position(|p| p == path).map(|i| i + 1)
+    }
+
+    fn push(
+        &mut self,
+        path: &Path,
+        width: u32,
+        height: u32,
+        pixels: impl Iterator<Item = u32>,
+    ) -> usize {
         let offset = self.pixels.len() as u32;
+        self.pixels.reserve((width * height) as usize);
+        self.pixels.extend(pixels);
+        self.push_info(path, width, height, offset, false)
+    }
 
-        let pixel_count = (width * height) as usize;
-        self.pixels.reserve(pixel_count);
-        self.pixels.extend(
-            img.as_raw()
-                .chunks_exact(4)
-                .map(|c| pack_rgba(c[0], c[1], c[2], c[3])),
-        );
+    fn push_float(
+        &mut self,
+        path: &Path,
+        width: u32,
+        height: u32,
+        pixels: impl Iterator<Item = [f32; 4]>,
+    ) -> usize {
+        let offset = self.float_pixels.len() as u32;
+        self.float_pixels.reserve((width * height) as usize);
+        self.float_pixels.extend(pixels);
+        self.push_info(path, width, height, offset, true)
+    }
 
+    fn push_info(&mut self, path: &Path, width: u32, height: u32, offset: u32, hdr: bool) -> usize {
         let id = self.infos.len();
         self.infos.push(TextureInfo {
             width,
             height,
             offset,
-            _pad: 0,
+            hdr: u32::from(hdr),
         });
-
-        log::info!(
-            "Loaded texture '{}' ({}x{}) as ID {id}",
-            path.display(),
-            width,
-            height
-        );
-        Ok(id)
+        self.sources.push(path.to_path_buf());
+        id
     }
 }
 
+/// Whether `path` is an HDR image, kept as float texels rather than 8-bit.
+pub fn is_hdr_texture(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| HDR_TEXTURE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
 #[inline]
 fn pack_rgba(r: u8, g: u8, b: u8, a: u8) -> u32 {
     (u32::from(a) << 24) | (u32::from(b) << 16) | (u32::from(g) << 8) | u32::from(r)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("resources/textures")
+            .join(name)
+    }
+
+    #[test]
+    fn test_incremental_rebuild_matches_full_rebuild() {
+        let wanted = [texture("fabric.jpg"), texture("brick.jpeg")];
+        let (full, full_ids) = TextureAtlas::new().rebuilt_with(&wanted);
+        assert_eq!(full.infos.len(), 3);
+
+        // Start from a different set: brick is reused, fabric must be decoded
+        // and missing.png is dropped.
+        let (previous, _) =
+            TextureAtlas::new().rebuilt_with(&[texture("missing.png"), texture("brick.jpeg")]);
+        let (incremental, ids) = previous.rebuilt_with(&wanted);
+        assert_eq!(incremental.infos, full.infos);
+        assert_eq!(incremental.pixels, full.pixels);
+        assert_eq!(incremental.float_pixels, full.float_pixels);
+        assert_eq!(ids, full_ids);
+        assert!(incremental.same_layout(&full));
+        assert!(!incremental.same_layout(&previous));
+    }
+
+    #[test]
+    fn test_hdr_texture_keeps_float_range() {
+        let path = std::env::temp_dir().join(format!("pathtracer_hdr_{}.EXR", std::process::id()));
+        let texels = [4.0, 0.5, 0.25, 1.0, 0.0, 0.0, 0.0, 1.0];
+        image::Rgba32FImage::from_raw(2, 1, texels.to_vec())
+            .unwrap()
+            .save_with_format(&path, image::ImageFormat::OpenExr)
+            .unwrap();
+        let (atlas, ids) = TextureAtlas::new().rebuilt_with(&[texture("brick.jpeg"), path.clone()]);
+        std::fs::remove_file(&path).ok();
+
+        let info = atlas.infos[ids[&path]];
+        assert_eq!((info.width, info.height, info.hdr), (2, 1, 1));
+        assert_eq!(
+            atlas.float_pixels[info.offset as usize],
+            [4.0, 0.5, 0.25, 1.0]
+        );
+        assert_eq!(atlas.infos[ids[&texture("brick.jpeg")]].hdr, 0);
+    }
+}
@@ -6,6 +6,7 @@
 mod accel;
 mod app;
 mod camera;
+mod cli;
 mod constants;
 mod gpu;
 mod input;
@@ -14,6 +15,7 @@ mod model;
 mod picking;
 mod render;
 mod scene;
+mod settings;
 mod shaders;
 mod ui;
 
@@ -23,5 +25,16 @@ use anyhow::Result;
 
 fn main() -> Result<()> {
     env_logger::init();
-    app::run(env::args().nth(1))
+    let cli = cli::CliArgs::parse(env::args().skip(1))?;
+    if cli.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if let Some(scene) = &cli.benchmark {
+        return app::run_benchmark(scene, &cli);
+    }
+    if let Some(manifest) = &cli.batch {
+        return app::run_batch(manifest, &cli);
+    }
+    app::run(cli)
 }
@@ -0,0 +1,202 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeMap;
+
+use glam::{Vec2, Vec3};
+
+use crate::constants::{DEGENERATE_TRIANGLE_SIN, MESH_MERGE_TOLERANCE};
+use crate::scene::shape::{Shape, ShapeType};
+
+/// Remove triangles with no area (repeated or collinear vertices) or with
+/// non-finite vertices. No ray can hit them, so they only cost memory and
+/// BVH build time. Returns how many were removed.
+pub fn drop_degenerate(triangles: &mut Vec<Shape>) -> usize {
+    let before = triangles.len();
+    triangles.retain(|tri| {
+        if tri.shape_type != ShapeType::Triangle {
+            return true;
+        }
+        let [v0, v1, v2] = [tri.v0, tri.v1, tri.v2].map(Vec3::from);
+        let (e1, e2) = (v1 - v0, v2 - v0);
+        let cross = e1.cross(e2);
+        cross.is_finite() && cross.length() > DEGENERATE_TRIANGLE_SIN * e1.length() * e2.length()
+    });
+    before - triangles.len()
+}
+
+/// Merge pairs of coplanar triangles that together form a single triangle:
+/// `BMA` and `MCA` where `M` lies on the straight edge `BC` become `BCA`.
+/// This undoes the edge splits many exporters leave in flat regions.
+///
+/// A pair is only merged when nothing else uses `M` (otherwise the merge
+/// would open a crack next to it), both halves share material, texture and
+/// mesh name, and the UVs at `M` sit on the line between `B` and `C`, so the
+/// surface looks the same afterwards. Runs until no pair is left. Returns
+/// how many triangles were removed.
+pub fn merge_coplanar(triangles: &mut Vec<Shape>) -> usize {
+    let before = triangles.len();
+    while merge_pass(triangles) {}
+    before - triangles.len()
+}
+
+/// One round of merges over the current triangles; false if none was found.
+fn merge_pass(triangles: &mut Vec<Shape>) -> bool {
+    let mut users: BTreeMap<[u32; 3], Vec<usize>> = BTreeMap::new();
+    for (idx, tri) in triangles.iter().enumerate() {
+        if tri.shape_type == ShapeType::Triangle && tri.vertex_colors.is_none() {
+            for v in [tri.v0, tri.v1, tri.v2] {
+                users.entry(vertex_key(v)).or_default().push(idx);
+            }
+        }
+    }
+
+    let mut removed = vec![false; triangles.len()];
+    let mut touched = vec![false; triangles.len()];
+    let mut merged_any = false;
+    for (key, tris) in &users {
+        let &[a, b] = tris.as_slice() else {
+            continue;
+        };
+        if touched[a] || touched[b] {
+            continue;
+        }
+        let merged = try_merge(&triangles[a], &triangles[b], *key)
+            .or_else(|| try_merge(&triangles[b], &triangles[a], *key));
+        if let Some(merged) = merged {
+            triangles[a] = merged;
+            removed[b] = true;
+            touched[a] = true;
+            touched[b] = true;
+            merged_any = true;
+        }
+    }
+
+    let mut idx = 0;
+    triangles.retain(|_| {
+        idx += 1;
+        !removed[idx - 1]
+    });
+    merged_any
+}
+
+/// `first` = (B, M, A) and `second` = (M, C, A) in winding order, with `m`
+/// the key of M, merged into (B, C, A) if M lies on BC.
+fn try_merge(first: &Shape, second: &Shape, m: [u32; 3]) -> Option<Shape> {
+    if first.material != second.material
+        || first.texture != second.texture
+        || first.name != second.name
+    {
+        return None;
+    }
+    let [b, m1, a1] = rotate_to(first, m, 1)?;
+    let [m2, c, a2] = rotate_to(second, m, 0)?;
+    if vertex_key(a1.0) != vertex_key(a2.0) {
+        return None;
+    }
+
+    // M strictly between B and C on the same line, in both position and UV.
+    let (pb, pm, pc) = (Vec3::from(b.0), Vec3::from(m1.0), Vec3::from(c.0));
+    let bc = pc - pb;
+    let t = (pm - pb).dot(bc) / bc.length_squared();
+    let scale = bc.length();
+    if !(t > 0.0 && t < 1.0) || pb.lerp(pc, t).distance(pm) > MESH_MERGE_TOLERANCE * scale {
+        return None;
+    }
+    let (ub, um, uc) = (Vec2::from(b.1), Vec2::from(m2.1), Vec2::from(c.1));
+    let uv_scale = ub.distance(uc).max(1.0);
+    if ub.lerp(uc, t).distance(um) > MESH_MERGE_TOLERANCE * uv_scale
+        || Vec2::from(m1.1).distance(um) > MESH_MERGE_TOLERANCE * uv_scale
+        || Vec2::from(a1.1).distance(Vec2::from(a2.1)) > MESH_MERGE_TOLERANCE * uv_scale
+    {
+        return None;
+    }
+
+    Some(Shape {
+        v0: b.0,
+        v1: c.0,
+        v2: a1.0,
+        uv0: b.1,
+        uv1: c.1,
+        uv2: a1.1,
+        ..first.clone()
+    })
+}
+
+/// The triangle's (position, uv) corners rotated so the one at `key` is at
+/// index `slot`, keeping the winding.
+fn rotate_to(tri: &Shape, key: [u32; 3], slot: usize) -> Option<[([f32; 3], [f32; 2]); 3]> {
+    let corners = [(tri.v0, tri.uv0), (tri.v1, tri.uv1), (tri.v2, tri.uv2)];
+    let at = corners.iter().position(|c| vertex_key(c.0) == key)?;
+    Some(std::array::from_fn(|i| corners[(at + 3 + i - slot) % 3]))
+}
+
+/// Vertices loaded from the same OBJ index come out bit-identical.
+fn vertex_key(v: [f32; 3]) -> [u32; 3] {
+    v.map(f32::to_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tri(v0: [f32; 3], v1: [f32; 3], v2: [f32; 3]) -> Shape {
+        let mut shape = ShapeType::Triangle.new_default_shape([0.0; 3]);
+        (shape.v0, shape.v1, shape.v2) = (v0, v1, v2);
+        (shape.uv0, shape.uv1, shape.uv2) = ([v0[0], v0[1]], [v1[0], v1[1]], [v2[0], v2[1]]);
+        shape
+    }
+
+    fn area(tris: &[Shape]) -> f32 {
+        tris.iter()
+            .map(|t| {
+                let [v0, v1, v2] = [t.v0, t.v1, t.v2].map(Vec3::from);
+                (v1 - v0).cross(v2 - v0).z * 0.5
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_drop_degenerate() {
+        let mut tris = vec![
+            tri([0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            tri([0.0; 3], [0.0; 3], [0.0, 1.0, 0.0]),
+            tri([0.0; 3], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]),
+            tri([0.0; 3], [f32::NAN, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ];
+        assert_eq!(drop_degenerate(&mut tris), 3);
+        assert_eq!(tris.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_split_edges() {
+        // A triangle whose bottom edge was split three times into a fan.
+        let apex = [2.0, 2.0, 0.0];
+        let mut tris: Vec<_> = (0..4)
+            .map(|i| tri([i as f32, 0.0, 0.0], [i as f32 + 1.0, 0.0, 0.0], apex))
+            .collect();
+        let total = area(&tris);
+        assert_eq!(merge_coplanar(&mut tris), 3);
+        assert_eq!(tris.len(), 1);
+        assert!(
+            (area(&tris) - total).abs() < 1e-5,
+            "winding or area changed"
+        );
+
+        // A split shared with a neighbour across the edge stays, or the
+        // neighbour would see a T-junction.
+        let mut tris = vec![
+            tri([0.0; 3], [1.0, 0.0, 0.0], apex),
+            tri([1.0, 0.0, 0.0], [2.0, 0.0, 0.0], apex),
+            tri([1.0, 0.0, 0.0], [0.0; 3], [1.0, -1.0, 0.0]),
+        ];
+        assert_eq!(merge_coplanar(&mut tris), 0);
+
+        // A bend at the split point is a real corner.
+        let mut tris = vec![
+            tri([0.0; 3], [1.0, 0.1, 0.0], apex),
+            tri([1.0, 0.1, 0.0], [2.0, 0.0, 0.0], apex),
+        ];
+        assert_eq!(merge_coplanar(&mut tris), 0);
+    }
+}
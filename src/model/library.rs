@@ -0,0 +1,158 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use glam::Vec3;
+
+use crate::accel::aabb::{Aabb, shape_aabb};
+use crate::accel::bvh::{Bvh, BvhParams};
+use crate::constants::resolve_resource_path;
+use crate::scene::material::Material;
+use crate::scene::scene::UpAxis;
+use crate::scene::shape::{Shape, ShapeType};
+
+/// An OBJ model loaded once and shared by every `Instance` shape that
+/// references it. Triangles are in model space, centred on the origin.
+#[derive(Clone)]
+pub struct InstancedModel {
+    pub triangles: Vec<Shape>,
+    /// BVH over `triangles`, traversed in model space.
+    pub blas: Bvh,
+    pub bounds: Aabb,
+}
+
+impl InstancedModel {
+    fn load(path: &str, scene_dir: &Path, up_axis: UpAxis, params: BvhParams) -> Result<Self> {
+        let triangles = super::obj_loader::load_obj(
+            &resolve_resource_path(scene_dir, path),
+            [0.0, 0.0, 0.0],
+            1.0,
+            up_axis,
+            &Material::default(),
+        )?;
+        if triangles.is_empty() {
+            bail!("'{path}' has no triangles");
+        }
+        Ok(Self::new(triangles, params))
+    }
+
+    /// Build the BLAS over model-space `triangles`.
+    pub fn new(triangles: Vec<Shape>, params: BvhParams) -> Self {
+        let aabbs: Vec<_> = triangles.iter().map(shape_aabb).collect();
+        let bounds = aabbs.iter().fold(Aabb::EMPTY, |acc, &b| acc.union(b));
+        Self {
+            blas: Bvh::build(&aabbs, params),
+            triangles,
+            bounds,
+        }
+    }
+
+    /// World-space bounds of this model placed by `instance`.
+    pub fn instance_aabb(&self, instance: &Shape) -> Aabb {
+        let transform = instance.instance_transform();
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        (0..8).fold(Aabb::EMPTY, |acc, corner| {
+            let p = Vec3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            acc.expand(transform.transform_point3(p))
+        })
+    }
+}
+
+/// Models referenced by the scene's `Instance` shapes, keyed by the path as
+/// written on the shape. Iteration order is stable, which the GPU buffer
+/// layout relies on.
+#[derive(Clone, Default)]
+pub struct ModelLibrary {
+    models: BTreeMap<String, InstancedModel>,
+    /// Paths that failed to load, so they are reported once rather than on
+    /// every rebuild.
+    failed: HashSet<String>,
+    bvh_params: BvhParams,
+    /// Scene up axis the loaded models were rotated for.
+    up_axis: UpAxis,
+}
+
+impl ModelLibrary {
+    /// Load models newly referenced by `shapes` and drop ones no longer used.
+    /// A different `up_axis` than last time reloads every model.
+    pub fn sync(&mut self, shapes: &[Shape], scene_dir: &Path, up_axis: UpAxis) {
+        if up_axis != self.up_axis {
+            self.models.clear();
+            self.failed.clear();
+            self.up_axis = up_axis;
+        }
+        let referenced: HashSet<&str> = shapes
+            .iter()
+            .filter(|s| s.shape_type == ShapeType::Instance)
+            .filter_map(|s| s.model.as_deref())
+            .collect();
+        self.models
+            .retain(|path, _| referenced.contains(path.as_str()));
+        self.failed
+            .retain(|path| referenced.contains(path.as_str()));
+
+        for path in referenced {
+            if self.models.contains_key(path) || self.failed.contains(path) {
+                continue;
+            }
+            match InstancedModel::load(path, scene_dir, up_axis, self.bvh_params) {
+                Ok(model) => {
+                    log::info!(
+                        "Loaded instanced model '{path}': {} triangles",
+                        model.triangles.len()
+                    );
+                    self.models.insert(path.to_string(), model);
+                }
+                Err(e) => {
+                    log::error!("Failed to load instanced model '{path}': {e:#}");
+                    self.failed.insert(path.to_string());
+                }
+            }
+        }
+    }
+
+    /// Build future BLASes with `params`, rebuilding the loaded ones if they
+    /// were built differently.
+    pub fn set_bvh_params(&mut self, params: BvhParams) {
+        if params == self.bvh_params {
+            return;
+        }
+        self.bvh_params = params;
+        for model in self.models.values_mut() {
+            let aabbs: Vec<_> = model.triangles.iter().map(shape_aabb).collect();
+            model.blas = Bvh::build(&aabbs, params);
+        }
+    }
+
+    /// The loaded model an `Instance` shape refers to, if any.
+    pub fn model_of(&self, shape: &Shape) -> Option<&InstancedModel> {
+        if shape.shape_type != ShapeType::Instance {
+            return None;
+        }
+        self.models.get(shape.model.as_deref()?)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &InstancedModel)> {
+        self.models
+            .iter()
+            .map(|(path, model)| (path.as_str(), model))
+    }
+
+    /// Every model triangle, in the order they follow the scene shapes on the GPU.
+    pub fn triangles(&self) -> impl Iterator<Item = &Shape> {
+        self.models.values().flat_map(|model| &model.triangles)
+    }
+
+    /// Add an already built model under `path`, replacing any previous one.
+    pub fn insert(&mut self, path: &str, model: InstancedModel) {
+        self.failed.remove(path);
+        self.models.insert(path.to_string(), model);
+    }
+}
@@ -1,4 +1,6 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod cleanup;
+pub mod library;
 pub mod obj_loader;
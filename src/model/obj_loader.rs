@@ -3,45 +3,38 @@
 
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::{Context, Result};
-use glam::Vec3;
+use glam::{Quat, Vec3};
+use rayon::prelude::*;
 
+use crate::accel::aabb::{Aabb, shape_aabb};
 use crate::constants::resolve_data_path;
 use crate::scene::material::Material;
+use crate::scene::scene::UpAxis;
 use crate::scene::shape::{Shape, ShapeType};
 
 /// Load an OBJ model, auto-scaling so its largest dimension equals `target_size`.
-/// Returns the loaded triangles positioned at `position`.
+/// Returns the loaded triangles positioned at `position`, rotated so the file's
+/// `up_axis` points along +Y.
 pub fn load_obj_auto_scaled(
     path: &str,
     position: [f32; 3],
     target_size: f32,
+    up_axis: UpAxis,
     default_material: &Material,
 ) -> Result<Vec<Shape>> {
-    let (models, obj_materials) = tobj::load_obj(Path::new(path), &tobj::GPU_LOAD_OPTIONS)
-        .with_context(|| format!("Failed to load OBJ: {path}"))?;
-
-    // Compute extent at scale 1.0 to determine auto-scale factor.
-    let mut bb_min = Vec3::splat(f32::MAX);
-    let mut bb_max = Vec3::splat(f32::MIN);
-    for model in &models {
-        for idx in &model.mesh.indices {
-            let v = read_vertex(&model.mesh.positions, *idx as usize, 1.0);
-            bb_min = bb_min.min(v);
-            bb_max = bb_max.max(v);
+    load_timed(path, position, up_axis, default_material, |models| {
+        // Compute extent at scale 1.0 to determine auto-scale factor.
+        let bounds = model_bounds(models, VertexTransform::new(1.0, up_axis));
+        let extent = (bounds.max - bounds.min).max_element();
+        if extent > 0.0 {
+            target_size / extent
+        } else {
+            1.0
         }
-    }
-    let size = bb_max - bb_min;
-    let extent = size.x.max(size.y).max(size.z);
-    let scale = if extent > 0.0 {
-        target_size / extent
-    } else {
-        1.0
-    };
-
-    let materials = resolve_materials(obj_materials, path);
-    build_triangles(&models, &materials, path, position, scale, default_material)
+    })
 }
 
 /// Load an OBJ model with an explicit scale factor.
@@ -49,13 +42,62 @@ pub fn load_obj(
     path: &str,
     position: [f32; 3],
     scale: f32,
+    up_axis: UpAxis,
+    default_material: &Material,
+) -> Result<Vec<Shape>> {
+    load_timed(path, position, up_axis, default_material, |_| scale)
+}
+
+/// Parse the OBJ at `path` and build its triangles at the scale `scale_of`
+/// picks for the parsed models, logging how long the whole load took.
+fn load_timed(
+    path: &str,
+    position: [f32; 3],
+    up_axis: UpAxis,
     default_material: &Material,
+    scale_of: impl FnOnce(&[tobj::Model]) -> f32,
 ) -> Result<Vec<Shape>> {
+    let start = Instant::now();
     let (models, obj_materials) = tobj::load_obj(Path::new(path), &tobj::GPU_LOAD_OPTIONS)
         .with_context(|| format!("Failed to load OBJ: {path}"))?;
 
+    let transform = VertexTransform::new(scale_of(&models), up_axis);
     let materials = resolve_materials(obj_materials, path);
-    build_triangles(&models, &materials, path, position, scale, default_material)
+    let triangles = build_triangles(
+        &models,
+        &materials,
+        path,
+        position,
+        transform,
+        default_material,
+    )?;
+    log::info!(
+        "Loaded OBJ '{}': {} triangles in {:.2?}",
+        path,
+        triangles.len(),
+        start.elapsed()
+    );
+    Ok(triangles)
+}
+
+/// Scale and up-axis rotation applied to every OBJ vertex before centering.
+#[derive(Clone, Copy)]
+struct VertexTransform {
+    scale: f32,
+    rotation: Quat,
+}
+
+impl VertexTransform {
+    fn new(scale: f32, up_axis: UpAxis) -> Self {
+        Self {
+            scale,
+            rotation: up_axis.to_y_up(),
+        }
+    }
+
+    fn apply(self, positions: &[f32], index: usize) -> Vec3 {
+        self.rotation * read_vertex(positions, index, self.scale)
+    }
 }
 
 fn resolve_materials(
@@ -77,12 +119,29 @@ fn resolve_materials(
     }
 }
 
+/// Bounds of every vertex the models' faces use, in parallel.
+fn model_bounds(models: &[tobj::Model], transform: VertexTransform) -> Aabb {
+    models
+        .par_iter()
+        .flat_map(|model| {
+            model
+                .mesh
+                .indices
+                .par_iter()
+                .map(|&idx| transform.apply(&model.mesh.positions, idx as usize))
+        })
+        .fold(|| Aabb::EMPTY, Aabb::expand)
+        .reduce(|| Aabb::EMPTY, Aabb::union)
+}
+
+/// Build the triangles of all models in parallel. Triangles are independent,
+/// so a model is split across threads too; the result keeps file order.
 fn build_triangles(
     models: &[tobj::Model],
     materials: &[tobj::Material],
     path: &str,
     position: [f32; 3],
-    scale: f32,
+    transform: VertexTransform,
     default_material: &Material,
 ) -> Result<Vec<Shape>> {
     let obj_dir = Path::new(path).parent();
@@ -94,83 +153,82 @@ fn build_triangles(
         .into();
 
     // Compute bounding box at scale to find model center.
-    let mut bb_min = Vec3::splat(f32::MAX);
-    let mut bb_max = Vec3::splat(f32::MIN);
-    for model in models {
-        for idx in &model.mesh.indices {
-            let v = read_vertex(&model.mesh.positions, *idx as usize, scale);
-            bb_min = bb_min.min(v);
-            bb_max = bb_max.max(v);
-        }
-    }
-    let center = (bb_min + bb_max) * 0.5;
+    let center = model_bounds(models, transform).center();
     let offset = Vec3::from(position) - center;
 
-    let total_tris: usize = models.iter().map(|m| m.mesh.indices.len() / 3).sum();
-    let mut triangles = Vec::with_capacity(total_tris);
-
-    for model in models {
-        let mesh = &model.mesh;
-        let has_uvs = !mesh.texcoords.is_empty();
-
-        let (mat, texture): (Material, Option<Arc<str>>) = if let Some(mat_id) = mesh.material_id
-            && mat_id < materials.len()
-        {
-            let obj_mat = &materials[mat_id];
-            let tex = obj_mat
-                .diffuse_texture
-                .as_ref()
-                .map(|tex_path| Arc::from(resolve_texture_path(obj_dir, tex_path).as_str()));
-            (obj_material_to_pbr(obj_mat, default_material), tex)
-        } else {
-            (default_material.clone(), None)
-        };
-
-        for tri in mesh.indices.chunks_exact(3) {
-            let i0 = tri[0] as usize;
-            let i1 = tri[1] as usize;
-            let i2 = tri[2] as usize;
-
-            let v0 = read_vertex(&mesh.positions, i0, scale) + offset;
-            let v1 = read_vertex(&mesh.positions, i1, scale) + offset;
-            let v2 = read_vertex(&mesh.positions, i2, scale) + offset;
-
-            let (uv0, uv1, uv2) = if has_uvs {
-                (
-                    read_uv(&mesh.texcoords, i0),
-                    read_uv(&mesh.texcoords, i1),
-                    read_uv(&mesh.texcoords, i2),
-                )
-            } else {
-                ([0.0, 0.0], [0.0, 0.0], [0.0, 0.0])
-            };
-
-            triangles.push(Shape {
-                name: Some(String::from(&*group_name)),
-                shape_type: ShapeType::Triangle,
-                negative: false,
-                position: [0.0, 0.0, 0.0],
-                normal: [0.0, 1.0, 0.0],
-                radius: 0.0,
-                radius2: 0.0,
-                height: 0.0,
-                rotation: [0.0, 0.0, 0.0],
-                v0: v0.into(),
-                v1: v1.into(),
-                v2: v2.into(),
-                power: 0.0,
-                max_iterations: 0,
-                texture: texture.as_ref().map(|t| String::from(&**t)),
-                texture_scale: None,
-                uv0,
-                uv1,
-                uv2,
-                material: mat.clone(),
-            });
-        }
-    }
+    let mut triangles: Vec<Shape> = models
+        .par_iter()
+        .flat_map(|model| {
+            let mesh = &model.mesh;
+            let has_uvs = !mesh.texcoords.is_empty();
+
+            let (mat, texture): (Material, Option<Arc<str>>) =
+                if let Some(mat_id) = mesh.material_id
+                    && mat_id < materials.len()
+                {
+                    let obj_mat = &materials[mat_id];
+                    let tex = obj_mat.diffuse_texture.as_ref().map(|tex_path| {
+                        Arc::from(resolve_texture_path(obj_dir, tex_path).as_str())
+                    });
+                    (obj_material_to_pbr(obj_mat, default_material), tex)
+                } else {
+                    (default_material.clone(), None)
+                };
+            let group_name = &group_name;
+
+            mesh.indices.par_chunks_exact(3).map(move |tri| {
+                let i0 = tri[0] as usize;
+                let i1 = tri[1] as usize;
+                let i2 = tri[2] as usize;
+
+                let v0 = transform.apply(&mesh.positions, i0) + offset;
+                let v1 = transform.apply(&mesh.positions, i1) + offset;
+                let v2 = transform.apply(&mesh.positions, i2) + offset;
+
+                let (uv0, uv1, uv2) = if has_uvs {
+                    (
+                        read_uv(&mesh.texcoords, i0),
+                        read_uv(&mesh.texcoords, i1),
+                        read_uv(&mesh.texcoords, i2),
+                    )
+                } else {
+                    ([0.0, 0.0], [0.0, 0.0], [0.0, 0.0])
+                };
 
-    log::info!("Loaded OBJ '{}': {} triangles", path, triangles.len());
+                Shape {
+                    name: Some(String::from(&**group_name)),
+                    shape_type: ShapeType::Triangle,
+                    negative: false,
+                    position: [0.0, 0.0, 0.0],
+                    normal: [0.0, 1.0, 0.0],
+                    radius: 0.0,
+                    radius2: 0.0,
+                    height: 0.0,
+                    rotation: [0.0, 0.0, 0.0],
+                    v0: v0.into(),
+                    v1: v1.into(),
+                    v2: v2.into(),
+                    power: 0.0,
+                    max_iterations: 0,
+                    march_steps: 0,
+                    orbit_trap: false,
+                    texture: texture.as_ref().map(|t| String::from(&**t)),
+                    texture_scale: None,
+                    uv0,
+                    uv1,
+                    uv2,
+                    material: mat.clone(),
+                    vertex_colors: None,
+                    model: None,
+                }
+            })
+        })
+        .collect();
+
+    let degenerate = super::cleanup::drop_degenerate(&mut triangles);
+    if degenerate > 0 {
+        log::info!("Dropped {degenerate} zero-area triangles from '{path}'");
+    }
     Ok(triangles)
 }
 
@@ -253,3 +311,48 @@ fn read_uv(texcoords: &[f32], index: usize) -> [f32; 2] {
         [0.0, 0.0]
     }
 }
+
+/// An upward-facing plane touching the bottom of `triangles`, so an
+/// imported model has a floor to cast shadows on. `None` for no triangles.
+pub fn ground_plane_under(triangles: &[Shape]) -> Option<Shape> {
+    if triangles.is_empty() {
+        return None;
+    }
+    let bounds = triangles
+        .iter()
+        .map(shape_aabb)
+        .fold(Aabb::EMPTY, Aabb::union);
+    let center = bounds.center();
+    let mut plane = ShapeType::Plane.new_default_shape([0.0; 3]);
+    plane.name = Some("Ground".to_string());
+    plane.position = [center.x, bounds.min.y, center.z];
+    plane.normal = [0.0, 1.0, 0.0];
+    Some(plane)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_scaled_model_is_centered_at_position() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/models/teddy.obj");
+        let position = [1.0, 2.0, 3.0];
+        let triangles =
+            load_obj_auto_scaled(path, position, 3.0, UpAxis::Y, &Material::default()).unwrap();
+        assert!(!triangles.is_empty());
+
+        let bounds = triangles
+            .iter()
+            .map(shape_aabb)
+            .fold(Aabb::EMPTY, Aabb::union);
+        let size = bounds.max - bounds.min;
+        assert!((size.max_element() - 3.0).abs() < 0.01);
+        assert!(bounds.center().abs_diff_eq(position.into(), 0.01));
+
+        let ground = ground_plane_under(&triangles).unwrap();
+        assert_eq!(ground.shape_type, ShapeType::Plane);
+        assert_eq!(ground.position[1], bounds.min.y);
+        assert!(ground_plane_under(&[]).is_none());
+    }
+}
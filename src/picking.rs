@@ -1,11 +1,14 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::ops::Range;
+
 use glam::Vec3;
 
 use crate::accel::aabb::{Aabb, shape_aabb};
 use crate::accel::bvh::Bvh;
 use crate::camera::camera::Camera;
+use crate::model::library::{InstancedModel, ModelLibrary};
 use crate::scene::shape::{Shape, ShapeType};
 
 /// Construct a world-space ray from the camera through a screen pixel.
@@ -30,6 +33,31 @@ pub fn picking_ray(
     (camera.position, dir)
 }
 
+/// Screen pixel at which the camera sees `point`, the inverse of
+/// `picking_ray`. `None` for points behind the camera.
+pub fn project_to_screen(
+    camera: &Camera,
+    point: Vec3,
+    width: u32,
+    height: u32,
+) -> Option<[f32; 2]> {
+    let (right, up, forward) = camera.basis_vectors();
+    let aspect = width as f32 / height as f32;
+    let focal_length = 1.0 / (camera.fov.to_radians() * 0.5).tan();
+
+    let rel = point - camera.position;
+    let depth = rel.dot(forward);
+    if depth <= 0.0 {
+        return None;
+    }
+    let ndc_x = rel.dot(right) * focal_length / depth / aspect;
+    let ndc_y = rel.dot(up) * focal_length / depth;
+    Some([
+        (ndc_x + 1.0) * 0.5 * width as f32,
+        (1.0 - ndc_y) * 0.5 * height as f32,
+    ])
+}
+
 // ---------------------------------------------------------------------------
 // Exact ray-shape intersection tests (match WGSL shader logic)
 // ---------------------------------------------------------------------------
@@ -74,18 +102,8 @@ fn ray_disc(origin: Vec3, dir: Vec3, center: Vec3, normal: Vec3, radius: f32) ->
 }
 
 fn ray_cube(origin: Vec3, dir: Vec3, center: Vec3, half: f32) -> Option<f32> {
-    let inv_dir = dir.recip();
-    let box_min = center - Vec3::splat(half);
-    let box_max = center + Vec3::splat(half);
-    let t1 = (box_min - origin) * inv_dir;
-    let t2 = (box_max - origin) * inv_dir;
-    let t_enter = t1.min(t2).max_element();
-    let t_exit = t1.max(t2).min_element();
-    if t_enter > t_exit || t_exit < 0.0 {
-        None
-    } else {
-        Some(if t_enter > 0.0 { t_enter } else { t_exit })
-    }
+    let aabb = Aabb::new(center - Vec3::splat(half), center + Vec3::splat(half));
+    ray_aabb(origin, dir.recip(), &aabb)
 }
 
 fn ray_cylinder(
@@ -145,13 +163,14 @@ fn ray_cone(
     dir: Vec3,
     center: Vec3,
     axis: Vec3,
-    tan_sq: f32,
+    radius: f32,
     height: f32,
 ) -> Option<f32> {
-    // Base disc at `center`, apex at `center + axis * height`. `tan_sq` is tan²(half-angle).
+    // Base disc at `center` with `radius`, apex at `center + axis * height`.
     let apex = center + axis * height;
     let oc = origin - apex;
-    let cos_sq = 1.0 / (1.0 + tan_sq);
+    let tan = radius / height.max(1e-6);
+    let cos_sq = 1.0 / (1.0 + tan * tan);
 
     let d_dot_v = dir.dot(axis);
     let oc_dot_v = oc.dot(axis);
@@ -161,14 +180,17 @@ fn ray_cone(
 
     let mut best: Option<f32> = None;
 
+    // Rays through the apex have a double root that rounding can push slightly
+    // negative; same tolerance as cone.wgsl.
     let discriminant = b * b - 4.0 * a * c;
-    if discriminant >= 0.0 && a.abs() > 1e-12 {
-        let sqrt_d = discriminant.sqrt();
+    if discriminant >= -CONE_DISC_TOLERANCE * b * b && a.abs() > 1e-12 {
+        let sqrt_d = discriminant.max(0.0).sqrt();
         for t in [(-b - sqrt_d) / (2.0 * a), (-b + sqrt_d) / (2.0 * a)] {
             if t > 0.0 && best.is_none_or(|prev| t < prev) {
                 let hit = origin + dir * t;
                 let y = (hit - center).dot(axis);
-                if (0.0..=height).contains(&y) {
+                // Slack at the tip so apex hits survive rounding.
+                if (0.0..=height * (1.0 + CONE_DISC_TOLERANCE)).contains(&y) {
                     best = Some(t);
                     break;
                 }
@@ -177,8 +199,7 @@ fn ray_cone(
     }
 
     // Base cap disc
-    let base_radius = height * tan_sq.sqrt();
-    if let Some(t) = ray_disc(origin, dir, center, -axis, base_radius)
+    if let Some(t) = ray_disc(origin, dir, center, -axis, radius)
         && best.is_none_or(|prev| t < prev)
     {
         best = Some(t);
@@ -187,13 +208,24 @@ fn ray_cone(
     best
 }
 
-/// Möller-Trumbore ray-triangle intersection.
-fn ray_triangle(origin: Vec3, dir: Vec3, v0: Vec3, v1: Vec3, v2: Vec3) -> Option<f32> {
+const CONE_DISC_TOLERANCE: f32 = 1e-4;
+
+/// Möller-Trumbore ray-triangle intersection. With `cull_backfaces`, a ray
+/// reaching the side the winding faces away from (negative determinant)
+/// misses.
+fn ray_triangle(
+    origin: Vec3,
+    dir: Vec3,
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+    cull_backfaces: bool,
+) -> Option<f32> {
     let e1 = v1 - v0;
     let e2 = v2 - v0;
     let h = dir.cross(e2);
     let a = e1.dot(h);
-    if a.abs() < 1e-7 {
+    if a.abs() < 1e-7 || (cull_backfaces && a < 0.0) {
         return None;
     }
     let f = 1.0 / a;
@@ -329,13 +361,13 @@ fn ray_pyramid(origin: Vec3, dir: Vec3, center: Vec3, radius: f32, height: f32)
     };
 
     // 4 side faces
-    check(ray_triangle(origin, dir, v[0], v[1], apex));
-    check(ray_triangle(origin, dir, v[1], v[2], apex));
-    check(ray_triangle(origin, dir, v[2], v[3], apex));
-    check(ray_triangle(origin, dir, v[3], v[0], apex));
+    check(ray_triangle(origin, dir, v[0], v[1], apex, false));
+    check(ray_triangle(origin, dir, v[1], v[2], apex, false));
+    check(ray_triangle(origin, dir, v[2], v[3], apex, false));
+    check(ray_triangle(origin, dir, v[3], v[0], apex, false));
     // 2 base triangles
-    check(ray_triangle(origin, dir, v[0], v[2], v[1]));
-    check(ray_triangle(origin, dir, v[0], v[3], v[2]));
+    check(ray_triangle(origin, dir, v[0], v[2], v[1], false));
+    check(ray_triangle(origin, dir, v[0], v[3], v[2], false));
 
     best
 }
@@ -363,27 +395,89 @@ fn ray_tetrahedron(origin: Vec3, dir: Vec3, center: Vec3, radius: f32) -> Option
         }
     };
 
-    check(ray_triangle(origin, dir, v0, v1, v2));
-    check(ray_triangle(origin, dir, v0, v2, v3));
-    check(ray_triangle(origin, dir, v0, v3, v1));
-    check(ray_triangle(origin, dir, v1, v3, v2));
+    check(ray_triangle(origin, dir, v0, v1, v2, false));
+    check(ray_triangle(origin, dir, v0, v2, v3, false));
+    check(ray_triangle(origin, dir, v0, v3, v1, false));
+    check(ray_triangle(origin, dir, v1, v3, v2, false));
 
     best
 }
 
+/// Sphere-traced torus around the Y axis, matching `figures/torus.wgsl`, so
+/// clicks through the hole miss.
+fn ray_torus(origin: Vec3, dir: Vec3, center: Vec3, major: f32, minor: f32) -> Option<f32> {
+    let len = dir.length();
+    if len <= 0.0 {
+        return None;
+    }
+    let dir = dir / len;
+    let oc = origin - center;
+
+    // March only inside the bounding sphere.
+    let bound_r = major + minor;
+    let b = oc.dot(dir);
+    let c = oc.dot(oc) - bound_r * bound_r;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrt_d = discriminant.sqrt();
+    let t_max = -b + sqrt_d;
+    let mut t = (-b - sqrt_d).max(0.0);
+
+    for _ in 0..TORUS_MARCH_STEPS {
+        let p = oc + dir * t;
+        let q = glam::Vec2::new(Vec3::new(p.x, 0.0, p.z).length() - major, p.y);
+        let d = q.length() - minor;
+        if d.abs() < TORUS_HIT_EPSILON * t.max(1.0) {
+            // Back to the caller's (possibly unnormalised) ray parameter.
+            return (t > 0.0).then_some(t / len);
+        }
+        // `abs` makes an origin inside the tube march out to the exit surface.
+        t += d.abs();
+        if t > t_max {
+            break;
+        }
+    }
+    None
+}
+
+const TORUS_MARCH_STEPS: usize = 256;
+const TORUS_HIT_EPSILON: f32 = 1e-4;
+
 // ---------------------------------------------------------------------------
 // AABB intersection (used for BVH traversal and SDF-based shape proxy)
 // ---------------------------------------------------------------------------
 
-/// Slab method AABB intersection. Returns the closest positive t, or None on miss.
+/// Slab method AABB intersection, taking the reciprocal ray direction.
+/// Returns the closest positive t, or None on miss.
+///
+/// Axes where the direction is zero (`inv_dir` infinite) are handled
+/// explicitly instead of through `0 * inf`, which is NaN when the origin lies
+/// on a slab plane. Boundaries are inclusive, so a ray grazing a face counts
+/// as a hit.
 fn ray_aabb(origin: Vec3, inv_dir: Vec3, aabb: &Aabb) -> Option<f32> {
-    let t1 = (aabb.min - origin) * inv_dir;
-    let t2 = (aabb.max - origin) * inv_dir;
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
 
-    let t_enter = t1.min(t2).max_element();
-    let t_exit = t1.max(t2).min_element();
+    for axis in 0..3 {
+        let (o, inv, lo, hi) = (origin[axis], inv_dir[axis], aabb.min[axis], aabb.max[axis]);
+        if !inv.is_finite() {
+            // Parallel to this slab: either inside it for every t or never.
+            if !(lo..=hi).contains(&o) {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (lo - o) * inv;
+        let t2 = (hi - o) * inv;
+        let (near, far) = if t1 <= t2 { (t1, t2) } else { (t2, t1) };
+        t_enter = t_enter.max(near);
+        t_exit = t_exit.min(far);
+    }
 
-    if t_enter > t_exit || t_exit < 0.0 {
+    // An infinite exit means every axis was parallel, i.e. a zero direction.
+    if t_enter > t_exit || t_exit < 0.0 || !t_exit.is_finite() {
         None
     } else {
         Some(if t_enter > 0.0 { t_enter } else { t_exit })
@@ -396,105 +490,171 @@ fn ray_aabb(origin: Vec3, inv_dir: Vec3, aabb: &Aabb) -> Option<f32> {
 
 /// Exact intersection test for a shape, matching WGSL shader logic.
 /// Returns `Some(t)` on hit, `None` on miss.
-/// SDF-based shapes (Torus, Mebius, Mandelbulb, Julia) fall back to AABB proxy.
+/// The remaining SDF-based shapes (Mebius, Mandelbulb, Julia) fall back to AABB proxy.
 fn intersect_shape(origin: Vec3, dir: Vec3, inv_dir: Vec3, shape: &Shape) -> Option<f32> {
     let pos = Vec3::from(shape.position);
     let normal = Vec3::from(shape.normal).normalize_or_zero();
 
     match shape.shape_type {
-        ShapeType::Skybox => None,
+        ShapeType::Skybox | ShapeType::SpotLight => None,
         ShapeType::Plane => ray_plane(origin, dir, pos, normal),
         ShapeType::Sphere => ray_sphere(origin, dir, pos, shape.radius),
         ShapeType::Disc => ray_disc(origin, dir, pos, normal, shape.radius),
         ShapeType::Cube => ray_cube(origin, dir, pos, shape.radius),
         ShapeType::Cylinder => ray_cylinder(origin, dir, pos, normal, shape.radius, shape.height),
-        ShapeType::Cone => ray_cone(origin, dir, pos, normal, shape.radius2, shape.height),
+        ShapeType::Cone => ray_cone(origin, dir, pos, normal, shape.radius, shape.height),
         ShapeType::Triangle => ray_triangle(
             origin,
             dir,
             Vec3::from(shape.v0),
             Vec3::from(shape.v1),
             Vec3::from(shape.v2),
+            shape.material.cull_backfaces,
         ),
-        ShapeType::Ellipsoid => {
-            let radii = Vec3::new(
-                shape.radius,
-                shape.height.max(shape.radius),
-                shape.radius2.max(shape.radius),
-            );
-            ray_ellipsoid(origin, dir, pos, radii)
-        }
+        ShapeType::Ellipsoid => ray_ellipsoid(origin, dir, pos, shape.ellipsoid_radii()),
         ShapeType::Paraboloid => ray_paraboloid(origin, dir, pos, shape.radius, shape.height),
         ShapeType::Hyperboloid => ray_hyperboloid(origin, dir, pos, shape.radius, shape.height),
         ShapeType::Pyramid => ray_pyramid(origin, dir, pos, shape.radius, shape.height),
         ShapeType::Tetrahedron => ray_tetrahedron(origin, dir, pos, shape.radius),
+        ShapeType::Torus => ray_torus(origin, dir, pos, shape.radius, shape.radius2),
         // SDF-based shapes — AABB proxy is sufficient for picking.
-        ShapeType::Torus | ShapeType::Mebius | ShapeType::Mandelbulb | ShapeType::Julia => {
+        ShapeType::Mebius | ShapeType::Mandelbulb | ShapeType::Julia => {
             ray_aabb(origin, inv_dir, &shape_aabb(shape))
         }
+        // Needs the model; see `intersect_instance`.
+        ShapeType::Instance => None,
     }
 }
 
+/// Exact test against an instance's model triangles, done in model space.
+/// The model-space direction is left unnormalised so `t` stays the world one.
+fn intersect_instance(
+    origin: Vec3,
+    dir: Vec3,
+    shape: &Shape,
+    model: &InstancedModel,
+) -> Option<f32> {
+    let world_to_model = shape.instance_transform().inverse();
+    let local_origin = world_to_model.transform_point3(origin);
+    let local_dir = world_to_model.transform_vector3(dir);
+    if !local_origin.is_finite() || !local_dir.is_finite() || local_dir == Vec3::ZERO {
+        return None;
+    }
+    let inv_dir = local_dir.recip();
+    closest_in_bvh(local_origin, inv_dir, &model.blas, f32::INFINITY, |i| {
+        let tri = &model.triangles[i];
+        if shape.material.cull_backfaces {
+            ray_triangle(
+                local_origin,
+                local_dir,
+                Vec3::from(tri.v0),
+                Vec3::from(tri.v1),
+                Vec3::from(tri.v2),
+                true,
+            )
+        } else {
+            intersect_shape(local_origin, local_dir, inv_dir, tri)
+        }
+    })
+    .map(|(_, t)| t)
+}
+
 // ---------------------------------------------------------------------------
 // BVH-accelerated pick
 // ---------------------------------------------------------------------------
 
-/// Returns (shape_index, t, hit_point) for the closest hit, or None.
-///
-/// `infinite_indices` lists global shape indices for shapes excluded from the
-/// BVH (e.g. planes) that must be tested linearly after BVH traversal.
-pub fn pick(
+/// Closest primitive of the tree rooted at node 0 of `bvh` that `test` hits
+/// nearer than `max_t`, as (primitive index, t).
+fn closest_in_bvh(
     origin: Vec3,
-    dir: Vec3,
+    inv_dir: Vec3,
     bvh: &Bvh,
-    shapes: &[Shape],
-    infinite_indices: &[u32],
-) -> Option<(usize, f32, Vec3)> {
-    if shapes.is_empty() {
+    max_t: f32,
+    mut test: impl FnMut(usize) -> Option<f32>,
+) -> Option<(usize, f32)> {
+    let mut closest_t = max_t;
+    let mut closest_idx: Option<usize> = None;
+    if bvh.nodes.is_empty() {
         return None;
     }
 
-    let inv_dir = dir.recip();
-    let mut closest_t = f32::INFINITY;
-    let mut closest_idx: Option<usize> = None;
+    let mut stack = Vec::with_capacity(64);
+    stack.push(0u32);
 
-    // BVH traversal for finite shapes.
-    if !bvh.nodes.is_empty() {
-        let mut stack = Vec::with_capacity(64);
-        stack.push(0u32);
-
-        while let Some(node_idx) = stack.pop() {
-            let node = &bvh.nodes[node_idx as usize];
+    while let Some(node_idx) = stack.pop() {
+        let node = &bvh.nodes[node_idx as usize];
+        // A leaf root (always so in brute force) is scanned without a bounds
+        // test, like the shader does in that mode.
+        if node_idx != 0 || node.prim_count == 0 {
             let node_aabb = Aabb::new(Vec3::from(node.aabb_min), Vec3::from(node.aabb_max));
-
             let Some(t_node) = ray_aabb(origin, inv_dir, &node_aabb) else {
                 continue;
             };
             if t_node > closest_t {
                 continue;
             }
+        }
 
-            if node.prim_count > 0 {
-                let first = node.left_or_prim as usize;
-                for i in first..(first + node.prim_count as usize) {
-                    let shape_idx = bvh.prim_indices[i] as usize;
-                    let shape = &shapes[shape_idx];
-
-                    if let Some(t) = intersect_shape(origin, dir, inv_dir, shape)
-                        && t > 0.0
-                        && t < closest_t
-                    {
-                        closest_t = t;
-                        closest_idx = Some(shape_idx);
-                    }
+        if node.prim_count > 0 {
+            let first = node.left_or_prim as usize;
+            for i in first..(first + node.prim_count as usize) {
+                let prim_idx = bvh.prim_indices[i] as usize;
+                if let Some(t) = test(prim_idx)
+                    && t > 0.0
+                    && t < closest_t
+                {
+                    closest_t = t;
+                    closest_idx = Some(prim_idx);
                 }
-            } else {
-                stack.push(node.left_or_prim);
-                stack.push(node_idx + 1);
             }
+        } else {
+            stack.push(node.left_or_prim);
+            stack.push(node_idx + 1);
         }
     }
 
+    closest_idx.map(|idx| (idx, closest_t))
+}
+
+/// Returns (shape_index, t, hit_point) for the closest hit, or None.
+///
+/// `infinite_indices` lists global shape indices for shapes excluded from the
+/// BVH (e.g. planes) that must be tested linearly after BVH traversal.
+/// Instances are tested against their model's triangles from `models`.
+///
+/// Only hits with `t` inside `clip` (the camera's `clip_range`) count. Like
+/// camera rays in the shader, the ray starts at `clip.start`, so a shape cut
+/// by the near clip is picked where the ray leaves it.
+pub fn pick(
+    origin: Vec3,
+    dir: Vec3,
+    bvh: &Bvh,
+    shapes: &[Shape],
+    infinite_indices: &[u32],
+    models: &ModelLibrary,
+    clip: Range<f32>,
+) -> Option<(usize, f32, Vec3)> {
+    if shapes.is_empty() || !origin.is_finite() || !dir.is_finite() || dir == Vec3::ZERO {
+        return None;
+    }
+
+    let inv_dir = dir.recip();
+    let origin = origin + dir * clip.start;
+    let max_t = clip.end - clip.start;
+
+    // BVH traversal for finite shapes.
+    let (mut closest_idx, mut closest_t) =
+        match closest_in_bvh(origin, inv_dir, bvh, max_t, |shape_idx| {
+            let shape = &shapes[shape_idx];
+            match models.model_of(shape) {
+                Some(model) => intersect_instance(origin, dir, shape, model),
+                None => intersect_shape(origin, dir, inv_dir, shape),
+            }
+        }) {
+            Some((idx, t)) => (Some(idx), t),
+            None => (None, max_t),
+        };
+
     // Linear test for infinite shapes (planes) excluded from the BVH.
     for &idx in infinite_indices {
         let shape_idx = idx as usize;
@@ -507,5 +667,598 @@ pub fn pick(
         }
     }
 
-    closest_idx.map(|idx| (idx, closest_t, origin + dir * closest_t))
+    closest_idx.map(|idx| (idx, clip.start + closest_t, origin + dir * closest_t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accel::bvh::BvhParams;
+    use crate::camera::camera::GpuCamera;
+
+    /// Screen sizes covering 16:9, 21:9, square and portrait windows.
+    const SIZES: [(u32, u32); 4] = [(1280, 720), (2560, 1080), (800, 800), (720, 1280)];
+
+    fn camera() -> Camera {
+        Camera::new(Vec3::new(1.0, 2.0, -5.0), [-15.0, 30.0, 0.0], 60.0, 1.0)
+    }
+
+    /// CPU copy of `generate_ray` in camera.wgsl for a sample at screen
+    /// position `(px, py)`, i.e. pixel plus jitter.
+    fn shader_ray_dir(gpu: &GpuCamera, px: f32, py: f32) -> Vec3 {
+        let ndc_x = (2.0 * px / gpu.width as f32 - 1.0) * gpu.aspect;
+        let ndc_y = 1.0 - 2.0 * py / gpu.height as f32;
+        (Vec3::from(gpu.right) * ndc_x
+            + Vec3::from(gpu.up) * ndc_y
+            + Vec3::from(gpu.forward) * gpu.focal_length)
+            .normalize()
+    }
+
+    #[test]
+    fn test_center_ray_is_forward() {
+        let cam = camera();
+        let (_, _, forward) = cam.basis_vectors();
+        for (w, h) in SIZES {
+            let (origin, dir) = picking_ray(&cam, w as f32 / 2.0, h as f32 / 2.0, w, h);
+            assert_eq!(origin, cam.position);
+            assert!(
+                dir.abs_diff_eq(forward, 1e-5),
+                "{w}x{h}: {dir} != {forward}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_projection_inverts_picking_ray() {
+        let cam = camera();
+        for (w, h) in SIZES {
+            for (px, py) in [(0.0, 0.0), (w as f32 * 0.3, h as f32 * 0.8)] {
+                let (origin, dir) = picking_ray(&cam, px, py, w, h);
+                let [x, y] = project_to_screen(&cam, origin + dir * 7.0, w, h).unwrap();
+                assert!((x - px).abs() < 1e-2 && (y - py).abs() < 1e-2, "{x}, {y}");
+                assert!(project_to_screen(&cam, origin - dir, w, h).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_corners_match_shader_frustum() {
+        let cam = camera();
+        let (right, up, forward) = cam.basis_vectors();
+        let tan_half_fov = (cam.fov.to_radians() * 0.5).tan();
+        for (w, h) in SIZES {
+            let gpu = cam.to_gpu(w, h, 0, 0);
+            let aspect = w as f32 / h as f32;
+            for (px, py, sx, sy) in [
+                (0.0, 0.0, -1.0, 1.0),
+                (w as f32, 0.0, 1.0, 1.0),
+                (0.0, h as f32, -1.0, -1.0),
+                (w as f32, h as f32, 1.0, -1.0),
+            ] {
+                let (_, dir) = picking_ray(&cam, px, py, w, h);
+                assert!(dir.abs_diff_eq(shader_ray_dir(&gpu, px, py), 1e-5));
+
+                // Vertical FOV is fixed; the horizontal extent scales with aspect.
+                let z = dir.dot(forward);
+                assert!((dir.dot(right) / z - sx * aspect * tan_half_fov).abs() < 1e-4);
+                assert!((dir.dot(up) / z - sy * tan_half_fov).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pick_shape_near_frustum_edge() {
+        let cam = camera();
+        for (w, h) in SIZES {
+            // Place a small sphere where the shader's ray through the middle of the
+            // right-most column points, then click that column.
+            let gpu = cam.to_gpu(w, h, 0, 0);
+            let px = w as f32 - 0.5;
+            let py = h as f32 / 2.0;
+            let center = cam.position + shader_ray_dir(&gpu, px, py) * 10.0;
+            let mut sphere: Shape = serde_yml::from_str("type: sphere\nradius: 0.05").unwrap();
+            sphere.position = center.into();
+            let shapes = vec![sphere];
+            let bvh = Bvh::build(&[shape_aabb(&shapes[0])], BvhParams::default());
+
+            let (origin, dir) = picking_ray(&cam, px, py, w, h);
+            let hit = pick(
+                origin,
+                dir,
+                &bvh,
+                &shapes,
+                &[],
+                &ModelLibrary::default(),
+                cam.clip_range(),
+            );
+            assert!(
+                matches!(hit, Some((0, t, _)) if (t - 9.95).abs() < 1e-2),
+                "{w}x{h}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_ray_down_cone_axis_hits_apex() {
+        let center = Vec3::new(1.0, -2.0, 3.0);
+        for axis in [Vec3::Y, Vec3::new(1.0, 1.0, 0.0).normalize(), -Vec3::Z] {
+            let (radius, height) = (0.75, 2.0);
+            let apex = center + axis * height;
+            let origin = apex + axis * 3.0;
+            let t = ray_cone(origin, -axis, center, axis, radius, height);
+            assert!(t.is_some_and(|t| (t - 3.0).abs() < 1e-3), "{axis}: {t:?}");
+
+            let mut cone: Shape = serde_yml::from_str("type: cone").unwrap();
+            cone.position = center.into();
+            cone.normal = axis.into();
+            cone.radius = radius;
+            cone.height = height;
+            let aabb = shape_aabb(&cone);
+            let inside = |p: Vec3| p.cmpge(aabb.min - 1e-5).all() && p.cmple(aabb.max + 1e-5).all();
+            assert!(inside(apex) && inside(center + axis.any_orthonormal_vector() * radius));
+        }
+    }
+
+    /// Unit box `[0, 1]^3` used by the slab-test cases below.
+    fn unit_box() -> Aabb {
+        Aabb::new(Vec3::ZERO, Vec3::ONE)
+    }
+
+    fn hit_unit_box(origin: Vec3, dir: Vec3) -> Option<f32> {
+        ray_aabb(origin, dir.recip(), &unit_box())
+    }
+
+    #[test]
+    fn test_ray_aabb_axis_aligned() {
+        for axis in 0..3 {
+            for sign in [1.0, -1.0] {
+                let mut dir = Vec3::ZERO;
+                dir[axis] = sign;
+                // Start 2 units outside the face the ray points at, through the box centre.
+                let origin = Vec3::splat(0.5) - dir * 2.5;
+                let t = hit_unit_box(origin, dir);
+                assert!(t.is_some_and(|t| (t - 2.0).abs() < 1e-6), "{dir}: {t:?}");
+
+                // Same ray shifted off the box sideways misses.
+                let mut offset = Vec3::ZERO;
+                offset[(axis + 1) % 3] = 1.5;
+                assert_eq!(hit_unit_box(origin + offset, dir), None, "{dir}");
+
+                // Box behind the origin misses.
+                assert_eq!(hit_unit_box(origin, -dir), None, "{dir}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_ray_aabb_grazing() {
+        // Along the top face, the edge and a corner diagonal: all touch the box.
+        let cases = [
+            (Vec3::new(-1.0, 1.0, 0.5), Vec3::X, 1.0),
+            (Vec3::new(-1.0, 1.0, 1.0), Vec3::X, 1.0),
+            (Vec3::new(0.5, 1.0, -2.0), Vec3::Z, 2.0),
+            // Negative zeros take the `-inf` reciprocal path.
+            (Vec3::new(2.0, 0.0, 0.0), Vec3::new(-1.0, -0.0, -0.0), 1.0),
+        ];
+        for (origin, dir, expected) in cases {
+            let t = hit_unit_box(origin, dir);
+            assert!(
+                t.is_some_and(|t| (t - expected).abs() < 1e-6),
+                "{origin} {dir}: {t:?}"
+            );
+        }
+
+        // Just outside a face plane, or parallel to a face from outside, misses.
+        assert_eq!(hit_unit_box(Vec3::new(-1.0, 1.0001, 0.5), Vec3::X), None);
+        assert_eq!(hit_unit_box(Vec3::new(-1.0, -0.0001, 0.5), Vec3::X), None);
+        assert_eq!(hit_unit_box(Vec3::new(0.5, 2.0, 0.5), Vec3::X), None);
+    }
+
+    #[test]
+    fn test_ray_aabb_inside_and_degenerate() {
+        let t = hit_unit_box(Vec3::splat(0.5), Vec3::Y);
+        assert!(t.is_some_and(|t| (t - 0.5).abs() < 1e-6), "{t:?}");
+
+        // Zero and NaN directions never hit, even from inside.
+        assert_eq!(hit_unit_box(Vec3::splat(0.5), Vec3::ZERO), None);
+        assert_eq!(hit_unit_box(Vec3::splat(0.5), Vec3::splat(f32::NAN)), None);
+
+        let sphere: Shape = serde_yml::from_str("type: sphere\nradius: 1.0").unwrap();
+        let bvh = Bvh::build(&[shape_aabb(&sphere)], BvhParams::default());
+        let models = ModelLibrary::default();
+        assert_eq!(
+            pick(
+                Vec3::ZERO,
+                Vec3::ZERO,
+                &bvh,
+                &[sphere],
+                &[],
+                &models,
+                0.0..f32::INFINITY
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ellipsoid_uses_radii_verbatim() {
+        let yaml = "type: ellipsoid\nposition: [1, 2, 3]\nradius: 1.0\nheight: 0.5\nradius2: 2.0";
+        let shape: Shape = serde_yml::from_str(yaml).unwrap();
+        let center = Vec3::from(shape.position);
+        let radii = Vec3::new(1.0, 0.5, 2.0);
+        for axis in 0..3 {
+            let mut dir = Vec3::ZERO;
+            dir[axis] = -1.0;
+            let origin = center - dir * 5.0;
+            let t = intersect_shape(origin, dir, dir.recip(), &shape);
+            assert!(
+                t.is_some_and(|t| (t - (5.0 - radii[axis])).abs() < 1e-5),
+                "axis {axis}: {t:?}"
+            );
+        }
+        let aabb = shape_aabb(&shape);
+        assert!(aabb.min.abs_diff_eq(center - radii, 1e-3));
+        assert!(aabb.max.abs_diff_eq(center + radii, 1e-3));
+    }
+
+    #[test]
+    fn test_backface_culling_rejects_back_side() {
+        // Counter-clockwise seen from +Z, so the front faces +Z.
+        let (v0, v1, v2) = (Vec3::ZERO, Vec3::X, Vec3::Y);
+        let point = Vec3::new(0.25, 0.25, 0.0);
+        let front = point + Vec3::Z;
+        let back = point - Vec3::Z;
+        assert_eq!(ray_triangle(front, -Vec3::Z, v0, v1, v2, true), Some(1.0));
+        assert_eq!(ray_triangle(back, Vec3::Z, v0, v1, v2, false), Some(1.0));
+        assert_eq!(ray_triangle(back, Vec3::Z, v0, v1, v2, true), None);
+    }
+
+    #[test]
+    fn test_torus_hole_misses() {
+        let center = Vec3::new(0.0, 1.0, 0.0);
+        let (major, minor) = (2.0, 0.5);
+        // Straight down through the hole.
+        assert_eq!(
+            ray_torus(Vec3::new(0.0, 6.0, 0.0), -Vec3::Y, center, major, minor),
+            None
+        );
+        // Straight down onto the top of the tube.
+        let t = ray_torus(Vec3::new(2.0, 6.0, 0.0), -Vec3::Y, center, major, minor);
+        assert!(t.is_some_and(|t| (t - 4.5).abs() < 1e-3), "{t:?}");
+        // Sideways through the tube, across the hole, hits the near side.
+        let t = ray_torus(Vec3::new(-5.0, 1.0, 0.0), Vec3::X, center, major, minor);
+        assert!(t.is_some_and(|t| (t - 2.5).abs() < 1e-3), "{t:?}");
+    }
+
+    // -----------------------------------------------------------------------
+    // Randomized checks of `intersect_shape` against independent references.
+    //
+    // The references are written from the geometry rather than copied from
+    // the WGSL, in f64, and report how close each ray passes to a hit/miss
+    // boundary (edge, silhouette, parallel plane) so grazing rays, where
+    // f32 rounding legitimately decides either way, are skipped.
+    // -----------------------------------------------------------------------
+
+    /// Xorshift64, so failures reproduce without a `rand` dependency.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next_f32(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn range(&mut self, lo: f32, hi: f32) -> f32 {
+            lo + (hi - lo) * self.next_f32()
+        }
+
+        fn vec3(&mut self, lo: f32, hi: f32) -> Vec3 {
+            Vec3::new(self.range(lo, hi), self.range(lo, hi), self.range(lo, hi))
+        }
+    }
+
+    /// Unit-direction rays aimed near the origin: most start 3-8 units away,
+    /// every fourth starts close to the origin (inside unit-sized shapes).
+    fn random_rays(count: usize) -> Vec<(Vec3, Vec3)> {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        (0..count)
+            .map(|i| {
+                let origin = if i % 4 == 0 {
+                    rng.vec3(-0.5, 0.5)
+                } else {
+                    rng.vec3(-1.0, 1.0).normalize_or(Vec3::Y) * rng.range(3.0, 8.0)
+                };
+                let target = rng.vec3(-1.5, 1.5);
+                (origin, (target - origin).normalize())
+            })
+            .collect()
+    }
+
+    /// Reference answer for one ray: the hit distance, and how far the ray is
+    /// from flipping between hit and miss.
+    struct Reference {
+        t: Option<f64>,
+        margin: f64,
+    }
+
+    const MIN_MARGIN: f64 = 1e-3;
+
+    fn d3(v: Vec3) -> glam::DVec3 {
+        v.as_dvec3()
+    }
+
+    fn assert_matches_reference(shape: &Shape, reference: impl Fn(Vec3, Vec3) -> Reference) {
+        let mut checked = 0;
+        let mut hits = 0;
+        for (origin, dir) in random_rays(2000) {
+            let expected = reference(origin, dir);
+            if expected.margin < MIN_MARGIN {
+                continue;
+            }
+            checked += 1;
+            let got = intersect_shape(origin, dir, dir.recip(), shape);
+            match (got, expected.t) {
+                (Some(t), Some(t_ref)) => {
+                    hits += 1;
+                    assert!(
+                        (t as f64 - t_ref).abs() <= 1e-4 * t_ref.max(1.0),
+                        "{:?}: t = {t}, expected {t_ref} for {origin} -> {dir}",
+                        shape.shape_type
+                    );
+                }
+                (None, None) => {}
+                _ => panic!(
+                    "{:?}: got {got:?}, expected {:?} for {origin} -> {dir}",
+                    shape.shape_type, expected.t
+                ),
+            }
+        }
+        // Enough of both outcomes for the comparison to mean something.
+        assert!(
+            checked > 1500,
+            "{:?}: only {checked} rays",
+            shape.shape_type
+        );
+        assert!(
+            hits > checked / 10 && hits < checked * 9 / 10,
+            "{:?}: {hits} of {checked} rays hit",
+            shape.shape_type
+        );
+    }
+
+    fn shape_from(yaml: &str) -> Shape {
+        serde_yml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_sphere_matches_reference() {
+        let shape = shape_from("type: sphere\nposition: [0.3, -0.2, 0.1]\nradius: 1.2");
+        let center = d3(Vec3::from(shape.position));
+        let r = shape.radius as f64;
+        assert_matches_reference(&shape, |o, d| {
+            let (o, d) = (d3(o), d3(d));
+            // |o + t d - c|^2 = r^2 as a general quadratic.
+            let oc = o - center;
+            let (a, b, c) = (d.dot(d), 2.0 * oc.dot(d), oc.dot(oc) - r * r);
+            let disc = b * b - 4.0 * a * c;
+            // Distance of the ray's line from the centre against the radius.
+            let line_dist = oc.cross(d).length() / d.length();
+            let margin = (line_dist - r).abs().min((oc.length() - r).abs());
+            let t = (disc >= 0.0)
+                .then(|| {
+                    let s = disc.sqrt();
+                    [(-b - s) / (2.0 * a), (-b + s) / (2.0 * a)]
+                        .into_iter()
+                        .find(|&t| t > 0.0)
+                })
+                .flatten();
+            Reference { t, margin }
+        });
+    }
+
+    #[test]
+    fn test_plane_matches_reference() {
+        let shape = shape_from("type: plane\nposition: [0.0, 0.25, 0.0]\nnormal: [0.2, 1.0, -0.1]");
+        let point = d3(Vec3::from(shape.position));
+        let n = d3(Vec3::from(shape.normal)).normalize();
+        assert_matches_reference(&shape, |o, d| {
+            let (o, d) = (d3(o), d3(d));
+            let denom = d.dot(n);
+            let height = (o - point).dot(n);
+            let t = -height / denom;
+            Reference {
+                t: (t > 0.0).then_some(t),
+                margin: denom.abs().min(height.abs()),
+            }
+        });
+    }
+
+    #[test]
+    fn test_cube_matches_reference() {
+        let shape = shape_from("type: cube\nposition: [0.1, 0.0, -0.2]\nradius: 1.0");
+        let center = d3(Vec3::from(shape.position));
+        let h = shape.radius as f64;
+        assert_matches_reference(&shape, |o, d| {
+            let (o, d) = (d3(o), d3(d));
+            // Nearest crossing of the six face squares, tested one by one.
+            let mut t_hit: Option<f64> = None;
+            let mut margin = f64::INFINITY;
+            for axis in 0..3 {
+                for side in [-1.0, 1.0] {
+                    if d[axis].abs() < 1e-12 {
+                        continue;
+                    }
+                    let t = (center[axis] + side * h - o[axis]) / d[axis];
+                    if t <= 0.0 {
+                        continue;
+                    }
+                    let p = o + d * t - center;
+                    // Distance inside the face square; negative when outside.
+                    let inside = (0..3)
+                        .filter(|&other| other != axis)
+                        .map(|other| h - p[other].abs())
+                        .fold(f64::INFINITY, f64::min);
+                    margin = margin.min(inside.abs()).min(t);
+                    if inside >= 0.0 && t_hit.is_none_or(|best| t < best) {
+                        t_hit = Some(t);
+                    }
+                }
+            }
+            Reference { t: t_hit, margin }
+        });
+    }
+
+    #[test]
+    fn test_triangle_matches_reference() {
+        let shape = shape_from(
+            "type: triangle\nv0: [-1.5, -1.0, 0.2]\nv1: [1.4, -0.8, -0.3]\nv2: [0.1, 1.6, 0.4]",
+        );
+        let [v0, v1, v2] = [shape.v0, shape.v1, shape.v2].map(|v| d3(Vec3::from(v)));
+        assert_matches_reference(&shape, |o, d| {
+            let (o, d) = (d3(o), d3(d));
+            let n = (v1 - v0).cross(v2 - v0);
+            let denom = d.dot(n);
+            let t = (v0 - o).dot(n) / denom;
+            let p = o + d * t;
+            // Barycentric weights from sub-triangle areas; all >= 0 inside.
+            let area = n.length_squared();
+            let weights =
+                [(v1, v2), (v2, v0), (v0, v1)].map(|(a, b)| (a - p).cross(b - p).dot(n) / area);
+            let inside = weights.iter().all(|&w| w >= 0.0);
+            let margin = weights
+                .iter()
+                .map(|w| w.abs())
+                .fold((denom / n.length()).abs(), f64::min)
+                .min(t.abs());
+            Reference {
+                t: (inside && t > 0.0).then_some(t),
+                margin,
+            }
+        });
+    }
+
+    /// Every exact test lands on a point inside the shape's bounding box, the
+    /// same box the BVH culls with; a hit outside it could never be rendered.
+    #[test]
+    fn test_hits_lie_within_shape_bounds() {
+        for &shape_type in ShapeType::ALL {
+            let shape = shape_type.new_default_shape([0.2, -0.1, 0.3]);
+            let aabb = shape_aabb(&shape);
+            for (origin, dir) in random_rays(500) {
+                let Some(t) = intersect_shape(origin, dir, dir.recip(), &shape) else {
+                    continue;
+                };
+                let p = origin + dir * t;
+                assert!(
+                    p.cmpge(aabb.min - 1e-3).all() && p.cmple(aabb.max + 1e-3).all(),
+                    "{shape_type:?}: hit {p} outside {aabb:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_pick_respects_clip_range() {
+        // Unit spheres at z = 5 and z = 10, and a floor-like plane facing the
+        // ray far behind them.
+        let sphere = |z: f32| {
+            let mut s: Shape = serde_yml::from_str("type: sphere\nradius: 1.0").unwrap();
+            s.position = [0.0, 0.0, z];
+            s
+        };
+        let mut plane: Shape = serde_yml::from_str("type: plane\nnormal: [0, 0, -1]").unwrap();
+        plane.position = [0.0, 0.0, 100.0];
+        let shapes = vec![sphere(5.0), sphere(10.0), plane];
+        let bvh = Bvh::build(
+            &[shape_aabb(&shapes[0]), shape_aabb(&shapes[1])],
+            BvhParams::default(),
+        );
+        let models = ModelLibrary::default();
+        let pick_in = |clip: Range<f32>| {
+            pick(Vec3::ZERO, Vec3::Z, &bvh, &shapes, &[2], &models, clip)
+                .map(|(idx, t, _)| (idx, t))
+        };
+
+        assert_eq!(pick_in(0.0..f32::INFINITY), Some((0, 4.0)));
+        // A near clip inside the first sphere picks it where the ray leaves.
+        assert_eq!(pick_in(4.5..f32::INFINITY), Some((0, 6.0)));
+        assert_eq!(pick_in(6.5..f32::INFINITY), Some((1, 9.0)));
+        assert_eq!(pick_in(11.5..f32::INFINITY), Some((2, 100.0)));
+        // The far clip drops everything beyond it, infinite shapes included.
+        assert_eq!(pick_in(11.5..50.0), None);
+        assert_eq!(pick_in(0.0..3.0), None);
+    }
+
+    #[test]
+    fn test_pick_rotated_scaled_instance() {
+        // Unit quad facing +Z in model space, turned to face +X and doubled.
+        let triangle = |v0: [f32; 3], v1: [f32; 3], v2: [f32; 3]| Shape {
+            v0,
+            v1,
+            v2,
+            ..ShapeType::Triangle.new_default_shape([0.0; 3])
+        };
+        let quad = vec![
+            triangle([-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [1.0, 1.0, 0.0]),
+            triangle([-1.0, -1.0, 0.0], [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0]),
+        ];
+        let mut models = ModelLibrary::default();
+        models.insert("quad.obj", InstancedModel::new(quad, BvhParams::default()));
+
+        let mut instance = ShapeType::Instance.new_default_shape([0.0, 0.0, 5.0]);
+        instance.model = Some("quad.obj".to_string());
+        instance.rotation = [0.0, 90.0, 0.0];
+        instance.radius = 2.0;
+        let model = models.model_of(&instance).unwrap();
+        let aabb = model.instance_aabb(&instance);
+        // The model bounds are padded around the flat quad.
+        assert!(aabb.min.abs_diff_eq(Vec3::new(0.0, -2.0, 3.0), 1e-3));
+        assert!(aabb.max.abs_diff_eq(Vec3::new(0.0, 2.0, 7.0), 1e-3));
+
+        let bvh = Bvh::build(&[aabb.pad()], BvhParams::default());
+        let shapes = [instance];
+        let pick_along_x = |z: f32| {
+            pick(
+                Vec3::new(-10.0, 1.5, z),
+                Vec3::X,
+                &bvh,
+                &shapes,
+                &[],
+                &models,
+                0.0..f32::INFINITY,
+            )
+        };
+        let hit = pick_along_x(6.5);
+        assert!(
+            matches!(hit, Some((0, t, p)) if (t - 10.0).abs() < 1e-4 && p.x.abs() < 1e-4),
+            "{hit:?}"
+        );
+        assert_eq!(pick_along_x(7.5), None);
+    }
+
+    #[test]
+    fn test_instance_backface_culling() {
+        // Counter-clockwise seen from +Z, so the front faces +Z.
+        let mut triangle = ShapeType::Triangle.new_default_shape([0.0; 3]);
+        (triangle.v0, triangle.v1, triangle.v2) = ([0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        let hits_from_behind = |model_culls: bool, instance_culls: bool| {
+            let mut triangle = triangle.clone();
+            triangle.material.cull_backfaces = model_culls;
+            let model = InstancedModel::new(vec![triangle], BvhParams::default());
+            let mut instance = ShapeType::Instance.new_default_shape([0.0; 3]);
+            instance.radius = 1.0;
+            instance.material.cull_backfaces = instance_culls;
+            let from_front =
+                intersect_instance(Vec3::new(0.25, 0.25, 1.0), -Vec3::Z, &instance, &model);
+            assert_eq!(from_front, Some(1.0));
+            intersect_instance(Vec3::new(0.25, 0.25, -1.0), Vec3::Z, &instance, &model).is_some()
+        };
+        assert!(hits_from_behind(false, false));
+        // The model triangle's own material culls...
+        assert!(!hits_from_behind(true, false));
+        // ...and so does the instance's, for the whole mesh.
+        assert!(!hits_from_behind(false, true));
+    }
 }
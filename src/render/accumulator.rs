@@ -3,6 +3,13 @@
 
 use std::time::Instant;
 
+use crate::constants::SAMPLES_PER_FRAME_BUDGET_SECS;
+
+/// Sample bookkeeping for progressive rendering. The accumulation buffer
+/// holds the running mean of the samples so far, updated in `path_trace.wgsl`
+/// as `mean += (sample - mean) / sample_count`, rather than a growing sum, so
+/// it stays readable without a division and keeps its precision over very
+/// long renders.
 pub struct Accumulator {
     pub sample_count: u32,
     pub render_start: Instant,
@@ -39,3 +46,98 @@ impl Accumulator {
         self.dirty
     }
 }
+
+/// Completion of a render with a target sample count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderProgress {
+    /// Fraction of the target reached, in `[0, 1]`.
+    pub fraction: f32,
+    /// Seconds left at the average rate so far; `None` before the first sample.
+    pub eta_secs: Option<f32>,
+}
+
+impl RenderProgress {
+    /// Progress towards `target` samples, or `None` when no target is set.
+    pub fn new(sample_count: u32, target: u32, elapsed_secs: f32) -> Option<Self> {
+        if target == 0 {
+            return None;
+        }
+        let done = sample_count.min(target);
+        let eta_secs = (done > 0 && elapsed_secs > 0.0).then(|| {
+            let rate = done as f32 / elapsed_secs;
+            (target - done) as f32 / rate
+        });
+        Some(Self {
+            fraction: done as f32 / target as f32,
+            eta_secs,
+        })
+    }
+}
+
+/// Samples to run next frame, working up towards `max` one at a time while
+/// frames take less than `SAMPLES_PER_FRAME_BUDGET_SECS` and halving when a
+/// frame (`frame_secs`) takes longer.
+pub fn adapt_frame_samples(current: u32, max: u32, frame_secs: f32) -> u32 {
+    let next = if frame_secs > SAMPLES_PER_FRAME_BUDGET_SECS {
+        current / 2
+    } else {
+        current + 1
+    };
+    next.clamp(1, max.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_progress() {
+        assert_eq!(RenderProgress::new(10, 0, 1.0), None);
+
+        let progress = RenderProgress::new(25, 100, 5.0).unwrap();
+        assert_eq!(progress.fraction, 0.25);
+        assert_eq!(progress.eta_secs, Some(15.0));
+
+        let done = RenderProgress::new(120, 100, 20.0).unwrap();
+        assert_eq!(done.fraction, 1.0);
+        assert_eq!(done.eta_secs, Some(0.0));
+
+        assert_eq!(RenderProgress::new(0, 100, 0.0).unwrap().eta_secs, None);
+    }
+
+    #[test]
+    fn test_frame_samples_stay_within_budget() {
+        let fast = SAMPLES_PER_FRAME_BUDGET_SECS * 0.5;
+        let slow = SAMPLES_PER_FRAME_BUDGET_SECS * 2.0;
+        assert_eq!(adapt_frame_samples(1, 4, fast), 2);
+        assert_eq!(adapt_frame_samples(4, 4, fast), 4);
+        assert_eq!(adapt_frame_samples(8, 16, slow), 4);
+        assert_eq!(adapt_frame_samples(1, 16, slow), 1);
+        // Lowering the setting takes effect at once.
+        assert_eq!(adapt_frame_samples(8, 2, fast), 2);
+        assert_eq!(adapt_frame_samples(3, 0, fast), 1);
+    }
+
+    /// The shader's f32 running-mean update stays on the true mean over a
+    /// million samples.
+    #[test]
+    fn test_running_mean_is_stable_over_long_renders() {
+        const SAMPLES: u32 = 1_000_000;
+        // Noisy-looking but deterministic samples in [0, 1).
+        let sample = |i: u32| (i as f64 * 0.618_033_988_749_895).fract() as f32;
+
+        let mut mean = 0.0f32;
+        let mut exact = 0.0f64;
+        for i in 0..SAMPLES {
+            let x = sample(i);
+            let n = (i + 1) as f32;
+            mean += (x - mean) / n;
+            exact += x as f64;
+        }
+        let exact = exact / SAMPLES as f64;
+
+        let mean_error = (mean as f64 - exact).abs();
+        // Well below one step of an 8-bit output channel.
+        assert!(mean_error < 1e-4, "running mean off by {mean_error}");
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::time::{Duration, Instant};
+
+use crate::constants::{
+    ACCUM_BYTES_PER_PIXEL, AUTO_EXPOSURE_ADAPT_SPEED, AUTO_EXPOSURE_INTERVAL_SECS,
+    AUTO_EXPOSURE_KEY, AUTO_EXPOSURE_MAX, AUTO_EXPOSURE_MIN, AUTO_EXPOSURE_SAMPLE_ROWS,
+};
+
+use super::readback::AsyncReadback;
+
+/// Camera-style auto-exposure: periodically reads a few evenly spaced rows of
+/// the accumulation buffer back to the CPU and eases an exposure multiplier
+/// towards the value that puts the average luminance at mid-gray.
+///
+/// Readbacks go through an `AsyncReadback`, so they never stall a frame.
+pub struct AutoExposure {
+    /// Multiplier applied on top of the user's exposure; 1 while disabled.
+    pub scale: f32,
+    target: Option<f32>,
+    readback: AsyncReadback,
+    last_request: Instant,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            target: None,
+            readback: AsyncReadback::default(),
+            last_request: Instant::now(),
+        }
+    }
+}
+
+impl AutoExposure {
+    /// Forget the adapted exposure, e.g. when the feature is switched off.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Copy sample rows of `accumulation` into a staging buffer, at most once
+    /// per interval and only while no earlier readback is in flight.
+    pub fn record_readback(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        accumulation: &wgpu::Buffer,
+        width: u32,
+        height: u32,
+    ) {
+        let interval = Duration::from_secs_f32(AUTO_EXPOSURE_INTERVAL_SECS);
+        if self.readback.is_pending() || self.last_request.elapsed() < interval || height == 0 {
+            return;
+        }
+        self.last_request = Instant::now();
+
+        let row_bytes = width as u64 * ACCUM_BYTES_PER_PIXEL;
+        let rows = AUTO_EXPOSURE_SAMPLE_ROWS.min(height);
+        let ranges: Vec<_> = (0..rows)
+            .map(|i| {
+                // Centre of each of `rows` horizontal bands.
+                let start = ((2 * i + 1) * height / (2 * rows)) as u64 * row_bytes;
+                start..start + row_bytes
+            })
+            .collect();
+        self.readback.record(
+            device,
+            encoder,
+            accumulation,
+            &ranges,
+            "auto exposure staging",
+        );
+    }
+
+    /// Start mapping a readback recorded this frame. Call after the frame's
+    /// encoder has been submitted.
+    pub fn map_pending(&mut self) {
+        self.readback.map_pending();
+    }
+
+    /// Collect a finished readback, if any, and move `scale` towards its
+    /// target. `dt` is the frame time in seconds.
+    pub fn update(&mut self, dt: f32) {
+        if let Some(data) = self.readback.take_finished()
+            && let Some(target) = target_exposure(&bytemuck::pod_collect_to_vec(&data))
+        {
+            self.target = Some(target);
+        }
+
+        if let Some(target) = self.target {
+            // Ease in log space so brightening and darkening feel the same.
+            let t = 1.0 - (-AUTO_EXPOSURE_ADAPT_SPEED * dt).exp();
+            self.scale = (self.scale.ln() + (target.ln() - self.scale.ln()) * t).exp();
+        }
+    }
+}
+
+/// Exposure that maps the log-average luminance of `pixels` (premultiplied
+/// accumulation values) to mid-gray, or `None` if nothing is covered yet.
+/// Transparent background pixels are skipped.
+pub fn target_exposure(pixels: &[[f32; 4]]) -> Option<f32> {
+    let mut log_sum = 0.0;
+    let mut count = 0u32;
+    for &[r, g, b, a] in pixels {
+        if a <= 0.0 {
+            continue;
+        }
+        let luminance = (0.2126 * r + 0.7152 * g + 0.0722 * b) / a;
+        if luminance.is_finite() {
+            // The offset keeps black pixels from dragging the average to zero.
+            log_sum += (luminance + 1e-4).ln();
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+    let average = (log_sum / count as f32).exp();
+    Some((AUTO_EXPOSURE_KEY / average).clamp(AUTO_EXPOSURE_MIN, AUTO_EXPOSURE_MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_exposure() {
+        assert_eq!(target_exposure(&[]), None);
+        assert_eq!(target_exposure(&[[1.0, 1.0, 1.0, 0.0]]), None);
+
+        let gray = [[0.36, 0.36, 0.36, 1.0]; 4];
+        let exposure = target_exposure(&gray).unwrap();
+        assert!((exposure - 0.5).abs() < 1e-3, "{exposure}");
+
+        // Half-covered pixels are unpremultiplied before averaging.
+        let half = [[0.18, 0.18, 0.18, 0.5]; 4];
+        assert!((target_exposure(&half).unwrap() - 0.5).abs() < 1e-3);
+
+        // A black frame asks for as much exposure as allowed.
+        assert_eq!(
+            target_exposure(&[[0.0, 0.0, 0.0, 1.0]]),
+            Some(AUTO_EXPOSURE_MAX)
+        );
+    }
+}
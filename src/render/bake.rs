@@ -0,0 +1,155 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::f32::consts::TAU;
+
+use glam::Vec3;
+
+use crate::camera::camera::Camera;
+use crate::constants::BAKE_RAY_OFFSET;
+use crate::scene::scene::SkyboxMode;
+use crate::scene::shape::{Shape, ShapeType};
+
+/// Single-bounce lighting for the vertices of the triangles at `members`:
+/// each vertex gathers `samples` cosine-weighted rays, picking up the
+/// emission of whatever they hit (`trace` returns the shape index) or the
+/// `sky` when they escape, and the result is tinted by the triangle's base
+/// color. Triangles are two-sided, so both sides are gathered and the
+/// brighter kept; the inside of a closed mesh is the dark one.
+///
+/// The sample directions are a fixed Hammersley set, so baking the same
+/// scene twice gives the same colors.
+pub fn bake_vertex_colors(
+    shapes: &[Shape],
+    members: &[usize],
+    samples: u32,
+    trace: impl Fn(Vec3, Vec3) -> Option<usize>,
+    sky: impl Fn(Vec3) -> Vec3,
+) -> Vec<[[f32; 3]; 3]> {
+    let directions = cosine_directions(samples.max(1));
+    let gather = |origin: Vec3, normal: Vec3| -> Vec3 {
+        let (tangent, bitangent) = normal.any_orthonormal_pair();
+        let total: Vec3 = directions
+            .iter()
+            .map(|d| {
+                let dir = tangent * d.x + bitangent * d.y + normal * d.z;
+                match trace(origin, dir) {
+                    Some(idx) => {
+                        let mat = &shapes[idx].material;
+                        Vec3::from(mat.emission) * mat.emission_strength
+                    }
+                    None => sky(dir),
+                }
+            })
+            .sum();
+        total / directions.len() as f32
+    };
+
+    members
+        .iter()
+        .map(|&idx| {
+            let shape = &shapes[idx];
+            if shape.shape_type != ShapeType::Triangle {
+                return [[0.0; 3]; 3];
+            }
+            let vertices = [shape.v0, shape.v1, shape.v2].map(Vec3::from);
+            let Some(normal) = (vertices[1] - vertices[0])
+                .cross(vertices[2] - vertices[0])
+                .try_normalize()
+            else {
+                return [[0.0; 3]; 3];
+            };
+            let centroid = (vertices[0] + vertices[1] + vertices[2]) / 3.0;
+            let albedo = Vec3::from(shape.material.base_color);
+            vertices.map(|vertex| {
+                // Nudge off the shared edges so neighbours don't shadow the vertex.
+                let p = vertex + (centroid - vertex) * 1e-3;
+                let front = gather(p + normal * BAKE_RAY_OFFSET, normal);
+                let back = gather(p - normal * BAKE_RAY_OFFSET, -normal);
+                let irradiance = if luminance(back) > luminance(front) {
+                    back
+                } else {
+                    front
+                };
+                (albedo * irradiance).into()
+            })
+        })
+        .collect()
+}
+
+/// Sky radiance in direction `dir` for baking. Solid and gradient skies
+/// match the renderer; HDR maps and the physical sky fall back to the
+/// gradient colors, which is close enough for a coarse bake.
+pub fn sky_radiance(camera: &Camera, dir: Vec3) -> Vec3 {
+    let zenith = Vec3::from(camera.skybox_color);
+    let color = match camera.skybox_mode {
+        SkyboxMode::SolidColor => zenith,
+        _ => {
+            let horizon = Vec3::from(camera.skybox_horizon_color);
+            horizon.lerp(zenith, 0.5 * (dir.normalize_or_zero().y + 1.0))
+        }
+    };
+    color * camera.skybox_brightness
+}
+
+/// `count` cosine-weighted directions about +Z from the Hammersley set.
+fn cosine_directions(count: u32) -> Vec<Vec3> {
+    (0..count)
+        .map(|i| {
+            let u = (i as f32 + 0.5) / count as f32;
+            let phi = TAU * (i.reverse_bits() as f32 / 2f32.powi(32));
+            let r = u.sqrt();
+            Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - u).sqrt())
+        })
+        .collect()
+}
+
+fn luminance(c: Vec3) -> f32 {
+    c.dot(Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle(v0: [f32; 3], v1: [f32; 3], v2: [f32; 3]) -> Shape {
+        let mut shape = ShapeType::Triangle.new_default_shape([0.0; 3]);
+        (shape.v0, shape.v1, shape.v2) = (v0, v1, v2);
+        shape.material.base_color = [0.5, 0.25, 1.0];
+        shape
+    }
+
+    #[test]
+    fn test_bake_gathers_the_lit_side() {
+        // Floor wound to face down, under a ceiling light (shape 1) filling
+        // the upper hemisphere; the sky below is black.
+        let mut light = ShapeType::Sphere.new_default_shape([0.0; 3]);
+        light.material.emission = [1.0; 3];
+        light.material.emission_strength = 2.0;
+        let shapes = [triangle([0.0; 3], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]), light];
+        let trace = |_: Vec3, dir: Vec3| (dir.y > 0.0).then_some(1);
+        let colors = bake_vertex_colors(&shapes, &[0], 64, trace, |_| Vec3::ZERO);
+        for color in colors[0] {
+            let expected = [1.0, 0.5, 2.0];
+            for (c, e) in color.iter().zip(expected) {
+                assert!((c - e).abs() < 1e-5, "{color:?}");
+            }
+        }
+
+        // A wall sees the light through half of its hemisphere, and baking
+        // again gives exactly the same colors.
+        let shapes = [
+            triangle([0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+            shapes[1].clone(),
+        ];
+        let bake = || bake_vertex_colors(&shapes, &[0], 256, trace, |_| Vec3::ZERO);
+        let colors = bake();
+        assert_eq!(colors, bake());
+        for color in colors[0] {
+            let expected = [0.5, 0.25, 1.0];
+            for (c, e) in color.iter().zip(expected) {
+                assert!((c - e).abs() < 0.02, "{color:?}");
+            }
+        }
+    }
+}
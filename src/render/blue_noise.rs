@@ -0,0 +1,165 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::constants::BLUE_NOISE_SIGMA;
+
+/// Tileable `size` x `size` blue-noise mask, row-major, with every value in
+/// (0, 1) used once. Neighbouring pixels get values far apart, so jitter
+/// read from it spreads the first few samples of a region evenly instead of
+/// clumping them like white noise.
+///
+/// Built with Ulichney's void-and-cluster method on a torus: points are
+/// added one at a time where the Gaussian-filtered density is lowest, and a
+/// pixel's value is its insertion rank. Deterministic, so it is generated at
+/// startup rather than shipped as an asset.
+pub fn blue_noise(size: usize) -> Vec<f32> {
+    let count = size * size;
+    let kernel = gaussian_kernel(size);
+    let mut mask = Mask {
+        size,
+        kernel: &kernel,
+        on: vec![false; count],
+        energy: vec![0.0; count],
+    };
+
+    // Seed with a tenth of the pixels picked by hash, then relax: move the
+    // tightest cluster's point into the largest void until that changes
+    // nothing.
+    let seeds = (count / 10).max(1);
+    let mut state = 0x9e37_79b9u32;
+    while mask.ones() < seeds {
+        state = pcg_hash(state);
+        mask.set(state as usize % count, true);
+    }
+    loop {
+        let cluster = mask.tightest_cluster();
+        mask.set(cluster, false);
+        let void = mask.largest_void();
+        mask.set(void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0usize; count];
+    let initial = mask.on.clone();
+    let initial_energy = mask.energy.clone();
+
+    // Rank the seed points by taking them out tightest cluster first...
+    for r in (0..seeds).rev() {
+        let cluster = mask.tightest_cluster();
+        mask.set(cluster, false);
+        rank[cluster] = r;
+    }
+    // ...then fill the rest of the mask largest void first.
+    mask.on = initial;
+    mask.energy = initial_energy;
+    for r in seeds..count {
+        let void = mask.largest_void();
+        mask.set(void, true);
+        rank[void] = r;
+    }
+
+    rank.iter()
+        .map(|&r| (r as f32 + 0.5) / count as f32)
+        .collect()
+}
+
+/// Binary pattern plus each pixel's filtered density of set pixels.
+struct Mask<'a> {
+    size: usize,
+    kernel: &'a [f32],
+    on: Vec<bool>,
+    energy: Vec<f32>,
+}
+
+impl Mask<'_> {
+    fn ones(&self) -> usize {
+        self.on.iter().filter(|&&on| on).count()
+    }
+
+    fn set(&mut self, idx: usize, on: bool) {
+        if self.on[idx] == on {
+            return;
+        }
+        self.on[idx] = on;
+        let sign = if on { 1.0 } else { -1.0 };
+        let (x0, y0) = (idx % self.size, idx / self.size);
+        for y in 0..self.size {
+            let dy = (y + self.size - y0) % self.size;
+            for x in 0..self.size {
+                let dx = (x + self.size - x0) % self.size;
+                self.energy[y * self.size + x] += sign * self.kernel[dy * self.size + dx];
+            }
+        }
+    }
+
+    /// Set pixel with the most set pixels around it.
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    /// Unset pixel with the fewest set pixels around it.
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, on: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best = None;
+        for (idx, &energy) in self.energy.iter().enumerate() {
+            if self.on[idx] == on && best.is_none_or(|b: usize| better(energy, self.energy[b])) {
+                best = Some(idx);
+            }
+        }
+        best.expect("mask has pixels in both states")
+    }
+}
+
+/// Gaussian weight of every toroidal offset, indexed like the mask.
+fn gaussian_kernel(size: usize) -> Vec<f32> {
+    let wrap = |d: usize| d.min(size - d) as f32;
+    let denom = 2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA;
+    (0..size * size)
+        .map(|i| {
+            let (dx, dy) = (wrap(i % size), wrap(i / size));
+            (-(dx * dx + dy * dy) / denom).exp()
+        })
+        .collect()
+}
+
+/// Same hash as `pcg_hash` in random.wgsl.
+fn pcg_hash(input: u32) -> u32 {
+    let state = input.wrapping_mul(747_796_405).wrapping_add(2_891_336_453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277_803_737);
+    (word >> 22) ^ word
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blue_noise_is_a_spread_out_permutation() {
+        let size = 32;
+        let mask = blue_noise(size);
+        let mut ranks: Vec<_> = mask
+            .iter()
+            .map(|v| (v * mask.len() as f32) as usize)
+            .collect();
+        ranks.sort_unstable();
+        assert_eq!(ranks, (0..mask.len()).collect::<Vec<_>>());
+
+        // Neighbours differ by 1/3 on average for white noise; blue noise
+        // keeps them further apart.
+        let mut diff = 0.0;
+        for y in 0..size {
+            for x in 0..size {
+                let v = mask[y * size + x];
+                diff += (v - mask[y * size + (x + 1) % size]).abs();
+                diff += (v - mask[(y + 1) % size * size + x]).abs();
+            }
+        }
+        let mean = diff / (2 * size * size) as f32;
+        assert!(mean > 0.38, "{mean}");
+    }
+}
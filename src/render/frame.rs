@@ -1,22 +1,367 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use crate::constants::WORKGROUP_SIZE;
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{BLUE_NOISE_SIZE, MAX_MATERIAL_BOUNCES, WORKGROUP_SIZE};
 use crate::gpu::buffers::dispatch_size;
+use crate::shaders::composer::ShaderComposer;
+
+/// Compute workgroup dimensions, substituted into the shaders as
+/// `{{WORKGROUP_X}}`/`{{WORKGROUP_Y}}` and used for the dispatch math.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkgroupSize {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Default for WorkgroupSize {
+    fn default() -> Self {
+        Self {
+            x: WORKGROUP_SIZE,
+            y: WORKGROUP_SIZE,
+        }
+    }
+}
+
+impl WorkgroupSize {
+    /// Parse `16x16`, `8x4` or a single `16` for a square workgroup.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (x, y) = match value.split_once(['x', 'X']) {
+            Some((x, y)) => (x.trim().parse().ok()?, y.trim().parse().ok()?),
+            None => {
+                let n = value.trim().parse().ok()?;
+                (n, n)
+            }
+        };
+        (x > 0 && y > 0).then_some(Self { x, y })
+    }
+
+    /// Whether the device can run this workgroup size.
+    pub fn fits(self, limits: &wgpu::Limits) -> bool {
+        self.x <= limits.max_compute_workgroup_size_x
+            && self.y <= limits.max_compute_workgroup_size_y
+            && self.x * self.y <= limits.max_compute_invocations_per_workgroup
+    }
+
+    /// `requested` if the device can run it, otherwise the default.
+    pub fn resolve(requested: Option<Self>, limits: &wgpu::Limits) -> Self {
+        match requested {
+            Some(size) if size.fits(limits) => size,
+            Some(size) => {
+                log::warn!(
+                    "Workgroup size {size} exceeds device limits, using {}",
+                    Self::default()
+                );
+                Self::default()
+            }
+            None => Self::default(),
+        }
+    }
+
+    pub fn define_constants(self, composer: &mut ShaderComposer) {
+        composer.set_constant("WORKGROUP_X", self.x);
+        composer.set_constant("WORKGROUP_Y", self.y);
+    }
+}
+
+impl std::fmt::Display for WorkgroupSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.x, self.y)
+    }
+}
+
+/// Optional path-tracer features compiled into `path_trace.wgsl` through
+/// `// #if` blocks, so disabled ones cost nothing on the GPU. Changing them
+/// recompiles the path-trace pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShaderFeatures {
+    /// Next event estimation: explicit area, spot and sun light sampling.
+    pub nee: bool,
+    /// Randomly terminate low-throughput paths after a few bounces.
+    pub russian_roulette: bool,
+}
+
+impl Default for ShaderFeatures {
+    fn default() -> Self {
+        Self {
+            nee: true,
+            russian_roulette: true,
+        }
+    }
+}
+
+impl ShaderFeatures {
+    pub fn define(self, composer: &mut ShaderComposer) {
+        composer.define("NEE", self.nee);
+        composer.define("RUSSIAN_ROULETTE", self.russian_roulette);
+    }
+}
+
+/// Substitute the limits the shaders share with the CPU side.
+pub fn define_shared_constants(composer: &mut ShaderComposer) {
+    composer.set_constant("MAX_MATERIAL_BOUNCES", MAX_MATERIAL_BOUNCES);
+    composer.set_constant("BLUE_NOISE_SIZE", BLUE_NOISE_SIZE);
+}
+
+/// Sub-pixel offsets of primary rays (`Camera.jitter_pattern` in WGSL).
+/// They all converge to the same image; they differ in how evenly the
+/// first few samples cover each pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JitterPattern {
+    /// Independent uniform offsets per sample.
+    #[default]
+    Random,
+    /// One offset per cell of a 4x4 grid over each 16 samples.
+    Stratified,
+    /// Offsets from a tiled blue-noise mask (`render::blue_noise`), so
+    /// neighbouring pixels sample different parts of their footprint and
+    /// early noise looks fine-grained instead of blotchy.
+    BlueNoise,
+}
+
+impl JitterPattern {
+    pub const ALL: &[Self] = &[Self::Random, Self::Stratified, Self::BlueNoise];
+
+    pub fn as_u32(self) -> u32 {
+        match self {
+            Self::Random => 0,
+            Self::Stratified => 1,
+            Self::BlueNoise => 2,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Random => "Random",
+            Self::Stratified => "Stratified",
+            Self::BlueNoise => "Blue Noise",
+        }
+    }
+}
 
+/// Encoding of the final 8-bit output (`Camera.color_space` in WGSL), applied
+/// after tone mapping. Screenshots are tagged to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    /// sRGB transfer curve, what ordinary monitors and image viewers expect.
+    #[default]
+    Srgb,
+    /// No transfer curve, for compositing. Bands visibly in 8 bits.
+    Linear,
+    /// BT.709 camera curve with the same primaries as sRGB, for video.
+    Rec709,
+}
+
+impl ColorSpace {
+    pub const ALL: &[Self] = &[Self::Srgb, Self::Linear, Self::Rec709];
+
+    pub fn as_u32(self) -> u32 {
+        match self {
+            Self::Srgb => 0,
+            Self::Linear => 1,
+            Self::Rec709 => 2,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Srgb => "sRGB",
+            Self::Linear => "Linear",
+            Self::Rec709 => "Rec.709",
+        }
+    }
+}
+
+/// Stereo output (`Camera.stereo_mode` in WGSL): two eye views
+/// `Camera.eye_separation` apart, combined into one image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StereoMode {
+    #[default]
+    Off,
+    /// Left eye in the left half of the image, right eye in the right half.
+    SideBySide,
+    /// Red/cyan: the left eye's red channel with the right eye's green and
+    /// blue. Traces both eyes for every sample.
+    Anaglyph,
+}
+
+impl StereoMode {
+    pub const ALL: &[Self] = &[Self::Off, Self::SideBySide, Self::Anaglyph];
+
+    pub fn as_u32(self) -> u32 {
+        match self {
+            Self::Off => 0,
+            Self::SideBySide => 1,
+            Self::Anaglyph => 2,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "Off",
+            Self::SideBySide => "Side by Side",
+            Self::Anaglyph => "Anaglyph (Red/Cyan)",
+        }
+    }
+}
+
+/// How the path-trace pass shades pixels (`Camera.view_mode` in WGSL).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViewMode {
+    /// Full progressive path tracing.
+    #[default]
+    PathTraced,
+    /// One primary ray per pixel with lambert shading from a headlight at the
+    /// camera. Nothing accumulates, so edits show up immediately.
+    Flat,
+    /// World-space surface normal mapped to RGB, for spotting flipped faces.
+    Normals,
+    /// Texture coordinates in red and green, for checking UV layouts.
+    Uv,
+}
+
+impl ViewMode {
+    pub fn as_u32(self) -> u32 {
+        match self {
+            Self::PathTraced => 0,
+            Self::Flat => 1,
+            Self::Normals => 2,
+            Self::Uv => 3,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::PathTraced => "Path Traced",
+            Self::Flat => "Flat Preview",
+            Self::Normals => "Normals",
+            Self::Uv => "UV",
+        }
+    }
+
+    /// Whether samples accumulate; the other modes render one frame per change.
+    pub fn accumulates(self) -> bool {
+        self == Self::PathTraced
+    }
+
+    pub const ALL: &[Self] = &[Self::PathTraced, Self::Flat, Self::Normals, Self::Uv];
+}
+
+/// Sub-rectangle of the frame to path trace, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RenderRegion {
+    pub fn full(width: u32, height: u32) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    /// Map from one frame resolution to another, e.g. window to render pixels.
+    pub fn scaled(self, sx: f32, sy: f32) -> Self {
+        let x = (self.x as f32 * sx).round() as u32;
+        let y = (self.y as f32 * sy).round() as u32;
+        Self {
+            x,
+            y,
+            width: (((self.x + self.width) as f32 * sx).round() as u32).saturating_sub(x),
+            height: (((self.y + self.height) as f32 * sy).round() as u32).saturating_sub(y),
+        }
+    }
+
+    /// Clip to a `width` x `height` frame. Returns `None` if nothing is left.
+    pub fn clamped(self, width: u32, height: u32) -> Option<Self> {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+        let w = self.width.min(width - x);
+        let h = self.height.min(height - y);
+        (w > 0 && h > 0).then_some(Self {
+            x,
+            y,
+            width: w,
+            height: h,
+        })
+    }
+}
+
+/// Blit pass uniform. Must match the WGSL `BlitParams` struct layout.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct BlitParams {
+    /// Contrast-adaptive sharpening strength in 0..1; 0 = plain bilinear.
+    pub sharpness: f32,
+    /// Shapes `selection_start..selection_end` are outlined; empty = none.
+    pub selection_start: u32,
+    pub selection_end: u32,
+    /// Source texels per window pixel when above 1 (supersampling): the
+    /// blit averages them. 0 or 1 = a single bilinear tap.
+    pub downsample: f32,
+}
+
+/// Internal render resolution for a `width` x `height` window: scaled down,
+/// keeping the aspect ratio, until the longest side is at most `max_side`
+/// (0 = no limit) and the frame has at most `max_pixels` pixels.
+pub fn clamp_render_size(width: u32, height: u32, max_side: u32, max_pixels: u64) -> (u32, u32) {
+    let longest = width.max(height).max(1) as f64;
+    let side_scale = if max_side == 0 {
+        1.0
+    } else {
+        (max_side as f64 / longest).min(1.0)
+    };
+    let pixels = width as f64 * height as f64;
+    let pixel_scale = (max_pixels as f64 / pixels.max(1.0)).sqrt().min(1.0);
+    let scale = side_scale.min(pixel_scale);
+    if scale >= 1.0 {
+        return (width, height);
+    }
+    let scaled = |v: u32| ((v as f64 * scale).floor() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+/// Nearest-neighbour resample of a `from` sized image of per-pixel texels to
+/// `to`, so an accumulated mean survives a resize at the new resolution.
+pub fn rescale_nearest<T: Copy>(texels: &[T], from: (u32, u32), to: (u32, u32)) -> Vec<T> {
+    let (from_w, from_h) = (from.0.max(1) as u64, from.1.max(1) as u64);
+    let (to_w, to_h) = (to.0 as u64, to.1 as u64);
+    let mut out = Vec::with_capacity((to_w * to_h) as usize);
+    for y in 0..to_h {
+        let src_y = (y * from_h / to_h.max(1)).min(from_h - 1);
+        for x in 0..to_w {
+            let src_x = (x * from_w / to_w.max(1)).min(from_w - 1);
+            out.push(texels[(src_y * from_w + src_x) as usize]);
+        }
+    }
+    out
+}
+
+/// Path trace `region` only; the shader offsets invocation ids by the camera's region origin.
 pub fn dispatch_path_trace(
     encoder: &mut wgpu::CommandEncoder,
     pipeline: &wgpu::ComputePipeline,
     bind_groups: &[&wgpu::BindGroup],
-    width: u32,
-    height: u32,
+    region: RenderRegion,
+    workgroup: WorkgroupSize,
 ) {
     dispatch_compute(
         encoder,
         pipeline,
         bind_groups,
-        width,
-        height,
+        workgroup,
+        region.width,
+        region.height,
         "path trace pass",
     );
 }
@@ -25,6 +370,7 @@ pub fn dispatch_post_process(
     encoder: &mut wgpu::CommandEncoder,
     pipeline: &wgpu::ComputePipeline,
     bind_group: &wgpu::BindGroup,
+    workgroup: WorkgroupSize,
     width: u32,
     height: u32,
 ) {
@@ -32,6 +378,7 @@ pub fn dispatch_post_process(
         encoder,
         pipeline,
         &[bind_group],
+        workgroup,
         width,
         height,
         "post process pass",
@@ -42,6 +389,7 @@ fn dispatch_compute(
     encoder: &mut wgpu::CommandEncoder,
     pipeline: &wgpu::ComputePipeline,
     bind_groups: &[&wgpu::BindGroup],
+    workgroup: WorkgroupSize,
     width: u32,
     height: u32,
     label: &str,
@@ -55,8 +403,96 @@ fn dispatch_compute(
         pass.set_bind_group(i as u32, Some(*bg), &[]);
     }
     pass.dispatch_workgroups(
-        dispatch_size(width, WORKGROUP_SIZE),
-        dispatch_size(height, WORKGROUP_SIZE),
+        dispatch_size(width, workgroup.x),
+        dispatch_size(height, workgroup.y),
         1,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_clamped_to_frame() {
+        let region = RenderRegion {
+            x: 900,
+            y: 500,
+            width: 400,
+            height: 400,
+        };
+        assert_eq!(
+            region.clamped(1280, 720),
+            Some(RenderRegion {
+                x: 900,
+                y: 500,
+                width: 380,
+                height: 220,
+            })
+        );
+        assert_eq!(region.clamped(800, 720), None);
+        assert_eq!(
+            RenderRegion::full(64, 32).clamped(64, 32),
+            Some(RenderRegion::full(64, 32))
+        );
+    }
+
+    #[test]
+    fn test_clamp_render_size() {
+        // Within limits, or no limit: unchanged.
+        assert_eq!(clamp_render_size(1920, 1080, 3840, u64::MAX), (1920, 1080));
+        assert_eq!(clamp_render_size(5120, 2880, 0, u64::MAX), (5120, 2880));
+        // 5K window capped to a 4K-wide render with the same aspect ratio.
+        assert_eq!(clamp_render_size(5120, 2880, 3840, u64::MAX), (3840, 2160));
+        assert_eq!(clamp_render_size(1440, 2560, 1280, u64::MAX), (720, 1280));
+        // Pixel budget (e.g. the accumulation buffer's binding limit) also applies.
+        let (w, h) = clamp_render_size(4000, 2000, 0, 2_000_000);
+        assert!(w as u64 * h as u64 <= 2_000_000, "{w}x{h}");
+        assert_eq!(w / h, 2);
+
+        let region = RenderRegion {
+            x: 100,
+            y: 50,
+            width: 200,
+            height: 100,
+        };
+        assert_eq!(
+            region.scaled(0.5, 0.5),
+            RenderRegion {
+                x: 50,
+                y: 25,
+                width: 100,
+                height: 50,
+            }
+        );
+    }
+
+    #[test]
+    fn test_rescale_nearest() {
+        let texels = [1, 2, 3, 4];
+        assert_eq!(
+            rescale_nearest(&texels, (2, 2), (4, 2)),
+            vec![1, 1, 2, 2, 3, 3, 4, 4]
+        );
+        assert_eq!(rescale_nearest(&texels, (2, 2), (1, 1)), vec![1]);
+        assert_eq!(rescale_nearest(&texels, (2, 2), (2, 2)), texels.to_vec());
+    }
+
+    #[test]
+    fn test_workgroup_size_parse_and_limits() {
+        assert_eq!(
+            WorkgroupSize::parse("16x8"),
+            Some(WorkgroupSize { x: 16, y: 8 })
+        );
+        assert_eq!(
+            WorkgroupSize::parse("16"),
+            Some(WorkgroupSize { x: 16, y: 16 })
+        );
+        assert_eq!(WorkgroupSize::parse("0x8"), None);
+        assert_eq!(WorkgroupSize::parse("big"), None);
+
+        let limits = wgpu::Limits::downlevel_defaults();
+        assert!(WorkgroupSize::default().fits(&limits));
+        assert!(!WorkgroupSize { x: 32, y: 32 }.fits(&limits));
+    }
+}
@@ -2,5 +2,10 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 pub mod accumulator;
+pub mod auto_exposure;
+pub mod bake;
+pub mod blue_noise;
 pub mod frame;
+pub mod pixel_probe;
 pub mod post_process;
+pub mod readback;
@@ -0,0 +1,67 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::constants::ACCUM_BYTES_PER_PIXEL;
+
+use super::readback::AsyncReadback;
+
+/// Reads single pixels of the accumulation buffer back to the CPU, for the
+/// crosshair inspector, through an `AsyncReadback` like auto-exposure does.
+#[derive(Default)]
+pub struct PixelProbe {
+    /// Accumulated linear color of the last pixel read, if it was covered.
+    pub color: Option<[f32; 3]>,
+    readback: AsyncReadback,
+}
+
+impl PixelProbe {
+    /// Copy pixel (`x`, `y`) of an accumulation buffer `width` pixels wide
+    /// into a staging buffer, unless an earlier readback is still in flight.
+    pub fn record_readback(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        accumulation: &wgpu::Buffer,
+        width: u32,
+        (x, y): (u32, u32),
+    ) {
+        let offset = (y as u64 * width as u64 + x as u64) * ACCUM_BYTES_PER_PIXEL;
+        self.readback.record(
+            device,
+            encoder,
+            accumulation,
+            std::slice::from_ref(&(offset..offset + ACCUM_BYTES_PER_PIXEL)),
+            "pixel probe staging",
+        );
+    }
+
+    /// Start mapping a readback recorded this frame. Call after the frame's
+    /// encoder has been submitted.
+    pub fn map_pending(&mut self) {
+        self.readback.map_pending();
+    }
+
+    /// Collect a finished readback into `color`, if any.
+    pub fn update(&mut self) {
+        if let Some(data) = self.readback.take_finished() {
+            self.color = unpremultiply(bytemuck::pod_read_unaligned(&data));
+        }
+    }
+}
+
+/// Color of a premultiplied accumulation pixel, or `None` where nothing
+/// covers it yet.
+pub fn unpremultiply([r, g, b, a]: [f32; 4]) -> Option<[f32; 3]> {
+    (a > 0.0).then(|| [r / a, g / a, b / a])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpremultiply() {
+        assert_eq!(unpremultiply([1.0, 1.0, 1.0, 0.0]), None);
+        assert_eq!(unpremultiply([0.25, 0.5, 1.0, 0.5]), Some([0.5, 1.0, 2.0]));
+    }
+}
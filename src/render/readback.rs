@@ -0,0 +1,97 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::ops::Range;
+use std::sync::mpsc;
+
+/// A GPU buffer readback that never stalls the frame: the copy is recorded
+/// into the frame's encoder, mapped after submit (`map_pending`) and
+/// collected by a later `take_finished` once the GPU is done. At most one
+/// readback is in flight.
+#[derive(Default)]
+pub struct AsyncReadback {
+    pending: Option<Readback>,
+}
+
+struct Readback {
+    staging: wgpu::Buffer,
+    mapped: Option<mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+impl AsyncReadback {
+    /// A readback was recorded and has not been collected yet.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Copy `ranges` of `source`, back to back, into a new staging buffer.
+    /// Does nothing while an earlier readback is in flight.
+    pub fn record(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::Buffer,
+        ranges: &[Range<u64>],
+        label: &str,
+    ) {
+        let total: u64 = ranges.iter().map(|r| r.end - r.start).sum();
+        if self.is_pending() || total == 0 {
+            return;
+        }
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: total,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut offset = 0;
+        for range in ranges {
+            let size = range.end - range.start;
+            encoder.copy_buffer_to_buffer(source, range.start, &staging, offset, size);
+            offset += size;
+        }
+        self.pending = Some(Readback {
+            staging,
+            mapped: None,
+        });
+    }
+
+    /// Start mapping a readback recorded this frame. Call after the frame's
+    /// encoder has been submitted.
+    pub fn map_pending(&mut self) {
+        let Some(readback) = self.pending.as_mut() else {
+            return;
+        };
+        if readback.mapped.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        readback
+            .staging
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        readback.mapped = Some(receiver);
+    }
+
+    /// The bytes of a finished readback, freeing the slot for the next one.
+    /// `None` while it is still in flight, or if it failed (the slot is
+    /// freed then too).
+    pub fn take_finished(&mut self) -> Option<Vec<u8>> {
+        let receiver = self.pending.as_ref()?.mapped.as_ref()?;
+        match receiver.try_recv() {
+            Ok(Ok(())) => {
+                let readback = self.pending.take()?;
+                let data = readback.staging.slice(..).get_mapped_range().to_vec();
+                readback.staging.unmap();
+                Some(data)
+            }
+            Ok(Err(_)) | Err(mpsc::TryRecvError::Disconnected) => {
+                self.pending = None;
+                None
+            }
+            Err(mpsc::TryRecvError::Empty) => None,
+        }
+    }
+}
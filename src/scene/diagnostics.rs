@@ -0,0 +1,260 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use super::lights;
+use super::scene::{ModelRef, SkyboxMode};
+use super::shape::{Shape, ShapeType};
+use crate::accel::aabb::shape_aabb;
+use crate::camera::camera::Camera;
+
+/// A common scene mistake that usually results in a black or empty render.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneIssue {
+    /// The scene has no shapes at all.
+    EmptyScene,
+    /// No emissive shapes and the skybox contributes no light.
+    NoLightSources,
+    /// The camera sits inside the bounding box of a closed shape.
+    CameraInsideShape(usize),
+    /// Every finite shape is behind the camera and there are no planes.
+    AllShapesBehindCamera,
+    /// A NaN or infinite value in a camera setting.
+    NonFiniteCamera(&'static str),
+    /// A NaN or infinite value in a shape field.
+    NonFiniteShape(usize, &'static str),
+    /// The first emissive shape whose type light sampling can't pick points on.
+    UnsampledLight(usize),
+}
+
+impl SceneIssue {
+    pub fn message(&self) -> String {
+        match self {
+            Self::EmptyScene => "The scene is empty".to_string(),
+            Self::NoLightSources => "No light sources".to_string(),
+            Self::CameraInsideShape(idx) => format!("Camera is inside shape #{idx}"),
+            Self::AllShapesBehindCamera => "All shapes are behind the camera".to_string(),
+            Self::NonFiniteCamera(field) => format!("Camera {field} is NaN or infinite"),
+            Self::NonFiniteShape(idx, field) => format!("Shape #{idx} {field} is NaN or infinite"),
+            Self::UnsampledLight(idx) => format!("Light #{idx} is not sampled directly"),
+        }
+    }
+
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Self::EmptyScene => "Add a shape via Scene > Add Shape or open an example scene.",
+            Self::NoLightSources => {
+                "Give a shape an emissive material (Emit preset) or raise the skybox brightness."
+            }
+            Self::CameraInsideShape(_) => {
+                "Move the camera out of the shape, or shrink/move the shape."
+            }
+            Self::AllShapesBehindCamera => "Turn the camera around or move the shapes in front.",
+            Self::NonFiniteCamera(_) => "Fix the value in the scene file's camera block.",
+            Self::NonFiniteShape(..) => "Fix the value in the object editor or the scene file.",
+            Self::UnsampledLight(_) => {
+                "Only sphere, disc and triangle lights are sampled; this one converges slowly."
+            }
+        }
+    }
+
+    /// Shape the issue refers to, for a "select" shortcut in the UI.
+    pub fn shape_index(&self) -> Option<usize> {
+        match self {
+            Self::CameraInsideShape(idx)
+            | Self::NonFiniteShape(idx, _)
+            | Self::UnsampledLight(idx) => Some(*idx),
+            _ => None,
+        }
+    }
+}
+
+/// A file the scene refers to that could not be loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MissingAsset {
+    /// A shape texture, by the path written on the shape.
+    Texture(String),
+    /// A model whose OBJ failed to load; kept whole so it can be loaded again
+    /// from a new path.
+    Model(ModelRef),
+}
+
+impl MissingAsset {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Texture(path) => path,
+            Self::Model(model_ref) => &model_ref.path,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Texture(_) => "Texture",
+            Self::Model(_) => "Model",
+        }
+    }
+}
+
+/// Shape textures that did not make it into the atlas, each listed once in
+/// order of first use.
+pub fn missing_textures(shapes: &[Shape], tex_cache: &HashMap<String, i32>) -> Vec<MissingAsset> {
+    let mut missing: Vec<MissingAsset> = Vec::new();
+    for path in shapes.iter().filter_map(|s| s.texture.as_deref()) {
+        let asset = MissingAsset::Texture(path.to_string());
+        if !tex_cache.contains_key(path) && !missing.contains(&asset) {
+            missing.push(asset);
+        }
+    }
+    missing
+}
+
+/// Check the scene for common mistakes. Cheap enough to run after every load or edit.
+pub fn diagnose(shapes: &[Shape], camera: &Camera) -> Vec<SceneIssue> {
+    let mut issues = Vec::new();
+
+    let position = camera.position.to_array();
+    let camera_fields: [(&'static str, &[f32]); 5] = [
+        ("position", &position),
+        ("fov", &[camera.fov]),
+        ("exposure", &[camera.exposure]),
+        ("white point", &[camera.white_point]),
+        ("skybox brightness", &[camera.skybox_brightness]),
+    ];
+    for (field, values) in camera_fields {
+        if !all_finite(values) {
+            issues.push(SceneIssue::NonFiniteCamera(field));
+        }
+    }
+
+    if shapes.is_empty() {
+        issues.push(SceneIssue::EmptyScene);
+        return issues;
+    }
+
+    for (i, shape) in shapes.iter().enumerate() {
+        if let Some(field) = first_non_finite_field(shape) {
+            issues.push(SceneIssue::NonFiniteShape(i, field));
+        }
+    }
+
+    let has_emissive = shapes.iter().any(|s| s.material.is_emissive());
+    let has_textured_skybox = shapes
+        .iter()
+        .any(|s| s.shape_type == ShapeType::Skybox && s.texture.is_some());
+    let sky_lit = camera.skybox_brightness > 0.0
+        || (camera.skybox_mode == SkyboxMode::HdrMap && has_textured_skybox);
+    if !has_emissive && !sky_lit {
+        issues.push(SceneIssue::NoLightSources);
+    }
+    if let Some(idx) = shapes
+        .iter()
+        .position(|s| lights::is_area_light(s) && lights::sampled_area(s).is_none())
+    {
+        issues.push(SceneIssue::UnsampledLight(idx));
+    }
+
+    let eye = camera.position;
+    for (i, shape) in shapes.iter().enumerate() {
+        let closed = !matches!(
+            shape.shape_type,
+            ShapeType::Plane
+                | ShapeType::Skybox
+                | ShapeType::Triangle
+                | ShapeType::Disc
+                | ShapeType::SpotLight
+                | ShapeType::Instance
+        );
+        if closed && !shape.negative {
+            let aabb = shape_aabb(shape);
+            if eye.cmpge(aabb.min).all() && eye.cmple(aabb.max).all() {
+                issues.push(SceneIssue::CameraInsideShape(i));
+            }
+        }
+    }
+
+    let (_, _, forward) = camera.basis_vectors();
+    let mut any_finite = false;
+    let mut any_visible = false;
+    for shape in shapes {
+        match shape.shape_type {
+            ShapeType::Plane => any_visible = true,
+            ShapeType::Skybox | ShapeType::SpotLight => {}
+            _ => {
+                any_finite = true;
+                let aabb = shape_aabb(shape);
+                let in_front = (0..8).any(|c| {
+                    let corner = Vec3::new(
+                        if c & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                        if c & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                        if c & 4 == 0 { aabb.min.z } else { aabb.max.z },
+                    );
+                    (corner - eye).dot(forward) > 0.0
+                });
+                any_visible |= in_front;
+            }
+        }
+        if any_visible {
+            break;
+        }
+    }
+    if any_finite && !any_visible {
+        issues.push(SceneIssue::AllShapesBehindCamera);
+    }
+
+    issues
+}
+
+fn all_finite(values: &[f32]) -> bool {
+    values.iter().all(|v| v.is_finite())
+}
+
+fn first_non_finite_field(shape: &Shape) -> Option<&'static str> {
+    let mat = &shape.material;
+    let fields: [(&'static str, &[f32]); 14] = [
+        ("position", &shape.position),
+        ("normal", &shape.normal),
+        ("rotation", &shape.rotation),
+        ("radius", &[shape.radius, shape.radius2]),
+        ("height", &[shape.height]),
+        ("v0", &shape.v0),
+        ("v1", &shape.v1),
+        ("v2", &shape.v2),
+        ("power", &[shape.power]),
+        ("base color", &mat.base_color),
+        ("emission", &mat.emission),
+        ("emission strength", &[mat.emission_strength]),
+        ("metallic/roughness", &[mat.metallic, mat.roughness]),
+        ("ior/transmission", &[mat.ior, mat.transmission]),
+    ];
+    fields
+        .into_iter()
+        .find(|(_, values)| !all_finite(values))
+        .map(|(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_textures_listed_once() {
+        let textured = |path: &str| Shape {
+            texture: Some(path.to_string()),
+            ..ShapeType::Sphere.new_default_shape([0.0; 3])
+        };
+        let shapes = [
+            textured("gone.png"),
+            textured("wood.png"),
+            textured("gone.png"),
+            ShapeType::Cube.new_default_shape([0.0; 3]),
+        ];
+        let cache = HashMap::from([("wood.png".to_string(), 1)]);
+        assert_eq!(
+            missing_textures(&shapes, &cache),
+            vec![MissingAsset::Texture("gone.png".to_string())]
+        );
+    }
+}
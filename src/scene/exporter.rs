@@ -68,3 +68,71 @@ fn collapse_block_arrays(yaml: &str) -> String {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::camera::Camera;
+    use crate::render::post_process::PostEffect;
+    use crate::scene::scene::{CameraConfig, PostEffectsConfig, SkyboxMode};
+
+    #[test]
+    fn test_camera_settings_survive_save_and_load() {
+        // Every render setting away from its default, as if tweaked in the UI.
+        let cfg = CameraConfig {
+            position: [1.5, 2.0, -7.25],
+            rotation: [12.0, -30.0, 0.0],
+            look_at: None,
+            fov: 45.0,
+            exposure: 1.75,
+            max_bounces: 9,
+            firefly_clamp: 20.0,
+            skybox_mode: SkyboxMode::SunSky,
+            skybox_color: [0.1, 0.2, 0.3],
+            skybox_horizon_color: [0.9, 0.8, 0.7],
+            skybox_brightness: 2.5,
+            sun_azimuth: 120.0,
+            sun_elevation: 15.0,
+            sky_turbidity: 6.0,
+            tone_mapper: 1,
+            white_point: 4.0,
+            fractal_march_steps: 128,
+            transparent_background: true,
+            near_clip: 0.5,
+            far_clip: 250.0,
+        };
+        let scene = Scene {
+            camera: Camera::from_config(&cfg).to_config(),
+            ..Default::default()
+        };
+        let path =
+            std::env::temp_dir().join(format!("pathtracer_save_{}.yaml", std::process::id()));
+        save_scene(&scene, &path).unwrap();
+        let loaded = crate::scene::loader::load_scene(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().camera, cfg);
+    }
+
+    #[test]
+    fn test_post_effects_survive_save_and_load() {
+        let post_effects = PostEffectsConfig {
+            effects: vec![PostEffect::OilPainting, PostEffect::Comic, PostEffect::Fxaa],
+            oil_radius: 6,
+            comic_levels: 3,
+        };
+        let scene = Scene {
+            post_effects: post_effects.clone(),
+            ..Default::default()
+        };
+        let path =
+            std::env::temp_dir().join(format!("pathtracer_post_{}.yaml", std::process::id()));
+        save_scene(&scene, &path).unwrap();
+        let loaded = crate::scene::loader::load_scene(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().post_effects, post_effects);
+
+        // An untouched chain is left out of the file.
+        let yaml = serde_yml::to_string(&Scene::empty()).unwrap();
+        assert!(!yaml.contains("post_effects"));
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+
+use super::scene::SkyboxMode;
+use super::shape::{Shape, ShapeType};
+use crate::accel::aabb::{Aabb, shape_aabb};
+use crate::camera::camera::Camera;
+use crate::constants::HEADLIGHT_SKY_THRESHOLD;
+
+pub const LIGHT_NONE: u32 = 0;
+pub const LIGHT_SPOT: u32 = 1;
+
+/// Delta light in the dedicated light buffer. Must match the WGSL `Light` struct layout.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct GpuLight {
+    pub position: [f32; 3],
+    pub light_type: u32,
+    pub direction: [f32; 3],
+    pub cos_inner: f32,
+    /// Emission color × strength × light group multiplier.
+    pub intensity: [f32; 3],
+    pub cos_outer: f32,
+}
+
+impl GpuLight {
+    /// Build a spot light from a `ShapeType::SpotLight` shape. The outer angle
+    /// is clamped to be at least the inner one so the falloff stays monotonic.
+    pub fn spot(shape: &Shape, multiplier: f32) -> Self {
+        let inner = shape.radius.clamp(0.0, 89.9);
+        let outer = shape.radius2.clamp(inner, 90.0);
+        let direction = Vec3::from(shape.normal)
+            .try_normalize()
+            .unwrap_or(Vec3::NEG_Y);
+        let mat = &shape.material;
+        let strength = mat.emission_strength * multiplier;
+        Self {
+            position: shape.position,
+            light_type: LIGHT_SPOT,
+            direction: direction.into(),
+            cos_inner: inner.to_radians().cos(),
+            intensity: (Vec3::from(mat.emission) * strength).into(),
+            cos_outer: outer.to_radians().cos(),
+        }
+    }
+}
+
+/// Entry of the `light_indices` buffer. NEE picks entry `i` with probability
+/// `pdf` by searching for the first `cdf` above a uniform random number.
+/// Must match the WGSL `AreaLight` struct layout.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct GpuAreaLight {
+    pub shape_index: u32,
+    pub pdf: f32,
+    /// Running sum of `pdf` up to and including this entry; 1 for the last one.
+    pub cdf: f32,
+}
+
+/// Placeholder uploaded when there are no area lights; its zero `pdf` makes
+/// the shader skip light sampling.
+pub const NO_AREA_LIGHTS: [GpuAreaLight; 1] = [GpuAreaLight {
+    shape_index: 0,
+    pdf: 0.0,
+    cdf: 0.0,
+}];
+
+/// Surface area the shader's `sample_area_light` picks points on uniformly,
+/// or `None` for shape types it cannot sample. Those emitters still light
+/// the scene, but only through rays that happen to hit them.
+pub fn sampled_area(shape: &Shape) -> Option<f32> {
+    use std::f32::consts::PI;
+    match shape.shape_type {
+        ShapeType::Sphere => Some(4.0 * PI * shape.radius * shape.radius),
+        ShapeType::Disc => Some(PI * shape.radius * shape.radius),
+        ShapeType::Triangle => {
+            let [v0, v1, v2] = [shape.v0, shape.v1, shape.v2].map(Vec3::from);
+            Some(0.5 * (v1 - v0).cross(v2 - v0).length())
+        }
+        _ => None,
+    }
+}
+
+/// Power used to weight light selection: the area NEE samples over times
+/// the luminance of the emission. Zero for shapes NEE cannot sample.
+pub fn area_light_power(shape: &Shape, emission_strength: f32) -> f32 {
+    let [r, g, b] = shape.material.emission;
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    sampled_area(shape).unwrap_or(0.0) * luminance * emission_strength
+}
+
+/// `(shape index, power)` of every area light NEE can sample, with emission
+/// strengths scaled by `multipliers` (see `emission_multipliers`). Shapes
+/// whose light group is off come out with zero power.
+pub fn area_light_candidates(shapes: &[Shape], multipliers: &[f32]) -> Vec<(u32, f32)> {
+    shapes
+        .iter()
+        .zip(multipliers)
+        .enumerate()
+        .filter(|(_, (shape, _))| is_area_light(shape) && sampled_area(shape).is_some())
+        .map(|(i, (shape, &multiplier))| {
+            let strength = shape.material.emission_strength * multiplier;
+            (i as u32, area_light_power(shape, strength))
+        })
+        .collect()
+}
+
+/// Selection table over `(shape index, power)` pairs, picking each light
+/// proportionally to its power. Lights without positive power are left out.
+pub fn build_area_light_table(candidates: &[(u32, f32)]) -> Vec<GpuAreaLight> {
+    let lit: Vec<(u32, f32)> = candidates
+        .iter()
+        .copied()
+        .filter(|&(_, power)| power > 0.0 && power.is_finite())
+        .collect();
+    let total: f64 = lit.iter().map(|&(_, power)| power as f64).sum();
+    let mut running = 0.0f64;
+    let mut table: Vec<GpuAreaLight> = lit
+        .iter()
+        .map(|&(shape_index, power)| {
+            running += power as f64;
+            GpuAreaLight {
+                shape_index,
+                pdf: (power as f64 / total) as f32,
+                cdf: (running / total) as f32,
+            }
+        })
+        .collect();
+    // Rounding must not leave a gap above the last entry.
+    if let Some(last) = table.last_mut() {
+        last.cdf = 1.0;
+    }
+    table
+}
+
+/// Emissive shapes sharing a name, relit together with one multiplier.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightGroup {
+    pub name: String,
+    pub shape_count: usize,
+    pub multiplier: f32,
+    pub enabled: bool,
+}
+
+impl LightGroup {
+    /// Factor applied to `emission_strength` of every shape in the group.
+    pub fn effective_multiplier(&self) -> f32 {
+        if self.enabled { self.multiplier } else { 0.0 }
+    }
+}
+
+/// Whether a shape is a light (the skybox's emission is its brightness instead).
+pub fn is_light(shape: &Shape) -> bool {
+    shape.material.is_emissive() && shape.shape_type != ShapeType::Skybox
+}
+
+/// Whether a shape is an emissive surface sampled through `light_indices`.
+pub fn is_area_light(shape: &Shape) -> bool {
+    is_light(shape) && shape.shape_type != ShapeType::SpotLight
+}
+
+/// Whether a shape lights the scene: an emissive surface or a spot light.
+pub fn is_light_source(shape: &Shape) -> bool {
+    is_light(shape) || shape.shape_type == ShapeType::SpotLight
+}
+
+/// Whether the camera can look through a shape (`AppState::toggle_light_view`):
+/// a spot light or an emissive shape.
+pub fn can_look_through(shape: &Shape) -> bool {
+    shape.shape_type == ShapeType::SpotLight || is_light(shape)
+}
+
+/// Where to mark each light in the viewport: the first shape of every light
+/// group with the center of the group's bounds, so an emissive mesh gets one
+/// marker. Infinite planes have no place to mark.
+pub fn light_anchors(shapes: &[Shape]) -> Vec<(usize, Vec3)> {
+    let mut anchors: Vec<(usize, Aabb)> = Vec::new();
+    let mut index_by_name: HashMap<String, usize> = HashMap::new();
+    for (i, shape) in shapes.iter().enumerate() {
+        if !is_light_source(shape) || shape.shape_type == ShapeType::Plane {
+            continue;
+        }
+        let bounds = shape_aabb(shape);
+        match index_by_name.entry(light_group_name(shape, i)) {
+            Entry::Occupied(entry) => {
+                let anchor = &mut anchors[*entry.get()].1;
+                *anchor = anchor.union(bounds);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(anchors.len());
+                anchors.push((i, bounds));
+            }
+        }
+    }
+    anchors
+        .into_iter()
+        .map(|(idx, bounds)| (idx, bounds.center()))
+        .collect()
+}
+
+/// Group key for a light: its name, or its label (`"Sphere #3"`) when unnamed.
+pub fn light_group_name(shape: &Shape, idx: usize) -> String {
+    shape.label(idx)
+}
+
+/// Derive light groups from the scene's emissive shapes, in order of first
+/// appearance. Multiplier and on/off state carry over from `previous` by name.
+pub fn collect_light_groups(shapes: &[Shape], previous: &[LightGroup]) -> Vec<LightGroup> {
+    let mut groups: Vec<LightGroup> = Vec::new();
+    let mut index_by_name: HashMap<String, usize> = HashMap::new();
+
+    for (i, shape) in shapes.iter().enumerate() {
+        if !is_light(shape) {
+            continue;
+        }
+        let name = light_group_name(shape, i);
+        if let Some(&g) = index_by_name.get(&name) {
+            groups[g].shape_count += 1;
+            continue;
+        }
+        let (multiplier, enabled) = previous
+            .iter()
+            .find(|g| g.name == name)
+            .map_or((1.0, true), |g| (g.multiplier, g.enabled));
+        index_by_name.insert(name.clone(), groups.len());
+        groups.push(LightGroup {
+            name,
+            shape_count: 1,
+            multiplier,
+            enabled,
+        });
+    }
+
+    groups
+}
+
+/// Per-shape emission multipliers (1.0 for non-lights and ungrouped shapes).
+pub fn emission_multipliers(shapes: &[Shape], groups: &[LightGroup]) -> Vec<f32> {
+    let by_name: HashMap<&str, f32> = groups
+        .iter()
+        .map(|g| (g.name.as_str(), g.effective_multiplier()))
+        .collect();
+    shapes
+        .iter()
+        .enumerate()
+        .map(|(i, shape)| {
+            if !is_light(shape) {
+                return 1.0;
+            }
+            by_name
+                .get(light_group_name(shape, i).as_str())
+                .copied()
+                .unwrap_or(1.0)
+        })
+        .collect()
+}
+
+/// Whether the scene lights itself well enough to edit by: some light group
+/// is on, or the sky is bright. HDR maps are judged by their brightness
+/// setting alone, and a sun above the horizon always counts.
+pub fn scene_is_lit(groups: &[LightGroup], camera: &Camera) -> bool {
+    if groups.iter().any(|g| g.effective_multiplier() > 0.0) {
+        return true;
+    }
+    let sky = match camera.skybox_mode {
+        SkyboxMode::SolidColor => Vec3::from(camera.skybox_color).max_element(),
+        SkyboxMode::Gradient => Vec3::from(camera.skybox_color)
+            .max(Vec3::from(camera.skybox_horizon_color))
+            .max_element(),
+        SkyboxMode::HdrMap => 1.0,
+        SkyboxMode::SunSky if camera.sun_elevation > 0.0 => return true,
+        SkyboxMode::SunSky => 0.0,
+    };
+    sky * camera.skybox_brightness >= HEADLIGHT_SKY_THRESHOLD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_area_light_table_is_proportional_to_power() {
+        let table = build_area_light_table(&[(0, 1.0), (2, 0.0), (5, 3.0)]);
+        assert_eq!(
+            table.iter().map(|l| l.shape_index).collect::<Vec<_>>(),
+            [0, 5]
+        );
+        assert!((table[0].pdf - 0.25).abs() < 1e-6);
+        assert!((table[1].pdf - 0.75).abs() < 1e-6);
+        assert!((table[0].cdf - 0.25).abs() < 1e-6);
+        assert_eq!(table[1].cdf, 1.0);
+        let pdf_sum: f32 = table.iter().map(|l| l.pdf).sum();
+        assert!((pdf_sum - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_zero_emission_has_no_power() {
+        let mut shape: Shape = serde_yml::from_str("type: sphere\nradius: 2.0").unwrap();
+        shape.material.emission = [1.0, 1.0, 1.0];
+        assert!(area_light_power(&shape, 5.0) > 0.0);
+        assert_eq!(area_light_power(&shape, 0.0), 0.0);
+        shape.material.emission = [0.0; 3];
+        assert!(build_area_light_table(&[(0, area_light_power(&shape, 5.0))]).is_empty());
+    }
+
+    #[test]
+    fn test_power_follows_the_emitter_area() {
+        let emissive = |yaml: &str| {
+            let mut shape: Shape = serde_yml::from_str(yaml).unwrap();
+            shape.material.emission = [1.0; 3];
+            shape.material.emission_strength = 1.0;
+            shape
+        };
+        // Right triangle with legs 2 and 3, as written in a scene file.
+        let triangle = emissive("type: triangle\nv0: [0, 0, 0]\nv1: [2, 0, 0]\nv2: [0, 3, 0]");
+        assert!((area_light_power(&triangle, 1.0) - 3.0).abs() < 1e-5);
+        let disc = emissive("type: disc\nradius: 2.0");
+        assert!((area_light_power(&disc, 1.0) - 4.0 * std::f32::consts::PI).abs() < 1e-4);
+        // No sampling for cubes: they light the scene through BSDF rays only.
+        let cube = emissive("type: cube\nradius: 1.0");
+        assert_eq!(area_light_power(&cube, 1.0), 0.0);
+
+        let shapes = [cube, triangle];
+        let candidates = area_light_candidates(&shapes, &[1.0, 1.0]);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].0, 1);
+        assert_eq!(build_area_light_table(&candidates)[0].shape_index, 1);
+    }
+
+    #[test]
+    fn test_shape_made_emissive_joins_the_light_table() {
+        let sphere = ShapeType::Sphere.new_default_shape([0.0; 3]);
+        let mut shapes = vec![sphere.clone(), sphere];
+        let lit = |shapes: &[Shape], groups: &[LightGroup]| -> Vec<u32> {
+            let multipliers = emission_multipliers(shapes, groups);
+            build_area_light_table(&area_light_candidates(shapes, &multipliers))
+                .iter()
+                .map(|light| light.shape_index)
+                .collect()
+        };
+        let groups = collect_light_groups(&shapes, &[]);
+        assert!(lit(&shapes, &groups).is_empty());
+
+        // Emission strength raised from 0 in the object editor: the shape is
+        // a light even before the light groups are refreshed.
+        shapes[1].material.emission = [1.0; 3];
+        shapes[1].material.emission_strength = 3.0;
+        assert_eq!(lit(&shapes, &groups), [1]);
+        let mut groups = collect_light_groups(&shapes, &groups);
+        assert_eq!(lit(&shapes, &groups), [1]);
+
+        groups[0].enabled = false;
+        assert!(lit(&shapes, &groups).is_empty());
+        shapes[1].material.emission_strength = 0.0;
+        assert!(lit(&shapes, &collect_light_groups(&shapes, &groups)).is_empty());
+    }
+
+    #[test]
+    fn test_light_anchors_mark_each_light_once() {
+        let mut lamp = ShapeType::Sphere.new_default_shape([4.0, 0.0, 0.0]);
+        lamp.material.emission = [1.0; 3];
+        lamp.material.emission_strength = 2.0;
+        let mut panel = ShapeType::Triangle.new_default_shape([0.0; 3]);
+        panel.name = Some("panel".into());
+        panel.material = lamp.material.clone();
+        let mut other_half = panel.clone();
+        other_half.v0 = [1.0, 1.0, 0.0];
+        let mut floor = ShapeType::Plane.new_default_shape([0.0; 3]);
+        floor.material = lamp.material.clone();
+        let shapes = [
+            ShapeType::Cube.new_default_shape([0.0; 3]),
+            lamp,
+            panel,
+            floor,
+            other_half,
+            ShapeType::SpotLight.new_default_shape([0.0, 3.0, 0.0]),
+        ];
+
+        let anchors = light_anchors(&shapes);
+        assert_eq!(
+            anchors.iter().map(|&(idx, _)| idx).collect::<Vec<_>>(),
+            [1, 2, 5]
+        );
+        assert!(anchors[0].1.abs_diff_eq(Vec3::new(4.0, 0.0, 0.0), 1e-4));
+        assert!(anchors[1].1.abs_diff_eq(Vec3::new(0.5, 0.5, 0.0), 1e-3));
+    }
+
+    #[test]
+    fn test_scene_is_lit() {
+        let mut camera = Camera {
+            skybox_mode: SkyboxMode::SolidColor,
+            skybox_color: [0.0; 3],
+            ..Default::default()
+        };
+        assert!(!scene_is_lit(&[], &camera));
+
+        let mut lamp = ShapeType::Sphere.new_default_shape([0.0; 3]);
+        lamp.material.emission = [1.0; 3];
+        lamp.material.emission_strength = 5.0;
+        let mut groups = collect_light_groups(&[lamp], &[]);
+        assert!(scene_is_lit(&groups, &camera));
+        groups[0].enabled = false;
+        assert!(!scene_is_lit(&groups, &camera));
+
+        camera.skybox_color = [0.2; 3];
+        assert!(scene_is_lit(&groups, &camera));
+        camera.skybox_brightness = 0.1;
+        assert!(!scene_is_lit(&groups, &camera));
+
+        camera.skybox_mode = SkyboxMode::SunSky;
+        camera.sun_elevation = 30.0;
+        assert!(scene_is_lit(&groups, &camera));
+        camera.sun_elevation = -5.0;
+        assert!(!scene_is_lit(&groups, &camera));
+    }
+}
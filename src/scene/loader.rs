@@ -4,31 +4,54 @@
 use std::fs;
 use std::path::Path;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use serde_yml::Value;
 
 use super::scene::Scene;
-use crate::constants::resolve_resource_path;
+use super::shape::ShapeType;
+
+/// Nesting limit for `!include`, which also stops include cycles.
+const MAX_INCLUDE_DEPTH: usize = 16;
 
 pub fn load_scene(path: &Path) -> Result<Scene> {
     let contents = fs::read_to_string(path)
         .with_context(|| format!("Failed to read scene file: {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
 
     let mut scene: Scene = match path.extension().and_then(|e| e.to_str()) {
         Some("json") => serde_json::from_str(&contents)
             .with_context(|| format!("Failed to parse JSON scene file: {}", path.display()))?,
-        _ => serde_yml::from_str(&contents)
+        _ => parse_yaml_scene(&contents, dir)
             .with_context(|| format!("Failed to parse YAML scene file: {}", path.display()))?,
     };
 
-    // Resolve relative texture / model paths so scenes work from any CWD.
-    let scene_dir = path.parent().unwrap_or(Path::new("."));
-    for shape in &mut scene.shapes {
-        if let Some(ref tex) = shape.texture {
-            shape.texture = Some(resolve_resource_path(scene_dir, tex));
+    // Texture and model paths stay as written and are resolved against
+    // `scene.dir` when loaded, so scenes work from any CWD.
+    scene.dir = dir.to_path_buf();
+    scene.camera_path.sort_by(|a, b| a.time.total_cmp(&b.time));
+    for (idx, shape) in scene.shapes.iter_mut().enumerate() {
+        let radius = shape.radius;
+        if shape.sanitize_radius() {
+            log::warn!(
+                "Shape {idx} ({}) has radius {radius}, using {}",
+                shape.shape_type.label(),
+                shape.radius
+            );
+        }
+        // Older scenes gave ellipsoids only `radius`; unset Y/Z radii were
+        // treated as equal to it.
+        if shape.shape_type == ShapeType::Ellipsoid {
+            if shape.height <= 0.0 {
+                shape.height = shape.radius;
+            }
+            if shape.radius2 <= 0.0 {
+                shape.radius2 = shape.radius;
+            }
+        }
+        // Older skyboxes left the brightness at 0, which rendered at 1.
+        if shape.shape_type == ShapeType::Skybox && shape.material.emission_strength <= 0.0 {
+            shape.material.emission_strength = 1.0;
         }
-    }
-    for model in &mut scene.models {
-        model.path = resolve_resource_path(scene_dir, &model.path);
     }
 
     log::info!(
@@ -39,3 +62,214 @@ pub fn load_scene(path: &Path) -> Result<Scene> {
 
     Ok(scene)
 }
+
+/// Parse a YAML scene, first splicing `!include <file>` entries of its shape
+/// list with the shapes of that file:
+///
+/// ```yaml
+/// shapes:
+///   - !include rigs/cornell_box.yaml
+///   - type: sphere
+///     radius: 1.0
+/// ```
+///
+/// The included file is either a bare list of shapes or a scene whose
+/// `shapes` are taken (its camera and other settings are ignored). Include
+/// paths, and texture paths inside included shapes, are relative to the file
+/// that contains them.
+fn parse_yaml_scene(contents: &str, dir: &Path) -> Result<Scene> {
+    let mut value: Value = serde_yml::from_str(contents)?;
+    if let Some(shapes) = shape_list_mut(&mut value) {
+        expand_includes(shapes, dir, Path::new(""), 0)?;
+    }
+    Ok(serde_yml::from_value(value)?)
+}
+
+fn shape_list_mut(value: &mut Value) -> Option<&mut Vec<Value>> {
+    let Value::Mapping(map) = value else {
+        return None;
+    };
+    let key = ["shapes", "figures"]
+        .into_iter()
+        .find(|key| map.contains_key(*key))?;
+    match map.get_mut(key) {
+        Some(Value::Sequence(shapes)) => Some(shapes),
+        _ => None,
+    }
+}
+
+/// Replace every `!include` in `shapes`, a list from a file in `file_dir`,
+/// with the shapes it names. `prefix` leads from the main scene's directory
+/// to `file_dir` and is prepended to relative texture paths.
+fn expand_includes(
+    shapes: &mut Vec<Value>,
+    file_dir: &Path,
+    prefix: &Path,
+    depth: usize,
+) -> Result<()> {
+    let mut expanded = Vec::with_capacity(shapes.len());
+    for item in shapes.drain(..) {
+        let Value::Tagged(tagged) = item else {
+            let mut shape = item;
+            rebase_texture(&mut shape, prefix);
+            expanded.push(shape);
+            continue;
+        };
+        if tagged.tag != "include" {
+            bail!("Unknown tag {} in shape list", tagged.tag);
+        }
+        let Value::String(file) = &tagged.value else {
+            bail!("!include expects a file path");
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            bail!("!include {file}: nested more than {MAX_INCLUDE_DEPTH} levels deep");
+        }
+        let path = file_dir.join(file);
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read included file: {}", path.display()))?;
+        let mut included: Value = serde_yml::from_str(&text)
+            .with_context(|| format!("Failed to parse included file: {}", path.display()))?;
+        let mut included_shapes = match &mut included {
+            Value::Sequence(list) => std::mem::take(list),
+            other => shape_list_mut(other)
+                .map(std::mem::take)
+                .unwrap_or_default(),
+        };
+        let file_parent = Path::new(file).parent().unwrap_or(Path::new(""));
+        expand_includes(
+            &mut included_shapes,
+            path.parent().unwrap_or(Path::new(".")),
+            &prefix.join(file_parent),
+            depth + 1,
+        )
+        .with_context(|| format!("In included file: {}", path.display()))?;
+        expanded.extend(included_shapes);
+    }
+    *shapes = expanded;
+    Ok(())
+}
+
+fn rebase_texture(shape: &mut Value, prefix: &Path) {
+    if prefix.as_os_str().is_empty() {
+        return;
+    }
+    if let Some(Value::String(texture)) = shape.get_mut("texture")
+        && Path::new(texture.as_str()).is_relative()
+    {
+        *texture = prefix.join(&*texture).to_string_lossy().into_owned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppState;
+
+    #[test]
+    fn test_scene_relative_textures_resolve() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/scenes/relative_texture.yaml");
+        let scene = load_scene(&path).unwrap();
+        assert_eq!(scene.dir, path.parent().unwrap());
+
+        // Paths stay as written, so they don't resolve from the CWD...
+        let tex = scene.shapes[0].texture.as_deref().unwrap();
+        assert_eq!(tex, "../textures/fabric.jpg");
+        assert!(!Path::new(tex).exists());
+
+        // ...but every texture loads relative to the scene file.
+        let (_, cache) = AppState::build_texture_atlas(&scene.shapes, &scene.dir);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_scene_relative_model_resolves() {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/scenes/relative_model.yaml");
+        let scene = load_scene(&path).unwrap();
+        assert_eq!(scene.models[0].path, "../models/box/box.obj");
+        assert!(!Path::new(&scene.models[0].path).exists());
+
+        let (shapes, missing) = AppState::load_scene_shapes(&scene);
+        assert!(missing.is_empty());
+        assert!(shapes.len() > scene.shapes.len(), "model triangles missing");
+    }
+
+    #[test]
+    fn test_include_splices_shapes() {
+        let dir = std::env::temp_dir().join(format!("pathtracer_include_{}", std::process::id()));
+        fs::create_dir_all(dir.join("rigs")).unwrap();
+        fs::write(
+            dir.join("rigs/lights.yaml"),
+            "\
+- type: sphere
+  radius: 0.5
+  texture: glow.png
+- type: sphere
+  radius: 0.25
+",
+        )
+        .unwrap();
+        let scene_path = dir.join("main.yaml");
+        fs::write(
+            &scene_path,
+            "\
+shapes:
+  - type: plane
+  - !include rigs/lights.yaml
+  - type: cube
+",
+        )
+        .unwrap();
+
+        let scene = load_scene(&scene_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let types: Vec<_> = scene.shapes.iter().map(|s| s.shape_type).collect();
+        assert_eq!(
+            types,
+            [
+                ShapeType::Plane,
+                ShapeType::Sphere,
+                ShapeType::Sphere,
+                ShapeType::Cube
+            ]
+        );
+        assert_eq!(scene.shapes[1].radius, 0.5);
+        let texture = scene.shapes[1].texture.as_deref().unwrap();
+        assert_eq!(Path::new(texture), Path::new("rigs/glow.png"));
+    }
+
+    #[test]
+    fn test_imported_identical_materials_share_one_entry() {
+        let dir = std::env::temp_dir().join(format!("pathtracer_dedup_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let scene_path = dir.join("spheres.yaml");
+        let spheres: String = (0..100)
+            .map(|i| {
+                format!(
+                    "  - type: sphere\n    position: [{i}, 0, 0]\n    \
+                     material: {{ base_color: [0.2, 0.4, 0.6], roughness: 0.3 }}\n"
+                )
+            })
+            .collect();
+        fs::write(&scene_path, format!("shapes:\n{spheres}")).unwrap();
+        let scene = load_scene(&scene_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Imported next to a shape of its own material, as `import_scene` does.
+        let mut shapes = vec![ShapeType::Cube.new_default_shape([0.0; 3])];
+        shapes.extend(AppState::load_scene_shapes(&scene).0);
+        assert_eq!(shapes.len(), 101);
+        let (gpu_shapes, materials, _, _) = AppState::build_gpu_data(
+            &shapes,
+            &Default::default(),
+            &[],
+            &Default::default(),
+            &[],
+            None,
+        );
+        assert_eq!(materials.len(), 2);
+        assert!(gpu_shapes[1..].iter().all(|s| s.material_idx == 1));
+        assert_eq!(materials[1].base_color, [0.2, 0.4, 0.6]);
+    }
+}
@@ -1,9 +1,13 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::collections::HashMap;
+
 use bytemuck::{Pod, Zeroable};
 use serde::{Deserialize, Serialize};
 
+use crate::constants::MAX_MATERIAL_BOUNCES;
+
 /// PBR metallic-roughness material (Cook-Torrance / GGX).
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Material {
@@ -28,6 +32,11 @@ pub struct Material {
     #[serde(default, skip_serializing_if = "is_zero_f32")]
     pub emission_strength: f32,
 
+    /// Multiply the emission by the shape's texture, so textured lights glow
+    /// with their pattern instead of a uniform color.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub emission_uses_texture: bool,
+
     #[serde(default = "default_ior", skip_serializing_if = "is_default_ior")]
     pub ior: f32,
 
@@ -36,6 +45,36 @@ pub struct Material {
 
     #[serde(default = "default_no_texture", skip_serializing_if = "is_no_texture")]
     pub texture_id: i32,
+
+    /// Bounces left for a path once it scatters off this material, counting
+    /// this one, up to `MAX_MATERIAL_BOUNCES`. It replaces whatever remains of
+    /// the global max bounces, so it can deepen paths as well as cut them
+    /// short; 0 keeps the global budget.
+    #[serde(default, skip_serializing_if = "is_zero_u32")]
+    pub max_bounces: u32,
+
+    /// Block light sampling rays. Off, the shape stays visible but lights
+    /// shine through it.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub casts_shadows: bool,
+
+    /// Darken where other shapes block the direct light. Off, the shape is
+    /// lit as if nothing stood between it and each light.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub receives_shadows: bool,
+
+    /// Invisible except for the shadows falling on it, for compositing
+    /// rendered shapes onto photos. Never casts shadows itself.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub shadow_catcher: bool,
+
+    /// Triangles are hit only from the side their winding faces (counter-
+    /// clockwise = front). Speeds up closed meshes and avoids self-hits on
+    /// them; leave off for thin sheets and glass, which are seen from behind.
+    /// On an instance it applies to every triangle of the model, on top of
+    /// the model's own materials.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub cull_backfaces: bool,
 }
 
 fn default_base_color() -> [f32; 3] {
@@ -54,10 +93,26 @@ fn default_no_texture() -> i32 {
     -1
 }
 
+fn default_true() -> bool {
+    true
+}
+
 fn is_zero_f32(v: &f32) -> bool {
     *v == 0.0
 }
 
+fn is_zero_u32(v: &u32) -> bool {
+    *v == 0
+}
+
+fn is_false(v: &bool) -> bool {
+    !*v
+}
+
+fn is_true(v: &bool) -> bool {
+    *v
+}
+
 fn is_zero_vec3(v: &[f32; 3]) -> bool {
     v[0] == 0.0 && v[1] == 0.0 && v[2] == 0.0
 }
@@ -86,14 +141,43 @@ impl Default for Material {
             roughness: default_roughness(),
             emission: [0.0; 3],
             emission_strength: 0.0,
+            emission_uses_texture: false,
             ior: default_ior(),
             transmission: 0.0,
             texture_id: default_no_texture(),
+            max_bounces: 0,
+            casts_shadows: true,
+            receives_shadows: true,
+            shadow_catcher: false,
+            cull_backfaces: false,
         }
     }
 }
 
+/// Common indices of refraction, in increasing order, for picking `ior` by
+/// name. Values are at visible wavelengths.
+pub const IOR_PRESETS: &[(&str, f32)] = &[
+    ("Air", 1.0),
+    ("Ice", 1.31),
+    ("Water", 1.333),
+    ("Acrylic", 1.49),
+    ("Glass", 1.5),
+    ("Amber", 1.55),
+    ("Flint Glass", 1.62),
+    ("Sapphire", 1.77),
+    ("Diamond", 2.42),
+    ("Moissanite", 2.65),
+];
+
 impl Material {
+    /// Name of the `IOR_PRESETS` entry `ior` matches, if any.
+    pub fn ior_preset_name(&self) -> Option<&'static str> {
+        IOR_PRESETS
+            .iter()
+            .find(|(_, ior)| (ior - self.ior).abs() < 1e-3)
+            .map(|&(name, _)| name)
+    }
+
     pub fn is_emissive(&self) -> bool {
         self.emission_strength > 0.0
             && (self.emission[0] > 0.0 || self.emission[1] > 0.0 || self.emission[2] > 0.0)
@@ -116,8 +200,20 @@ pub struct GpuMaterial {
     pub ior: f32,
     pub transmission: f32,
     pub texture_id: i32,
+    pub max_bounces: u32,
+    pub emission_uses_texture: u32,
+    /// `MATERIAL_NO_CAST_SHADOW | MATERIAL_NO_RECEIVE_SHADOW | MATERIAL_SHADOW_CATCHER` bits.
+    pub shadow_flags: u32,
+    pub cull_backfaces: u32,
 }
 
+/// `GpuMaterial::shadow_flags` bit: shadow rays pass through the shape.
+pub const MATERIAL_NO_CAST_SHADOW: u32 = 1;
+/// `GpuMaterial::shadow_flags` bit: light sampling from the shape skips the shadow test.
+pub const MATERIAL_NO_RECEIVE_SHADOW: u32 = 2;
+/// `GpuMaterial::shadow_flags` bit: the surface only shows the shadows it receives.
+pub const MATERIAL_SHADOW_CATCHER: u32 = 4;
+
 impl From<&Material> for GpuMaterial {
     fn from(mat: &Material) -> Self {
         Self {
@@ -129,6 +225,46 @@ impl From<&Material> for GpuMaterial {
             ior: mat.ior,
             transmission: mat.transmission,
             texture_id: mat.texture_id,
+            max_bounces: mat.max_bounces.min(MAX_MATERIAL_BOUNCES),
+            emission_uses_texture: mat.emission_uses_texture as u32,
+            shadow_flags: if mat.casts_shadows {
+                0
+            } else {
+                MATERIAL_NO_CAST_SHADOW
+            } | if mat.receives_shadows {
+                0
+            } else {
+                MATERIAL_NO_RECEIVE_SHADOW
+            } | if mat.shadow_catcher {
+                MATERIAL_SHADOW_CATCHER
+            } else {
+                0
+            },
+            cull_backfaces: mat.cull_backfaces as u32,
         }
     }
 }
+
+/// The material buffer under construction. Identical materials share one
+/// entry, so scenes merged from many files (or meshes of thousands of
+/// same-material triangles) keep the buffer small.
+#[derive(Default)]
+pub struct MaterialTable {
+    materials: Vec<GpuMaterial>,
+    /// Entry index by the material's bits.
+    lookup: HashMap<[u32; 16], u32>,
+}
+
+impl MaterialTable {
+    /// Index of `mat` in the table, adding it if no identical one is there.
+    pub fn insert(&mut self, mat: GpuMaterial) -> u32 {
+        *self.lookup.entry(bytemuck::cast(mat)).or_insert_with(|| {
+            self.materials.push(mat);
+            self.materials.len() as u32 - 1
+        })
+    }
+
+    pub fn into_vec(self) -> Vec<GpuMaterial> {
+        self.materials
+    }
+}
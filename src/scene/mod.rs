@@ -1,7 +1,9 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod diagnostics;
 pub mod exporter;
+pub mod lights;
 pub mod loader;
 pub mod material;
 #[allow(clippy::module_inception)]
@@ -1,14 +1,20 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::path::{Component, Path, PathBuf};
+
+use glam::Quat;
 use serde::{Deserialize, Serialize};
 
 use super::shape::Shape;
 use crate::constants::{
-    DEFAULT_CAMERA_POSITION, DEFAULT_EXPOSURE, DEFAULT_FIREFLY_CLAMP, DEFAULT_FOV,
-    DEFAULT_FRACTAL_MARCH_STEPS, DEFAULT_MAX_BOUNCES, DEFAULT_SKYBOX_BRIGHTNESS,
-    DEFAULT_SKYBOX_COLOR, DEFAULT_TONE_MAPPER,
+    DEFAULT_CAMERA_POSITION, DEFAULT_COMIC_LEVELS, DEFAULT_EXPOSURE, DEFAULT_FAR_CLIP,
+    DEFAULT_FIREFLY_CLAMP, DEFAULT_FOV, DEFAULT_FRACTAL_MARCH_STEPS, DEFAULT_MAX_BOUNCES,
+    DEFAULT_NEAR_CLIP, DEFAULT_OIL_RADIUS, DEFAULT_SKY_TURBIDITY, DEFAULT_SKYBOX_BRIGHTNESS,
+    DEFAULT_SKYBOX_COLOR, DEFAULT_SKYBOX_HORIZON_COLOR, DEFAULT_SUN_AZIMUTH, DEFAULT_SUN_ELEVATION,
+    DEFAULT_TONE_MAPPER, DEFAULT_WHITE_POINT,
 };
+use crate::render::post_process::PostEffect;
 
 fn is_zero_vec3(v: &[f32; 3]) -> bool {
     *v == [0.0, 0.0, 0.0]
@@ -50,33 +56,120 @@ serde_default_fns!(
     [f32; 3],
     DEFAULT_SKYBOX_COLOR
 );
+serde_default_fns!(
+    default_skybox_horizon_color,
+    is_default_skybox_horizon_color,
+    [f32; 3],
+    DEFAULT_SKYBOX_HORIZON_COLOR
+);
+serde_default_fns!(
+    default_skybox_mode,
+    is_default_skybox_mode,
+    SkyboxMode,
+    SkyboxMode::HdrMap
+);
 serde_default_fns!(
     default_skybox_brightness,
     is_default_skybox_brightness,
     f32,
     DEFAULT_SKYBOX_BRIGHTNESS
 );
+serde_default_fns!(
+    default_sun_azimuth,
+    is_default_sun_azimuth,
+    f32,
+    DEFAULT_SUN_AZIMUTH
+);
+serde_default_fns!(
+    default_sun_elevation,
+    is_default_sun_elevation,
+    f32,
+    DEFAULT_SUN_ELEVATION
+);
+serde_default_fns!(
+    default_sky_turbidity,
+    is_default_sky_turbidity,
+    f32,
+    DEFAULT_SKY_TURBIDITY
+);
 serde_default_fns!(
     default_tone_mapper,
     is_default_tone_mapper,
     u32,
     DEFAULT_TONE_MAPPER
 );
+serde_default_fns!(
+    default_white_point,
+    is_default_white_point,
+    f32,
+    DEFAULT_WHITE_POINT
+);
 serde_default_fns!(
     default_fractal_march_steps,
     is_default_fractal_march_steps,
     u32,
     DEFAULT_FRACTAL_MARCH_STEPS
 );
+serde_default_fns!(
+    default_near_clip,
+    is_default_near_clip,
+    f32,
+    DEFAULT_NEAR_CLIP
+);
+serde_default_fns!(default_far_clip, is_default_far_clip, f32, DEFAULT_FAR_CLIP);
+
+/// How rays that escape the scene are shaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkyboxMode {
+    /// Uniform `skybox_color`.
+    SolidColor,
+    /// Blend from `skybox_horizon_color` straight down to `skybox_color` at the zenith.
+    Gradient,
+    /// Equirectangular texture of the scene's Skybox shape; falls back to `Gradient`
+    /// when the scene has no textured skybox.
+    HdrMap,
+    /// Preetham analytic daylight with a sun disc that also acts as a directional light.
+    SunSky,
+}
+
+impl SkyboxMode {
+    pub fn as_u32(self) -> u32 {
+        match self {
+            Self::SolidColor => 0,
+            Self::Gradient => 1,
+            Self::HdrMap => 2,
+            Self::SunSky => 3,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::SolidColor => "Solid Color",
+            Self::Gradient => "Gradient",
+            Self::HdrMap => "HDR Map",
+            Self::SunSky => "Sun & Sky",
+        }
+    }
+
+    pub const ALL: &[Self] = &[Self::SolidColor, Self::Gradient, Self::HdrMap, Self::SunSky];
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CameraConfig {
     #[serde(default, skip_serializing_if = "is_zero_vec3")]
     pub position: [f32; 3],
 
+    /// `[pitch, yaw, roll]` in degrees; roll is ignored. Zero looks down +Z,
+    /// positive pitch tilts down, positive yaw turns towards +X.
     #[serde(default, skip_serializing_if = "is_zero_vec3")]
     pub rotation: [f32; 3],
 
+    /// Point to face from `position`; overrides `rotation` when set. Never
+    /// written back: saving emits the equivalent `rotation`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub look_at: Option<[f32; 3]>,
+
     #[serde(default = "default_fov", skip_serializing_if = "is_default_fov")]
     pub fov: f32,
 
@@ -98,29 +191,91 @@ pub struct CameraConfig {
     )]
     pub firefly_clamp: f32,
 
+    #[serde(
+        default = "default_skybox_mode",
+        skip_serializing_if = "is_default_skybox_mode"
+    )]
+    pub skybox_mode: SkyboxMode,
+
+    /// Solid color, or the zenith color in gradient mode.
     #[serde(
         default = "default_skybox_color",
         skip_serializing_if = "is_default_skybox_color"
     )]
     pub skybox_color: [f32; 3],
 
+    #[serde(
+        default = "default_skybox_horizon_color",
+        skip_serializing_if = "is_default_skybox_horizon_color"
+    )]
+    pub skybox_horizon_color: [f32; 3],
+
     #[serde(
         default = "default_skybox_brightness",
         skip_serializing_if = "is_default_skybox_brightness"
     )]
     pub skybox_brightness: f32,
 
+    /// Sun azimuth in degrees (sun & sky mode).
+    #[serde(
+        default = "default_sun_azimuth",
+        skip_serializing_if = "is_default_sun_azimuth"
+    )]
+    pub sun_azimuth: f32,
+
+    /// Sun elevation above the horizon in degrees (sun & sky mode).
+    #[serde(
+        default = "default_sun_elevation",
+        skip_serializing_if = "is_default_sun_elevation"
+    )]
+    pub sun_elevation: f32,
+
+    /// Atmospheric turbidity (2 = clear, 10 = hazy) for sun & sky mode.
+    #[serde(
+        default = "default_sky_turbidity",
+        skip_serializing_if = "is_default_sky_turbidity"
+    )]
+    pub sky_turbidity: f32,
+
     #[serde(
         default = "default_tone_mapper",
         skip_serializing_if = "is_default_tone_mapper"
     )]
     pub tone_mapper: u32,
 
+    /// Exposed radiance that maps to pure white under ACES and Reinhard;
+    /// 0 keeps each curve's own shoulder.
+    #[serde(
+        default = "default_white_point",
+        skip_serializing_if = "is_default_white_point"
+    )]
+    pub white_point: f32,
+
     #[serde(
         default = "default_fractal_march_steps",
         skip_serializing_if = "is_default_fractal_march_steps"
     )]
     pub fractal_march_steps: u32,
+
+    /// Camera rays that miss every shape leave the pixel transparent instead
+    /// of showing the sky.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub transparent_background: bool,
+
+    /// Distance along a camera ray before which nothing is hit.
+    #[serde(
+        default = "default_near_clip",
+        skip_serializing_if = "is_default_near_clip"
+    )]
+    pub near_clip: f32,
+
+    /// Distance along a camera ray beyond which hits are ignored and the sky
+    /// shows instead; 0 = no far clip.
+    #[serde(
+        default = "default_far_clip",
+        skip_serializing_if = "is_default_far_clip"
+    )]
+    pub far_clip: f32,
 }
 
 impl Default for CameraConfig {
@@ -128,19 +283,65 @@ impl Default for CameraConfig {
         Self {
             position: DEFAULT_CAMERA_POSITION,
             rotation: [0.0, 0.0, 0.0],
+            look_at: None,
             fov: DEFAULT_FOV,
             exposure: DEFAULT_EXPOSURE,
             max_bounces: DEFAULT_MAX_BOUNCES,
             firefly_clamp: DEFAULT_FIREFLY_CLAMP,
+            skybox_mode: SkyboxMode::HdrMap,
             skybox_color: DEFAULT_SKYBOX_COLOR,
+            skybox_horizon_color: DEFAULT_SKYBOX_HORIZON_COLOR,
             skybox_brightness: DEFAULT_SKYBOX_BRIGHTNESS,
+            sun_azimuth: DEFAULT_SUN_AZIMUTH,
+            sun_elevation: DEFAULT_SUN_ELEVATION,
+            sky_turbidity: DEFAULT_SKY_TURBIDITY,
             tone_mapper: DEFAULT_TONE_MAPPER,
+            white_point: DEFAULT_WHITE_POINT,
             fractal_march_steps: DEFAULT_FRACTAL_MARCH_STEPS,
+            transparent_background: false,
+            near_clip: DEFAULT_NEAR_CLIP,
+            far_clip: DEFAULT_FAR_CLIP,
+        }
+    }
+}
+
+/// Which axis of an imported model points up. Models are rotated on load so
+/// that axis becomes the renderer's +Y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpAxis {
+    X,
+    #[default]
+    Y,
+    Z,
+}
+
+impl UpAxis {
+    /// Rotation taking this axis onto +Y.
+    pub fn to_y_up(self) -> Quat {
+        match self {
+            Self::X => Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+            Self::Y => Quat::IDENTITY,
+            Self::Z => Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::X => "X-up",
+            Self::Y => "Y-up",
+            Self::Z => "Z-up",
         }
     }
+
+    pub const ALL: &[Self] = &[Self::Y, Self::Z, Self::X];
+}
+
+fn is_y_up(axis: &UpAxis) -> bool {
+    *axis == UpAxis::Y
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ModelRef {
     pub path: String,
 
@@ -153,6 +354,10 @@ pub struct ModelRef {
     #[serde(default = "default_scale")]
     pub scale: f32,
 
+    /// Up axis of this model's file; defaults to the scene's `up_axis`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub up_axis: Option<UpAxis>,
+
     #[serde(default)]
     pub material: super::material::Material,
 }
@@ -166,15 +371,238 @@ pub struct Scene {
     #[serde(default)]
     pub camera: CameraConfig,
 
+    /// Additional named framings of the scene, selectable from the toolbar.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cameras: Vec<NamedCamera>,
+
+    /// Keyframes of a camera fly-through, in time order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub camera_path: Vec<CameraKeyframe>,
+
     #[serde(default, alias = "figures")]
     pub shapes: Vec<Shape>,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub models: Vec<ModelRef>,
+
+    /// Up axis assumed for models loaded into this scene, including ones
+    /// imported from the UI.
+    #[serde(default, skip_serializing_if = "is_y_up")]
+    pub up_axis: UpAxis,
+
+    #[serde(default, skip_serializing_if = "PostEffectsConfig::is_default")]
+    pub post_effects: PostEffectsConfig,
+
+    /// Directory the scene file was loaded from. Shape texture paths are kept
+    /// as written and resolved against it when textures are loaded, so saved
+    /// scenes stay portable.
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+/// One of a scene's additional cameras.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedCamera {
+    pub name: String,
+    #[serde(default)]
+    pub camera: CameraConfig,
+}
+
+/// Camera placement at `time` seconds along the scene's camera path. Only
+/// the position, orientation and FOV of `camera` are animated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    #[serde(default)]
+    pub camera: CameraConfig,
+}
+
+/// Post-process chain applied to the final image, in order, with the
+/// parameters of the effects that have any.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PostEffectsConfig {
+    pub effects: Vec<PostEffect>,
+    pub oil_radius: u32,
+    pub comic_levels: u32,
+}
+
+impl Default for PostEffectsConfig {
+    fn default() -> Self {
+        Self {
+            effects: Vec::new(),
+            oil_radius: DEFAULT_OIL_RADIUS,
+            comic_levels: DEFAULT_COMIC_LEVELS,
+        }
+    }
+}
+
+impl PostEffectsConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 impl Scene {
     pub fn empty() -> Self {
         Self::default()
     }
+
+    /// Camera `idx` of the scene: 0 is the primary `camera`, followed by
+    /// `cameras` in order.
+    pub fn camera_config(&self, idx: usize) -> Option<&CameraConfig> {
+        match idx {
+            0 => Some(&self.camera),
+            _ => self.cameras.get(idx - 1).map(|named| &named.camera),
+        }
+    }
+
+    pub fn camera_config_mut(&mut self, idx: usize) -> Option<&mut CameraConfig> {
+        match idx {
+            0 => Some(&mut self.camera),
+            _ => self.cameras.get_mut(idx - 1).map(|named| &mut named.camera),
+        }
+    }
+
+    /// Display names of the cameras in `camera_config` order.
+    pub fn camera_names(&self) -> Vec<String> {
+        std::iter::once("Main".to_string())
+            .chain(self.cameras.iter().map(|named| named.name.clone()))
+            .collect()
+    }
+
+    /// Move the scene to `dir` (where it is about to be saved), rewriting
+    /// relative texture and model paths so they still point at the same
+    /// files. Paths that don't resolve against the old `dir` (absolute ones,
+    /// or those found from the CWD or the app's data directory) stay as written.
+    pub fn rebase_asset_paths(&mut self, dir: &Path) {
+        let paths = self
+            .shapes
+            .iter_mut()
+            .flat_map(|shape| [&mut shape.texture, &mut shape.model])
+            .flatten()
+            .chain(self.models.iter_mut().map(|model| &mut model.path));
+        for path in paths {
+            if let Some(rebased) = rebase_path(&self.dir, dir, path) {
+                *path = rebased;
+            }
+        }
+        self.dir = dir.to_path_buf();
+    }
+}
+
+/// `path`, written relative to `from`, as seen from `to`.
+fn rebase_path(from: &Path, to: &Path, path: &str) -> Option<String> {
+    if Path::new(path).is_absolute() {
+        return None;
+    }
+    let target = from.join(path).canonicalize().ok()?;
+    let rebased = match to.canonicalize() {
+        Ok(to) => relative_path(&to, &target),
+        Err(_) => target,
+    };
+    Some(rebased.to_string_lossy().into_owned())
+}
+
+/// `target` relative to the directory `base`; both must be absolute. Falls
+/// back to `target` when they share no root (e.g. different drives).
+fn relative_path(base: &Path, target: &Path) -> PathBuf {
+    let base: Vec<Component> = base.components().collect();
+    let target_parts: Vec<Component> = target.components().collect();
+    let common = base
+        .iter()
+        .zip(&target_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return target.to_path_buf();
+    }
+    std::iter::repeat_n(Component::ParentDir, base.len() - common)
+        .chain(target_parts[common..].iter().copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn test_up_axis_maps_to_y() {
+        for axis in UpAxis::ALL {
+            let up = match axis {
+                UpAxis::X => Vec3::X,
+                UpAxis::Y => Vec3::Y,
+                UpAxis::Z => Vec3::Z,
+            };
+            let rotated = axis.to_y_up() * up;
+            assert!(rotated.abs_diff_eq(Vec3::Y, 1e-6), "{axis:?}: {rotated}");
+        }
+    }
+
+    #[test]
+    fn test_model_up_axis_defaults_to_scene() {
+        let scene: Scene = serde_yml::from_str(
+            "up_axis: z\nmodels:\n  - path: a.obj\n  - path: b.obj\n    up_axis: y\n",
+        )
+        .unwrap();
+        assert_eq!(scene.up_axis, UpAxis::Z);
+        assert_eq!(scene.models[0].up_axis, None);
+        assert_eq!(scene.models[1].up_axis, Some(UpAxis::Y));
+        assert!(
+            !serde_yml::to_string(&Scene::empty())
+                .unwrap()
+                .contains("up_axis")
+        );
+    }
+
+    #[test]
+    fn test_rebase_asset_paths_follow_the_scene() {
+        let root = std::env::temp_dir().join(format!("pathtracer_rebase_{}", std::process::id()));
+        let old_dir = root.join("scenes");
+        let new_dir = root.join("out/saved");
+        std::fs::create_dir_all(old_dir.join("tex")).unwrap();
+        std::fs::create_dir_all(&new_dir).unwrap();
+        std::fs::write(old_dir.join("tex/wood.png"), b"").unwrap();
+        std::fs::write(root.join("teapot.obj"), b"").unwrap();
+
+        let mut scene: Scene = serde_yml::from_str(
+            "shapes:\n  - type: sphere\n    texture: tex/wood.png\n  \
+             - type: sphere\n    texture: missing.png\n\
+             models:\n  - path: ../teapot.obj\n",
+        )
+        .unwrap();
+        scene.dir = old_dir.clone();
+        scene.rebase_asset_paths(&new_dir);
+
+        let sep = std::path::MAIN_SEPARATOR;
+        let texture = scene.shapes[0].texture.as_deref().unwrap();
+        assert_eq!(
+            texture,
+            format!("..{sep}..{sep}scenes{sep}tex{sep}wood.png")
+        );
+        assert!(new_dir.join(texture).exists());
+        assert_eq!(scene.shapes[1].texture.as_deref(), Some("missing.png"));
+        assert_eq!(scene.models[0].path, format!("..{sep}..{sep}teapot.obj"));
+        assert_eq!(scene.dir, new_dir);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_named_cameras() {
+        let scene: Scene = serde_yml::from_str(
+            "camera:\n  fov: 50\ncameras:\n  - name: Top\n    camera:\n      position: [0, 10, 0]\n",
+        )
+        .unwrap();
+        assert_eq!(scene.camera_names(), ["Main", "Top"]);
+        assert_eq!(scene.camera_config(0).unwrap().fov, 50.0);
+        assert_eq!(scene.camera_config(1).unwrap().position, [0.0, 10.0, 0.0]);
+        assert_eq!(scene.camera_config(2), None);
+        assert!(
+            !serde_yml::to_string(&Scene::empty())
+                .unwrap()
+                .contains("cameras")
+        );
+    }
 }
@@ -2,9 +2,11 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use bytemuck::{Pod, Zeroable};
+use glam::{Affine3A, EulerRot, Quat, Vec3};
 use serde::{Deserialize, Serialize};
 
 use super::material::Material;
+use crate::constants::MIN_SHAPE_RADIUS;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -27,6 +29,12 @@ pub enum ShapeType {
     Mebius = 14,
     Pyramid = 15,
     Tetrahedron = 16,
+    /// Delta spot light: `normal` is the direction, `radius`/`radius2` the
+    /// inner/outer cone half-angles in degrees.
+    SpotLight = 17,
+    /// A placed copy of the OBJ model at `model`: `position` and `rotation`
+    /// move it, `radius` scales it uniformly.
+    Instance = 18,
 }
 
 impl ShapeType {
@@ -53,9 +61,94 @@ impl ShapeType {
             Self::Mebius => "Mebius",
             Self::Pyramid => "Pyramid",
             Self::Tetrahedron => "Tetrahedron",
+            Self::SpotLight => "Spot Light",
+            Self::Instance => "Instance",
         }
     }
 
+    /// A new shape of this type at `position` with sensible geometry and a
+    /// default material, as added from the UI. Planes and skyboxes ignore
+    /// `position` and sit at the origin.
+    pub fn new_default_shape(self, position: [f32; 3]) -> Shape {
+        let mut shape = Shape {
+            name: None,
+            shape_type: self,
+            negative: false,
+            position,
+            normal: default_normal(),
+            radius: default_radius(),
+            radius2: 0.3,
+            height: 2.0,
+            rotation: [0.0, 0.0, 0.0],
+            v0: [0.0, 0.0, 0.0],
+            v1: [1.0, 0.0, 0.0],
+            v2: [0.0, 1.0, 0.0],
+            power: default_power(),
+            max_iterations: default_max_iterations(),
+            march_steps: 0,
+            orbit_trap: false,
+            texture: None,
+            texture_scale: None,
+            uv0: [0.0, 0.0],
+            uv1: [0.0, 0.0],
+            uv2: [0.0, 0.0],
+            material: Material::default(),
+            vertex_colors: None,
+            model: None,
+        };
+
+        match self {
+            Self::Plane => {
+                shape.position = [0.0, 0.0, 0.0];
+            }
+            Self::Mandelbulb => {
+                shape.radius = 1.5;
+            }
+            Self::Julia => {
+                shape.radius = 1.5;
+                shape.rotation = [-0.8, 0.156, 0.0]; // Julia C.xyz
+                shape.radius2 = -0.046; // Julia C.w
+                shape.max_iterations = 14;
+            }
+            Self::Ellipsoid => {
+                shape.height = 1.5;
+                shape.radius2 = 0.75;
+            }
+            Self::Skybox => {
+                // Untinted, unit brightness; the texture is picked in the object editor.
+                shape.position = [0.0, 0.0, 0.0];
+                shape.material.base_color = [1.0, 1.0, 1.0];
+                shape.material.emission_strength = 1.0;
+            }
+            Self::SpotLight => {
+                shape.normal = [0.0, -1.0, 0.0];
+                shape.radius = 20.0; // inner cone angle
+                shape.radius2 = 30.0; // outer cone angle
+                shape.material.emission = [1.0, 1.0, 1.0];
+                shape.material.emission_strength = 20.0;
+            }
+            _ => {}
+        }
+        shape
+    }
+
+    /// Shapes that face one way, along `normal` or by triangle winding.
+    pub fn has_flippable_normal(self) -> bool {
+        matches!(self, Self::Plane | Self::Disc | Self::Triangle)
+    }
+
+    /// Shapes whose sphere-marching step limit follows the scene's
+    /// `fractal_march_steps` and can be overridden per shape.
+    pub fn has_march_steps(self) -> bool {
+        matches!(self, Self::Mandelbulb | Self::Julia | Self::Mebius)
+    }
+
+    /// Shapes whose size is `radius`, which must then be positive. Planes and
+    /// triangles ignore it; spot lights keep an angle there.
+    pub fn uses_radius(self) -> bool {
+        !matches!(self, Self::Plane | Self::Triangle | Self::SpotLight)
+    }
+
     pub const ALL: &[Self] = &[
         Self::Sphere,
         Self::Plane,
@@ -74,6 +167,8 @@ impl ShapeType {
         Self::Mebius,
         Self::Pyramid,
         Self::Tetrahedron,
+        Self::SpotLight,
+        Self::Instance,
     ];
 
     pub const ELEMENTARY: &[Self] = &[
@@ -97,6 +192,7 @@ impl ShapeType {
         Self::Mandelbulb,
         Self::Julia,
         Self::Skybox,
+        Self::SpotLight,
     ];
 }
 
@@ -118,19 +214,22 @@ pub struct Shape {
     #[serde(default = "default_normal", skip_serializing_if = "is_default_normal")]
     pub normal: [f32; 3],
 
-    /// Radius (sphere, cylinder, cone, disc, torus major, mandelbulb, julia).
+    /// Radius (sphere, cylinder, cone base, disc, torus major, mandelbulb, julia).
     #[serde(default = "default_radius", skip_serializing_if = "is_default_radius")]
     pub radius: f32,
 
-    /// Secondary radius (torus minor radius, cone half-angle, cylinder height).
+    /// Secondary radius (torus minor radius, ellipsoid Z radius, spot light outer angle).
     #[serde(default, skip_serializing_if = "is_zero_f32")]
     pub radius2: f32,
 
-    /// Height (cylinder, cone).
+    /// Height (cylinder, cone, ellipsoid Y radius).
     #[serde(default, skip_serializing_if = "is_zero_f32")]
     pub height: f32,
 
-    /// Rotation in degrees (Euler XYZ).
+    /// Rotation in degrees as intrinsic Euler XYZ: a point is turned about Z
+    /// first, then Y, then X, each counter-clockwise looking down the axis
+    /// (so 90° about Y takes +Z to +X). Only instances are rotated by it;
+    /// Julia sets keep their constant here instead.
     #[serde(default, skip_serializing_if = "is_zero_vec3")]
     pub rotation: [f32; 3],
 
@@ -155,6 +254,15 @@ pub struct Shape {
     )]
     pub max_iterations: u32,
 
+    /// Sphere-marching step limit (Mandelbulb/Julia/Mebius), 0 = the scene's
+    /// `fractal_march_steps`.
+    #[serde(default, skip_serializing_if = "is_zero_u32")]
+    pub march_steps: u32,
+
+    /// Tint Mandelbulb/Julia surfaces by their escape iteration and orbit trap.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub orbit_trap: bool,
+
     /// Texture image path.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub texture: Option<String>,
@@ -173,6 +281,84 @@ pub struct Shape {
 
     #[serde(default, skip_serializing_if = "Material::is_default")]
     pub material: Material,
+
+    /// Lighting baked at `v0`, `v1` and `v2` (triangles only); when set the
+    /// triangle shows these colors unlit instead of its material.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vertex_colors: Option<[[f32; 3]; 3]>,
+
+    /// OBJ model path (Instance only), resolved against the scene directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+impl Shape {
+    /// Display name: `name`, or the type and list index (`"Sphere #3"`) when unnamed.
+    pub fn label(&self, idx: usize) -> String {
+        match &self.name {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => format!("{} #{}", self.shape_type.label(), idx),
+        }
+    }
+
+    /// tan²(half-angle) of a cone, derived from its base `radius` and `height`.
+    /// A cone's geometry is fully defined by those two; nothing else is stored.
+    pub fn cone_tan_sq(&self) -> f32 {
+        let tan = self.radius / self.height.max(1e-6);
+        tan * tan
+    }
+
+    /// Ellipsoid semi-axes along X/Y/Z, stored in `radius`, `height` and `radius2`.
+    pub fn ellipsoid_radii(&self) -> Vec3 {
+        Vec3::new(self.radius, self.height, self.radius2)
+    }
+
+    /// Raise a non-positive or NaN `radius` to `MIN_SHAPE_RADIUS` for shapes
+    /// sized by it. Returns whether it was changed.
+    pub fn sanitize_radius(&mut self) -> bool {
+        if !self.shape_type.uses_radius() || self.radius >= MIN_SHAPE_RADIUS {
+            return false;
+        }
+        self.radius = MIN_SHAPE_RADIUS;
+        true
+    }
+
+    /// Make the shape face the other way: negate `normal`, or for a triangle
+    /// swap the winding of its last two vertices along with their UVs and
+    /// baked colors.
+    pub fn flip_normal(&mut self) {
+        if self.shape_type == ShapeType::Triangle {
+            std::mem::swap(&mut self.v1, &mut self.v2);
+            std::mem::swap(&mut self.uv1, &mut self.uv2);
+            if let Some(colors) = &mut self.vertex_colors {
+                colors.swap(1, 2);
+            }
+        } else {
+            self.normal = self.normal.map(|n| -n);
+        }
+    }
+
+    /// `rotation` (degrees, Euler XYZ) as a quaternion.
+    pub fn rotation_quat(&self) -> Quat {
+        let [x, y, z] = self.rotation.map(f32::to_radians);
+        Quat::from_euler(EulerRot::XYZ, x, y, z)
+    }
+
+    /// Set `rotation` to the Euler angles of `rotation`.
+    pub fn set_rotation_quat(&mut self, rotation: Quat) {
+        let (x, y, z) = rotation.to_euler(EulerRot::XYZ);
+        self.rotation = [x, y, z].map(|angle| angle.to_degrees());
+    }
+
+    /// Model-to-world transform of an instance: uniform scale by `radius`,
+    /// then `rotation`, then translation to `position`.
+    pub fn instance_transform(&self) -> Affine3A {
+        Affine3A::from_scale_rotation_translation(
+            Vec3::splat(self.radius),
+            self.rotation_quat(),
+            Vec3::from(self.position),
+        )
+    }
 }
 
 fn default_normal() -> [f32; 3] {
@@ -203,6 +389,10 @@ fn is_zero_f32(v: &f32) -> bool {
     *v == 0.0
 }
 
+fn is_zero_u32(v: &u32) -> bool {
+    *v == 0
+}
+
 fn is_default_normal(v: &[f32; 3]) -> bool {
     *v == default_normal()
 }
@@ -219,6 +409,10 @@ fn is_default_max_iterations(v: &u32) -> bool {
     *v == default_max_iterations()
 }
 
+/// `GpuShape::csg_op` of a triangle with baked `vertex_colors`, which then
+/// sit in `rotation` as `pack_rgb9e5` bits.
+pub const FIGURE_BAKED_COLORS: u32 = 2;
+
 /// GPU-compatible shape representation. Must match the WGSL `Figure` struct layout.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Pod, Zeroable)]
@@ -248,29 +442,68 @@ pub struct GpuShape {
 }
 
 impl GpuShape {
+    /// An instance of a model whose BLAS starts at node `blas_root`. The
+    /// vertex slots carry the columns of the world-to-model matrix and
+    /// `radius2` the root index bits, since instances have no geometry of
+    /// their own. The model triangles keep their own materials; the
+    /// instance's `material_idx` supplies the shadow flags and back-face
+    /// culling applied to the whole mesh.
+    pub fn instance(shape: &Shape, blas_root: u32, material_idx: u32) -> Self {
+        let world_to_model = shape.instance_transform().matrix3.inverse();
+        Self {
+            shape_type: ShapeType::Instance.as_u32(),
+            material_idx,
+            radius2: f32::from_bits(blas_root),
+            position: shape.position,
+            v0: world_to_model.x_axis.into(),
+            v1: world_to_model.y_axis.into(),
+            v2: world_to_model.z_axis.into(),
+            ..Self::zeroed()
+        }
+    }
+
     pub fn from_shape(shape: &Shape, material_idx: u32) -> Self {
         let normal = glam::Vec3::from(shape.normal).normalize_or_zero();
-        let is_fractal = matches!(shape.shape_type, ShapeType::Mandelbulb | ShapeType::Julia);
-        // For fractals, pack power and max_iterations into v0 (unused by fractals otherwise).
-        let v0 = if is_fractal {
-            [shape.power, shape.max_iterations as f32, 0.0]
-        } else {
-            shape.v0
+        // SDF shapes have no vertices: v0 carries power, max_iterations and the
+        // march step override, v1.x the orbit-trap flag.
+        let (v0, v1) = match shape.shape_type {
+            ShapeType::Mandelbulb | ShapeType::Julia => (
+                [
+                    shape.power,
+                    shape.max_iterations as f32,
+                    shape.march_steps as f32,
+                ],
+                [f32::from(u8::from(shape.orbit_trap)), 0.0, 0.0],
+            ),
+            ShapeType::Mebius => ([0.0, 0.0, shape.march_steps as f32], shape.v1),
+            _ => (shape.v0, shape.v1),
+        };
+        let (csg_op, rotation) = match shape.vertex_colors {
+            Some(colors) if shape.shape_type == ShapeType::Triangle && !shape.negative => (
+                FIGURE_BAKED_COLORS,
+                colors.map(|color| f32::from_bits(pack_rgb9e5(color))),
+            ),
+            _ => (u32::from(shape.negative), shape.rotation),
         };
         Self {
             shape_type: shape.shape_type.as_u32(),
             material_idx,
             radius: shape.radius,
-            radius2: shape.radius2,
+            // The cone shader takes the derived tan² in place of a secondary radius.
+            radius2: if shape.shape_type == ShapeType::Cone {
+                shape.cone_tan_sq()
+            } else {
+                shape.radius2
+            },
             position: shape.position,
             height: shape.height,
             normal: normal.into(),
-            csg_op: u32::from(shape.negative),
-            rotation: shape.rotation,
+            csg_op,
+            rotation,
             texture_scale: shape.texture_scale.unwrap_or(1.0),
             v0,
             _pad2: pack_f16x2(shape.uv0[0], shape.uv0[1]),
-            v1: shape.v1,
+            v1,
             _pad3: pack_f16x2(shape.uv1[0], shape.uv1[1]),
             v2: shape.v2,
             _pad4: pack_f16x2(shape.uv2[0], shape.uv2[1]),
@@ -278,6 +511,25 @@ impl GpuShape {
     }
 }
 
+/// Pack a non-negative HDR color into the shared-exponent RGB9E5 format:
+/// 9-bit mantissas with a common 5-bit exponent, red in the low bits.
+/// Matches `unpack_rgb9e5` in triangle.wgsl.
+pub fn pack_rgb9e5(color: [f32; 3]) -> u32 {
+    const MANTISSA_BITS: i32 = 9;
+    const BIAS: i32 = 15;
+    const MAX: f32 = 65408.0; // (511 / 512) * 2^16
+    let rgb = color.map(|c| if c > 0.0 { c.min(MAX) } else { 0.0 });
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    let mut exponent = (max.log2().floor() as i32).max(-BIAS - 1) + 1 + BIAS;
+    let mut scale = 2f32.powi(exponent - BIAS - MANTISSA_BITS);
+    if (max / scale + 0.5).floor() >= 512.0 {
+        scale *= 2.0;
+        exponent += 1;
+    }
+    let [r, g, b] = rgb.map(|c| (c / scale + 0.5).floor() as u32);
+    r | (g << 9) | (b << 18) | ((exponent as u32) << 27)
+}
+
 /// Pack two f32 values into a single f32 using IEEE 754 half-float encoding.
 /// Matches WGSL `pack2x16float` / `unpack2x16float` layout.
 fn pack_f16x2(a: f32, b: f32) -> f32 {
@@ -320,3 +572,151 @@ fn f32_to_f16_bits(val: f32) -> u16 {
 
     (sign | ((new_exp as u32) << 10) | (mantissa >> 13)) as u16
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Shape {
+        serde_yml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_sdf_settings_packed_into_spare_fields() {
+        let bulb = parse("type: mandelbulb\nmarch_steps: 96\norbit_trap: true\n");
+        let gpu = GpuShape::from_shape(&bulb, 0);
+        assert_eq!(gpu.v0, [8.0, 12.0, 96.0]);
+        assert_eq!(gpu.v1[0], 1.0);
+
+        let mebius = parse("type: mebius\nmarch_steps: 64\n");
+        assert_eq!(GpuShape::from_shape(&mebius, 0).v0[2], 64.0);
+
+        let yaml = serde_yml::to_string(&parse("type: julia\n")).unwrap();
+        assert!(!yaml.contains("march_steps") && !yaml.contains("orbit_trap"));
+    }
+
+    #[test]
+    fn test_default_shapes_are_valid() {
+        for &shape_type in ShapeType::ALL {
+            let shape = shape_type.new_default_shape([1.0, 2.0, 3.0]);
+            assert_eq!(shape.shape_type, shape_type);
+            assert!(shape.radius > 0.0 && shape.height > 0.0, "{shape_type:?}");
+            assert!(
+                (Vec3::from(shape.normal).length() - 1.0).abs() < 1e-6,
+                "{shape_type:?}"
+            );
+            let gpu = GpuShape::from_shape(&shape, 0);
+            assert!(
+                bytemuck::cast_slice::<GpuShape, f32>(&[gpu])
+                    .iter()
+                    .all(|v| v.is_finite()),
+                "{shape_type:?}"
+            );
+
+            // Saving and reloading keeps every field.
+            let yaml = serde_yml::to_string(&shape).unwrap();
+            let reloaded: Shape = serde_yml::from_str(&yaml).unwrap();
+            assert_eq!(serde_yml::to_string(&reloaded).unwrap(), yaml);
+        }
+        let spot = ShapeType::SpotLight.new_default_shape([0.0; 3]);
+        assert!(spot.radius < spot.radius2);
+        assert_eq!(
+            ShapeType::Plane.new_default_shape([1.0; 3]).position,
+            [0.0; 3]
+        );
+    }
+
+    #[test]
+    fn test_flip_normal() {
+        let mut tri = parse(
+            "type: triangle\nv0: [0, 0, 0]\nv1: [1, 0, 0]\nv2: [0, 1, 0]\n\
+             uv1: [1, 0]\nuv2: [0, 1]\nvertex_colors: [[0, 0, 0], [1, 0, 0], [0, 1, 0]]\n",
+        );
+        let face = |s: &Shape| {
+            let [v0, v1, v2] = [s.v0, s.v1, s.v2].map(Vec3::from);
+            (v1 - v0).cross(v2 - v0)
+        };
+        let before = tri.clone();
+        tri.flip_normal();
+        assert_eq!(face(&tri), -face(&before));
+        assert_eq!((tri.uv1, tri.uv2), (before.uv2, before.uv1));
+        assert_eq!(tri.vertex_colors.unwrap()[1], [0.0, 1.0, 0.0]);
+        tri.flip_normal();
+        assert_eq!(tri.v1, before.v1);
+
+        let mut plane = ShapeType::Plane.new_default_shape([0.0; 3]);
+        plane.flip_normal();
+        assert_eq!(plane.normal, [0.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_rotation_convention() {
+        let mut shape = ShapeType::Instance.new_default_shape([1.0, 2.0, 3.0]);
+        shape.radius = 1.0;
+        let cases = [
+            ([90.0, 0.0, 0.0], Vec3::Y, Vec3::Z),
+            ([0.0, 90.0, 0.0], Vec3::Z, Vec3::X),
+            ([0.0, 0.0, 90.0], Vec3::X, Vec3::Y),
+            // Z is applied before Y, Y before X.
+            ([90.0, 90.0, 0.0], Vec3::Z, Vec3::X),
+            ([0.0, 90.0, 90.0], Vec3::X, Vec3::Y),
+        ];
+        for (rotation, from, to) in cases {
+            shape.rotation = rotation;
+            let world = shape.instance_transform().transform_point3(from);
+            let expected = Vec3::from(shape.position) + to;
+            assert!(world.abs_diff_eq(expected, 1e-5), "{rotation:?}: {world}");
+
+            // The shader's `instance_ray` takes the world point back to `from`.
+            let gpu = GpuShape::instance(&shape, 0, 0);
+            let world_to_model = glam::Mat3::from_cols(gpu.v0.into(), gpu.v1.into(), gpu.v2.into());
+            let local = world_to_model * (world - Vec3::from(gpu.position));
+            assert!(local.abs_diff_eq(from, 1e-5), "{rotation:?}: {local}");
+
+            let mut copy = shape.clone();
+            copy.set_rotation_quat(shape.rotation_quat());
+            assert!(
+                copy.rotation_quat()
+                    .abs_diff_eq(shape.rotation_quat(), 1e-5)
+            );
+        }
+    }
+
+    #[test]
+    fn test_sanitize_radius() {
+        let mut sphere = parse("type: sphere\nradius: 0\n");
+        assert!(sphere.sanitize_radius());
+        assert_eq!(sphere.radius, MIN_SHAPE_RADIUS);
+        assert!(!sphere.sanitize_radius());
+
+        let mut instance = parse("type: instance\nradius: -2\n");
+        assert!(instance.sanitize_radius());
+        assert_eq!(instance.radius, MIN_SHAPE_RADIUS);
+
+        // A zero inner cone angle is a valid spot light.
+        let mut spot = parse("type: spot_light\nradius: 0\nradius2: 30\n");
+        assert!(!spot.sanitize_radius());
+        assert_eq!(spot.radius, 0.0);
+    }
+
+    #[test]
+    fn test_rgb9e5_round_trip() {
+        // CPU copy of `unpack_rgb9e5` in triangle.wgsl.
+        let unpack = |bits: u32| {
+            let scale = 2f32.powi((bits >> 27) as i32 - 24);
+            [0, 9, 18].map(|shift| ((bits >> shift) & 0x1ff) as f32 * scale)
+        };
+        for color in [[0.0; 3], [1.0, 0.5, 0.25], [12.0, 0.01, 3.5], [0.001; 3]] {
+            let unpacked = unpack(pack_rgb9e5(color));
+            let max = color.iter().copied().fold(0.0, f32::max);
+            for (u, c) in unpacked.iter().zip(color) {
+                // Half a step of the shared 9-bit mantissa.
+                assert!(
+                    (u - c).abs() <= max / 512.0 + 1e-7,
+                    "{color:?} -> {unpacked:?}"
+                );
+            }
+        }
+        assert_eq!(unpack(pack_rgb9e5([-1.0, f32::NAN, 2.0])), [0.0, 0.0, 2.0]);
+    }
+}
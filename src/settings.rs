@@ -0,0 +1,242 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::{
+    CAMERA_DEFAULT_MOVE_SPEED, CAMERA_DEFAULT_SENSITIVITY, DEFAULT_MAX_RENDER_SIZE,
+    DEFAULT_SPAWN_DISTANCE,
+};
+use crate::gpu::context::PresentModeChoice;
+use crate::render::frame::{ColorSpace, JitterPattern};
+
+const SETTINGS_FILE_NAME: &str = "settings.yaml";
+
+/// Where shapes added from the toolbar and imported models appear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpawnPlacement {
+    /// `spawn_distance` along the camera's view direction.
+    #[default]
+    InFront,
+    /// At the world origin.
+    Origin,
+    /// Centred on the surface point last clicked in the viewport, or in
+    /// front of the camera before anything was clicked.
+    ClickedPoint,
+}
+
+impl SpawnPlacement {
+    pub const ALL: &[Self] = &[Self::InFront, Self::Origin, Self::ClickedPoint];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::InFront => "In Front of Camera",
+            Self::Origin => "At Origin",
+            Self::ClickedPoint => "At Clicked Point",
+        }
+    }
+}
+
+/// Runtime preferences persisted across launches in the OS config directory.
+///
+/// Missing fields fall back to defaults, so older files keep loading.
+/// `PATHTRACER_MOUSE_SENS` still overrides the stored sensitivity for the
+/// current run without being written back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub mouse_sensitivity: f32,
+    pub move_speed: f32,
+    pub invert_y: bool,
+    /// Grid step for snapping dragged shapes, 0 = off.
+    pub grid_snap: f32,
+    pub spawn_placement: SpawnPlacement,
+    /// How far in front of the camera new shapes appear.
+    pub spawn_distance: f32,
+    /// Frame-rate cap in frames per second, 0 = uncapped.
+    pub fps_cap: u32,
+    /// Path-trace samples per frame, fewer while frames run long.
+    pub samples_per_frame: u32,
+    /// Render at reduced resolution while the camera moves.
+    pub dynamic_resolution: bool,
+    /// Adapt exposure to the scene's brightness; the exposure slider becomes a bias.
+    pub auto_exposure: bool,
+    /// Longest side of the internal render resolution, 0 = match the window.
+    pub max_render_size: u32,
+    /// Render at this multiple of the window size (1 = off), for anti-aliasing.
+    pub supersampling: f32,
+    /// Sharpen the image when the blit upscales it.
+    pub sharpen_upscale: bool,
+    /// Rescale the accumulated image when the window is resized instead of
+    /// starting over.
+    pub keep_samples_on_resize: bool,
+    pub present_mode: PresentModeChoice,
+    /// Sub-pixel sampling pattern of primary rays.
+    pub jitter_pattern: JitterPattern,
+    /// Encoding of the displayed image and screenshots.
+    pub color_space: ColorSpace,
+    /// Light unlit scenes from the camera while editing.
+    pub headlight: bool,
+    /// Size of the UI in physical pixels per point, `None` = the display's
+    /// scale factor.
+    pub ui_scale: Option<f32>,
+    /// Open `last_scene` when launched without a scene argument.
+    pub reopen_last_scene: bool,
+    /// Scene file last opened or saved.
+    pub last_scene: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mouse_sensitivity: CAMERA_DEFAULT_SENSITIVITY,
+            move_speed: CAMERA_DEFAULT_MOVE_SPEED,
+            invert_y: false,
+            grid_snap: 0.0,
+            spawn_placement: SpawnPlacement::InFront,
+            spawn_distance: DEFAULT_SPAWN_DISTANCE,
+            fps_cap: 0,
+            samples_per_frame: 1,
+            dynamic_resolution: false,
+            auto_exposure: false,
+            max_render_size: DEFAULT_MAX_RENDER_SIZE,
+            supersampling: 1.0,
+            sharpen_upscale: false,
+            keep_samples_on_resize: false,
+            present_mode: PresentModeChoice::AutoVsync,
+            jitter_pattern: JitterPattern::Random,
+            color_space: ColorSpace::Srgb,
+            headlight: true,
+            ui_scale: None,
+            reopen_last_scene: false,
+            last_scene: None,
+        }
+    }
+}
+
+impl Settings {
+    /// `<config dir>/settings.yaml`, or `None` if the OS has no home directory.
+    pub fn path() -> Option<PathBuf> {
+        directories::ProjectDirs::from("", "", "PathTracer")
+            .map(|dirs| dirs.config_dir().join(SETTINGS_FILE_NAME))
+    }
+
+    /// Load settings, falling back to defaults when the file is missing or invalid.
+    pub fn load() -> Self {
+        match Self::path() {
+            Some(path) if path.exists() => match Self::load_from(&path) {
+                Ok(settings) => {
+                    log::info!("Loaded settings from {}", path.display());
+                    settings
+                }
+                Err(e) => {
+                    log::warn!("Ignoring settings file: {e:#}");
+                    Self::default()
+                }
+            },
+            _ => Self::default(),
+        }
+    }
+
+    fn load_from(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let settings = serde_yml::from_str(&text)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(settings)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().context("No config directory available")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let text = serde_yml::to_string(self)?;
+        std::fs::write(&path, text)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        log::info!("Saved settings to {}", path.display());
+        Ok(())
+    }
+
+    /// The scene to open on a launch without a scene argument, if reopening
+    /// is on and the file still exists.
+    pub fn scene_to_reopen(&self) -> Option<&Path> {
+        let path = self
+            .last_scene
+            .as_deref()
+            .filter(|_| self.reopen_last_scene)?;
+        if !path.exists() {
+            log::warn!("Last scene {} no longer exists", path.display());
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Mouse sensitivity to use this run: `PATHTRACER_MOUSE_SENS` if set and valid,
+    /// otherwise the stored value.
+    pub fn effective_mouse_sensitivity(&self) -> f32 {
+        let Ok(val) = std::env::var("PATHTRACER_MOUSE_SENS") else {
+            return self.mouse_sensitivity;
+        };
+        match val.parse::<f32>() {
+            Ok(sens) if sens > 0.0 && sens.is_finite() => {
+                log::info!("PATHTRACER_MOUSE_SENS={sens}");
+                sens
+            }
+            _ => {
+                log::warn!("PATHTRACER_MOUSE_SENS={val:?} invalid, using settings");
+                self.mouse_sensitivity
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let settings = Settings {
+            mouse_sensitivity: 0.3,
+            invert_y: true,
+            grid_snap: 0.5,
+            spawn_placement: SpawnPlacement::ClickedPoint,
+            fps_cap: 60,
+            present_mode: PresentModeChoice::Mailbox,
+            reopen_last_scene: true,
+            last_scene: Some(PathBuf::from("scenes/room.yaml")),
+            ..Default::default()
+        };
+        let yaml = serde_yml::to_string(&settings).unwrap();
+        let loaded: Settings = serde_yml::from_str(&yaml).unwrap();
+        assert_eq!(loaded, settings);
+    }
+
+    #[test]
+    fn test_deleted_last_scene_is_not_reopened() {
+        let existing = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let mut settings = Settings {
+            last_scene: Some(existing.clone()),
+            ..Default::default()
+        };
+        assert_eq!(settings.scene_to_reopen(), None);
+        settings.reopen_last_scene = true;
+        assert_eq!(settings.scene_to_reopen(), Some(existing.as_path()));
+        settings.last_scene = Some(existing.with_file_name("deleted.yaml"));
+        assert_eq!(settings.scene_to_reopen(), None);
+    }
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let loaded: Settings = serde_yml::from_str("fps_cap: 30\n").unwrap();
+        assert_eq!(loaded.fps_cap, 30);
+        assert_eq!(loaded.mouse_sensitivity, CAMERA_DEFAULT_SENSITIVITY);
+        assert_eq!(loaded.present_mode, PresentModeChoice::AutoVsync);
+    }
+}
@@ -1,16 +1,33 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 /// WGSL shader composer that resolves `// #import module_name` directives.
 ///
 /// Each `.wgsl` file can declare imports at the top, and the composer
-/// concatenates them in dependency order with deduplication.
+/// concatenates them in dependency order with deduplication. `{{NAME}}`
+/// placeholders are then replaced with values set via `set_constant`.
+///
+/// Feature toggles are flags enabled with `define` or a `// #define NAME` line.
+/// Lines between `// #if NAME` (or `// #if !NAME`), an optional `// #else`
+/// and `// #endif` are kept only when the condition holds; blocks nest, and
+/// imports inside a dropped block are not resolved.
 pub struct ShaderComposer {
     modules: HashMap<String, String>,
+    constants: HashMap<String, String>,
+    defines: HashSet<String>,
+}
+
+/// One open `#if` block while preprocessing a module.
+struct Conditional {
+    /// Lines in the current branch are emitted.
+    active: bool,
+    /// The enclosing block is active, so `#else` may switch this one on.
+    parent_active: bool,
+    seen_else: bool,
 }
 
 impl ShaderComposer {
@@ -18,7 +35,25 @@ impl ShaderComposer {
     pub fn from_directory(dir: &Path) -> Result<Self> {
         let mut modules = HashMap::new();
         Self::load_dir(dir, dir, &mut modules)?;
-        Ok(Self { modules })
+        Ok(Self {
+            modules,
+            constants: HashMap::new(),
+            defines: HashSet::new(),
+        })
+    }
+
+    /// Enable or disable the feature flag `name` for `// #if` blocks.
+    pub fn define(&mut self, name: &str, enabled: bool) {
+        if enabled {
+            self.defines.insert(name.to_string());
+        } else {
+            self.defines.remove(name);
+        }
+    }
+
+    /// Substitute `{{name}}` with `value` in every composed shader.
+    pub fn set_constant(&mut self, name: &str, value: impl ToString) {
+        self.constants.insert(name.to_string(), value.to_string());
     }
 
     fn load_dir(base: &Path, dir: &Path, modules: &mut HashMap<String, String>) -> Result<()> {
@@ -47,11 +82,24 @@ impl ShaderComposer {
             .replace(std::path::MAIN_SEPARATOR, "::")
     }
 
-    /// Compose a shader by resolving all imports recursively.
+    /// Compose a shader by resolving all imports recursively, then substituting constants.
     pub fn compose(&self, entry_module: &str) -> Result<String> {
         let mut output = String::new();
         let mut visited = HashSet::new();
-        self.resolve(entry_module, &mut output, &mut visited)?;
+        let mut defines = self.defines.clone();
+        self.resolve(entry_module, &mut output, &mut visited, &mut defines)?;
+        for (name, value) in &self.constants {
+            output = output.replace(&format!("{{{{{name}}}}}"), value);
+        }
+        if let Some(start) = output.find("{{") {
+            let end = output[start..]
+                .find("}}")
+                .map_or(output.len(), |e| start + e + 2);
+            bail!(
+                "Unresolved placeholder {} in shader '{entry_module}'",
+                &output[start..end]
+            );
+        }
         Ok(output)
     }
 
@@ -60,6 +108,7 @@ impl ShaderComposer {
         module_name: &str,
         output: &mut String,
         visited: &mut HashSet<String>,
+        defines: &mut HashSet<String>,
     ) -> Result<()> {
         if visited.contains(module_name) {
             return Ok(());
@@ -73,21 +122,60 @@ impl ShaderComposer {
 
         // Resolve imports first, then emit non-import lines — single pass.
         let mut body = String::new();
-        for line in source.lines() {
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for (line_no, line) in source.lines().enumerate() {
             let trimmed = line.trim();
-            if let Some(import_name) = trimmed.strip_prefix("// #import ") {
-                self.resolve(import_name.trim(), output, visited)?;
+            let active = conditionals.last().is_none_or(|c| c.active);
+            let location = || format!("{module_name}:{}", line_no + 1);
+            if let Some(condition) = trimmed.strip_prefix("// #if ") {
+                let condition = condition.trim();
+                let holds = match condition.strip_prefix('!') {
+                    Some(name) => !defines.contains(name.trim()),
+                    None => defines.contains(condition),
+                };
+                conditionals.push(Conditional {
+                    active: active && holds,
+                    parent_active: active,
+                    seen_else: false,
+                });
+            } else if trimmed == "// #else" {
+                let Some(block) = conditionals.last_mut() else {
+                    bail!("#else without #if at {}", location());
+                };
+                if block.seen_else {
+                    bail!("Duplicate #else at {}", location());
+                }
+                block.seen_else = true;
+                block.active = block.parent_active && !block.active;
+            } else if trimmed == "// #endif" {
+                if conditionals.pop().is_none() {
+                    bail!("#endif without #if at {}", location());
+                }
+            } else if !active {
+                continue;
+            } else if let Some(name) = trimmed.strip_prefix("// #define ") {
+                defines.insert(name.trim().to_string());
+            } else if let Some(import_name) = trimmed.strip_prefix("// #import ") {
+                self.resolve(import_name.trim(), output, visited, defines)?;
             } else {
                 body.push_str(line);
                 body.push('\n');
             }
         }
+        if !conditionals.is_empty() {
+            bail!("Unterminated #if in shader module '{module_name}'");
+        }
         output.push_str(&body);
         output.push('\n');
 
         Ok(())
     }
 
+    /// Source of module `name` as loaded or registered, before composing.
+    pub fn module(&self, name: &str) -> Option<&str> {
+        self.modules.get(name).map(String::as_str)
+    }
+
     pub fn register(&mut self, name: &str, source: &str) {
         self.modules.insert(name.to_string(), source.to_string());
     }
@@ -97,6 +185,7 @@ impl ShaderComposer {
     /// 2. `<exe_dir>/../Resources/shaders/` — macOS `.app` bundle
     /// 3. `<exe_dir>/../../src/shaders/wgsl` — `cargo run` (target/debug/ or target/release/)
     /// 4. `src/shaders/wgsl` — fallback relative to CWD
+    #[allow(clippy::collapsible_if)]
     pub fn shader_dir() -> PathBuf {
         if let Ok(exe) = std::env::current_exe() {
             if let Some(exe_dir) = exe.parent() {
@@ -127,6 +216,8 @@ mod tests {
     fn make_composer(entries: &[(&str, &str)]) -> ShaderComposer {
         let mut composer = ShaderComposer {
             modules: HashMap::new(),
+            constants: HashMap::new(),
+            defines: HashSet::new(),
         };
         for &(name, src) in entries {
             composer.register(name, src);
@@ -159,4 +250,83 @@ mod tests {
         let result = composer.compose("main").unwrap();
         assert_eq!(result.matches("fn base_fn()").count(), 1);
     }
+
+    #[test]
+    fn test_constant_substitution() {
+        let mut composer = make_composer(&[(
+            "main",
+            "@compute @workgroup_size({{WG_X}}, {{WG_Y}})\nfn main() {}",
+        )]);
+        composer.set_constant("WG_X", 16);
+        composer.set_constant("WG_Y", 4);
+
+        let result = composer.compose("main").unwrap();
+        assert!(result.contains("@workgroup_size(16, 4)"));
+    }
+
+    #[test]
+    fn test_unresolved_placeholder_is_an_error() {
+        let composer = make_composer(&[("main", "@compute @workgroup_size({{WG_X}})")]);
+        let err = composer.compose("main").unwrap_err().to_string();
+        assert!(err.contains("{{WG_X}}"));
+    }
+
+    #[test]
+    fn test_conditional_blocks() {
+        let source = "\
+// #if NEE
+fn nee() {}
+// #if RR
+fn nee_rr() {}
+// #endif
+// #else
+fn no_nee() {}
+// #endif
+// #if !RR
+fn no_rr() {}
+// #endif";
+        let mut composer = make_composer(&[("main", source)]);
+
+        let result = composer.compose("main").unwrap();
+        assert!(!result.contains("fn nee()"));
+        assert!(!result.contains("fn nee_rr()"));
+        assert!(result.contains("fn no_nee()"));
+        assert!(result.contains("fn no_rr()"));
+
+        composer.define("NEE", true);
+        composer.define("RR", true);
+        let result = composer.compose("main").unwrap();
+        assert!(result.contains("fn nee()"));
+        assert!(result.contains("fn nee_rr()"));
+        assert!(!result.contains("fn no_nee()"));
+        assert!(!result.contains("fn no_rr()"));
+        assert!(!result.contains("#if"));
+    }
+
+    #[test]
+    fn test_define_and_skipped_imports() {
+        let composer = make_composer(&[
+            ("extra", "fn extra() {}"),
+            (
+                "main",
+                "// #define FAST\n// #if FAST\nfn fast() {}\n// #else\n// #import extra\n// #endif",
+            ),
+        ]);
+        let result = composer.compose("main").unwrap();
+        assert!(result.contains("fn fast()"));
+        assert!(!result.contains("fn extra()"));
+    }
+
+    #[test]
+    fn test_unbalanced_conditionals_are_errors() {
+        for source in [
+            "// #if A\nfn a() {}",
+            "// #endif",
+            "// #else",
+            "// #if A\n// #else\n// #else\n// #endif",
+        ] {
+            let composer = make_composer(&[("main", source)]);
+            assert!(composer.compose("main").is_err(), "{source:?}");
+        }
+    }
 }
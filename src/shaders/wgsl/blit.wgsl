@@ -3,6 +3,22 @@
 
 @group(0) @binding(0) var t_output: texture_2d<f32>;
 @group(0) @binding(1) var s_output: sampler;
+@group(0) @binding(2) var<uniform> params: BlitParams;
+// Shape index + 1 seen through each output texel, 0 = none.
+@group(0) @binding(3) var<storage, read> object_ids: array<u32>;
+
+struct BlitParams {
+    // Contrast-adaptive sharpening strength in 0..1; 0 = plain bilinear.
+    sharpness: f32,
+    // Shapes selection_start..selection_end are outlined; empty = none.
+    selection_start: u32,
+    selection_end: u32,
+    // Source texels per screen pixel; above 1 (supersampling) the pixel
+    // averages four taps spread over its footprint.
+    downsample: f32,
+}
+
+const OUTLINE_COLOR: vec3f = vec3f(1.0, 0.55, 0.1);
 
 struct VertexOutput {
     @builtin(position) position: vec4f,
@@ -20,7 +36,75 @@ fn vs_main(@builtin(vertex_index) idx: u32) -> VertexOutput {
     return out;
 }
 
+// Transparent pixels (see `Camera::transparent_background`) are shown over
+// a grey checkerboard, the way image editors do.
+fn over_checkerboard(color: vec4f, position: vec2f) -> vec4f {
+    let cell = vec2u(position / 8.0);
+    let checker = select(0.2, 0.3, (cell.x + cell.y) % 2u == 0u);
+    return vec4f(mix(vec3f(checker), color.rgb, color.a), 1.0);
+}
+
+// Whether the shape seen through output texel `texel` is selected. Texels
+// off the image are not.
+fn is_selected(texel: vec2i, size: vec2i) -> bool {
+    if any(texel < vec2i(0)) || any(texel >= size) {
+        return false;
+    }
+    let id = object_ids[u32(texel.y * size.x + texel.x)];
+    return id > params.selection_start && id <= params.selection_end;
+}
+
+// Selection outline: texels just outside the selected shapes' silhouette,
+// found where the selection changes between neighbouring object ids.
+fn on_outline(uv: vec2f) -> bool {
+    if params.selection_end <= params.selection_start {
+        return false;
+    }
+    let size = vec2i(textureDimensions(t_output));
+    let texel = min(vec2i(uv * vec2f(size)), size - 1);
+    if is_selected(texel, size) {
+        return false;
+    }
+    return is_selected(texel + vec2i(1, 0), size) || is_selected(texel - vec2i(1, 0), size)
+        || is_selected(texel + vec2i(0, 1), size) || is_selected(texel - vec2i(0, 1), size);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4f {
-    return textureSample(t_output, s_output, in.uv);
+    if on_outline(in.uv) {
+        return vec4f(OUTLINE_COLOR, 1.0);
+    }
+    if params.downsample > 1.0 {
+        // Taps a quarter pixel off the center, each bilinear over the
+        // texels around it: an exact 2x2 box at 2x, close to one at 1.5x.
+        let d = 0.25 * params.downsample / vec2f(textureDimensions(t_output));
+        let sum = textureSample(t_output, s_output, in.uv + vec2f(-d.x, -d.y))
+            + textureSample(t_output, s_output, in.uv + vec2f(d.x, -d.y))
+            + textureSample(t_output, s_output, in.uv + vec2f(-d.x, d.y))
+            + textureSample(t_output, s_output, in.uv + vec2f(d.x, d.y));
+        return over_checkerboard(sum * 0.25, in.position.xy);
+    }
+    let center = textureSample(t_output, s_output, in.uv);
+    if params.sharpness <= 0.0 {
+        return over_checkerboard(center, in.position.xy);
+    }
+
+    // Contrast-adaptive sharpening (AMD FidelityFX CAS, simplified to the
+    // 4-neighbour cross). Neighbours are one source texel away, so the
+    // filter undoes the softness of the bilinear upscale rather than
+    // sharpening at display resolution.
+    let texel = 1.0 / vec2f(textureDimensions(t_output));
+    let c = center.rgb;
+    let n = textureSample(t_output, s_output, in.uv - vec2f(0.0, texel.y)).rgb;
+    let s = textureSample(t_output, s_output, in.uv + vec2f(0.0, texel.y)).rgb;
+    let w = textureSample(t_output, s_output, in.uv - vec2f(texel.x, 0.0)).rgb;
+    let e = textureSample(t_output, s_output, in.uv + vec2f(texel.x, 0.0)).rgb;
+
+    let lo = min(c, min(min(n, s), min(w, e)));
+    let hi = max(c, max(max(n, s), max(w, e)));
+    // Sharpen less where local contrast already reaches black or white.
+    let amp = sqrt(clamp(min(lo, 1.0 - hi) / max(hi, vec3f(1e-5)), vec3f(0.0), vec3f(1.0)));
+    let weight = amp * (-1.0 / mix(8.0, 5.0, params.sharpness));
+    let rgb = (c + (n + s + w + e) * weight) / (1.0 + 4.0 * weight);
+    return over_checkerboard(vec4f(clamp(rgb, vec3f(0.0), vec3f(1.0)), center.a), in.position.xy);
 }
@@ -14,6 +14,23 @@ fn intersect_aabb(origin: vec3f, inv_dir: vec3f, aabb_min: vec3f, aabb_max: vec3
     return max(t_near, 0.0);
 }
 
+// A scene without finite shapes gets a single zeroed root node, which reads
+// as an inner node pointing back at itself; traversal must not enter it.
+fn scene_bvh_is_empty() -> bool {
+    let root = bvh_nodes[0];
+    return root.prim_count == 0u && root.left_or_prim == 0u;
+}
+
+// Entry distance into a BVH node's bounds. In brute force mode the tree is
+// one leaf over every shape and the test is skipped, so hits never depend on
+// the computed bounds.
+fn node_entry_t(origin: vec3f, inv_dir: vec3f, node: BvhNode) -> f32 {
+    if camera.brute_force != 0u {
+        return 0.0;
+    }
+    return intersect_aabb(origin, inv_dir, node.aabb_min, node.aabb_max);
+}
+
 // Check if a point is inside a figure's volume (for CSG subtraction).
 fn is_inside_figure(p: vec3f, idx: u32) -> bool {
     let fig = figures[idx];
@@ -141,7 +158,12 @@ fn is_inside_any_negative(p: vec3f) -> bool {
 
 // BVH closest-hit traversal using a stack, with CSG subtraction.
 fn trace_bvh(ray: Ray) -> HitRecord {
-    var closest = trace_bvh_positive(ray);
+    return trace_bvh_range(ray, MAX_T);
+}
+
+// `trace_bvh` limited to hits closer than `max_t`.
+fn trace_bvh_range(ray: Ray, max_t: f32) -> HitRecord {
+    var closest = trace_bvh_positive(ray, max_t);
 
     // Apply CSG subtraction: if hit is inside a negative shape, advance past it.
     if closest.hit {
@@ -175,7 +197,7 @@ fn trace_bvh(ray: Ray) -> HitRecord {
                 current_ray.direction
             );
 
-            closest = trace_bvh_positive(current_ray);
+            closest = trace_bvh_positive(current_ray, max_t - total_t_offset);
             if closest.hit {
                 closest.t += total_t_offset;
             } else {
@@ -208,6 +230,14 @@ fn test_infinite_shapes(ray: Ray, closest: ptr<function, HitRecord>) {
     }
 }
 
+// Whether a figure blocks shadow rays: negative (subtraction) shapes and
+// shadow catchers never do, and materials can opt out.
+fn casts_shadow(idx: u32) -> bool {
+    let fig = figures[idx];
+    let no_cast = MATERIAL_NO_CAST_SHADOW | MATERIAL_SHADOW_CATCHER;
+    return fig.csg_op != 1u && (materials[fig.material_idx].shadow_flags & no_cast) == 0u;
+}
+
 // Shadow variant: returns true if any infinite shape occludes the ray in (EPSILON, max_t).
 fn test_infinite_shapes_shadow(ray: Ray, max_t: f32) -> bool {
     let num = arrayLength(&infinite_indices);
@@ -218,7 +248,7 @@ fn test_infinite_shapes_shadow(ray: Ray, max_t: f32) -> bool {
             continue;
         }
         // Skip negative (subtraction) shapes — they don't block light.
-        if figures[prim_idx].csg_op == 1u {
+        if !casts_shadow(prim_idx) {
             continue;
         }
         let hit = intersect_figure(ray, prim_idx);
@@ -231,11 +261,104 @@ fn test_infinite_shapes_shadow(ray: Ray, max_t: f32) -> bool {
     return false;
 }
 
+// Ray in an instance's model space. The direction is not renormalised, so
+// distances along it equal the world-space ones.
+fn instance_ray(ray: Ray, fig: Figure) -> Ray {
+    let world_to_model = mat3x3f(fig.v0, fig.v1, fig.v2);
+    return Ray(world_to_model * (ray.origin - fig.position), world_to_model * ray.direction);
+}
+
+// Model triangle `idx`, one-sided when its own material or, with `cull_all`,
+// the instance's culls back faces.
+fn intersect_model_triangle(ray: Ray, idx: u32, cull_all: bool) -> HitRecord {
+    if !cull_all {
+        return intersect_figure(ray, idx);
+    }
+    var hit = intersect_triangle(ray, figures[idx], true);
+    if hit.hit {
+        hit.figure_idx = idx;
+        hit.object_idx = idx;
+    }
+    return hit;
+}
+
+// Traverse the model BVH of instance `fig` with the ray taken into model
+// space. Finds the closest hit nearer than `max_t`, or with `any_hit` returns
+// the first shadow-casting hit in (EPSILON, max_t) as soon as it is found.
+// Position and normal of the hit stay in model space.
+fn traverse_instance(ray: Ray, fig: Figure, max_t: f32, any_hit: bool) -> HitRecord {
+    var closest = HitRecord();
+    closest.t = max_t;
+    closest.hit = false;
+
+    let local_ray = instance_ray(ray, fig);
+    let cull_all = materials[fig.material_idx].cull_backfaces != 0u;
+    let inv_dir = 1.0 / local_ray.direction;
+
+    var stack: array<u32, 32>;
+    var stack_ptr = 1;
+    stack[0] = bitcast<u32>(fig.radius2);
+
+    while stack_ptr > 0 {
+        stack_ptr -= 1;
+        let node_idx = stack[stack_ptr];
+        let node = bvh_nodes[node_idx];
+
+        let t_box = node_entry_t(local_ray.origin, inv_dir, node);
+        if t_box >= closest.t {
+            continue;
+        }
+
+        if node.prim_count > 0u {
+            for (var i = 0u; i < node.prim_count; i++) {
+                let prim_idx = bvh_prims[node.left_or_prim + i];
+                if any_hit && !casts_shadow(prim_idx) {
+                    continue;
+                }
+                let hit = intersect_model_triangle(local_ray, prim_idx, cull_all);
+                if !hit.hit || hit.t <= EPSILON || hit.t >= closest.t {
+                    continue;
+                }
+                if !any_hit {
+                    closest = hit;
+                } else if hit.t < max_t - EPSILON
+                    && !is_inside_any_negative(ray.origin + ray.direction * hit.t) {
+                    return hit;
+                }
+            }
+        } else if stack_ptr < 30 {
+            stack[stack_ptr] = node.left_or_prim;
+            stack[stack_ptr + 1] = node_idx + 1u;
+            stack_ptr += 2;
+        }
+    }
+    return closest;
+}
+
+// Closest hit in the model BVH of instance `idx`, nearer than `max_t`.
+// Position and normal are returned in world space.
+fn trace_instance(ray: Ray, idx: u32, max_t: f32) -> HitRecord {
+    let fig = figures[idx];
+    var closest = traverse_instance(ray, fig, max_t, false);
+    if closest.hit {
+        closest.object_idx = idx;
+        closest.position = ray.origin + ray.direction * closest.t;
+        // Normals take the inverse transpose of the model-to-world matrix.
+        closest.normal = normalize(closest.normal * mat3x3f(fig.v0, fig.v1, fig.v2));
+    }
+    return closest;
+}
+
+// Whether any model triangle of instance `idx` blocks the ray in (EPSILON, max_t).
+fn instance_occludes(ray: Ray, idx: u32, max_t: f32) -> bool {
+    return traverse_instance(ray, figures[idx], max_t, true).hit;
+}
+
 // BVH traversal for non-subtracted shapes, followed by a linear test for
 // infinite shapes (planes) that are excluded from the BVH.
-fn trace_bvh_positive(ray: Ray) -> HitRecord {
+fn trace_bvh_positive(ray: Ray, max_t: f32) -> HitRecord {
     var closest = HitRecord();
-    closest.t = MAX_T;
+    closest.t = max_t;
     closest.hit = false;
 
     let inv_dir = 1.0 / ray.direction;
@@ -243,14 +366,14 @@ fn trace_bvh_positive(ray: Ray) -> HitRecord {
     var stack: array<u32, 32>;
     var stack_ptr = 0;
     stack[0] = 0u;
-    stack_ptr = 1;
+    stack_ptr = select(1, 0, scene_bvh_is_empty());
 
     while stack_ptr > 0 {
         stack_ptr -= 1;
         let node_idx = stack[stack_ptr];
         let node = bvh_nodes[node_idx];
 
-        let t_box = intersect_aabb(ray.origin, inv_dir, node.aabb_min, node.aabb_max);
+        let t_box = node_entry_t(ray.origin, inv_dir, node);
         if t_box >= closest.t {
             continue;
         }
@@ -263,6 +386,13 @@ fn trace_bvh_positive(ray: Ray) -> HitRecord {
                 if figures[prim_idx].csg_op == 1u {
                     continue;
                 }
+                if figures[prim_idx].figure_type == FIG_INSTANCE {
+                    let hit = trace_instance(ray, prim_idx, closest.t);
+                    if hit.hit {
+                        closest = hit;
+                    }
+                    continue;
+                }
                 let hit = intersect_figure(ray, prim_idx);
                 if hit.hit && hit.t < closest.t && hit.t > EPSILON {
                     closest = hit;
@@ -311,14 +441,14 @@ fn trace_shadow(ray: Ray, max_t: f32) -> bool {
     var stack: array<u32, 32>;
     var stack_ptr = 0;
     stack[0] = 0u;
-    stack_ptr = 1;
+    stack_ptr = select(1, 0, scene_bvh_is_empty());
 
     while stack_ptr > 0 {
         stack_ptr -= 1;
         let node_idx = stack[stack_ptr];
         let node = bvh_nodes[node_idx];
 
-        let t_box = intersect_aabb(ray.origin, inv_dir, node.aabb_min, node.aabb_max);
+        let t_box = node_entry_t(ray.origin, inv_dir, node);
         if t_box >= max_t {
             continue;
         }
@@ -326,8 +456,15 @@ fn trace_shadow(ray: Ray, max_t: f32) -> bool {
         if node.prim_count > 0u {
             for (var i = 0u; i < node.prim_count; i++) {
                 let prim_idx = bvh_prims[node.left_or_prim + i];
+                // Instances carry no material; their triangles decide.
+                if figures[prim_idx].figure_type == FIG_INSTANCE {
+                    if instance_occludes(ray, prim_idx, max_t) {
+                        return true;
+                    }
+                    continue;
+                }
                 // Skip negative shapes — they don't block light.
-                if figures[prim_idx].csg_op == 1u {
+                if !casts_shadow(prim_idx) {
                     continue;
                 }
                 let hit = intersect_figure(ray, prim_idx);
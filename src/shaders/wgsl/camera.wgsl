@@ -1,15 +1,77 @@
 // #import types
 // #import random
 
+// Blue-noise mask from `render::blue_noise`, tiled over the screen.
+@group(1) @binding(9) var<storage, read> blue_noise: array<f32>;
+
+const BLUE_NOISE_SIZE: u32 = {{BLUE_NOISE_SIZE}}u;
+// Stratified jitter splits the pixel into a JITTER_STRATA^2 grid and visits
+// every cell once per that many frames.
+const JITTER_STRATA: u32 = 4u;
+// R2 sequence step in 0.32 fixed point, so the offset stays exact however
+// many frames have accumulated.
+const R2_STEP: vec2u = vec2u(3242174889u, 2447445413u);
+// Stereo eyes converge this many eye separations in front of the camera
+// (the stereographers' 1/30 rule); nearer objects stand out of the screen.
+const STEREO_CONVERGENCE: f32 = 30.0;
+
 // Generate a camera ray using pre-computed basis vectors.
 // Sub-pixel jitter provides built-in anti-aliasing through progressive accumulation.
-fn generate_ray(cam: Camera, pixel: vec2f) -> Ray {
-    // Sub-pixel jitter for AA
-    let jitter = rand_vec2() - 0.5;
-    let px = pixel + jitter;
+fn generate_ray(cam: Camera, pixel: vec2u) -> Ray {
+    // Sub-pixel jitter for AA. Pixel (i, j) covers [i, i + 1) x [j, j + 1),
+    // the same convention as cursor coordinates in `picking::picking_ray`.
+    return generate_ray_through(cam, vec2f(pixel) + pixel_jitter(cam, pixel));
+}
+
+// Sub-pixel offset in [0, 1)^2 for this pixel and frame.
+fn pixel_jitter(cam: Camera, pixel: vec2u) -> vec2f {
+    switch cam.jitter_pattern {
+        case JITTER_STRATIFIED: {
+            // Stepping by 7 (coprime with 16) jumps across the grid between
+            // frames, and a per-pixel start keeps neighbours out of lockstep.
+            let cells = JITTER_STRATA * JITTER_STRATA;
+            let start = pcg_hash(pixel.x + pixel.y * 65536u);
+            let cell = (start + cam.frame_index * 7u) % cells;
+            let corner = vec2f(f32(cell % JITTER_STRATA), f32(cell / JITTER_STRATA));
+            return (corner + rand_vec2()) / f32(JITTER_STRATA);
+        }
+        case JITTER_BLUE_NOISE: {
+            // The mask read at two offsets gives the two axes. Shifting it by
+            // the R2 sequence each frame keeps a pixel's own samples spread
+            // out over time while neighbours stay decorrelated.
+            let p = pixel % BLUE_NOISE_SIZE;
+            let q = (pixel + vec2u(BLUE_NOISE_SIZE / 2u, 0u)) % BLUE_NOISE_SIZE;
+            let noise = vec2f(
+                blue_noise[p.y * BLUE_NOISE_SIZE + p.x],
+                blue_noise[q.y * BLUE_NOISE_SIZE + q.x],
+            );
+            let shift = vec2f(R2_STEP * cam.frame_index) / 4294967296.0;
+            return fract(noise + shift);
+        }
+        default: {
+            return rand_vec2();
+        }
+    }
+}
 
+// Camera ray through screen position `px` (pixel plus offset), no jitter.
+// Side-by-side stereo gives each half of the image its own eye.
+fn generate_ray_through(cam: Camera, px: vec2f) -> Ray {
+    if cam.stereo_mode == STEREO_SIDE_BY_SIDE {
+        let half = f32(cam.width) * 0.5;
+        let right_eye = px.x >= half;
+        let x = select(px.x, px.x - half, right_eye);
+        let ray = view_ray(cam, vec2f(x, px.y), half);
+        return eye_ray(cam, ray, select(-1.0, 1.0, right_eye));
+    }
+    return view_ray(cam, px, f32(cam.width));
+}
+
+// Ray from the camera through `px` of a view `width` pixels wide.
+fn view_ray(cam: Camera, px: vec2f, width: f32) -> Ray {
     // Normalized device coordinates [-1, 1]
-    let ndc_x = (2.0 * px.x / f32(cam.width) - 1.0) * cam.aspect;
+    let aspect = cam.aspect * width / f32(cam.width);
+    let ndc_x = (2.0 * px.x / width - 1.0) * aspect;
     let ndc_y = 1.0 - 2.0 * px.y / f32(cam.height);
 
     // Ray direction from pre-computed basis vectors (no per-ray trig!)
@@ -17,3 +79,14 @@ fn generate_ray(cam: Camera, pixel: vec2f) -> Ray {
 
     return Ray(cam.position, dir);
 }
+
+// `ray` as seen by the left (`eye` = -1) or right (+1) stereo eye: moved
+// half the eye separation sideways and turned to meet the original ray on
+// the convergence plane, so both eyes share one (off-axis) image plane.
+fn eye_ray(cam: Camera, ray: Ray, eye: f32) -> Ray {
+    let offset = cam.right * (eye * cam.eye_separation * 0.5);
+    let convergence = cam.eye_separation * STEREO_CONVERGENCE;
+    let target_point = ray.origin + ray.direction * (convergence / dot(ray.direction, cam.forward));
+    let origin = ray.origin + offset;
+    return Ray(origin, normalize(target_point - origin));
+}
@@ -1,6 +1,10 @@
 // #import types
 
-// Cone intersection with pre-computed tan^2(half_angle).
+// Cone with its base disc at `position` (radius `radius`) and apex at
+// `position + normal * height`. `radius2` holds tan^2(half_angle) =
+// (radius / height)^2, derived on upload by `GpuShape::from_shape`.
+const CONE_DISC_TOLERANCE: f32 = 1e-4;
+
 fn intersect_cone(ray: Ray, fig: Figure) -> HitRecord {
     var hit = HitRecord();
     hit.hit = false;
@@ -8,18 +12,11 @@ fn intersect_cone(ray: Ray, fig: Figure) -> HitRecord {
 
     let axis = fig.normal;
     let apex = fig.position + axis * fig.height;
-    let tan2 = fig.radius2; // Pre-computed tan^2(half_angle) stored in radius2
+    let tan2 = fig.radius2;
 
     let oc = ray.origin - apex;
-    let d_axis = dot(ray.direction, axis);
-    let oc_axis = dot(oc, axis);
-
-    let a = d_axis * d_axis * (1.0 + tan2) - dot(ray.direction, ray.direction) * tan2
-            - d_axis * d_axis + dot(ray.direction, ray.direction);
-    // Simplified:
-    let a2 = d_axis * d_axis - dot(ray.direction, ray.direction) * tan2 / (1.0 + tan2);
 
-    // Use standard cone equation
+    // Standard cone equation
     let d_dot_v = dot(ray.direction, axis);
     let oc_dot_v = dot(oc, axis);
     let cos2 = 1.0 / (1.0 + tan2);
@@ -28,12 +25,14 @@ fn intersect_cone(ray: Ray, fig: Figure) -> HitRecord {
     let b_c = d_dot_v * oc_dot_v - cos2 * dot(ray.direction, oc);
     let c_c = oc_dot_v * oc_dot_v - cos2 * dot(oc, oc);
 
+    // A ray through the apex has a double root; allow rounding error to push
+    // the discriminant slightly negative so such rays still hit the tip.
     let disc = b_c * b_c - a_c * c_c;
-    if disc < 0.0 {
+    if disc < -CONE_DISC_TOLERANCE * b_c * b_c {
         return hit;
     }
 
-    let sqrtd = sqrt(disc);
+    let sqrtd = sqrt(max(disc, 0.0));
     let inv_a = 1.0 / a_c;
 
     for (var i = 0; i < 2; i++) {
@@ -43,8 +42,9 @@ fn intersect_cone(ray: Ray, fig: Figure) -> HitRecord {
         }
         let p = ray.origin + ray.direction * t;
         let proj = dot(p - apex, axis);
-        // Cone extends from apex (proj=0) downward (proj=-height)
-        if proj >= -fig.height && proj <= 0.0 {
+        // Cone extends from apex (proj=0) downward (proj=-height), with slack
+        // at the tip so apex hits survive rounding.
+        if proj >= -fig.height && proj <= fig.height * CONE_DISC_TOLERANCE {
             hit.hit = true;
             hit.t = t;
             hit.position = p;
@@ -46,7 +46,7 @@ fn intersect_figure(ray: Ray, idx: u32) -> HitRecord {
             hit = intersect_disc(ray, fig);
         }
         case FIG_TRIANGLE: {
-            hit = intersect_triangle(ray, fig);
+            hit = intersect_triangle(ray, fig, materials[fig.material_idx].cull_backfaces != 0u);
         }
         case FIG_SKYBOX: {
             hit = intersect_skybox(ray, fig);
@@ -83,6 +83,7 @@ fn intersect_figure(ray: Ray, idx: u32) -> HitRecord {
 
     if hit.hit {
         hit.figure_idx = idx;
+        hit.object_idx = idx;
     }
 
     return hit;
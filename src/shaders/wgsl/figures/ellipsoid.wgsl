@@ -7,7 +7,7 @@ fn intersect_ellipsoid(ray: Ray, fig: Figure) -> HitRecord {
     hit.hit = false;
     hit.t = MAX_T;
 
-    let radii = vec3f(fig.radius, max(fig.height, fig.radius), max(fig.radius2, fig.radius));
+    let radii = vec3f(fig.radius, fig.height, fig.radius2);
     let inv_radii = 1.0 / radii;
 
     // Transform ray to unit-sphere space
@@ -1,4 +1,5 @@
 // #import types
+// #import figures::sdf
 
 // Quaternion Julia set SDF with over-relaxation sphere marching.
 fn quat_mult(a: vec4f, b: vec4f) -> vec4f {
@@ -31,6 +32,24 @@ fn sdf_julia(p: vec3f, c: vec4f, max_iter: i32) -> f32 {
     return 0.5 * r * log(r) / dr;
 }
 
+// Escape iteration (as a fraction of max_iter) and closest approach to the
+// origin of the orbit of `p`, used for orbit-trap coloring.
+fn orbit_julia(p: vec3f, c: vec4f, max_iter: i32) -> vec2f {
+    var z = vec4f(p, 0.0);
+    var r2 = dot(z, z);
+    var trap = r2;
+    var i = 0;
+    for (; i < max_iter; i++) {
+        if r2 > 16.0 {
+            break;
+        }
+        z = quat_mult(z, z) + c;
+        r2 = dot(z, z);
+        trap = min(trap, r2);
+    }
+    return vec2f(f32(i) / f32(max(max_iter, 1)), sqrt(trap));
+}
+
 fn intersect_julia(ray: Ray, fig: Figure) -> HitRecord {
     var hit = HitRecord();
     hit.hit = false;
@@ -56,7 +75,8 @@ fn intersect_julia(ray: Ray, fig: Figure) -> HitRecord {
     let max_t = -b + sqrt(disc);
     var prev_d = 0.0;
 
-    for (var i = 0u; i < camera.fractal_march_steps; i++) {
+    let steps = sdf_march_steps(fig);
+    for (var i = 0u; i < steps; i++) {
         let p = ray.origin + ray.direction * t - fig.position;
         let scaled_p = p / fig.radius;
         let d = sdf_julia(scaled_p, c, max_iter) * fig.radius;
@@ -86,6 +106,9 @@ fn intersect_julia(ray: Ray, fig: Figure) -> HitRecord {
             );
 
             hit.uv = vec2f(0.0);
+            if uses_orbit_trap(fig) {
+                hit.uv = orbit_julia(local, c, max_iter);
+            }
             return hit;
         }
 
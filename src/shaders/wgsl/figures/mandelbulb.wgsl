@@ -1,4 +1,5 @@
 // #import types
+// #import figures::sdf
 
 // Mandelbulb SDF using trig-based triplex algebra (supports variable power).
 // Reference: Inigo Quilez — https://iquilezles.org/articles/mandelbulb/
@@ -29,6 +30,27 @@ fn sdf_mandelbulb(p: vec3f, power: f32, max_iter: i32) -> f32 {
     return 0.25 * log(m) * r / dz;
 }
 
+// Escape iteration (as a fraction of max_iter) and closest approach to the
+// origin of the orbit of `p`, used for orbit-trap coloring.
+fn orbit_mandelbulb(p: vec3f, power: f32, max_iter: i32) -> vec2f {
+    var w = p;
+    var m = dot(w, w);
+    var trap = m;
+    var i = 0;
+    for (; i < max_iter; i++) {
+        let r = sqrt(m);
+        let b = power * acos(clamp(w.y / r, -1.0, 1.0));
+        let a = power * atan2(w.x, w.z);
+        w = p + pow(r, power) * vec3f(sin(b) * sin(a), cos(b), sin(b) * cos(a));
+        m = dot(w, w);
+        trap = min(trap, m);
+        if m > 256.0 {
+            break;
+        }
+    }
+    return vec2f(f32(i) / f32(max(max_iter, 1)), sqrt(trap));
+}
+
 fn intersect_mandelbulb(ray: Ray, fig: Figure) -> HitRecord {
     var hit = HitRecord();
     hit.hit = false;
@@ -54,7 +76,8 @@ fn intersect_mandelbulb(ray: Ray, fig: Figure) -> HitRecord {
     let max_t = -b + sqrt(disc);
     var prev_d = 0.0;
 
-    for (var i = 0u; i < camera.fractal_march_steps; i++) {
+    let steps = sdf_march_steps(fig);
+    for (var i = 0u; i < steps; i++) {
         let p = ray.origin + ray.direction * t - fig.position;
         let scaled_p = p / fig.radius;
         let d = sdf_mandelbulb(scaled_p, power, max_iter) * fig.radius;
@@ -86,6 +109,9 @@ fn intersect_mandelbulb(ray: Ray, fig: Figure) -> HitRecord {
             );
 
             hit.uv = vec2f(0.0);
+            if uses_orbit_trap(fig) {
+                hit.uv = orbit_mandelbulb(local, power, max_iter);
+            }
             return hit;
         }
 
@@ -1,4 +1,5 @@
 // #import types
+// #import figures::sdf
 
 // Möbius strip SDF using parametric distance estimation.
 // The strip is centered at the origin with major radius R = fig.radius.
@@ -53,7 +54,8 @@ fn intersect_mebius(ray: Ray, fig: Figure) -> HitRecord {
     var t = max(-b - sqrt(disc), EPSILON);
     let max_t = -b + sqrt(disc);
 
-    for (var i = 0; i < 256; i++) {
+    let steps = sdf_march_steps(fig);
+    for (var i = 0u; i < steps; i++) {
         let p = ray.origin + ray.direction * t - fig.position;
         let d = sdf_mebius(p, fig.radius);
 
@@ -0,0 +1,15 @@
+// #import types
+
+// Helpers shared by the sphere-marched (SDF) figures.
+
+// Sphere-marching step limit of an SDF figure: its own override (packed in
+// v0.z by the CPU) or the global setting.
+fn sdf_march_steps(fig: Figure) -> u32 {
+    let steps = u32(fig.v0.z);
+    return select(camera.fractal_march_steps, steps, steps > 0u);
+}
+
+// Whether a fractal should be tinted by its orbit (flag packed in v1.x).
+fn uses_orbit_trap(fig: Figure) -> bool {
+    return (fig.figure_type == FIG_MANDELBULB || fig.figure_type == FIG_JULIA) && fig.v1.x > 0.5;
+}
@@ -1,29 +1,45 @@
 // #import types
 // #import textures
+// #import sky
 
 // Skybox: direct environment map lookup on ray miss (no intersection needed).
-// Returns a procedural sky gradient when no skybox texture is available.
-fn sample_skybox(direction: vec3f) -> vec3f {
-    // Check if there's a skybox figure with a texture
-    let num_figs = arrayLength(&figures);
-    for (var i = 0u; i < num_figs; i++) {
-        if figures[i].figure_type == FIG_SKYBOX {
-            let sky_mat = materials[figures[i].material_idx];
-            if sky_mat.texture_id >= 0 {
-                let d = normalize(direction);
-                let uv = vec2f(
-                    0.5 + atan2(d.z, d.x) / TWO_PI,
-                    0.5 - asin(clamp(d.y, -1.0, 1.0)) / PI
-                );
-                return sample_texture(sky_mat.texture_id, uv) * sky_mat.base_color;
+// camera.skybox_mode selects a solid color, a horizon→zenith gradient, the
+// texture of a Skybox figure (falling back to the gradient when none exists),
+// or the analytic sun+sky model. `include_sun` is false when the sun was
+// already sampled explicitly at the previous vertex.
+fn sample_skybox(direction: vec3f, include_sun: bool) -> vec3f {
+    let d = normalize(direction);
+    if camera.skybox_mode == SKY_SUN_SKY {
+        var sky = preetham_sky(d);
+        if include_sun && dot(d, camera.sun_direction) >= sun_cos_max() {
+            sky += sun_radiance();
+        }
+        return sky;
+    }
+    if camera.skybox_mode == SKY_SOLID_COLOR {
+        return camera.skybox_color * camera.skybox_brightness;
+    }
+    if camera.skybox_mode == SKY_HDR_MAP {
+        // Check if there's a skybox figure with a texture
+        let num_figs = arrayLength(&figures);
+        for (var i = 0u; i < num_figs; i++) {
+            if figures[i].figure_type == FIG_SKYBOX {
+                let sky_mat = materials[figures[i].material_idx];
+                if sky_mat.texture_id >= 0 {
+                    let uv = vec2f(
+                        0.5 + atan2(d.z, d.x) / TWO_PI,
+                        0.5 - asin(clamp(d.y, -1.0, 1.0)) / PI
+                    );
+                    // emission_strength is the skybox brightness; 0 (unset) means 1.0
+                    let strength = select(1.0, sky_mat.emission_strength, sky_mat.emission_strength > 0.0);
+                    return sample_texture(sky_mat.texture_id, uv) * sky_mat.base_color * strength;
+                }
             }
         }
     }
-    // Fallback: procedural sky gradient
-    let t = 0.5 * (direction.y + 1.0);
-    let sky_bottom = vec3f(1.0, 1.0, 1.0);
-    let sky_top = camera.skybox_color;
-    return mix(sky_bottom, sky_top, t) * camera.skybox_brightness;
+    // Procedural gradient: horizon color straight down, zenith color straight up
+    let t = 0.5 * (d.y + 1.0);
+    return mix(camera.skybox_horizon_color, camera.skybox_color, t) * camera.skybox_brightness;
 }
 
 // Skybox intersection for compatibility with BVH (used when skybox is a figure).
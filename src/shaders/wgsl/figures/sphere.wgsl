@@ -29,12 +29,15 @@ fn intersect_sphere(ray: Ray, fig: Figure) -> HitRecord {
     hit.position = ray.origin + ray.direction * t;
     hit.normal = normalize(hit.position - fig.position);
 
-    // Spherical UV mapping
-    let local = normalize(hit.position - fig.position);
-    hit.uv = vec2f(
+    hit.uv = sphere_uv(hit.normal);
+
+    return hit;
+}
+
+// Spherical UV mapping of a unit direction from the sphere's center.
+fn sphere_uv(local: vec3f) -> vec2f {
+    return vec2f(
         0.5 + atan2(local.z, local.x) / TWO_PI,
         0.5 - asin(clamp(local.y, -1.0, 1.0)) / PI
     );
-
-    return hit;
 }
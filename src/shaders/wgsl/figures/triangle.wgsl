@@ -1,7 +1,9 @@
 // #import types
 
 // Möller-Trumbore triangle intersection (gold standard).
-fn intersect_triangle(ray: Ray, fig: Figure) -> HitRecord {
+// With `cull_backfaces`, rays reaching the back of the winding
+// (negative determinant) miss.
+fn intersect_triangle(ray: Ray, fig: Figure, cull_backfaces: bool) -> HitRecord {
     var hit = HitRecord();
     hit.hit = false;
     hit.t = MAX_T;
@@ -11,7 +13,7 @@ fn intersect_triangle(ray: Ray, fig: Figure) -> HitRecord {
     let h = cross(ray.direction, e2);
     let a = dot(e1, h);
 
-    if abs(a) < EPSILON {
+    if abs(a) < EPSILON || (cull_backfaces && a < 0.0) {
         return hit;
     }
 
@@ -51,3 +53,33 @@ fn intersect_triangle(ray: Ray, fig: Figure) -> HitRecord {
 
     return hit;
 }
+
+// Shared-exponent RGB as packed by `scene::shape::pack_rgb9e5`.
+fn unpack_rgb9e5(bits: u32) -> vec3f {
+    let scale = exp2(f32(i32(bits >> 27u) - 24));
+    return vec3f(
+        f32(bits & 0x1ffu),
+        f32((bits >> 9u) & 0x1ffu),
+        f32((bits >> 18u) & 0x1ffu),
+    ) * scale;
+}
+
+// Baked lighting of a FIGURE_BAKED_COLORS triangle at surface point `p`,
+// interpolated between its vertex colors.
+fn baked_color(fig: Figure, p: vec3f) -> vec3f {
+    let e1 = fig.v1 - fig.v0;
+    let e2 = fig.v2 - fig.v0;
+    let d = p - fig.v0;
+    let d11 = dot(e1, e1);
+    let d12 = dot(e1, e2);
+    let d22 = dot(e2, e2);
+    let denom = d11 * d22 - d12 * d12;
+    if denom <= 0.0 {
+        return unpack_rgb9e5(bitcast<u32>(fig.rotation.x));
+    }
+    let u = (d22 * dot(d, e1) - d12 * dot(d, e2)) / denom;
+    let v = (d11 * dot(d, e2) - d12 * dot(d, e1)) / denom;
+    return (1.0 - u - v) * unpack_rgb9e5(bitcast<u32>(fig.rotation.x))
+        + u * unpack_rgb9e5(bitcast<u32>(fig.rotation.y))
+        + v * unpack_rgb9e5(bitcast<u32>(fig.rotation.z));
+}
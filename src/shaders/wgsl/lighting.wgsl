@@ -1,28 +1,70 @@
 // #import types
+// #import utils
+// #import figures::sphere
 
 // Next Event Estimation: direct light sampling.
 
-// Sample a point on a sphere light.
-fn sample_sphere_light(light: Figure, hit_pos: vec3f) -> vec3f {
-    // Uniform point on sphere surface
-    let r = rand_vec2();
-    let cos_theta = 1.0 - 2.0 * r.x;
-    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
-    let phi = TWO_PI * r.y;
-
-    let local_point = vec3f(
-        sin_theta * cos(phi),
-        sin_theta * sin(phi),
-        cos_theta
-    ) * light.radius;
+// Point on an area light with the surface normal and texture coordinates there.
+struct LightSample {
+    position: vec3f,
+    normal: vec3f,
+    uv: vec2f,
+}
 
-    return light.position + local_point;
+// Uniform point on the surface of a sphere, disc or triangle light;
+// `lights::sampled_area` keeps other shape types out of `light_indices`.
+fn sample_area_light(light: Figure) -> LightSample {
+    let r = rand_vec2();
+    var s: LightSample;
+    switch light.figure_type {
+        case FIG_TRIANGLE: {
+            // Square-root warp of the unit square onto uniform barycentrics.
+            let su = sqrt(r.x);
+            let b1 = su * (1.0 - r.y);
+            let b2 = su * r.y;
+            let e1 = light.v1 - light.v0;
+            let e2 = light.v2 - light.v0;
+            s.position = light.v0 + b1 * e1 + b2 * e2;
+            s.normal = normalize(cross(e1, e2));
+            let uv0 = unpack2x16float(bitcast<u32>(light._pad2));
+            let uv1 = unpack2x16float(bitcast<u32>(light._pad3));
+            let uv2 = unpack2x16float(bitcast<u32>(light._pad4));
+            s.uv = (1.0 - b1 - b2) * uv0 + b1 * uv1 + b2 * uv2;
+        }
+        case FIG_DISC: {
+            let radius = light.radius * sqrt(r.x);
+            let phi = TWO_PI * r.y;
+            let offset = build_onb(light.normal) * vec3f(radius * cos(phi), radius * sin(phi), 0.0);
+            s.position = light.position + offset;
+            s.normal = light.normal;
+            s.uv = (offset.xz / light.radius + 1.0) * 0.5;
+        }
+        default: {
+            let cos_theta = 1.0 - 2.0 * r.x;
+            let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+            let phi = TWO_PI * r.y;
+            s.normal = vec3f(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+            s.position = light.position + s.normal * light.radius;
+            s.uv = sphere_uv(s.normal);
+        }
+    }
+    return s;
 }
 
-// PDF for sampling a point on a sphere surface (uniform area).
-fn sphere_light_pdf(light: Figure, hit_pos: vec3f) -> f32 {
-    let area = 4.0 * PI * light.radius * light.radius;
-    return 1.0 / area;
+// Area PDF of `sample_area_light`: one over the surface area, which
+// `lights::sampled_area` also weights the light's selection by.
+fn area_light_pdf(light: Figure) -> f32 {
+    switch light.figure_type {
+        case FIG_TRIANGLE: {
+            return 2.0 / length(cross(light.v1 - light.v0, light.v2 - light.v0));
+        }
+        case FIG_DISC: {
+            return 1.0 / (PI * light.radius * light.radius);
+        }
+        default: {
+            return 1.0 / (4.0 * PI * light.radius * light.radius);
+        }
+    }
 }
 
 // Convert area PDF to solid angle PDF.
@@ -32,3 +74,29 @@ fn area_to_solid_angle_pdf(area_pdf: f32, dist_sq: f32, cos_light: f32) -> f32 {
     }
     return area_pdf * dist_sq / cos_light;
 }
+
+// Smooth spot light cone falloff: 1 inside the inner cone, 0 outside the outer one.
+// `to_point` is the unit direction from the light to the shaded point.
+fn spot_falloff(light: Light, to_point: vec3f) -> f32 {
+    let cos_angle = dot(to_point, light.direction);
+    if light.cos_inner <= light.cos_outer {
+        return select(0.0, 1.0, cos_angle >= light.cos_outer);
+    }
+    return smoothstep(light.cos_outer, light.cos_inner, cos_angle);
+}
+
+// Index of the area light whose CDF interval contains `u` in [0, 1): the
+// first entry with `cdf > u`, found by binary search.
+fn pick_area_light(u: f32, count: u32) -> u32 {
+    var lo = 0u;
+    var hi = count - 1u;
+    while lo < hi {
+        let mid = (lo + hi) / 2u;
+        if light_indices[mid].cdf > u {
+            hi = mid;
+        } else {
+            lo = mid + 1u;
+        }
+    }
+    return lo;
+}
@@ -14,20 +14,32 @@
 @group(0) @binding(0) var<uniform> camera: Camera;
 @group(0) @binding(1) var<storage, read_write> accumulation: array<vec4f>;
 @group(0) @binding(2) var output: texture_storage_2d<rgba8unorm, write>;
+// Shape seen through each pixel, as index + 1 (0 = none), for the selection outline.
+@group(0) @binding(3) var<storage, read_write> object_ids: array<u32>;
 
 // --- Bind Group 1: Scene Data ---
 @group(1) @binding(0) var<storage, read> figures: array<Figure>;
 @group(1) @binding(1) var<storage, read> materials: array<Material>;
 @group(1) @binding(2) var<storage, read> bvh_nodes: array<BvhNode>;
 @group(1) @binding(3) var<storage, read> bvh_prims: array<u32>;
-@group(1) @binding(4) var<storage, read> light_indices: array<u32>;
+@group(1) @binding(4) var<storage, read> light_indices: array<AreaLight>;
 @group(1) @binding(7) var<storage, read> infinite_indices: array<u32>;
+@group(1) @binding(8) var<storage, read> lights: array<Light>;
 
 const MIN_BOUNCES_RR: u32 = 3u;
+const MAX_MATERIAL_BOUNCES: u32 = {{MAX_MATERIAL_BOUNCES}}u;
 
-@compute @workgroup_size(8, 8)
+// Object id of this invocation's last camera ray, see `object_ids`.
+var<private> primary_object: u32 = 0u;
+
+@compute @workgroup_size({{WORKGROUP_X}}, {{WORKGROUP_Y}})
 fn main(@builtin(global_invocation_id) gid: vec3u) {
-    let pixel = gid.xy;
+    // The dispatch covers only the render region; pixels outside keep their
+    // previous accumulation and output.
+    if gid.x >= camera.region_size.x || gid.y >= camera.region_size.y {
+        return;
+    }
+    let pixel = gid.xy + camera.region_origin;
     if pixel.x >= camera.width || pixel.y >= camera.height {
         return;
     }
@@ -35,54 +47,270 @@ fn main(@builtin(global_invocation_id) gid: vec3u) {
     // Initialize RNG per (pixel, frame)
     init_rng(pixel, camera.frame_index);
 
+    let idx = pixel.y * camera.width + pixel.x;
+
+    // Flat preview: one unjittered ray through the pixel centre, written
+    // straight to the buffer without accumulating.
+    if camera.view_mode == VIEW_FLAT {
+        let center_ray = generate_ray_through(camera, vec2f(pixel) + 0.5);
+        let flat_color = shade_flat(center_ray);
+        accumulation[idx] = flat_color;
+        object_ids[idx] = primary_object;
+        textureStore(output, pixel, vec4f(apply_tonemap(flat_color.rgb, camera.exposure), flat_color.a));
+        return;
+    }
+
+    // Normal and UV views: the same single ray, but the colors are data, so
+    // they skip exposure and tone mapping.
+    if camera.view_mode == VIEW_NORMALS || camera.view_mode == VIEW_UV {
+        let center_ray = generate_ray_through(camera, vec2f(pixel) + 0.5);
+        let debug_color = shade_debug(center_ray);
+        accumulation[idx] = debug_color;
+        object_ids[idx] = primary_object;
+        textureStore(output, pixel, debug_color);
+        return;
+    }
+
     // Generate camera ray with sub-pixel jitter
-    let ray = generate_ray(camera, vec2f(f32(pixel.x), f32(pixel.y)));
+    let ray = generate_ray(camera, pixel);
 
-    // Path trace
-    let radiance = trace_path(ray);
+    // Path trace; alpha is the pixel's coverage (0 where a transparent
+    // background shows through). Anaglyph stereo traces both eyes along the
+    // jittered ray and keeps red from the left, green and blue from the right.
+    var radiance: vec4f;
+    if camera.stereo_mode == STEREO_ANAGLYPH {
+        let left = trace_path(eye_ray(camera, ray, -1.0));
+        let right = trace_path(eye_ray(camera, ray, 1.0));
+        radiance = vec4f(left.r, right.gb, 0.5 * (left.a + right.a));
+    } else {
+        radiance = trace_path(ray);
+    }
+
+    // A NaN or infinite sample would stay in the running mean for good, so it
+    // is dropped: zero coverage leaves the unpremultiplied color unchanged. A
+    // mean that is already bad restarts from this sample.
+    let sample = select(vec4f(0.0), radiance, all_finite(radiance));
+    let stored = accumulation[idx];
+    let prev = select(sample, stored, all_finite(stored));
 
     // Welford's progressive accumulation (numerically stable)
-    let idx = pixel.y * camera.width + pixel.x;
-    let prev = accumulation[idx].xyz;
     let n = max(f32(camera.sample_count), 1.0);
-    let accumulated = prev + (radiance - prev) / n;
-    accumulation[idx] = vec4f(accumulated, 1.0);
+    let accumulated = prev + (sample - prev) / n;
+    accumulation[idx] = accumulated;
+    // Ids follow the first sample only, so jitter doesn't make outlines flicker.
+    if camera.sample_count <= 1u {
+        object_ids[idx] = primary_object;
+    }
+
+    // Radiance is premultiplied by coverage; the output stores straight alpha.
+    let color = apply_tonemap(accumulated.rgb / max(accumulated.a, 1e-4), camera.exposure);
+    textureStore(output, pixel, vec4f(color, accumulated.a));
+}
+
+// Emitted radiance. With emission_uses_texture the emission color is
+// multiplied by the surface texture, so textured lights glow with their pattern.
+fn material_emission(mat: Material, tex_color: vec3f) -> vec3f {
+    let le = mat.emission * mat.emission_strength;
+    return select(le, le * tex_color, mat.emission_uses_texture != 0u);
+}
+
+// Cosine palette over a fractal's escape iteration, shifted by its orbit trap.
+fn orbit_trap_tint(orbit: vec2f) -> vec3f {
+    let t = orbit.x + 0.5 * orbit.y;
+    return 0.5 + 0.5 * cos(TWO_PI * (t + vec3f(0.0, 0.33, 0.67)));
+}
+
+// Fraction of the direct light at `p` that is not blocked, from one sample
+// of an area light plus every spot light and the sun: 1 = fully lit,
+// 0 = fully in shadow. Drives shadow-catcher surfaces.
+fn shadow_catcher_visibility(p: vec3f, n: vec3f) -> f32 {
+    let origin = p + n * EPSILON * 2.0;
+    var unshadowed = 0.0;
+    var lit = 0.0;
+
+    let entry = light_indices[pick_area_light(rand_f32(), arrayLength(&light_indices))];
+    if entry.pdf > 0.0 {
+        let light_fig = figures[entry.shape_index];
+        let light_mat = materials[light_fig.material_idx];
+        let light_sample = sample_area_light(light_fig);
+        let to_light = light_sample.position - p;
+        let dist = length(to_light);
+        let dir = to_light / dist;
+        let cos_light = abs(dot(dir, light_sample.normal));
+        let n_dot_l = dot(n, dir);
+        if n_dot_l > 0.0 {
+            let le = luminance(light_mat.emission) * light_mat.emission_strength;
+            let e = le * n_dot_l * cos_light / (dist * dist * entry.pdf);
+            unshadowed += e;
+            if !trace_shadow(Ray(origin, dir), dist) {
+                lit += e;
+            }
+        }
+    }
+
+    for (var li = 0u; li < arrayLength(&lights); li++) {
+        let light = lights[li];
+        if light.light_type != LIGHT_SPOT {
+            continue;
+        }
+        let to_light = light.position - p;
+        let dist_sq = dot(to_light, to_light);
+        let dir = to_light / sqrt(dist_sq);
+        let n_dot_l = dot(n, dir);
+        let falloff = spot_falloff(light, -dir);
+        if n_dot_l > 0.0 && falloff > 0.0 {
+            let e = luminance(light.intensity) * falloff * n_dot_l / dist_sq;
+            unshadowed += e;
+            if !trace_shadow(Ray(origin, dir), sqrt(dist_sq)) {
+                lit += e;
+            }
+        }
+    }
+
+    if camera.skybox_mode == SKY_SUN_SKY && camera.sun_direction.y > 0.0 {
+        let sun_dir = sample_sun_direction();
+        let n_dot_l = dot(n, sun_dir);
+        if n_dot_l > 0.0 {
+            let e = luminance(sun_radiance()) * n_dot_l * TWO_PI * (1.0 - sun_cos_max());
+            unshadowed += e;
+            if !trace_shadow(Ray(origin, sun_dir), MAX_T) {
+                lit += e;
+            }
+        }
+    }
+
+    return select(1.0, lit / unshadowed, unshadowed > 0.0);
+}
+
+// Closest hit of a camera ray between the near and far clip distances. The
+// ray starts at the near clip, so shapes it cuts through show their inside.
+fn trace_camera_ray(ray: Ray) -> HitRecord {
+    let start = Ray(ray.origin + ray.direction * camera.near_clip, ray.direction);
+    let far = select(MAX_T, camera.far_clip, camera.far_clip > 0.0);
+    var hit = trace_bvh_range(start, far - camera.near_clip);
+    hit.t += camera.near_clip;
+    primary_object = select(0u, hit.object_idx + 1u, hit.hit);
+    return hit;
+}
+
+// Flat preview shading: base color (with texture) lit by a headlight at the
+// camera plus a little ambient, emitters at full strength, sky as usual.
+fn shade_flat(ray: Ray) -> vec4f {
+    let hit = trace_camera_ray(ray);
+    if !hit.hit {
+        if camera.transparent_background != 0u {
+            return vec4f(0.0);
+        }
+        return vec4f(sample_skybox(ray.direction, true), 1.0);
+    }
+    let fig = figures[hit.figure_idx];
+    if fig.csg_op == FIGURE_BAKED_COLORS {
+        return vec4f(baked_color(fig, hit.position), 1.0);
+    }
+    var mat = materials[fig.material_idx];
+    if uses_orbit_trap(fig) {
+        mat.base_color *= orbit_trap_tint(hit.uv);
+    }
+    let tex_color = sample_texture(mat.texture_id, hit.uv * fig.texture_scale);
+    if mat.emission_strength > 0.0 {
+        return vec4f(material_emission(mat, tex_color), 1.0);
+    }
+    let headlight = abs(dot(hit.normal, ray.direction));
+    return vec4f(mat.base_color * tex_color * (0.15 + 0.85 * headlight), 1.0);
+}
 
-    // Tone map and write output
-    let color = apply_tonemap(accumulated, camera.exposure);
-    textureStore(output, pixel, vec4f(color, 1.0));
+// Debug views: the world-space normal as returned by the intersection (not
+// flipped towards the ray, so inverted faces stand out) mapped to RGB, or
+// the wrapped texture UV in red/green. Misses are black.
+fn shade_debug(ray: Ray) -> vec4f {
+    let hit = trace_camera_ray(ray);
+    if !hit.hit {
+        return vec4f(0.0, 0.0, 0.0, select(1.0, 0.0, camera.transparent_background != 0u));
+    }
+    if camera.view_mode == VIEW_NORMALS {
+        return vec4f(hit.normal * 0.5 + 0.5, 1.0);
+    }
+    let uv = fract(hit.uv * figures[hit.figure_idx].texture_scale);
+    return vec4f(uv, 0.0, 1.0);
 }
 
-fn trace_path(initial_ray: Ray) -> vec3f {
+fn trace_path(initial_ray: Ray) -> vec4f {
     var ray = initial_ray;
     var throughput = vec3f(1.0);
     var radiance = vec3f(0.0);
 
     let num_lights = arrayLength(&light_indices);
+    let sun_lit = camera.skybox_mode == SKY_SUN_SKY && camera.sun_direction.y > 0.0;
+    // Whether the last scattering event skipped NEE (camera ray or specular/glass
+    // bounce), in which case a ray reaching the sun must pick up its radiance.
+    var specular_bounce = true;
+    // Whether the path is still the camera ray, possibly continued through
+    // shadow catchers, and how much direct light those catchers received.
+    var camera_path = true;
+    var catcher_visibility = 1.0;
 
-    for (var bounce = 0u; bounce < camera.max_bounces; bounce++) {
-        let hit = trace_bvh(ray);
+    // Bounces left for this path. Materials with their own max_bounces reset
+    // it when hit, so the loop bound only stops budgets being refilled forever.
+    var budget = camera.max_bounces;
+    let max_path_bounces = max(camera.max_bounces, MAX_MATERIAL_BOUNCES);
+    for (var bounce = 0u; bounce < max_path_bounces && budget > 0u; bounce++) {
+        budget -= 1u;
+        // Only the primary ray is clipped to the camera's near/far range.
+        var hit: HitRecord;
+        if bounce == 0u {
+            hit = trace_camera_ray(ray);
+        } else {
+            hit = trace_bvh(ray);
+        }
         if !hit.hit {
+            if camera_path && camera.transparent_background != 0u {
+                // Black, as opaque as the shadow caught on the way.
+                return vec4f(0.0, 0.0, 0.0, 1.0 - catcher_visibility);
+            }
             // Sky contribution
-            radiance += throughput * sample_skybox(ray.direction);
+            radiance += throughput * sample_skybox(ray.direction, specular_bounce);
             break;
         }
 
         let fig = figures[hit.figure_idx];
         var mat = materials[fig.material_idx];
 
-        // Apply texture: modulate base_color
+        // Shadow catchers let the ray carry on unchanged; seen from the camera
+        // they darken what lies behind by the shadow they receive.
+        if (mat.shadow_flags & MATERIAL_SHADOW_CATCHER) != 0u {
+            if camera_path {
+                let n = select(hit.normal, -hit.normal, dot(hit.normal, ray.direction) > 0.0);
+                let visibility = shadow_catcher_visibility(hit.position, n);
+                catcher_visibility *= visibility;
+                throughput *= visibility;
+            }
+            ray = Ray(hit.position + ray.direction * EPSILON * 2.0, ray.direction);
+            continue;
+        }
+        camera_path = false;
+
+        // Baked triangles show their stored lighting and scatter nothing.
+        if fig.csg_op == FIGURE_BAKED_COLORS {
+            radiance += throughput * baked_color(fig, hit.position);
+            break;
+        }
+
+        if uses_orbit_trap(fig) {
+            mat.base_color *= orbit_trap_tint(hit.uv);
+        }
+
+        // Apply texture: modulate base_color. HDR texels above 1 only
+        // brighten emission; a surface can't reflect more than it receives.
         let tex_uv = hit.uv * fig.texture_scale;
         let tex_color = sample_texture(mat.texture_id, tex_uv);
-        mat.base_color = mat.base_color * tex_color;
+        mat.base_color = mat.base_color * min(tex_color, vec3f(1.0));
 
         // Emission: always add on specular/first bounce; on diffuse bounces NEE
         // already sampled this light, so ideally we'd apply a MIS weight here.
         // For now, add unconditionally (double-counting is acceptable at this
         // roughness level and the simpler code avoids storing the previous pdf).
         if mat.emission_strength > 0.0 {
-            let le = mat.emission * mat.emission_strength;
-            radiance += throughput * le;
+            radiance += throughput * material_emission(mat, tex_color);
             break;
         }
 
@@ -93,29 +321,40 @@ fn trace_path(initial_ray: Ray) -> vec3f {
             n = -n;
         }
 
+        // Per-material depth budget, counting this bounce: direct lighting is
+        // still gathered at the last bounce, but the path doesn't scatter any further.
+        if mat.max_bounces > 0u {
+            budget = mat.max_bounces - 1u;
+        }
+        let last_bounce = budget == 0u;
+        let receives_shadows = (mat.shadow_flags & MATERIAL_NO_RECEIVE_SHADOW) == 0u;
+
         // Glass/transmission
         if mat.transmission > 0.5 {
+            if last_bounce {
+                break;
+            }
             let glass_sample = sample_glass(wo, n, mat);
             if length(glass_sample.direction) < 0.001 {
                 break;
             }
             throughput *= glass_sample.brdf_cos;
             ray = Ray(hit.position + glass_sample.direction * EPSILON * 2.0, glass_sample.direction);
+            specular_bounce = true;
             continue;
         }
 
+        // #if NEE
         // NEE: Direct light sampling (for non-specular surfaces)
-        if mat.roughness > 0.04 && num_lights > 0u {
-            // Pick a random light
-            let light_pick = u32(rand_f32() * f32(num_lights));
-            let light_idx_safe = min(light_pick, num_lights - 1u);
-            let light_fig_idx = light_indices[light_idx_safe];
-            let light_fig = figures[light_fig_idx];
+        let light_entry = light_indices[pick_area_light(rand_f32(), num_lights)];
+        if mat.roughness > 0.04 && light_entry.pdf > 0.0 {
+            // Pick a light proportionally to its power
+            let light_fig = figures[light_entry.shape_index];
             let light_mat = materials[light_fig.material_idx];
 
             // Sample a point on the light
-            let light_point = sample_sphere_light(light_fig, hit.position);
-            let to_light = light_point - hit.position;
+            let light_sample = sample_area_light(light_fig);
+            let to_light = light_sample.position - hit.position;
             let light_dist = length(to_light);
             let light_dir = to_light / light_dist;
 
@@ -123,17 +362,21 @@ fn trace_path(initial_ray: Ray) -> vec3f {
             if n_dot_l > 0.0 {
                 // Shadow ray
                 let shadow_ray = Ray(hit.position + n * EPSILON * 2.0, light_dir);
-                let occluded = trace_shadow(shadow_ray, light_dist);
+                let occluded = receives_shadows && trace_shadow(shadow_ray, light_dist);
 
                 if !occluded {
-                    let le = light_mat.emission * light_mat.emission_strength;
-
                     // Light surface normal at sampled point
-                    let light_normal = normalize(light_point - light_fig.position);
+                    let light_normal = light_sample.normal;
+                    var light_tex = vec3f(1.0);
+                    if light_mat.emission_uses_texture != 0u {
+                        let light_uv = light_sample.uv * light_fig.texture_scale;
+                        light_tex = sample_texture(light_mat.texture_id, light_uv);
+                    }
+                    let le = material_emission(light_mat, light_tex);
                     let cos_light = abs(dot(-light_dir, light_normal));
 
                     // PDF conversions
-                    let light_area_pdf = sphere_light_pdf(light_fig, hit.position);
+                    let light_area_pdf = area_light_pdf(light_fig);
                     let light_solid_pdf = area_to_solid_angle_pdf(
                         light_area_pdf, light_dist * light_dist, cos_light
                     );
@@ -143,16 +386,75 @@ fn trace_path(initial_ray: Ray) -> vec3f {
 
                     // MIS weight
                     let brdf_pdf_val = n_dot_l * INV_PI; // Approximate BRDF pdf
-                    let w = mis_weight(light_solid_pdf * f32(num_lights), brdf_pdf_val);
+                    let w = mis_weight(light_solid_pdf * light_entry.pdf, brdf_pdf_val);
 
                     if light_solid_pdf > 0.0 {
                         radiance += throughput * le * brdf * n_dot_l * w
-                            / (light_solid_pdf * f32(num_lights));
+                            / (light_solid_pdf * light_entry.pdf);
                     }
                 }
             }
         }
 
+        // NEE: delta lights (spot lights) can only be reached by explicit sampling
+        if mat.roughness > 0.04 {
+            let num_delta = arrayLength(&lights);
+            for (var li = 0u; li < num_delta; li++) {
+                let light = lights[li];
+                if light.light_type != LIGHT_SPOT {
+                    continue;
+                }
+                let to_light = light.position - hit.position;
+                let dist_sq = dot(to_light, to_light);
+                let light_dir = to_light / sqrt(dist_sq);
+                let n_dot_l = dot(n, light_dir);
+                let falloff = spot_falloff(light, -light_dir);
+                if n_dot_l > 0.0 && falloff > 0.0 {
+                    let shadow_ray = Ray(hit.position + n * EPSILON * 2.0, light_dir);
+                    if !(receives_shadows && trace_shadow(shadow_ray, sqrt(dist_sq))) {
+                        let brdf = eval_brdf(wo, light_dir, n, mat);
+                        radiance += throughput * light.intensity * falloff * brdf * n_dot_l / dist_sq;
+                    }
+                }
+            }
+        }
+
+        // NEE: the sun disc acts as a directional light in sun+sky mode
+        if mat.roughness > 0.04 && sun_lit {
+            let sun_dir = sample_sun_direction();
+            let n_dot_l = dot(n, sun_dir);
+            if n_dot_l > 0.0 {
+                let shadow_ray = Ray(hit.position + n * EPSILON * 2.0, sun_dir);
+                if !(receives_shadows && trace_shadow(shadow_ray, MAX_T)) {
+                    let solid_angle = TWO_PI * (1.0 - sun_cos_max());
+                    let brdf = eval_brdf(wo, sun_dir, n, mat);
+                    radiance += throughput * sun_radiance() * brdf * n_dot_l * solid_angle;
+                }
+            }
+        }
+        // #endif
+
+        // Editor headlight: a light at the camera without distance falloff,
+        // so unlit scenes can still be edited. Scaled so a white diffuse
+        // surface facing the camera shows at `headlight` brightness.
+        if camera.headlight > 0.0 && mat.roughness > 0.04 {
+            let to_camera = camera.position - hit.position;
+            let camera_dist = length(to_camera);
+            let light_dir = to_camera / camera_dist;
+            let n_dot_l = dot(n, light_dir);
+            if n_dot_l > 0.0 {
+                let shadow_ray = Ray(hit.position + n * EPSILON * 2.0, light_dir);
+                if !(receives_shadows && trace_shadow(shadow_ray, camera_dist)) {
+                    let brdf = eval_brdf(wo, light_dir, n, mat);
+                    radiance += throughput * camera.headlight * PI * brdf * n_dot_l;
+                }
+            }
+        }
+
+        if last_bounce {
+            break;
+        }
+
         // BRDF importance sampling
         let brdf_sample = sample_brdf(wo, n, mat);
         if length(brdf_sample.direction) < 0.001 || brdf_sample.pdf < EPSILON {
@@ -161,7 +463,14 @@ fn trace_path(initial_ray: Ray) -> vec3f {
 
         throughput *= brdf_sample.brdf_cos / brdf_sample.pdf;
         ray = Ray(hit.position + brdf_sample.direction * EPSILON * 2.0, brdf_sample.direction);
+        // #if NEE
+        specular_bounce = mat.roughness <= 0.04;
+        // #else
+        // Without NEE the sun disc can only be reached by BRDF rays.
+        specular_bounce = true;
+        // #endif
 
+        // #if RUSSIAN_ROULETTE
         // Russian Roulette (after minimum bounces)
         if bounce >= MIN_BOUNCES_RR {
             let survival = min(max(throughput.x, max(throughput.y, throughput.z)), 0.95);
@@ -170,6 +479,7 @@ fn trace_path(initial_ray: Ray) -> vec3f {
             }
             throughput /= survival;
         }
+        // #endif
 
         // Firefly clamping
         let lum = luminance(throughput);
@@ -178,5 +488,5 @@ fn trace_path(initial_ray: Ray) -> vec3f {
         }
     }
 
-    return max(radiance, vec3f(0.0));
+    return vec4f(max(radiance, vec3f(0.0)), 1.0);
 }
@@ -1,6 +1,9 @@
+// #import types
+
 // Post-processing compute shader with corrected effects.
 // Reads from accumulation buffer, writes to output texture.
 // Supports chaining multiple effects in user-defined order.
+// In A/B compare mode, pixels at or right of `split_x` run the second chain.
 
 struct PostParams {
     width: u32,
@@ -10,9 +13,11 @@ struct PostParams {
     effects_0_3: vec4u,
     effects_4_7: vec4u,
     comic_levels: u32,
-    _pad2: u32,
-    _pad3: u32,
-    _pad4: u32,
+    split_x: u32,
+    effect_count_b: u32,
+    _pad: u32,
+    effects_b_0_3: vec4u,
+    effects_b_4_7: vec4u,
 }
 
 @group(0) @binding(0) var<uniform> params: PostParams;
@@ -29,9 +34,18 @@ const EFFECT_BW: u32 = 6u;
 const EFFECT_COMIC: u32 = 7u;
 const EFFECT_CASTING: u32 = 8u;
 
+// Accumulated radiance is premultiplied by coverage (alpha); effects work on
+// the straight color.
 fn read_pixel(pixel: vec2u) -> vec3f {
-    let idx = pixel.y * params.width + pixel.x;
-    return accum[idx].rgb;
+    let texel = read_texel(pixel);
+    return texel.rgb / max(texel.a, 1e-4);
+}
+
+// Accumulated value at `pixel`, with NaN/inf texels read as empty so they
+// can't spread through neighbourhood effects.
+fn read_texel(pixel: vec2u) -> vec4f {
+    let texel = accum[pixel.y * params.width + pixel.x];
+    return select(vec4f(0.0), texel, all_finite(texel));
 }
 
 fn read_pixel_clamped(x: i32, y: i32) -> vec3f {
@@ -40,7 +54,13 @@ fn read_pixel_clamped(x: i32, y: i32) -> vec3f {
     return read_pixel(vec2u(u32(cx), u32(cy)));
 }
 
-fn get_effect_id(i: u32) -> u32 {
+fn get_effect_id(i: u32, chain_b: bool) -> u32 {
+    if chain_b {
+        if i < 4u {
+            return params.effects_b_0_3[i];
+        }
+        return params.effects_b_4_7[i - 4u];
+    }
     if i < 4u {
         return params.effects_0_3[i];
     }
@@ -85,20 +105,28 @@ fn apply_single_effect(color: vec3f, pixel: vec2u, effect: u32) -> vec3f {
     }
 }
 
-@compute @workgroup_size(8, 8)
+@compute @workgroup_size({{WORKGROUP_X}}, {{WORKGROUP_Y}})
 fn main(@builtin(global_invocation_id) gid: vec3u) {
     let pixel = gid.xy;
     if pixel.x >= params.width || pixel.y >= params.height {
         return;
     }
 
+    let chain_b = pixel.x >= params.split_x;
+    let count = select(params.effect_count, params.effect_count_b, chain_b);
+    // An empty chain keeps the path tracer's tone-mapped output as-is.
+    if count == 0u {
+        return;
+    }
+
     var result = read_pixel(pixel);
-    for (var i = 0u; i < params.effect_count; i++) {
-        let eid = get_effect_id(i);
+    for (var i = 0u; i < count; i++) {
+        let eid = get_effect_id(i, chain_b);
         result = apply_single_effect(result, pixel, eid);
     }
 
-    textureStore(output, pixel, vec4f(result, 1.0));
+    let alpha = read_texel(pixel).a;
+    textureStore(output, pixel, vec4f(result, alpha));
 }
 
 // Real FXAA 3.11 (edge-detect + directional blur).
@@ -0,0 +1,97 @@
+// #import types
+// #import utils
+
+// Preetham et al. 1999 analytic daylight model ("A Practical Analytic Model
+// for Daylight"). Evaluated in CIE xyY and converted to linear sRGB.
+
+// Angular radius of the sun disc in radians (about twice the real sun so it
+// stays visible at typical resolutions).
+const SUN_ANGULAR_RADIUS: f32 = 0.0093;
+// Illuminance delivered by the sun disc per unit of skybox brightness.
+const SUN_ILLUMINANCE: f32 = 10.0;
+// Scales absolute zenith luminance (kcd/m²) into scene radiance units.
+const SKY_LUMINANCE_SCALE: f32 = 0.2;
+
+// Perez luminance distribution for the Y, x and y channels at once.
+fn perez(cos_theta: f32, gamma: f32, a: vec3f, b: vec3f, c: vec3f, d: vec3f, e: vec3f) -> vec3f {
+    let cos_gamma = cos(gamma);
+    return (1.0 + a * exp(b / max(cos_theta, 0.01)))
+        * (1.0 + c * exp(d * gamma) + e * cos_gamma * cos_gamma);
+}
+
+fn xyy_to_linear_srgb(yxy: vec3f) -> vec3f {
+    let big_y = yxy.x;
+    let x = yxy.y;
+    let y = max(yxy.z, 1e-4);
+    let big_x = x * big_y / y;
+    let big_z = (1.0 - x - y) * big_y / y;
+    return vec3f(
+        3.2406 * big_x - 1.5372 * big_y - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * big_y + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * big_y + 1.0570 * big_z
+    );
+}
+
+// Sky radiance (without the sun disc) for a view direction.
+fn preetham_sky(direction: vec3f) -> vec3f {
+    let t = camera.sky_turbidity;
+    let sun = camera.sun_direction;
+    let theta_s = acos(clamp(sun.y, 0.0, 1.0));
+
+    // Rays below the horizon see the horizon color.
+    let d = normalize(vec3f(direction.x, max(direction.y, 0.0), direction.z));
+    let gamma = acos(clamp(dot(d, sun), -1.0, 1.0));
+
+    let a = vec3f(0.1787 * t - 1.4630, -0.0193 * t - 0.2592, -0.0167 * t - 0.2608);
+    let b = vec3f(-0.3554 * t + 0.4275, -0.0665 * t + 0.0008, -0.0950 * t + 0.0092);
+    let c = vec3f(-0.0227 * t + 5.3251, -0.0004 * t + 0.2125, -0.0079 * t + 0.2102);
+    let dd = vec3f(0.1206 * t - 2.5771, -0.0641 * t - 0.8989, -0.0441 * t - 1.6537);
+    let e = vec3f(-0.0670 * t + 0.3703, -0.0033 * t + 0.0452, -0.0109 * t + 0.0529);
+
+    // Zenith luminance and chromaticity
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+    let zenith_y = (4.0453 * t - 4.9710) * tan(chi) - 0.2155 * t + 2.4192;
+    let th = vec3f(theta_s * theta_s * theta_s, theta_s * theta_s, theta_s);
+    let t2 = t * t;
+    let zenith_x = t2 * dot(vec3f(0.00166, -0.00375, 0.00209), th)
+        + t * (dot(vec3f(-0.02903, 0.06377, -0.03202), th) + 0.00394)
+        + dot(vec3f(0.11693, -0.21196, 0.06052), th) + 0.25886;
+    let zenith_yc = t2 * dot(vec3f(0.00275, -0.00610, 0.00317), th)
+        + t * (dot(vec3f(-0.04214, 0.08970, -0.04153), th) + 0.00516)
+        + dot(vec3f(0.15346, -0.26756, 0.06670), th) + 0.26688;
+    let zenith = vec3f(max(zenith_y, 0.0), zenith_x, zenith_yc);
+
+    let ratio = perez(d.y, gamma, a, b, c, dd, e) / perez(1.0, theta_s, a, b, c, dd, e);
+    let yxy = vec3f(zenith.x * ratio.x * SKY_LUMINANCE_SCALE, zenith.y * ratio.y, zenith.z * ratio.z);
+    return max(xyy_to_linear_srgb(yxy), vec3f(0.0)) * camera.skybox_brightness;
+}
+
+// Cosine of the sun disc's angular radius.
+fn sun_cos_max() -> f32 {
+    return cos(SUN_ANGULAR_RADIUS);
+}
+
+// Radiance of the sun disc, reddened by atmospheric extinction near the horizon.
+fn sun_radiance() -> vec3f {
+    let elevation = camera.sun_direction.y;
+    if elevation <= 0.0 {
+        return vec3f(0.0);
+    }
+    // Kasten-Young air mass approximation
+    let zenith_deg = degrees(acos(elevation));
+    let air_mass = 1.0 / (elevation + 0.50572 * pow(max(96.07995 - zenith_deg, 0.1), -1.6364));
+    let extinction = vec3f(0.03, 0.07, 0.16) * (0.5 + 0.25 * camera.sky_turbidity);
+    let transmittance = exp(-extinction * air_mass);
+    let solid_angle = TWO_PI * (1.0 - sun_cos_max());
+    return transmittance * SUN_ILLUMINANCE * camera.skybox_brightness / solid_angle;
+}
+
+// Uniformly sample a direction inside the sun cone. Pdf is 1 / solid_angle.
+fn sample_sun_direction() -> vec3f {
+    let r = rand_vec2();
+    let cos_theta = 1.0 - r.x * (1.0 - sun_cos_max());
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let phi = TWO_PI * r.y;
+    let local = vec3f(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+    return normalize(build_onb(camera.sun_direction) * local);
+}
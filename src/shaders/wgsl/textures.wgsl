@@ -4,11 +4,12 @@ struct TextureInfo {
     width: u32,
     height: u32,
     offset: u32,
-    _pad: u32,
+    hdr: u32,  // 1 = texels are in tex_float_pixels
 }
 
 @group(1) @binding(5) var<storage, read> tex_pixels: array<u32>;
 @group(1) @binding(6) var<storage, read> tex_infos: array<TextureInfo>;
+@group(1) @binding(10) var<storage, read> tex_float_pixels: array<vec4f>;
 
 fn sample_texture(texture_id: i32, uv: vec2f) -> vec3f {
     if texture_id < 0 {
@@ -20,6 +21,9 @@ fn sample_texture(texture_id: i32, uv: vec2f) -> vec3f {
     let px = clamp(u32(wrapped.x * f32(info.width)), 0u, info.width - 1u);
     let py = clamp(u32(wrapped.y * f32(info.height)), 0u, info.height - 1u);
     let idx = info.offset + py * info.width + px;
+    if info.hdr != 0u {
+        return tex_float_pixels[idx].rgb;  // linear, may exceed 1
+    }
     let packed = tex_pixels[idx];
     // Unpack 0xAABBGGRR
     let r = f32(packed & 0xFFu) / 255.0;
@@ -1,18 +1,32 @@
-// Tone mapping + sRGB gamma correction.
+// Tone mapping + output encoding (sRGB, linear or Rec.709).
 
-// ACES filmic tone mapping curve (Stephen Hill's fit).
-fn aces_tonemap(x: vec3f) -> vec3f {
+// ACES filmic tone mapping curve (Stephen Hill's fit), unclamped.
+fn aces_curve(x: vec3f) -> vec3f {
     let a = 2.51;
     let b = 0.03;
     let c = 2.43;
     let d = 0.59;
     let e = 0.14;
-    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3f(0.0), vec3f(1.0));
+    return (x * (a * x + b)) / (x * (c * x + d) + e);
 }
 
-// Reinhard tone mapping.
-fn reinhard_tonemap(x: vec3f) -> vec3f {
-    return x / (vec3f(1.0) + x);
+// ACES, rescaled so `white` maps to 1 when it is positive.
+fn aces_tonemap(x: vec3f, white: f32) -> vec3f {
+    var mapped = aces_curve(x);
+    if white > 0.0 {
+        mapped /= aces_curve(vec3f(white));
+    }
+    return clamp(mapped, vec3f(0.0), vec3f(1.0));
+}
+
+// Reinhard tone mapping; the extended operator when `white` is positive, so
+// that `white` maps to 1 instead of only approaching it at infinity.
+fn reinhard_tonemap(x: vec3f, white: f32) -> vec3f {
+    if white <= 0.0 {
+        return x / (vec3f(1.0) + x);
+    }
+    let mapped = x * (vec3f(1.0) + x / (white * white)) / (vec3f(1.0) + x);
+    return clamp(mapped, vec3f(0.0), vec3f(1.0));
 }
 
 // Linear to sRGB gamma correction.
@@ -22,21 +36,45 @@ fn linear_to_srgb(c: vec3f) -> vec3f {
     return select(hi, lo, c <= vec3f(0.0031308));
 }
 
-// Full tone mapping pipeline: exposure → tone map → sRGB.
-// camera.tone_mapper: 0=ACES, 1=Reinhard, 2=None
+// Linear to the BT.709 camera transfer curve.
+fn linear_to_rec709(c: vec3f) -> vec3f {
+    let lo = c * 4.5;
+    let hi = 1.099 * pow(c, vec3f(0.45)) - 0.099;
+    return select(hi, lo, c < vec3f(0.018));
+}
+
+// Encode tone-mapped linear color for camera.color_space. The primaries of
+// all three are the sRGB/Rec.709 ones, so only the transfer curve differs.
+fn encode_output(c: vec3f) -> vec3f {
+    switch camera.color_space {
+        case COLOR_SPACE_LINEAR: {
+            return c;
+        }
+        case COLOR_SPACE_REC709: {
+            return linear_to_rec709(c);
+        }
+        default: {
+            return linear_to_srgb(c);
+        }
+    }
+}
+
+// Full tone mapping pipeline: exposure → tone map → output encoding.
+// camera.tone_mapper: 0=ACES, 1=Reinhard, 2=None; camera.white_point
+// applies to the first two.
 fn apply_tonemap(color: vec3f, exposure: f32) -> vec3f {
     let exposed = color * exposure;
     var mapped: vec3f;
     switch camera.tone_mapper {
         case 1u: {
-            mapped = reinhard_tonemap(exposed);
+            mapped = reinhard_tonemap(exposed, camera.white_point);
         }
         case 2u: {
             mapped = clamp(exposed, vec3f(0.0), vec3f(1.0));
         }
         default: {
-            mapped = aces_tonemap(exposed);
+            mapped = aces_tonemap(exposed, camera.white_point);
         }
     }
-    return linear_to_srgb(mapped);
+    return encode_output(mapped);
 }
@@ -18,7 +18,25 @@ struct Camera {
     firefly_clamp: f32,
     skybox_brightness: f32,
     skybox_color: vec3f,
-    _pad2: f32,
+    skybox_mode: u32,
+    skybox_horizon_color: vec3f,
+    view_mode: u32,
+    sun_direction: vec3f,
+    sky_turbidity: f32,
+    region_origin: vec2u,
+    region_size: vec2u,
+    transparent_background: u32,  // 1 = escaped camera rays are transparent
+    white_point: f32,             // 0 = tone mapper's own curve
+    near_clip: f32,
+    far_clip: f32,                // 0 = no far clip
+    jitter_pattern: u32,          // JITTER_* for primary-ray sub-pixel offsets
+    headlight: f32,               // editor light at the camera, 0 = off
+    color_space: u32,             // COLOR_SPACE_* encoding of the output
+    stereo_mode: u32,             // STEREO_* eye views combined into the image
+    eye_separation: f32,          // distance between the stereo eyes
+    brute_force: u32,             // 1 = BVH is one leaf; skip bounds tests
+    _pad0: u32,
+    _pad1: u32,
 }
 
 struct Figure {
@@ -29,7 +47,7 @@ struct Figure {
     position: vec3f,
     height: f32,
     normal: vec3f,
-    csg_op: u32,    // 0=union (normal), 1=subtraction (negative)
+    csg_op: u32,    // 0=union (normal), 1=subtraction (negative), 2=baked triangle
     rotation: vec3f,
     texture_scale: f32,
     v0: vec3f,
@@ -49,8 +67,17 @@ struct Material {
     ior: f32,
     transmission: f32,
     texture_id: i32,
+    max_bounces: u32,   // 0 = use camera.max_bounces
+    emission_uses_texture: u32,  // 1 = emission is multiplied by the texture
+    shadow_flags: u32,  // MATERIAL_NO_CAST_SHADOW | MATERIAL_NO_RECEIVE_SHADOW | MATERIAL_SHADOW_CATCHER
+    cull_backfaces: u32,  // 1 = triangles are one-sided
 }
 
+// Material shadow flag bits (must match `scene::material::MATERIAL_*`)
+const MATERIAL_NO_CAST_SHADOW: u32 = 1u;
+const MATERIAL_NO_RECEIVE_SHADOW: u32 = 2u;
+const MATERIAL_SHADOW_CATCHER: u32 = 4u;
+
 struct BvhNode {
     aabb_min: vec3f,
     left_or_prim: u32,  // inner: right child idx, leaf: first prim idx
@@ -65,6 +92,7 @@ struct HitRecord {
     uv: vec2f,
     figure_idx: u32,
     hit: bool,
+    object_idx: u32,    // scene shape hit: the instance for model triangles
 }
 
 struct Ray {
@@ -72,6 +100,10 @@ struct Ray {
     direction: vec3f,
 }
 
+// `csg_op` of a triangle showing baked vertex colors (rgb9e5 bits in
+// `rotation`) unlit (must match `scene::shape::FIGURE_BAKED_COLORS`)
+const FIGURE_BAKED_COLORS: u32 = 2u;
+
 // Figure type constants
 const FIG_SPHERE: u32 = 0u;
 const FIG_PLANE: u32 = 1u;
@@ -90,9 +122,66 @@ const FIG_HYPERBOLOID: u32 = 13u;
 const FIG_MEBIUS: u32 = 14u;
 const FIG_PYRAMID: u32 = 15u;
 const FIG_TETRAHEDRON: u32 = 16u;
+const FIG_SPOTLIGHT: u32 = 17u;
+const FIG_INSTANCE: u32 = 18u;
+
+// Power-weighted area light entry of `light_indices` (must match `GpuAreaLight`).
+struct AreaLight {
+    shape_index: u32,
+    pdf: f32,
+    cdf: f32,
+}
+
+// Delta light from the dedicated light buffer (must match `GpuLight`).
+struct Light {
+    position: vec3f,
+    light_type: u32,
+    direction: vec3f,
+    cos_inner: f32,
+    intensity: vec3f,
+    cos_outer: f32,
+}
+
+// Light type constants (must match `scene::lights::LIGHT_*`)
+const LIGHT_NONE: u32 = 0u;
+const LIGHT_SPOT: u32 = 1u;
+
+// View mode constants (must match `ViewMode::as_u32`)
+const VIEW_PATH_TRACED: u32 = 0u;
+const VIEW_FLAT: u32 = 1u;
+const VIEW_NORMALS: u32 = 2u;
+const VIEW_UV: u32 = 3u;
+
+// Primary-ray jitter constants (must match `JitterPattern::as_u32`)
+const JITTER_RANDOM: u32 = 0u;
+const JITTER_STRATIFIED: u32 = 1u;
+const JITTER_BLUE_NOISE: u32 = 2u;
+
+// Output color space constants (must match `ColorSpace::as_u32`)
+const COLOR_SPACE_SRGB: u32 = 0u;
+const COLOR_SPACE_LINEAR: u32 = 1u;
+const COLOR_SPACE_REC709: u32 = 2u;
+
+// Stereo mode constants (must match `StereoMode::as_u32`)
+const STEREO_OFF: u32 = 0u;
+const STEREO_SIDE_BY_SIDE: u32 = 1u;
+const STEREO_ANAGLYPH: u32 = 2u;
+
+// Skybox mode constants (must match `SkyboxMode::as_u32`)
+const SKY_SOLID_COLOR: u32 = 0u;
+const SKY_GRADIENT: u32 = 1u;
+const SKY_HDR_MAP: u32 = 2u;
+const SKY_SUN_SKY: u32 = 3u;
 
 const PI: f32 = 3.14159265359;
 const TWO_PI: f32 = 6.28318530718;
 const INV_PI: f32 = 0.31830988618;
 const EPSILON: f32 = 0.0001;
 const MAX_T: f32 = 1e20;
+
+// Whether no component of `v` is NaN or infinite. Tests the exponent bits,
+// since the compiler may assume floats are finite and fold `x != x` away.
+fn all_finite(v: vec4f) -> bool {
+    let exponent = bitcast<vec4u>(v) & vec4u(0x7f800000u);
+    return all(exponent != vec4u(0x7f800000u));
+}
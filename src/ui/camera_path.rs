@@ -0,0 +1,97 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use egui::{Context, RichText};
+
+use super::{Pointer, UiActions, UiState};
+use crate::constants::MAX_CAMERA_PATH_DURATION_SECS;
+
+/// Floating window for keyframing camera views and playing them back.
+pub fn draw_camera_path(ctx: &Context, state: &mut UiState, actions: &mut UiActions) {
+    let mut open = state.camera_path_open;
+    egui::Window::new("Camera Path")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .default_width(280.0)
+        .show(ctx, |ui| {
+            if ui
+                .button("➕ Add Keyframe")
+                .on_hover_text("Append the current view to the path")
+                .pointer()
+                .clicked()
+            {
+                actions.add_keyframe = true;
+            }
+
+            if state.keyframe_times.is_empty() {
+                ui.label(RichText::new("No keyframes yet.").weak());
+            }
+            let mut times_changed = false;
+            for (idx, time) in state.keyframe_times.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("#{}", idx + 1));
+                    let response = ui.add(
+                        egui::DragValue::new(time)
+                            .speed(0.05)
+                            .range(0.0..=f32::MAX)
+                            .suffix(" s"),
+                    );
+                    // Reorder only once editing is done, so rows don't jump
+                    // around under the cursor.
+                    times_changed |= response.drag_stopped() || response.lost_focus();
+                    if ui
+                        .small_button("Go")
+                        .on_hover_text("Move the camera to this keyframe")
+                        .pointer()
+                        .clicked()
+                    {
+                        actions.goto_keyframe = Some(idx);
+                    }
+                    if ui.small_button("🗑").pointer().clicked() {
+                        actions.remove_keyframe = Some(idx);
+                    }
+                });
+            }
+            actions.keyframe_times_changed |= times_changed;
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Duration:");
+                ui.add(
+                    egui::Slider::new(
+                        &mut state.path_duration,
+                        0.0..=MAX_CAMERA_PATH_DURATION_SECS,
+                    )
+                    .suffix(" s")
+                    .custom_formatter(|v, _| {
+                        if v == 0.0 {
+                            "As keyed".to_string()
+                        } else {
+                            format!("{v:.1}")
+                        }
+                    }),
+                )
+                .on_hover_text("Time the whole path takes to play; 0 uses the keyframe times");
+            });
+            let label = if state.path_playing {
+                "⏹ Stop"
+            } else {
+                "▶ Play"
+            };
+            if ui
+                .add_enabled(state.keyframe_times.len() >= 2, egui::Button::new(label))
+                .pointer()
+                .clicked()
+            {
+                actions.toggle_path_playback = true;
+            }
+            ui.label(
+                RichText::new(
+                    "Saved with the scene. Batch jobs with `frames` render the path to images.",
+                )
+                .weak(),
+            );
+        });
+    state.camera_path_open = open;
+}
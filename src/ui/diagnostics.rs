@@ -0,0 +1,40 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use egui::{Color32, Context, RichText};
+
+use super::{Pointer, UiActions, UiState};
+
+/// Floating window listing detected scene issues with a hint for each.
+pub fn draw_diagnostics(ctx: &Context, state: &mut UiState, actions: &mut UiActions) {
+    let mut open = state.diagnostics_open;
+    egui::Window::new("Scene Diagnostics")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            if state.scene_issues.is_empty() {
+                ui.label("No problems detected.");
+            }
+            for issue in &state.scene_issues {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("⚠").color(Color32::YELLOW));
+                    ui.strong(issue.message());
+                    if let Some(idx) = issue.shape_index()
+                        && ui.small_button("Select").pointer().clicked()
+                    {
+                        state.selected_shape = Some(idx);
+                        actions.selected_shape = Some(idx);
+                    }
+                });
+                ui.label(RichText::new(issue.hint()).weak());
+                ui.add_space(4.0);
+            }
+            ui.separator();
+            if ui.button("Re-check").pointer().clicked() {
+                actions.recheck_diagnostics = true;
+            }
+        });
+    state.diagnostics_open = open;
+}
@@ -1,19 +1,40 @@
 // Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+pub mod camera_path;
+pub mod diagnostics;
 pub mod object_editor;
+pub mod overlays;
+pub mod shader_console;
 pub mod toolbar;
 
 use egui::{Color32, Context, RichText};
 
 use std::path::PathBuf;
 
+use crate::accel::bvh::{BvhParams, BvhStats};
 use crate::constants::{
-    DEFAULT_COMIC_LEVELS, DEFAULT_FIREFLY_CLAMP, DEFAULT_FRACTAL_MARCH_STEPS, DEFAULT_MAX_BOUNCES,
-    DEFAULT_OIL_RADIUS, DEFAULT_SKYBOX_BRIGHTNESS, DEFAULT_SKYBOX_COLOR, DEFAULT_TONE_MAPPER,
+    CAMERA_DEFAULT_MOVE_SPEED, CAMERA_DEFAULT_SENSITIVITY, DEFAULT_COMIC_LEVELS, DEFAULT_FAR_CLIP,
+    DEFAULT_FIREFLY_CLAMP, DEFAULT_FRACTAL_MARCH_STEPS, DEFAULT_HEADLIGHT_STRENGTH,
+    DEFAULT_MAX_BOUNCES, DEFAULT_MAX_RENDER_SIZE, DEFAULT_NEAR_CLIP, DEFAULT_OIL_RADIUS,
+    DEFAULT_SKY_TURBIDITY, DEFAULT_SKYBOX_BRIGHTNESS, DEFAULT_SKYBOX_COLOR,
+    DEFAULT_SKYBOX_HORIZON_COLOR, DEFAULT_SPAWN_DISTANCE, DEFAULT_STEREO_IPD, DEFAULT_SUN_AZIMUTH,
+    DEFAULT_SUN_ELEVATION, DEFAULT_TONE_MAPPER, DEFAULT_WHITE_POINT,
+};
+use crate::gpu::buffers::GpuMemoryUsage;
+use crate::gpu::context::PresentModeChoice;
+use crate::render::frame::{
+    ColorSpace, JitterPattern, RenderRegion, ShaderFeatures, StereoMode, ViewMode,
 };
 use crate::render::post_process::PostEffect;
+use crate::scene::diagnostics::{MissingAsset, SceneIssue};
+use crate::scene::lights::LightGroup;
+use crate::scene::scene::{CameraConfig, SkyboxMode, UpAxis};
 use crate::scene::shape::{Shape, ShapeType};
+use crate::settings::SpawnPlacement;
+
+/// Marks render settings that differ from the scene file.
+const SCENE_DIFF_COLOR: Color32 = Color32::from_rgb(255, 170, 0);
 
 /// Extension trait that sets a pointing-hand cursor on hover for interactive widgets.
 pub(crate) trait Pointer {
@@ -26,6 +47,53 @@ impl Pointer for egui::Response {
     }
 }
 
+/// Small button that restores `value` to `default`, greyed out while it
+/// already has that value. Returns true when clicked.
+pub fn reset_button<T: PartialEq + Copy>(ui: &mut egui::Ui, value: &mut T, default: T) -> bool {
+    let clicked = ui
+        .add_enabled(*value != default, egui::Button::new("⟲").small())
+        .on_hover_text("Reset to default")
+        .pointer()
+        .clicked();
+    if clicked {
+        *value = default;
+    }
+    clicked
+}
+
+/// Dot shown next to a render setting that differs from `scene`, the scene
+/// file's value; clicking it reverts the setting. Returns true when clicked.
+pub fn scene_diff_marker<T: PartialEq + Copy>(ui: &mut egui::Ui, value: &mut T, scene: T) -> bool {
+    if *value == scene {
+        return false;
+    }
+    let clicked = ui
+        .add(egui::Button::new(RichText::new("●").color(SCENE_DIFF_COLOR)).frame(false))
+        .on_hover_text("Differs from the scene file. Click to revert")
+        .pointer()
+        .clicked();
+    if clicked {
+        *value = scene;
+    }
+    clicked
+}
+
+/// Slider built by `slider` followed by a reset button; returns true if the
+/// value changed either way. Clicking the slider's number lets the user type
+/// an exact value.
+pub fn slider_with_reset<T: egui::emath::Numeric>(
+    ui: &mut egui::Ui,
+    value: &mut T,
+    default: T,
+    slider: impl FnOnce(&mut T) -> egui::Slider<'_>,
+) -> bool {
+    ui.horizontal(|ui| {
+        let changed = ui.add(slider(value)).pointer().changed();
+        reset_button(ui, value, default) || changed
+    })
+    .inner
+}
+
 #[derive(Default)]
 pub struct UiActions {
     pub open_screenshot_dialog: bool,
@@ -51,34 +119,267 @@ pub struct UiActions {
     pub open_import_model_dialog: bool,
     /// Open a bundled example scene by its resolved path.
     pub open_example_scene: Option<PathBuf>,
+    /// Re-run scene diagnostics on demand (e.g. after moving the camera).
+    pub recheck_diagnostics: bool,
+    /// A light group's multiplier or on/off state changed.
+    pub light_groups_changed: bool,
+    /// Reconfigure the surface with a different present mode.
+    pub present_mode_changed: Option<PresentModeChoice>,
+    /// Mouse sensitivity, move speed or invert-Y changed.
+    pub controls_changed: bool,
+    /// `UiState::ui_scale` was set or cleared.
+    pub ui_scale_changed: bool,
+    /// The render region was set or cleared.
+    pub render_region_changed: bool,
+    /// Switched between path tracing and the flat preview.
+    pub view_mode_changed: bool,
+    /// The maximum render resolution or supersampling changed; size-dependent
+    /// buffers must be rebuilt.
+    pub render_size_changed: bool,
+    /// A shader feature was toggled; the path-trace pipeline must be recompiled.
+    pub shader_features_changed: bool,
+    /// Pick a new file for this missing asset.
+    pub relocate_asset: Option<MissingAsset>,
+    /// Return the camera to the scene file's view.
+    pub reset_camera: bool,
+    /// Return render settings to the scene file's values.
+    pub reset_render_settings: bool,
+    /// Switch to this scene camera (see `Scene::camera_config`).
+    pub camera_selected: Option<usize>,
+    /// Give the selected instance the camera's orientation.
+    pub align_selected_to_camera: bool,
+    /// Bake lighting into the vertex colors of the selected triangle's mesh.
+    pub bake_selected_mesh: bool,
+    /// Drop the selected mesh's baked colors and shade it normally again.
+    pub clear_selected_bake: bool,
+    /// Turn the selected plane or disc, or the selected mesh's triangles,
+    /// to face the other way.
+    pub flip_selected_normals: bool,
+    /// Append the current view to the camera path.
+    pub add_keyframe: bool,
+    pub remove_keyframe: Option<usize>,
+    /// `UiState::keyframe_times` was edited.
+    pub keyframe_times_changed: bool,
+    /// Move the camera to this keyframe.
+    pub goto_keyframe: Option<usize>,
+    /// Start or stop camera path playback.
+    pub toggle_path_playback: bool,
+    /// Give every shape of this type the selected shape's material.
+    pub material_to_type: Option<ShapeType>,
+    /// Render only the selected shape, or the whole scene again.
+    pub toggle_solo: bool,
+    /// Look through the selected light, or go back to the previous camera.
+    pub toggle_light_view: bool,
+    /// Move the camera to frame this shape (double-click in the shape list).
+    pub fly_to_shape: Option<usize>,
+    /// `UiState::bvh_params` changed; rebuild the BVHs with them.
+    pub bvh_params_changed: bool,
+    /// Recompile the path tracer with `UiState::user_bsdf_source`.
+    pub apply_user_bsdf: bool,
+    /// Go back to the user BSDF module shipped on disk.
+    pub revert_user_bsdf: bool,
+}
+
+impl UiActions {
+    /// Whether applying these actions invalidates the accumulated samples.
+    /// Pausing, dialogs, diagnostics and post-effect tweaks only affect the
+    /// display pass, so they keep the current image converging.
+    pub fn resets_accumulation(&self) -> bool {
+        self.exposure_changed.is_some()
+            || self.max_bounces_changed.is_some()
+            || self.render_settings_changed
+            || self.light_groups_changed
+            || self.render_region_changed
+            || self.view_mode_changed
+            || self.render_size_changed
+            || self.shader_features_changed
+            || self.reset_camera
+            || self.reset_render_settings
+            || self.camera_selected.is_some()
+            || self.align_selected_to_camera
+            || self.bake_selected_mesh
+            || self.clear_selected_bake
+            || self.flip_selected_normals
+            || self.goto_keyframe.is_some()
+            || self.fly_to_shape.is_some()
+            || self.toggle_solo
+            || self.toggle_light_view
+            || self.material_to_type.is_some()
+            || self.scene_dirty
+            || self.shape_to_add.is_some()
+            || self.shape_to_delete.is_some()
+    }
+
+    /// Whether the post-process uniform needs rebuilding. Post effects run on
+    /// the accumulated image, so this alone never restarts accumulation.
+    pub fn updates_post_params(&self) -> bool {
+        self.post_effect_params_changed || self.effects_changed.is_some()
+    }
 }
 
 pub struct UiState {
     pub paused: bool,
+    pub view_mode: ViewMode,
     pub active_effects: Vec<PostEffect>,
     pub exposure: f32,
     pub max_bounces: u32,
     pub selected_shape: Option<usize>,
+    /// Text the toolbar shape list is narrowed to.
+    pub shape_search: String,
+    /// Shape type the toolbar shape list is narrowed to, if any.
+    pub shape_type_filter: Option<ShapeType>,
+    /// Names of the scene's cameras; the toolbar offers a switch when there
+    /// is more than one.
+    pub camera_names: Vec<String>,
+    pub active_camera: usize,
+    /// Render settings of the active camera as the scene file has them, for
+    /// `scene_diff_marker`.
+    pub scene_camera: CameraConfig,
+    pub camera_path_open: bool,
+    /// Times of the camera path keyframes, in order.
+    pub keyframe_times: Vec<f32>,
+    /// Seconds a playback of the whole path takes, 0 = the keyframe times.
+    pub path_duration: f32,
+    pub path_playing: bool,
     pub fps: f32,
+    /// Estimated VRAM of the major GPU resources, refreshed every frame.
+    pub gpu_memory: GpuMemoryUsage,
     pub sample_count: u32,
+    /// Stop accumulating after this many samples, 0 = never.
+    pub target_samples: u32,
     pub render_elapsed_secs: f32,
     pub save_dialog_open: bool,
     pub save_filename: String,
+    /// Up axis assumed for models imported into the current scene.
+    pub model_up_axis: UpAxis,
+    /// Run `model::cleanup::merge_coplanar` on imported OBJ models.
+    pub merge_coplanar_on_import: bool,
+    /// Add a ground plane under imported OBJ models.
+    pub ground_plane_on_import: bool,
+    /// Switch to the camera of scenes imported into the current one.
+    pub import_scene_camera: bool,
     pub confirm_delete_shape: Option<usize>,
     pub confirm_overwrite_save: bool,
     pub firefly_clamp: f32,
+    pub skybox_mode: SkyboxMode,
     pub skybox_color: [f32; 3],
+    pub skybox_horizon_color: [f32; 3],
     pub skybox_brightness: f32,
+    pub transparent_background: bool,
+    pub sun_azimuth: f32,
+    pub sun_elevation: f32,
+    pub sky_turbidity: f32,
     pub tone_mapper: u32,
+    pub white_point: f32,
     pub fractal_march_steps: u32,
+    pub near_clip: f32,
+    /// 0 = no far clip.
+    pub far_clip: f32,
+    /// Frame-rate cap in frames per second, 0 = uncapped.
+    pub fps_cap: u32,
+    /// Most path-trace samples per frame.
+    pub samples_per_frame: u32,
+    /// Render at reduced resolution while the camera moves.
+    pub dynamic_resolution: bool,
+    /// Adapt exposure to the scene's brightness; `exposure` becomes a bias.
+    pub auto_exposure: bool,
+    /// Current auto-exposure multiplier, shown next to the exposure slider.
+    pub auto_exposure_scale: f32,
+    /// Longest side of the internal render resolution, 0 = match the window.
+    pub max_render_size: u32,
+    /// Render at this multiple of the window size; the blit averages it down.
+    pub supersampling: f32,
+    /// Internal render resolution when capped below the (supersampled) window size.
+    pub clamped_render_size: Option<(u32, u32)>,
+    /// Sharpen the image when the blit upscales it.
+    pub sharpen_upscale: bool,
+    /// Rescale accumulated samples on a window resize instead of discarding them.
+    pub keep_samples_on_resize: bool,
+    pub shader_features: ShaderFeatures,
+    pub present_mode: PresentModeChoice,
+    pub supported_present_modes: Vec<PresentModeChoice>,
+    pub mouse_sensitivity: f32,
+    pub move_speed: f32,
+    pub invert_y: bool,
+    /// Grid step for snapping dragged shapes, 0 = off.
+    pub grid_snap: f32,
+    pub spawn_placement: SpawnPlacement,
+    pub spawn_distance: f32,
+    pub jitter_pattern: JitterPattern,
+    /// UI pixels per point, `None` following the display's scale factor.
+    pub ui_scale: Option<f32>,
+    pub color_space: ColorSpace,
+    pub stereo_mode: StereoMode,
+    pub stereo_ipd: f32,
+    /// Editor headlight, used only while `scene_is_lit` says the scene is dark.
+    pub headlight: bool,
+    pub headlight_strength: f32,
+    /// Whether the headlight is lighting the current frame.
+    pub headlight_active: bool,
+    /// Draw a crosshair at the view's center and inspect what it points at.
+    pub crosshair: bool,
+    /// Shape under the crosshair, with the hit point and its distance.
+    pub crosshair_hit: Option<(usize, [f32; 3], f32)>,
+    /// Accumulated linear color of the pixel under the crosshair.
+    pub crosshair_color: Option<[f32; 3]>,
+    /// Mark every light in the viewport with a clickable icon.
+    pub light_gizmos: bool,
+    /// Lights in view with their window-pixel positions, while
+    /// `light_gizmos` is on.
+    pub light_gizmo_points: Vec<(usize, [f32; 2])>,
+    /// Started with `--dev`: offer the developer tools.
+    pub dev_mode: bool,
+    pub shader_console_open: bool,
+    /// Source of the user BSDF module as edited in the shader console.
+    pub user_bsdf_source: String,
+    /// Composed `path_trace` source of the running pipeline (`--dev` only).
+    pub composed_trace_source: String,
+    /// Compile error of the last edit applied from the shader console.
+    pub shader_console_error: Option<String>,
+    /// Whether only the selected shape is rendered (see `AppState::toggle_solo`).
+    pub solo: bool,
+    /// Render a black sky while solo is on.
+    pub solo_hides_sky: bool,
+    /// Whether the camera is looking through a light (see `AppState::toggle_light_view`).
+    pub light_view: bool,
+    pub bvh_params: BvhParams,
+    /// Shape of the last built scene BVH and how long the build took.
+    pub bvh_stats: BvhStats,
+    pub bvh_build_ms: f32,
+    /// Restrict path tracing to this rectangle (physical pixels).
+    pub render_region: Option<RenderRegion>,
+    /// Next drag on the viewport draws a new render region.
+    pub drawing_render_region: bool,
     pub oil_radius: u32,
     pub comic_levels: u32,
+    /// A/B split-screen: the right side of the divider runs `compare_effects`.
+    pub compare_enabled: bool,
+    pub compare_effects: Vec<PostEffect>,
+    /// Divider position as a fraction of the window width.
+    pub compare_split: f32,
     /// Current scale for the selected model group (for the scale slider).
     pub model_scale: f32,
     /// Cached list of example scene stem names.
     pub example_scenes: Vec<String>,
     pub shortcuts_dialog_open: bool,
     pub about_dialog_open: bool,
+    /// Open the last scene on a launch without a scene argument.
+    pub reopen_last_scene: bool,
+    /// Draw no UI at all, only the rendered image (toggled with F1).
+    pub ui_hidden: bool,
+    /// Problems found by the last `scene::diagnostics::diagnose` run.
+    pub scene_issues: Vec<SceneIssue>,
+    pub diagnostics_open: bool,
+    /// Error shown in a modal until dismissed (e.g. scene too large for the GPU).
+    pub error_message: Option<String>,
+    /// Textures and models the scene refers to but that failed to load.
+    pub missing_assets: Vec<MissingAsset>,
+    /// Hint shown while a file is dragged over the window.
+    pub drop_hint: Option<String>,
+    /// Emissive shape groups with their render-time multipliers.
+    pub light_groups: Vec<LightGroup>,
+    /// A lighting bake is running in the background.
+    pub baking: bool,
 }
 
 impl UiState {
@@ -87,10 +388,19 @@ impl UiState {
         self.exposure = camera.exposure;
         self.max_bounces = camera.max_bounces;
         self.firefly_clamp = camera.firefly_clamp;
+        self.skybox_mode = camera.skybox_mode;
         self.skybox_color = camera.skybox_color;
+        self.skybox_horizon_color = camera.skybox_horizon_color;
         self.skybox_brightness = camera.skybox_brightness;
+        self.transparent_background = camera.transparent_background;
+        self.sun_azimuth = camera.sun_azimuth;
+        self.sun_elevation = camera.sun_elevation;
+        self.sky_turbidity = camera.sky_turbidity;
         self.tone_mapper = camera.tone_mapper;
+        self.white_point = camera.white_point;
         self.fractal_march_steps = camera.fractal_march_steps;
+        self.near_clip = camera.near_clip;
+        self.far_clip = camera.far_clip;
     }
 }
 
@@ -98,34 +408,119 @@ impl Default for UiState {
     fn default() -> Self {
         Self {
             paused: false,
+            view_mode: ViewMode::default(),
             active_effects: Vec::new(),
             exposure: 1.0,
             max_bounces: DEFAULT_MAX_BOUNCES,
             selected_shape: None,
+            shape_search: String::new(),
+            shape_type_filter: None,
+            camera_names: Vec::new(),
+            active_camera: 0,
+            scene_camera: CameraConfig::default(),
+            camera_path_open: false,
+            keyframe_times: Vec::new(),
+            path_duration: 0.0,
+            path_playing: false,
             fps: 0.0,
+            gpu_memory: GpuMemoryUsage::default(),
             sample_count: 0,
+            target_samples: 0,
             render_elapsed_secs: 0.0,
             save_dialog_open: false,
             save_filename: "scene_saved.yaml".to_string(),
+            model_up_axis: UpAxis::Y,
+            merge_coplanar_on_import: false,
+            ground_plane_on_import: false,
+            import_scene_camera: false,
             confirm_delete_shape: None,
             confirm_overwrite_save: false,
             firefly_clamp: DEFAULT_FIREFLY_CLAMP,
+            skybox_mode: SkyboxMode::HdrMap,
             skybox_color: DEFAULT_SKYBOX_COLOR,
+            skybox_horizon_color: DEFAULT_SKYBOX_HORIZON_COLOR,
             skybox_brightness: DEFAULT_SKYBOX_BRIGHTNESS,
+            transparent_background: false,
+            sun_azimuth: DEFAULT_SUN_AZIMUTH,
+            sun_elevation: DEFAULT_SUN_ELEVATION,
+            sky_turbidity: DEFAULT_SKY_TURBIDITY,
             tone_mapper: DEFAULT_TONE_MAPPER,
+            white_point: DEFAULT_WHITE_POINT,
             fractal_march_steps: DEFAULT_FRACTAL_MARCH_STEPS,
+            near_clip: DEFAULT_NEAR_CLIP,
+            far_clip: DEFAULT_FAR_CLIP,
+            fps_cap: 0,
+            samples_per_frame: 1,
+            dynamic_resolution: false,
+            auto_exposure: false,
+            auto_exposure_scale: 1.0,
+            max_render_size: DEFAULT_MAX_RENDER_SIZE,
+            supersampling: 1.0,
+            clamped_render_size: None,
+            sharpen_upscale: false,
+            keep_samples_on_resize: false,
+            shader_features: ShaderFeatures::default(),
+            present_mode: PresentModeChoice::AutoVsync,
+            supported_present_modes: vec![PresentModeChoice::AutoVsync],
+            mouse_sensitivity: CAMERA_DEFAULT_SENSITIVITY,
+            move_speed: CAMERA_DEFAULT_MOVE_SPEED,
+            invert_y: false,
+            grid_snap: 0.0,
+            spawn_placement: SpawnPlacement::InFront,
+            spawn_distance: DEFAULT_SPAWN_DISTANCE,
+            jitter_pattern: JitterPattern::Random,
+            ui_scale: None,
+            color_space: ColorSpace::Srgb,
+            stereo_mode: StereoMode::Off,
+            stereo_ipd: DEFAULT_STEREO_IPD,
+            headlight: true,
+            headlight_strength: DEFAULT_HEADLIGHT_STRENGTH,
+            headlight_active: false,
+            crosshair: false,
+            crosshair_hit: None,
+            light_gizmos: false,
+            light_gizmo_points: Vec::new(),
+            crosshair_color: None,
+            dev_mode: false,
+            shader_console_open: false,
+            user_bsdf_source: String::new(),
+            composed_trace_source: String::new(),
+            shader_console_error: None,
+            solo: false,
+            solo_hides_sky: false,
+            light_view: false,
+            bvh_params: BvhParams::default(),
+            bvh_stats: BvhStats::default(),
+            bvh_build_ms: 0.0,
+            render_region: None,
+            drawing_render_region: false,
             oil_radius: DEFAULT_OIL_RADIUS,
             comic_levels: DEFAULT_COMIC_LEVELS,
+            compare_enabled: false,
+            compare_effects: Vec::new(),
+            compare_split: 0.5,
             model_scale: 1.0,
             example_scenes: Vec::new(),
             shortcuts_dialog_open: false,
+            reopen_last_scene: false,
+            ui_hidden: false,
             about_dialog_open: false,
+            scene_issues: Vec::new(),
+            diagnostics_open: false,
+            error_message: None,
+            missing_assets: Vec::new(),
+            drop_hint: None,
+            light_groups: Vec::new(),
+            baking: false,
         }
     }
 }
 
 pub fn draw_ui(ctx: &Context, state: &mut UiState, shapes: &mut [Shape]) -> UiActions {
     let mut actions = UiActions::default();
+    if state.ui_hidden {
+        return actions;
+    }
 
     toolbar::draw_toolbar(ctx, state, shapes, &mut actions);
 
@@ -155,6 +550,9 @@ pub fn draw_ui(ctx: &Context, state: &mut UiState, shapes: &mut [Shape]) -> UiAc
                             ui.strong("Scene > Add Shape");
                             ui.label("Create a new primitive");
                             ui.end_row();
+                            ui.strong("Drop a file");
+                            ui.label("Open a scene or import an OBJ model");
+                            ui.end_row();
                         });
                 });
             });
@@ -198,6 +596,54 @@ pub fn draw_ui(ctx: &Context, state: &mut UiState, shapes: &mut [Shape]) -> UiAc
         }
     }
 
+    if state.diagnostics_open {
+        diagnostics::draw_diagnostics(ctx, state, &mut actions);
+    }
+    if state.camera_path_open {
+        camera_path::draw_camera_path(ctx, state, &mut actions);
+    }
+    if state.dev_mode && state.shader_console_open {
+        shader_console::draw_shader_console(ctx, state, &mut actions);
+    }
+
+    if state.compare_enabled {
+        overlays::draw_compare_divider(ctx, state, &mut actions);
+    }
+    overlays::draw_render_region(ctx, state, &mut actions);
+    if state.light_gizmos {
+        overlays::draw_light_gizmos(ctx, state, shapes, &mut actions);
+    }
+    if state.crosshair {
+        overlays::draw_crosshair(ctx, state, shapes);
+    }
+    if let Some(hint) = &state.drop_hint {
+        overlays::draw_drop_hint(ctx, hint);
+    }
+    if !state.missing_assets.is_empty() {
+        overlays::draw_missing_assets(ctx, state, &mut actions);
+    }
+
+    // --- Error modal ---
+    if let Some(message) = &state.error_message {
+        let mut dismissed = false;
+        egui::Window::new("Error")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(RichText::new(message).color(Color32::from_rgb(255, 120, 120)));
+                ui.add_space(10.0);
+                ui.vertical_centered(|ui| {
+                    if ui.button("OK").pointer().clicked() {
+                        dismissed = true;
+                    }
+                });
+            });
+        if dismissed {
+            state.error_message = None;
+        }
+    }
+
     // --- Save dialog modal ---
     if state.save_dialog_open {
         let mut confirmed = false;
@@ -284,7 +730,7 @@ pub fn draw_ui(ctx: &Context, state: &mut UiState, shapes: &mut [Shape]) -> UiAc
     // --- Delete confirmation modal ---
     if let Some(idx) = state.confirm_delete_shape {
         let label = if idx < shapes.len() {
-            shape_label(&shapes[idx], idx)
+            shapes[idx].label(idx)
         } else {
             format!("Shape #{idx}")
         };
@@ -352,6 +798,9 @@ pub fn draw_ui(ctx: &Context, state: &mut UiState, shapes: &mut [Shape]) -> UiAc
                             ("Left Mouse", "Select / drag shape"),
                             ("Numpad + / -", "Camera speed"),
                             ("F12", "Screenshot"),
+                            ("/", "Solo selected shape"),
+                            ("F1", "Hide / show the UI"),
+                            ("Double-click shape", "Fly to shape (Scene list)"),
                             ("Escape", "Release mouse / Exit"),
                         ];
                         for (key, desc) in shortcuts {
@@ -442,9 +891,229 @@ fn scale_model_group(shapes: &mut [Shape], group_name: &Option<String>, ratio: f
     }
 }
 
-pub fn shape_label(shape: &Shape, idx: usize) -> String {
-    match &shape.name {
-        Some(name) if !name.is_empty() => name.clone(),
-        _ => format!("{} #{}", shape.shape_type.label(), idx),
+/// Whether the shape list shows shape `idx`: its label contains `search`
+/// (case-insensitive) and its type is `type_filter`, when one is set.
+pub fn shape_matches_filter(
+    shape: &Shape,
+    idx: usize,
+    search: &str,
+    type_filter: Option<ShapeType>,
+) -> bool {
+    type_filter.is_none_or(|t| shape.shape_type == t)
+        && shape
+            .label(idx)
+            .to_lowercase()
+            .contains(&search.trim().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sphere() -> Shape {
+        serde_yml::from_str("type: sphere\nposition: [0, 0, -5]").unwrap()
+    }
+
+    /// Run one egui frame of the full UI with the given input and return its actions.
+    fn run_frame(
+        ctx: &Context,
+        state: &mut UiState,
+        shapes: &mut [Shape],
+        input: egui::RawInput,
+    ) -> UiActions {
+        let mut actions = UiActions::default();
+        let _ = ctx.run(input, |ctx| {
+            actions = draw_ui(ctx, state, shapes);
+        });
+        actions
+    }
+
+    fn screen_input(events: Vec<egui::Event>) -> egui::RawInput {
+        egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(1280.0, 720.0),
+            )),
+            events,
+            ..Default::default()
+        }
+    }
+
+    fn assert_no_dirty_flags(actions: &UiActions) {
+        assert!(!actions.resets_accumulation());
+        assert!(!actions.scene_dirty);
+        assert!(!actions.textures_dirty);
+        assert!(!actions.render_settings_changed);
+        assert!(!actions.post_effect_params_changed);
+        assert!(!actions.light_groups_changed);
+        assert!(actions.effects_changed.is_none());
+        assert!(actions.model_scale_ratio.is_none());
+    }
+
+    #[test]
+    fn test_shape_filter_matches_labels_and_types() {
+        let mut named = sphere();
+        named.name = Some("Teapot".into());
+        let unnamed = sphere();
+        assert!(shape_matches_filter(&named, 0, "", None));
+        assert!(shape_matches_filter(&named, 0, " tea", None));
+        assert!(!shape_matches_filter(&named, 0, "sphere", None));
+        assert!(shape_matches_filter(&unnamed, 3, "SPHERE #3", None));
+        assert!(shape_matches_filter(
+            &unnamed,
+            3,
+            "",
+            Some(ShapeType::Sphere)
+        ));
+        assert!(!shape_matches_filter(
+            &unnamed,
+            3,
+            "",
+            Some(ShapeType::Cube)
+        ));
+    }
+
+    #[test]
+    fn test_default_actions_keep_accumulation() {
+        assert!(!UiActions::default().resets_accumulation());
+        assert!(!UiActions::default().updates_post_params());
+    }
+
+    #[test]
+    fn test_display_only_actions_keep_accumulation() {
+        let actions = UiActions {
+            paused: true,
+            open_screenshot_dialog: true,
+            open_scene_dialog: true,
+            open_import_scene_dialog: true,
+            open_import_model_dialog: true,
+            recheck_diagnostics: true,
+            post_effect_params_changed: true,
+            effects_changed: Some(Vec::new()),
+            selected_shape: Some(0),
+            ..Default::default()
+        };
+        assert!(!actions.resets_accumulation());
+
+        // What dragging the oil painting radius reports.
+        let post_tweak = UiActions {
+            post_effect_params_changed: true,
+            ..Default::default()
+        };
+        assert!(post_tweak.updates_post_params());
+        assert!(!post_tweak.resets_accumulation());
+    }
+
+    #[test]
+    fn test_render_changes_reset_accumulation() {
+        let cases = [
+            UiActions {
+                exposure_changed: Some(2.0),
+                ..Default::default()
+            },
+            UiActions {
+                max_bounces_changed: Some(4),
+                ..Default::default()
+            },
+            UiActions {
+                render_settings_changed: true,
+                ..Default::default()
+            },
+            UiActions {
+                light_groups_changed: true,
+                ..Default::default()
+            },
+            UiActions {
+                render_region_changed: true,
+                ..Default::default()
+            },
+            UiActions {
+                scene_dirty: true,
+                ..Default::default()
+            },
+            UiActions {
+                flip_selected_normals: true,
+                ..Default::default()
+            },
+            UiActions {
+                shape_to_add: Some(ShapeType::Sphere),
+                ..Default::default()
+            },
+            UiActions {
+                shape_to_delete: Some(0),
+                ..Default::default()
+            },
+        ];
+        for actions in cases {
+            assert!(actions.resets_accumulation());
+        }
+    }
+
+    #[test]
+    fn test_idle_frame_produces_no_dirty_flags() {
+        let ctx = Context::default();
+        let mut state = UiState {
+            selected_shape: Some(0),
+            ..Default::default()
+        };
+        let mut shapes = vec![sphere()];
+        // The first frames lay out windows; none of them may report an edit.
+        for _ in 0..3 {
+            let actions = run_frame(&ctx, &mut state, &mut shapes, screen_input(Vec::new()));
+            assert_no_dirty_flags(&actions);
+        }
+    }
+
+    #[test]
+    fn test_hovering_produces_no_dirty_flags() {
+        let ctx = Context::default();
+        let mut state = UiState {
+            selected_shape: Some(0),
+            ..Default::default()
+        };
+        let mut shapes = vec![sphere()];
+        run_frame(&ctx, &mut state, &mut shapes, screen_input(Vec::new()));
+        // Sweep the pointer over the toolbar and the object editor without clicking.
+        for y in (0..720).step_by(24) {
+            for x in (0..1280).step_by(64) {
+                let pos = egui::pos2(x as f32, y as f32);
+                let input = screen_input(vec![egui::Event::PointerMoved(pos)]);
+                let actions = run_frame(&ctx, &mut state, &mut shapes, input);
+                assert_no_dirty_flags(&actions);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pause_toggle_produces_no_dirty_flags() {
+        let ctx = Context::default();
+        let mut state = UiState::default();
+        let mut shapes = vec![sphere()];
+        run_frame(&ctx, &mut state, &mut shapes, screen_input(Vec::new()));
+
+        // The pause button is the first widget in the toolbar.
+        let pos = egui::pos2(20.0, 10.0);
+        let press = |pressed| egui::Event::PointerButton {
+            pos,
+            button: egui::PointerButton::Primary,
+            pressed,
+            modifiers: egui::Modifiers::NONE,
+        };
+        run_frame(
+            &ctx,
+            &mut state,
+            &mut shapes,
+            screen_input(vec![egui::Event::PointerMoved(pos), press(true)]),
+        );
+        let actions = run_frame(
+            &ctx,
+            &mut state,
+            &mut shapes,
+            screen_input(vec![press(false)]),
+        );
+
+        assert!(state.paused);
+        assert!(actions.paused);
+        assert_no_dirty_flags(&actions);
     }
 }
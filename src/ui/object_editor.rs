@@ -5,8 +5,10 @@ use std::path::Path;
 
 use egui::{Color32, Context, Ui};
 
-use super::{Pointer, UiActions, UiState, shape_label};
-use crate::scene::material::Material;
+use super::{Pointer, UiActions, UiState, reset_button, slider_with_reset};
+use crate::constants::{MAX_MATERIAL_BOUNCES, MIN_SHAPE_RADIUS, TEXTURE_EXTENSIONS};
+use crate::scene::material::{IOR_PRESETS, Material};
+use crate::scene::scene::SkyboxMode;
 use crate::scene::shape::{Shape, ShapeType};
 
 pub fn draw_object_editor(
@@ -28,7 +30,7 @@ pub fn draw_object_editor(
                     let mut changed = false;
 
                     ui.horizontal(|ui| {
-                        ui.strong(shape_label(shape, shape_idx));
+                        ui.strong(shape.label(shape_idx));
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                             if ui.small_button("x").pointer().clicked() {
                                 state.selected_shape = None;
@@ -47,6 +49,27 @@ pub fn draw_object_editor(
                         ui.text_edit_singleline(name);
                     });
 
+                    if shape.shape_type == ShapeType::Skybox {
+                        if draw_skybox_fields(ui, shape, state.skybox_mode, actions) {
+                            actions.scene_dirty = true;
+                        }
+                        return;
+                    }
+
+                    if shape.shape_type == ShapeType::SpotLight {
+                        if draw_spot_light_fields(ui, shape) {
+                            actions.scene_dirty = true;
+                        }
+                        return;
+                    }
+
+                    if shape.shape_type == ShapeType::Instance {
+                        if draw_instance_fields(ui, shape, actions) {
+                            actions.scene_dirty = true;
+                        }
+                        return;
+                    }
+
                     if shape.negative {
                         ui.colored_label(Color32::YELLOW, "⚠ Negative (CSG subtraction)");
                     }
@@ -66,6 +89,48 @@ pub fn draw_object_editor(
                         {
                             actions.model_scale_ratio = Some(state.model_scale / prev);
                         }
+                        ui.horizontal(|ui| {
+                            let label = if state.baking {
+                                "Baking…"
+                            } else {
+                                "Bake Lighting"
+                            };
+                            if ui
+                                .add_enabled(!state.baking, egui::Button::new(label).small())
+                                .on_hover_text(
+                                    "Trace the light reaching this mesh into vertex colors \
+                                     and show them unlit",
+                                )
+                                .pointer()
+                                .clicked()
+                            {
+                                actions.bake_selected_mesh = true;
+                            }
+                            if shape.vertex_colors.is_some()
+                                && ui
+                                    .small_button("Clear Bake")
+                                    .on_hover_text("Shade this mesh from its material again")
+                                    .pointer()
+                                    .clicked()
+                            {
+                                actions.clear_selected_bake = true;
+                            }
+                        });
+                    }
+
+                    if shape.shape_type.has_flippable_normal()
+                        && ui
+                            .small_button("Flip Normals")
+                            .on_hover_text(if is_triangle {
+                                "Reverse the winding of this mesh's triangles so they face \
+                                 the other way"
+                            } else {
+                                "Point the normal the other way"
+                            })
+                            .pointer()
+                            .clicked()
+                    {
+                        actions.flip_selected_normals = true;
                     }
 
                     if !is_triangle {
@@ -84,9 +149,6 @@ pub fn draw_object_editor(
                                 .add(egui::Slider::new(&mut shape.radius2, -2.0..=2.0).text("C.w"))
                                 .pointer()
                                 .changed();
-                        } else if !is_fractal {
-                            ui.label("Rotation");
-                            changed |= drag_vec3_deg(ui, &mut shape.rotation, 1.0);
                         }
 
                         let has_normal = matches!(
@@ -101,11 +163,27 @@ pub fn draw_object_editor(
                             changed |= drag_vec3(ui, &mut shape.normal, 0.01, Some(-1.0..=1.0));
                         }
 
-                        if shape.radius > 0.0 {
+                        let is_cone = shape.shape_type == ShapeType::Cone;
+                        if shape.shape_type == ShapeType::Ellipsoid {
+                            for (value, label) in [
+                                (&mut shape.radius, "Radius X"),
+                                (&mut shape.height, "Radius Y"),
+                                (&mut shape.radius2, "Radius Z"),
+                            ] {
+                                changed |= ui
+                                    .add(
+                                        egui::Slider::new(value, MIN_SHAPE_RADIUS..=100.0)
+                                            .text(label)
+                                            .logarithmic(true),
+                                    )
+                                    .pointer()
+                                    .changed();
+                            }
+                        } else if shape.shape_type.uses_radius() {
                             changed |= ui
                                 .add(
-                                    egui::Slider::new(&mut shape.radius, 0.01..=100.0)
-                                        .text("Radius")
+                                    egui::Slider::new(&mut shape.radius, MIN_SHAPE_RADIUS..=100.0)
+                                        .text(if is_cone { "Base Radius" } else { "Radius" })
                                         .logarithmic(true),
                                 )
                                 .pointer()
@@ -129,6 +207,10 @@ pub fn draw_object_editor(
                                 .pointer()
                                 .changed();
                         }
+                        if is_cone {
+                            let half_angle = shape.cone_tan_sq().sqrt().atan().to_degrees();
+                            ui.weak(format!("Half-angle: {half_angle:.1}°"));
+                        }
 
                         if shape.shape_type == ShapeType::Torus {
                             changed |= ui
@@ -167,6 +249,28 @@ pub fn draw_object_editor(
                                 changed = true;
                             }
                         }
+                        if shape.shape_type.has_march_steps() {
+                            changed |= slider_with_reset(ui, &mut shape.march_steps, 0, |v| {
+                                egui::Slider::new(v, 0..=1024)
+                                    .text("March Steps")
+                                    .logarithmic(true)
+                                    .custom_formatter(|v, _| {
+                                        if v == 0.0 {
+                                            "Global".to_string()
+                                        } else {
+                                            format!("{v:.0}")
+                                        }
+                                    })
+                            });
+                            ui.weak("Global = Fractal Steps in Settings");
+                        }
+                        if is_fractal {
+                            changed |= ui
+                                .checkbox(&mut shape.orbit_trap, "Orbit-trap coloring")
+                                .on_hover_text("Tint the surface by how quickly its orbit escapes")
+                                .pointer()
+                                .changed();
+                        }
                     }
 
                     ui.separator();
@@ -190,6 +294,16 @@ pub fn draw_object_editor(
                             shape.negative = false;
                             changed = true;
                         }
+                        if preset_button(
+                            ui,
+                            "Catch",
+                            "Shadow catcher (invisible, shows only shadows)",
+                        ) {
+                            apply_preset(mat, 0.0, 0.9, 0.0, mat.ior, [0.0; 3], 0.0);
+                            mat.shadow_catcher = true;
+                            shape.negative = false;
+                            changed = true;
+                        }
                         if preset_button(ui, "Trans", "Transparent (clear)") {
                             apply_preset(mat, 0.0, 0.0, 1.0, 1.0, [0.0; 3], 0.0);
                             shape.negative = false;
@@ -206,6 +320,8 @@ pub fn draw_object_editor(
                         }
                     });
 
+                    let is_triangle = shape.shape_type == ShapeType::Triangle;
+                    let has_triangles = is_triangle || shape.shape_type == ShapeType::Instance;
                     let mat = &mut shape.material;
 
                     ui.horizontal(|ui| {
@@ -217,25 +333,95 @@ pub fn draw_object_editor(
                         }
                     });
 
+                    let defaults = Material::default();
+                    changed |= slider_with_reset(ui, &mut mat.metallic, defaults.metallic, |v| {
+                        egui::Slider::new(v, 0.0..=1.0).text("Metallic")
+                    });
+                    changed |= slider_with_reset(ui, &mut mat.roughness, defaults.roughness, |v| {
+                        egui::Slider::new(v, 0.0..=1.0).text("Roughness")
+                    });
+                    changed |=
+                        slider_with_reset(ui, &mut mat.transmission, defaults.transmission, |v| {
+                            egui::Slider::new(v, 0.0..=1.0).text("Transmission")
+                        });
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .add(egui::Slider::new(&mut mat.ior, 1.0..=3.0).text("IOR"))
+                            .pointer()
+                            .changed();
+                        changed |= reset_button(ui, &mut mat.ior, defaults.ior);
+                        egui::ComboBox::from_id_salt("ior_preset")
+                            .width(90.0)
+                            .selected_text(mat.ior_preset_name().unwrap_or("Custom"))
+                            .show_ui(ui, |ui| {
+                                for &(name, ior) in IOR_PRESETS {
+                                    let selected = mat.ior_preset_name() == Some(name);
+                                    if ui
+                                        .selectable_label(selected, format!("{name} ({ior})"))
+                                        .pointer()
+                                        .clicked()
+                                    {
+                                        mat.ior = ior;
+                                        changed = true;
+                                    }
+                                }
+                            })
+                            .response
+                            .on_hover_text("Index of refraction of common materials");
+                    });
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut mat.max_bounces, 0..=MAX_MATERIAL_BOUNCES)
+                                    .text("Max Bounces")
+                                    .custom_formatter(|v, _| {
+                                        if v == 0.0 {
+                                            "Global".to_string()
+                                        } else {
+                                            format!("{v:.0}")
+                                        }
+                                    }),
+                            )
+                            .on_hover_text(
+                                "Bounces left for paths after hitting this material, \
+                                 replacing the global setting",
+                            )
+                            .pointer()
+                            .changed();
+                        changed |= reset_button(ui, &mut mat.max_bounces, defaults.max_bounces);
+                    });
+
+                    ui.horizontal(|ui| {
+                        changed |= ui
+                            .checkbox(&mut mat.casts_shadows, "Casts shadows")
+                            .on_hover_text("Off: lights shine through this shape")
+                            .pointer()
+                            .changed();
+                        changed |= ui
+                            .checkbox(&mut mat.receives_shadows, "Receives shadows")
+                            .on_hover_text("Off: other shapes don't shadow this one")
+                            .pointer()
+                            .changed();
+                    });
                     changed |= ui
-                        .add(egui::Slider::new(&mut mat.metallic, 0.0..=1.0).text("Metallic"))
-                        .pointer()
-                        .changed();
-                    changed |= ui
-                        .add(egui::Slider::new(&mut mat.roughness, 0.0..=1.0).text("Roughness"))
-                        .pointer()
-                        .changed();
-                    changed |= ui
-                        .add(
-                            egui::Slider::new(&mut mat.transmission, 0.0..=1.0)
-                                .text("Transmission"),
+                        .checkbox(&mut mat.shadow_catcher, "Shadow catcher")
+                        .on_hover_text(
+                            "Invisible except for the shadows falling on it; \
+                             use with a transparent background for compositing",
                         )
                         .pointer()
                         .changed();
-                    changed |= ui
-                        .add(egui::Slider::new(&mut mat.ior, 1.0..=3.0).text("IOR"))
-                        .pointer()
-                        .changed();
+                    if has_triangles {
+                        changed |= ui
+                            .checkbox(&mut mat.cull_backfaces, "Cull back faces")
+                            .on_hover_text(
+                                "Hit triangles only from the front (counter-clockwise) side; \
+                                 for closed meshes, not thin sheets or glass. On a model \
+                                 instance this applies to every triangle of the model",
+                            )
+                            .pointer()
+                            .changed();
+                    }
 
                     if mat.emission_strength > 0.0 {
                         ui.separator();
@@ -254,47 +440,24 @@ pub fn draw_object_editor(
                             )
                             .pointer()
                             .changed();
+                        changed |= ui
+                            .checkbox(&mut mat.emission_uses_texture, "Textured emission")
+                            .on_hover_text("Multiply the emission by the texture below")
+                            .pointer()
+                            .changed();
                     }
 
                     ui.separator();
                     ui.label("Texture");
-
-                    ui.horizontal(|ui| {
-                        if ui.small_button("...").pointer().clicked()
-                            && let Some(path) = rfd::FileDialog::new()
-                                .add_filter("Images", &["png", "jpg", "jpeg", "bmp", "tga"])
-                                .pick_file()
-                        {
-                            shape.texture = Some(path.to_string_lossy().to_string());
-                            changed = true;
-                            actions.textures_dirty = true;
-                        }
-                        if let Some(ref tex_path) = shape.texture {
-                            let display_name = Path::new(tex_path)
-                                .file_name()
-                                .map(|n| n.to_string_lossy().to_string())
-                                .unwrap_or_else(|| tex_path.clone());
-                            ui.label(&display_name);
-                            if ui.small_button("x").pointer().clicked() {
-                                shape.texture = None;
-                                changed = true;
-                                actions.textures_dirty = true;
-                            }
-                        } else {
-                            ui.label("None");
-                        }
-                    });
+                    changed |= texture_picker(ui, shape, actions);
 
                     if shape.texture.is_some() {
                         let scale = shape.texture_scale.get_or_insert(1.0);
-                        changed |= ui
-                            .add(
-                                egui::Slider::new(scale, 0.01..=10.0)
-                                    .text("Scale")
-                                    .logarithmic(true),
-                            )
-                            .pointer()
-                            .changed();
+                        changed |= slider_with_reset(ui, scale, 1.0, |v| {
+                            egui::Slider::new(v, 0.01..=10.0)
+                                .text("Scale")
+                                .logarithmic(true)
+                        });
                     }
 
                     if changed {
@@ -304,6 +467,145 @@ pub fn draw_object_editor(
         });
 }
 
+/// Skybox shapes only expose the equirectangular texture, a tint and a brightness.
+fn draw_skybox_fields(
+    ui: &mut Ui,
+    shape: &mut Shape,
+    sky_mode: SkyboxMode,
+    actions: &mut UiActions,
+) -> bool {
+    ui.label("Environment map (equirectangular)");
+    let mut changed = texture_picker(ui, shape, actions);
+
+    let mat = &mut shape.material;
+    ui.horizontal(|ui| {
+        ui.label("Tint:");
+        let mut color = mat.base_color;
+        if ui.color_edit_button_rgb(&mut color).pointer().changed() {
+            mat.base_color = color;
+            changed = true;
+        }
+    });
+    changed |= slider_with_reset(ui, &mut mat.emission_strength, 1.0, |v| {
+        egui::Slider::new(v, 0.01..=20.0)
+            .text("Brightness")
+            .logarithmic(true)
+    });
+
+    if shape.texture.is_none() {
+        ui.colored_label(
+            Color32::YELLOW,
+            "No texture: the procedural sky is used instead.",
+        );
+    } else if sky_mode != SkyboxMode::HdrMap {
+        ui.colored_label(
+            Color32::YELLOW,
+            "Set the sky mode to HDR map to show this texture.",
+        );
+    }
+    changed
+}
+
+/// Spot lights expose position, direction, cone angles and intensity only.
+fn draw_spot_light_fields(ui: &mut Ui, shape: &mut Shape) -> bool {
+    ui.label("Position");
+    let mut changed = drag_vec3(ui, &mut shape.position, 0.1, None);
+    ui.label("Direction");
+    changed |= drag_vec3(ui, &mut shape.normal, 0.01, Some(-1.0..=1.0));
+
+    changed |= ui
+        .add(egui::Slider::new(&mut shape.radius, 0.0..=89.0).text("Inner °"))
+        .pointer()
+        .changed();
+    changed |= ui
+        .add(egui::Slider::new(&mut shape.radius2, 0.0..=90.0).text("Outer °"))
+        .pointer()
+        .changed();
+    if shape.radius2 < shape.radius {
+        shape.radius2 = shape.radius;
+    }
+
+    ui.separator();
+    let mat = &mut shape.material;
+    ui.horizontal(|ui| {
+        ui.label("Color:");
+        let mut color = mat.emission;
+        if ui.color_edit_button_rgb(&mut color).pointer().changed() {
+            mat.emission = color;
+            changed = true;
+        }
+    });
+    changed |= ui
+        .add(
+            egui::Slider::new(&mut mat.emission_strength, 0.0..=1000.0)
+                .text("Intensity")
+                .logarithmic(true),
+        )
+        .pointer()
+        .changed();
+    changed
+}
+
+/// Placement of an instanced model. Its materials come from the model file.
+fn draw_instance_fields(ui: &mut Ui, shape: &mut Shape, actions: &mut UiActions) -> bool {
+    ui.horizontal(|ui| {
+        ui.label("Model:");
+        ui.weak(shape.model.as_deref().unwrap_or("(none)"));
+    });
+    ui.label("Position");
+    let mut changed = drag_vec3(ui, &mut shape.position, 0.1, None);
+    ui.label("Rotation");
+    changed |= drag_vec3_deg(ui, &mut shape.rotation, 1.0);
+    if ui
+        .small_button("Align to camera")
+        .on_hover_text("Point the model's +Z where the camera looks, +Y along the camera's up")
+        .pointer()
+        .clicked()
+    {
+        actions.align_selected_to_camera = true;
+    }
+    changed |= ui
+        .add(
+            egui::Slider::new(&mut shape.radius, MIN_SHAPE_RADIUS..=100.0)
+                .text("Scale")
+                .logarithmic(true),
+        )
+        .pointer()
+        .changed();
+    changed
+}
+
+/// File picker row for `shape.texture`, returning true if the texture changed.
+fn texture_picker(ui: &mut Ui, shape: &mut Shape, actions: &mut UiActions) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        if ui.small_button("...").pointer().clicked()
+            && let Some(path) = rfd::FileDialog::new()
+                .add_filter("Images", TEXTURE_EXTENSIONS)
+                .pick_file()
+        {
+            shape.texture = Some(path.to_string_lossy().to_string());
+            changed = true;
+            actions.textures_dirty = true;
+        }
+        if let Some(ref tex_path) = shape.texture {
+            let display_name = Path::new(tex_path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| tex_path.clone());
+            ui.label(&display_name);
+            if ui.small_button("x").pointer().clicked() {
+                shape.texture = None;
+                changed = true;
+                actions.textures_dirty = true;
+            }
+        } else {
+            ui.label("None");
+        }
+    });
+    changed
+}
+
 /// Render three DragValues for an XYZ vector, returning true if any changed.
 fn drag_vec3(
     ui: &mut Ui,
@@ -324,7 +626,7 @@ fn drag_vec3(
     changed
 }
 
-/// Render three DragValues for an XYZ rotation (degrees).
+/// Render three DragValues for an XYZ rotation (degrees) plus a reset to zero.
 fn drag_vec3_deg(ui: &mut Ui, v: &mut [f32; 3], speed: f64) -> bool {
     let mut changed = false;
     ui.horizontal(|ui| {
@@ -339,6 +641,7 @@ fn drag_vec3_deg(ui: &mut Ui, v: &mut [f32; 3], speed: f64) -> bool {
                 .pointer()
                 .changed();
         }
+        changed |= reset_button(ui, v, [0.0; 3]);
     });
     changed
 }
@@ -371,4 +674,5 @@ fn apply_preset(
     mat.ior = ior;
     mat.emission = emission;
     mat.emission_strength = emission_strength;
+    mat.shadow_catcher = false;
 }
@@ -0,0 +1,283 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use egui::{Color32, Context, Id, Order, Rect, Sense};
+
+use super::{Pointer, UiActions, UiState};
+use crate::render::frame::RenderRegion;
+use crate::scene::shape::Shape;
+
+const REGION_COLOR: Color32 = Color32::from_rgb(255, 170, 0);
+
+/// Regions smaller than this (in points) are treated as an accidental click.
+const MIN_REGION_POINTS: f32 = 4.0;
+
+/// Half the length of the crosshair's arms, in points.
+const CROSSHAIR_SIZE: f32 = 8.0;
+
+/// Radius of a light gizmo's disc, in points.
+const LIGHT_GIZMO_RADIUS: f32 = 10.0;
+
+/// Draggable vertical divider for the A/B post-effect comparison.
+pub fn draw_compare_divider(ctx: &Context, state: &mut UiState, actions: &mut UiActions) {
+    // The split is a fraction of the full frame, but the handle stays below the toolbar.
+    let screen = ctx.screen_rect();
+    let viewport = ctx.available_rect();
+    let x = screen.left() + screen.width() * state.compare_split;
+    let handle = Rect::from_min_max(
+        egui::pos2(x - 4.0, viewport.top()),
+        egui::pos2(x + 4.0, viewport.bottom()),
+    );
+    egui::Area::new(Id::new("compare_divider"))
+        .fixed_pos(handle.min)
+        .order(Order::Middle)
+        .show(ctx, |ui| {
+            let (rect, response) = ui.allocate_exact_size(handle.size(), Sense::drag());
+            let response = response.on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+            if response.dragged()
+                && let Some(pos) = response.interact_pointer_pos()
+            {
+                state.compare_split = ((pos.x - screen.left()) / screen.width()).clamp(0.0, 1.0);
+                actions.post_effect_params_changed = true;
+            }
+            let stroke_color = if response.hovered() || response.dragged() {
+                Color32::WHITE
+            } else {
+                Color32::from_white_alpha(160)
+            };
+            let painter = ui.painter();
+            painter.vline(rect.center().x, rect.y_range(), (2.0, stroke_color));
+            let label_y = viewport.bottom() - 24.0;
+            let font = egui::FontId::proportional(14.0);
+            painter.text(
+                egui::pos2(x - 10.0, label_y),
+                egui::Align2::RIGHT_CENTER,
+                "A",
+                font.clone(),
+                stroke_color,
+            );
+            painter.text(
+                egui::pos2(x + 10.0, label_y),
+                egui::Align2::LEFT_CENTER,
+                "B",
+                font,
+                stroke_color,
+            );
+        });
+}
+
+/// Border of the active render region, plus the drag-to-draw overlay while
+/// a new region is being picked.
+pub fn draw_render_region(ctx: &Context, state: &mut UiState, actions: &mut UiActions) {
+    let ppp = ctx.pixels_per_point();
+
+    if state.drawing_render_region {
+        // A foreground area makes egui claim the pointer, so the drag doesn't
+        // also select or move shapes underneath.
+        let viewport = ctx.available_rect();
+        egui::Area::new(Id::new("render_region_draw"))
+            .fixed_pos(viewport.min)
+            .order(Order::Foreground)
+            .show(ctx, |ui| {
+                let (_, response) = ui.allocate_exact_size(viewport.size(), Sense::drag());
+                let response = response.on_hover_cursor(egui::CursorIcon::Crosshair);
+                let origin = ui.input(|i| i.pointer.press_origin());
+                if let (Some(origin), Some(current)) = (origin, response.interact_pointer_pos()) {
+                    let rect = Rect::from_two_pos(origin, current).intersect(viewport);
+                    ui.painter().rect_stroke(rect, 0.0, (1.5, REGION_COLOR));
+                    if response.drag_stopped() {
+                        state.drawing_render_region = false;
+                        if rect.width() >= MIN_REGION_POINTS && rect.height() >= MIN_REGION_POINTS {
+                            state.render_region = Some(region_from_points(rect, ppp));
+                            actions.render_region_changed = true;
+                        }
+                    }
+                }
+            });
+        return;
+    }
+
+    if let Some(region) = state.render_region {
+        let rect = Rect::from_min_size(
+            egui::pos2(region.x as f32, region.y as f32) / ppp,
+            egui::vec2(region.width as f32, region.height as f32) / ppp,
+        );
+        ctx.layer_painter(egui::LayerId::new(
+            Order::Background,
+            Id::new("render_region"),
+        ))
+        .rect_stroke(rect, 0.0, (1.5, REGION_COLOR));
+    }
+}
+
+/// Crosshair at the center of the view, with a readout of what it points at
+/// and the accumulated color of the pixel under it.
+pub fn draw_crosshair(ctx: &Context, state: &UiState, shapes: &[Shape]) {
+    let center = ctx.screen_rect().center();
+    let painter = ctx.layer_painter(egui::LayerId::new(Order::Foreground, Id::new("crosshair")));
+    for stroke in [(3.0, Color32::BLACK), (1.0, Color32::WHITE)] {
+        painter.line_segment(
+            [
+                center - egui::vec2(CROSSHAIR_SIZE, 0.0),
+                center + egui::vec2(CROSSHAIR_SIZE, 0.0),
+            ],
+            stroke,
+        );
+        painter.line_segment(
+            [
+                center - egui::vec2(0.0, CROSSHAIR_SIZE),
+                center + egui::vec2(0.0, CROSSHAIR_SIZE),
+            ],
+            stroke,
+        );
+    }
+
+    egui::Area::new(Id::new("crosshair_readout"))
+        .fixed_pos(center + egui::vec2(CROSSHAIR_SIZE + 6.0, CROSSHAIR_SIZE + 6.0))
+        .order(Order::Foreground)
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                match state.crosshair_hit.filter(|&(idx, ..)| idx < shapes.len()) {
+                    Some((idx, [x, y, z], distance)) => {
+                        let shape = &shapes[idx];
+                        let mat = &shape.material;
+                        ui.strong(shape.label(idx));
+                        ui.label(format!("Position: {x:.3}, {y:.3}, {z:.3}"));
+                        ui.label(format!("Distance: {distance:.3}"));
+                        let [r, g, b] = mat.base_color;
+                        ui.label(format!(
+                            "Material: base {r:.2}, {g:.2}, {b:.2}; metallic {:.2}; roughness {:.2}",
+                            mat.metallic, mat.roughness
+                        ));
+                        if mat.emission_strength > 0.0 {
+                            let [r, g, b] = mat.emission;
+                            ui.label(format!(
+                                "Emission: {r:.2}, {g:.2}, {b:.2} × {:.2}",
+                                mat.emission_strength
+                            ));
+                        }
+                    }
+                    None => {
+                        ui.strong("Sky");
+                    }
+                }
+                ui.horizontal(|ui| match state.crosshair_color {
+                    Some([r, g, b]) => {
+                        let (rect, _) =
+                            ui.allocate_exact_size(egui::vec2(12.0, 12.0), Sense::hover());
+                        ui.painter()
+                            .rect_filled(rect, 2.0, egui::Rgba::from_rgb(r, g, b));
+                        ui.label(format!("Pixel: {r:.4}, {g:.4}, {b:.4}"));
+                    }
+                    None => {
+                        ui.weak("Pixel: not rendered yet");
+                    }
+                });
+            });
+        });
+}
+
+/// A bulb icon on every light in view, ringed in its emission color; a
+/// click selects the light. Drawn by egui only, so never in screenshots.
+pub fn draw_light_gizmos(
+    ctx: &Context,
+    state: &mut UiState,
+    shapes: &[Shape],
+    actions: &mut UiActions,
+) {
+    let ppp = ctx.pixels_per_point();
+    let size = egui::Vec2::splat(LIGHT_GIZMO_RADIUS * 2.0);
+    for &(idx, [x, y]) in &state.light_gizmo_points {
+        let Some(shape) = shapes.get(idx) else {
+            continue;
+        };
+        let center = egui::pos2(x, y) / ppp;
+        let [r, g, b] = shape.material.emission;
+        let peak = r.max(g).max(b).max(1e-6);
+        let ring = egui::Rgba::from_rgb(r / peak, g / peak, b / peak);
+        let selected = state.selected_shape == Some(idx);
+        let response = egui::Area::new(Id::new(("light_gizmo", idx)))
+            .fixed_pos(center - size * 0.5)
+            .order(Order::Background)
+            .show(ctx, |ui| {
+                let (rect, response) = ui.allocate_exact_size(size, Sense::click());
+                let painter = ui.painter();
+                painter.circle(
+                    rect.center(),
+                    LIGHT_GIZMO_RADIUS,
+                    Color32::from_black_alpha(160),
+                    (if selected { 2.5 } else { 1.5 }, ring),
+                );
+                painter.text(
+                    rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    "💡",
+                    egui::FontId::proportional(LIGHT_GIZMO_RADIUS * 1.2),
+                    Color32::WHITE,
+                );
+                response
+            })
+            .inner
+            .on_hover_text(shape.label(idx))
+            .pointer();
+        if response.clicked() {
+            state.selected_shape = Some(idx);
+            state.model_scale = 1.0;
+            actions.selected_shape = Some(idx);
+        }
+    }
+}
+
+/// Centered banner telling what dropping the hovered file will do.
+pub fn draw_drop_hint(ctx: &Context, hint: &str) {
+    egui::Area::new(Id::new("drop_hint"))
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .order(Order::Foreground)
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.heading(hint);
+            });
+        });
+}
+
+/// Dismissible notice listing files the scene could not load, each with a
+/// button to pick its new location.
+pub fn draw_missing_assets(ctx: &Context, state: &mut UiState, actions: &mut UiActions) {
+    let mut dismissed = false;
+    egui::Window::new("Missing Files")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .show(ctx, |ui| {
+            for asset in &state.missing_assets {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new("⚠").color(Color32::YELLOW));
+                    ui.strong(asset.kind());
+                    ui.label(asset.path());
+                    if ui.small_button("Relocate…").pointer().clicked() {
+                        actions.relocate_asset = Some(asset.clone());
+                    }
+                });
+            }
+            ui.add_space(4.0);
+            if ui.button("Dismiss").pointer().clicked() {
+                dismissed = true;
+            }
+        });
+    if dismissed {
+        state.missing_assets.clear();
+    }
+}
+
+fn region_from_points(rect: Rect, pixels_per_point: f32) -> RenderRegion {
+    let min = rect.min * pixels_per_point;
+    let max = rect.max * pixels_per_point;
+    RenderRegion {
+        x: min.x.max(0.0).round() as u32,
+        y: min.y.max(0.0).round() as u32,
+        width: (max.x - min.x).round() as u32,
+        height: (max.y - min.y).round() as u32,
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright (C) Pavlo Hrytsenko <pashagricenko@gmail.com>
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use egui::{Color32, Context, RichText};
+
+use super::{Pointer, UiActions, UiState};
+use crate::constants::USER_BSDF_MODULE;
+
+/// `--dev` window for editing the user BSDF module and recompiling the
+/// path tracer with it, next to the composed source it ends up in.
+pub fn draw_shader_console(ctx: &Context, state: &mut UiState, actions: &mut UiActions) {
+    let mut open = state.shader_console_open;
+    egui::Window::new("Shader Console")
+        .open(&mut open)
+        .default_width(560.0)
+        .default_height(480.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong(format!("{USER_BSDF_MODULE}.wgsl"));
+                if ui
+                    .button("Apply")
+                    .on_hover_text("Recompile the path tracer with this module (Ctrl+Enter)")
+                    .pointer()
+                    .clicked()
+                {
+                    actions.apply_user_bsdf = true;
+                }
+                if ui
+                    .button("Revert")
+                    .on_hover_text("Discard the edits and use the module on disk again")
+                    .pointer()
+                    .clicked()
+                {
+                    actions.revert_user_bsdf = true;
+                }
+            });
+            if let Some(error) = &state.shader_console_error {
+                egui::ScrollArea::vertical()
+                    .id_salt("shader error")
+                    .max_height(120.0)
+                    .show(ui, |ui| {
+                        ui.label(RichText::new(error).monospace().color(Color32::LIGHT_RED));
+                    });
+            }
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .id_salt("user bsdf")
+                .max_height(320.0)
+                .show(ui, |ui| {
+                    let editor = ui.add(
+                        egui::TextEdit::multiline(&mut state.user_bsdf_source)
+                            .code_editor()
+                            .desired_rows(20)
+                            .desired_width(f32::INFINITY),
+                    );
+                    if editor.has_focus()
+                        && ui.input(|i| i.modifiers.command && i.key_pressed(egui::Key::Enter))
+                    {
+                        actions.apply_user_bsdf = true;
+                    }
+                });
+
+            egui::CollapsingHeader::new("Composed path_trace")
+                .id_salt("composed source")
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .id_salt("composed")
+                        .max_height(320.0)
+                        .show(ui, |ui| {
+                            // Read-only: a `&str` buffer can be selected but not edited.
+                            ui.add(
+                                egui::TextEdit::multiline(
+                                    &mut state.composed_trace_source.as_str(),
+                                )
+                                .code_editor()
+                                .desired_width(f32::INFINITY),
+                            );
+                        });
+                });
+        });
+    state.shader_console_open = open;
+}
@@ -3,16 +3,49 @@
 
 use egui::Context;
 
-use super::{Pointer, UiActions, UiState, shape_label};
-use crate::constants::{EXAMPLE_SCENES_DIR, resolve_data_path};
+use super::{Pointer, UiActions, UiState, reset_button, scene_diff_marker, shape_matches_filter};
+use crate::constants::{
+    CAMERA_DEFAULT_MOVE_SPEED, CAMERA_DEFAULT_SENSITIVITY, CAMERA_SPEED_MAX, CAMERA_SPEED_MIN,
+    DEFAULT_COMIC_LEVELS, DEFAULT_EXPOSURE, DEFAULT_FAR_CLIP, DEFAULT_FIREFLY_CLAMP,
+    DEFAULT_FRACTAL_MARCH_STEPS, DEFAULT_HEADLIGHT_STRENGTH, DEFAULT_MAX_BOUNCES,
+    DEFAULT_NEAR_CLIP, DEFAULT_OIL_RADIUS, DEFAULT_SKY_TURBIDITY, DEFAULT_SKYBOX_BRIGHTNESS,
+    DEFAULT_SPAWN_DISTANCE, DEFAULT_STEREO_IPD, DEFAULT_SUN_AZIMUTH, DEFAULT_SUN_ELEVATION,
+    DEFAULT_WHITE_POINT, EXAMPLE_SCENES_DIR, FPS_CAP_OPTIONS, MAX_BVH_LEAF_PRIMS, MAX_BVH_NUM_BINS,
+    MAX_FAR_CLIP, MAX_NEAR_CLIP, MAX_RENDER_SIZE_OPTIONS, MAX_SAMPLES_PER_FRAME,
+    MAX_SPAWN_DISTANCE, MAX_STEREO_IPD, MAX_UI_SCALE, MAX_WHITE_POINT, MIN_UI_SCALE,
+    SUPERSAMPLING_OPTIONS, TONE_MAPPER_NAMES, resolve_data_path,
+};
+use crate::gpu::buffers::MIB;
+use crate::gpu::context::PresentModeChoice;
+use crate::render::accumulator::RenderProgress;
+use crate::render::frame::{ColorSpace, JitterPattern, StereoMode, ViewMode};
 use crate::render::post_process::PostEffect;
+use crate::scene::lights::can_look_through;
+use crate::scene::scene::{SkyboxMode, UpAxis};
 use crate::scene::shape::{Shape, ShapeType};
+use crate::settings::SpawnPlacement;
 
-/// Render a labelled slider and set `*changed = true` when the value is modified.
+/// Render a labelled slider with a reset-to-`default` button and set
+/// `*changed = true` when the value is modified.
 fn labeled_slider<T: egui::emath::Numeric>(
     ui: &mut egui::Ui,
     label: &str,
     value: &mut T,
+    default: T,
+    range: std::ops::RangeInclusive<T>,
+    changed: &mut bool,
+) {
+    indented_slider(ui, 0.0, label, value, default, range, changed);
+}
+
+/// `labeled_slider` for a render setting the scene file also sets, marked
+/// while it differs from the file's value `scene`.
+fn scene_slider<T: egui::emath::Numeric>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut T,
+    default: T,
+    scene: T,
     range: std::ops::RangeInclusive<T>,
     changed: &mut bool,
 ) {
@@ -21,6 +54,12 @@ fn labeled_slider<T: egui::emath::Numeric>(
         if ui.add(egui::Slider::new(value, range)).pointer().changed() {
             *changed = true;
         }
+        if reset_button(ui, value, default) {
+            *changed = true;
+        }
+        if scene_diff_marker(ui, value, scene) {
+            *changed = true;
+        }
     });
 }
 
@@ -30,6 +69,7 @@ fn indented_slider<T: egui::emath::Numeric>(
     indent: f32,
     label: &str,
     value: &mut T,
+    default: T,
     range: std::ops::RangeInclusive<T>,
     changed: &mut bool,
 ) {
@@ -39,7 +79,205 @@ fn indented_slider<T: egui::emath::Numeric>(
         if ui.add(egui::Slider::new(value, range)).pointer().changed() {
             *changed = true;
         }
+        if reset_button(ui, value, default) {
+            *changed = true;
+        }
+    });
+}
+
+/// Camera controller and editing preferences; persisted in the settings file.
+fn draw_controls_settings(ui: &mut egui::Ui, state: &mut UiState, actions: &mut UiActions) {
+    ui.horizontal(|ui| {
+        ui.label("Mouse Sensitivity:");
+        if ui
+            .add(egui::Slider::new(&mut state.mouse_sensitivity, 0.01..=1.0).logarithmic(true))
+            .pointer()
+            .changed()
+        {
+            actions.controls_changed = true;
+        }
+        if reset_button(ui, &mut state.mouse_sensitivity, CAMERA_DEFAULT_SENSITIVITY) {
+            actions.controls_changed = true;
+        }
+    });
+    labeled_slider(
+        ui,
+        "Move Speed:",
+        &mut state.move_speed,
+        CAMERA_DEFAULT_MOVE_SPEED,
+        CAMERA_SPEED_MIN..=CAMERA_SPEED_MAX,
+        &mut actions.controls_changed,
+    );
+    if ui
+        .checkbox(&mut state.invert_y, "Invert Mouse Y")
+        .pointer()
+        .changed()
+    {
+        actions.controls_changed = true;
+    }
+    ui.horizontal(|ui| {
+        ui.label("Grid Snap:");
+        ui.add(
+            egui::Slider::new(&mut state.grid_snap, 0.0..=2.0).custom_formatter(|v, _| {
+                if v == 0.0 {
+                    "Off".to_string()
+                } else {
+                    format!("{v:.2}")
+                }
+            }),
+        )
+        .on_hover_text("Snap dragged shapes to a grid of this size")
+        .pointer();
     });
+    ui.horizontal(|ui| {
+        ui.label("New Shapes:");
+        egui::ComboBox::from_id_salt("spawn_placement")
+            .selected_text(state.spawn_placement.label())
+            .show_ui(ui, |ui| {
+                for &placement in SpawnPlacement::ALL {
+                    ui.selectable_value(&mut state.spawn_placement, placement, placement.label())
+                        .pointer();
+                }
+            })
+            .response
+            .on_hover_text("Where added shapes and imported models appear")
+            .pointer();
+    });
+    if state.spawn_placement != SpawnPlacement::Origin {
+        ui.horizontal(|ui| {
+            ui.label("Spawn Distance:");
+            ui.add(
+                egui::Slider::new(&mut state.spawn_distance, 0.5..=MAX_SPAWN_DISTANCE)
+                    .logarithmic(true),
+            )
+            .on_hover_text("How far in front of the camera new shapes appear")
+            .pointer();
+            reset_button(ui, &mut state.spawn_distance, DEFAULT_SPAWN_DISTANCE);
+        });
+    }
+    ui.horizontal(|ui| {
+        ui.label("UI Scale:");
+        let native = ui.ctx().native_pixels_per_point().unwrap_or(1.0);
+        let mut auto = state.ui_scale.is_none();
+        if ui
+            .checkbox(&mut auto, "Auto")
+            .on_hover_text("Follow the display's scale factor")
+            .pointer()
+            .changed()
+        {
+            state.ui_scale = (!auto).then_some(native);
+            actions.ui_scale_changed = true;
+        }
+        let mut scale = state.ui_scale.unwrap_or(native);
+        let response = ui
+            .add_enabled(
+                !auto,
+                egui::Slider::new(&mut scale, MIN_UI_SCALE..=MAX_UI_SCALE).step_by(0.05),
+            )
+            .pointer();
+        if !auto {
+            state.ui_scale = Some(scale);
+            // Rescale once the drag ends, so the slider doesn't move under the mouse.
+            actions.ui_scale_changed |=
+                response.drag_stopped() || (response.changed() && !response.dragged());
+        }
+    });
+}
+
+/// Checkbox list plus reorder controls for one post-effect chain.
+/// Returns true when the chain itself changed; parameter sliders set `params_changed`.
+fn effect_chain_editor(
+    ui: &mut egui::Ui,
+    id_salt: &str,
+    effects: &mut Vec<PostEffect>,
+    oil_radius: &mut u32,
+    comic_levels: &mut u32,
+    params_changed: &mut bool,
+) -> bool {
+    let mut effects_changed = false;
+    egui::ScrollArea::vertical()
+        .id_salt(id_salt)
+        .max_height(200.0)
+        .show(ui, |ui| {
+            for &effect in PostEffect::ALL_EFFECTS {
+                let active = effects.contains(&effect);
+                let mut checked = active;
+                if ui
+                    .checkbox(&mut checked, effect.label())
+                    .pointer()
+                    .clicked()
+                {
+                    if checked {
+                        effects.push(effect);
+                    } else {
+                        effects.retain(|&e| e != effect);
+                    }
+                    effects_changed = true;
+                }
+                if checked && effect == PostEffect::OilPainting {
+                    indented_slider(
+                        ui,
+                        20.0,
+                        "Radius:",
+                        oil_radius,
+                        DEFAULT_OIL_RADIUS,
+                        1..=8,
+                        params_changed,
+                    );
+                }
+                if checked && effect == PostEffect::Comic {
+                    indented_slider(
+                        ui,
+                        20.0,
+                        "Levels:",
+                        comic_levels,
+                        DEFAULT_COMIC_LEVELS,
+                        2..=16,
+                        params_changed,
+                    );
+                }
+            }
+
+            if effects.len() >= 2 {
+                ui.separator();
+                ui.strong("Order");
+                let mut swap: Option<(usize, usize)> = None;
+                for i in 0..effects.len() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("{}. {}", i + 1, effects[i].label()));
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if i + 1 < effects.len() && ui.small_button("Dn").pointer().clicked() {
+                                swap = Some((i, i + 1));
+                            }
+                            if i > 0 && ui.small_button("Up").pointer().clicked() {
+                                swap = Some((i, i - 1));
+                            }
+                        });
+                    });
+                }
+                if let Some((a, b)) = swap {
+                    effects.swap(a, b);
+                    effects_changed = true;
+                }
+            }
+        });
+    effects_changed
+}
+
+fn max_render_size_label(size: u32) -> String {
+    if size == 0 {
+        "Window".to_string()
+    } else {
+        format!("{size} px")
+    }
+}
+
+fn fps_cap_label(cap: u32) -> String {
+    if cap == 0 {
+        "Off".to_string()
+    } else {
+        format!("{cap} FPS")
+    }
 }
 
 pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], actions: &mut UiActions) {
@@ -58,6 +296,88 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
             }
             actions.paused = state.paused;
 
+            egui::ComboBox::from_id_salt("view_mode")
+                .selected_text(state.view_mode.label())
+                .show_ui(ui, |ui| {
+                    for &mode in ViewMode::ALL {
+                        if ui
+                            .selectable_value(&mut state.view_mode, mode, mode.label())
+                            .pointer()
+                            .changed()
+                        {
+                            actions.view_mode_changed = true;
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Flat Preview skips path tracing for fast scene layout; \
+                     Normals and UV show surface normals and texture coordinates",
+                )
+                .pointer();
+
+            if ui
+                .add_enabled(
+                    state.solo || state.selected_shape.is_some(),
+                    egui::Button::new("Solo").selected(state.solo),
+                )
+                .on_hover_text("Render only the selected shape (/)")
+                .pointer()
+                .clicked()
+            {
+                actions.toggle_solo = true;
+            }
+            if state.solo
+                && ui
+                    .checkbox(&mut state.solo_hides_sky, "Hide sky")
+                    .on_hover_text("Render the soloed shape against black")
+                    .pointer()
+                    .changed()
+            {
+                actions.render_settings_changed = true;
+            }
+            let selected_light = state
+                .selected_shape
+                .and_then(|idx| shapes.get(idx))
+                .is_some_and(can_look_through);
+            if ui
+                .add_enabled(
+                    state.light_view || selected_light,
+                    egui::Button::new("Light View").selected(state.light_view),
+                )
+                .on_hover_text(
+                    "Look from the selected light along its direction; \
+                     click again to return to the previous camera",
+                )
+                .pointer()
+                .clicked()
+            {
+                actions.toggle_light_view = true;
+            }
+
+            if state.camera_names.len() > 1 {
+                let selected = state
+                    .camera_names
+                    .get(state.active_camera)
+                    .map_or("", String::as_str);
+                egui::ComboBox::from_id_salt("scene_camera")
+                    .selected_text(format!("🎥 {selected}"))
+                    .show_ui(ui, |ui| {
+                        for (idx, name) in state.camera_names.iter().enumerate() {
+                            if ui
+                                .selectable_label(idx == state.active_camera, name)
+                                .pointer()
+                                .clicked()
+                            {
+                                actions.camera_selected = Some(idx);
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Switch between the scene's cameras")
+                    .pointer();
+            }
+
             ui.separator();
 
             ui.menu_button("🎬 Scene", |ui| {
@@ -76,6 +396,36 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
 
                 ui.separator();
 
+                if ui
+                    .button("↺ Reset Camera")
+                    .on_hover_text("Back to the scene file's camera position, view and FOV")
+                    .pointer()
+                    .clicked()
+                {
+                    actions.reset_camera = true;
+                    ui.close_menu();
+                }
+                if ui
+                    .button("🎥 Camera Path...")
+                    .on_hover_text("Keyframe camera views and play a fly-through")
+                    .pointer()
+                    .clicked()
+                {
+                    state.camera_path_open = true;
+                    ui.close_menu();
+                }
+                if ui
+                    .button("↺ Reset Render Settings")
+                    .on_hover_text("Back to the scene file's bounces, clamp, sky and tone mapping")
+                    .pointer()
+                    .clicked()
+                {
+                    actions.reset_render_settings = true;
+                    ui.close_menu();
+                }
+
+                ui.separator();
+
                 ui.menu_button("📂 Import...", |ui| {
                     if ui.button("Scene (.yaml)").pointer().clicked() {
                         actions.open_import_scene_dialog = true;
@@ -85,6 +435,41 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
                         actions.open_import_model_dialog = true;
                         ui.close_menu();
                     }
+                    ui.checkbox(&mut state.import_scene_camera, "Use imported scene's camera")
+                        .on_hover_text(
+                            "Look through the camera of an imported scene instead of \
+                             keeping the current view",
+                        )
+                        .pointer();
+                    ui.horizontal(|ui| {
+                        ui.label("Model up axis:");
+                        egui::ComboBox::from_id_salt("model_up_axis")
+                            .selected_text(state.model_up_axis.label())
+                            .show_ui(ui, |ui| {
+                                for &axis in UpAxis::ALL {
+                                    ui.selectable_value(
+                                        &mut state.model_up_axis,
+                                        axis,
+                                        axis.label(),
+                                    )
+                                    .pointer();
+                                }
+                            });
+                    })
+                    .response
+                    .on_hover_text("Rotate imported models so this axis points up");
+                    ui.checkbox(&mut state.merge_coplanar_on_import, "Merge split triangles")
+                        .on_hover_text(
+                            "Join flat triangle pairs that only exist because an edge was \
+                             split, for fewer shapes and faster BVH builds",
+                        )
+                        .pointer();
+                    ui.checkbox(&mut state.ground_plane_on_import, "Add ground plane")
+                        .on_hover_text(
+                            "Put a plane under imported models for them to stand and \
+                             cast shadows on",
+                        )
+                        .pointer();
                 })
                 .response
                 .pointer();
@@ -135,12 +520,35 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
                 .response
                 .pointer();
 
+                let selected = state.selected_shape.filter(|&idx| idx < shapes.len());
+                ui.add_enabled_ui(selected.is_some(), |ui| {
+                    ui.menu_button("🎨 Apply Material to All", |ui| {
+                        for &shape_type in ShapeType::ALL {
+                            let count = shapes.iter().filter(|s| s.shape_type == shape_type).count();
+                            if count > 0
+                                && ui
+                                    .button(format!("{} ({count})", shape_type.label()))
+                                    .pointer()
+                                    .clicked()
+                            {
+                                actions.material_to_type = Some(shape_type);
+                                ui.close_menu();
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Give every shape of a type the selected shape's material")
+                    .on_disabled_hover_text("Select the shape whose material to copy")
+                    .pointer();
+                });
+
                 ui.separator();
 
                 ui.strong("Shapes");
                 if shapes.is_empty() {
                     ui.label("No shapes in scene");
                 } else {
+                    draw_shape_filter(ui, shapes, state);
                     egui::ScrollArea::vertical()
                         .max_height(300.0)
                         .show(ui, |ui| {
@@ -155,7 +563,11 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
                 ui.set_min_width(200.0);
 
                 ui.horizontal(|ui| {
-                    ui.label("Exposure:");
+                    ui.label(if state.auto_exposure {
+                        "Exposure Bias:"
+                    } else {
+                        "Exposure:"
+                    });
                     if ui
                         .add(egui::Slider::new(&mut state.exposure, 0.1..=10.0).logarithmic(true))
                         .pointer()
@@ -163,6 +575,24 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
                     {
                         actions.exposure_changed = Some(state.exposure);
                     }
+                    if reset_button(ui, &mut state.exposure, DEFAULT_EXPOSURE) {
+                        actions.exposure_changed = Some(state.exposure);
+                    }
+                    if scene_diff_marker(ui, &mut state.exposure, state.scene_camera.exposure) {
+                        actions.exposure_changed = Some(state.exposure);
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut state.auto_exposure, "Auto Exposure")
+                        .on_hover_text(
+                            "Adapt exposure to the scene's average brightness; \
+                             the exposure slider then brightens or darkens relative to it",
+                        )
+                        .pointer();
+                    if state.auto_exposure {
+                        ui.weak(format!("×{:.2}", state.auto_exposure_scale));
+                    }
                 });
 
                 ui.horizontal(|ui| {
@@ -174,6 +604,123 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
                     {
                         actions.max_bounces_changed = Some(state.max_bounces);
                     }
+                    if reset_button(ui, &mut state.max_bounces, DEFAULT_MAX_BOUNCES) {
+                        actions.max_bounces_changed = Some(state.max_bounces);
+                    }
+                    let scene = state.scene_camera.max_bounces;
+                    if scene_diff_marker(ui, &mut state.max_bounces, scene) {
+                        actions.max_bounces_changed = Some(state.max_bounces);
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Target Samples:");
+                    ui.add(
+                        egui::Slider::new(&mut state.target_samples, 0..=16384)
+                            .logarithmic(true)
+                            .custom_formatter(|v, _| {
+                                if v == 0.0 {
+                                    "Off".to_string()
+                                } else {
+                                    format!("{v:.0}")
+                                }
+                            }),
+                    )
+                    .on_hover_text("Stop rendering once this many samples have accumulated")
+                    .pointer();
+                    reset_button(ui, &mut state.target_samples, 0);
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Pixel Jitter:");
+                    egui::ComboBox::from_id_salt("jitter_pattern")
+                        .selected_text(state.jitter_pattern.label())
+                        .show_ui(ui, |ui| {
+                            for &pattern in JitterPattern::ALL {
+                                if ui
+                                    .selectable_value(
+                                        &mut state.jitter_pattern,
+                                        pattern,
+                                        pattern.label(),
+                                    )
+                                    .pointer()
+                                    .changed()
+                                {
+                                    actions.render_settings_changed = true;
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Where in each pixel the camera rays go. Blue noise gives the \
+                             least blotchy first frames; all converge to the same image",
+                        );
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Color Space:");
+                    egui::ComboBox::from_id_salt("color_space")
+                        .selected_text(state.color_space.label())
+                        .show_ui(ui, |ui| {
+                            for &space in ColorSpace::ALL {
+                                if ui
+                                    .selectable_value(&mut state.color_space, space, space.label())
+                                    .pointer()
+                                    .changed()
+                                {
+                                    actions.render_settings_changed = true;
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Encoding of the final image, also tagged in saved PNGs. \
+                             Linear is for compositing and looks dark on a plain monitor",
+                        );
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Stereo:");
+                    egui::ComboBox::from_id_salt("stereo_mode")
+                        .selected_text(state.stereo_mode.label())
+                        .show_ui(ui, |ui| {
+                            for &mode in StereoMode::ALL {
+                                if ui
+                                    .selectable_value(&mut state.stereo_mode, mode, mode.label())
+                                    .pointer()
+                                    .changed()
+                                {
+                                    actions.render_settings_changed = true;
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Render two eye views for 3D viewing. Anaglyph needs red/cyan \
+                             glasses and traces twice as many rays",
+                        );
+                });
+                ui.add_enabled_ui(state.stereo_mode != StereoMode::Off, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Eye Separation:");
+                        if ui
+                            .add(
+                                egui::Slider::new(&mut state.stereo_ipd, 0.001..=MAX_STEREO_IPD)
+                                    .logarithmic(true),
+                            )
+                            .pointer()
+                            .on_hover_text(
+                                "Interpupillary distance in scene units. Objects nearer \
+                                 than 30 separations stand out of the screen",
+                            )
+                            .changed()
+                        {
+                            actions.render_settings_changed = true;
+                        }
+                        if reset_button(ui, &mut state.stereo_ipd, DEFAULT_STEREO_IPD) {
+                            actions.render_settings_changed = true;
+                        }
+                    });
                 });
 
                 ui.horizontal(|ui| {
@@ -188,19 +735,81 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
                     {
                         actions.render_settings_changed = true;
                     }
+                    if reset_button(ui, &mut state.firefly_clamp, DEFAULT_FIREFLY_CLAMP) {
+                        actions.render_settings_changed = true;
+                    }
+                    let scene = state.scene_camera.firefly_clamp;
+                    if scene_diff_marker(ui, &mut state.firefly_clamp, scene) {
+                        actions.render_settings_changed = true;
+                    }
                 });
 
-                labeled_slider(
+                scene_slider(
                     ui,
                     "Fractal Steps:",
                     &mut state.fractal_march_steps,
+                    DEFAULT_FRACTAL_MARCH_STEPS,
+                    state.scene_camera.fractal_march_steps,
                     32..=512,
                     &mut actions.render_settings_changed,
                 );
 
+                ui.horizontal(|ui| {
+                    ui.label("Near Clip:");
+                    if ui
+                        .add(
+                            egui::Slider::new(&mut state.near_clip, 0.0..=MAX_NEAR_CLIP)
+                                .logarithmic(true),
+                        )
+                        .on_hover_text("Camera rays see nothing closer than this")
+                        .pointer()
+                        .changed()
+                    {
+                        actions.render_settings_changed = true;
+                    }
+                    if reset_button(ui, &mut state.near_clip, DEFAULT_NEAR_CLIP) {
+                        actions.render_settings_changed = true;
+                    }
+                    let scene = state.scene_camera.near_clip;
+                    if scene_diff_marker(ui, &mut state.near_clip, scene) {
+                        actions.render_settings_changed = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Far Clip:");
+                    if ui
+                        .add(
+                            egui::Slider::new(&mut state.far_clip, 0.0..=MAX_FAR_CLIP)
+                                .logarithmic(true)
+                                .custom_formatter(|v, _| {
+                                    if v == 0.0 {
+                                        "Off".to_string()
+                                    } else {
+                                        format!("{v:.1}")
+                                    }
+                                }),
+                        )
+                        .on_hover_text(
+                            "Camera rays ignore anything farther than this and show the sky",
+                        )
+                        .pointer()
+                        .changed()
+                    {
+                        actions.render_settings_changed = true;
+                    }
+                    if reset_button(ui, &mut state.far_clip, DEFAULT_FAR_CLIP) {
+                        actions.render_settings_changed = true;
+                    }
+                    let scene = state.scene_camera.far_clip;
+                    if scene_diff_marker(ui, &mut state.far_clip, scene) {
+                        actions.render_settings_changed = true;
+                    }
+                });
+
                 ui.horizontal(|ui| {
                     ui.label("Tone Mapper:");
-                    let labels = ["ACES", "Reinhard", "None"];
+                    let labels = TONE_MAPPER_NAMES;
                     let current = labels.get(state.tone_mapper as usize).unwrap_or(&"ACES");
                     egui::ComboBox::from_id_salt("tone_mapper")
                         .selected_text(*current)
@@ -215,112 +824,357 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
                                 }
                             }
                         });
+                    let scene = state.scene_camera.tone_mapper;
+                    if scene_diff_marker(ui, &mut state.tone_mapper, scene) {
+                        actions.render_settings_changed = true;
+                    }
+                });
+
+                ui.add_enabled_ui(state.tone_mapper != 2, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("White Point:");
+                        if ui
+                            .add(
+                                egui::Slider::new(&mut state.white_point, 0.0..=MAX_WHITE_POINT)
+                                    .custom_formatter(|v, _| {
+                                        if v == 0.0 {
+                                            "Auto".to_string()
+                                        } else {
+                                            format!("{v:.1}")
+                                        }
+                                    }),
+                            )
+                            .on_hover_text(
+                                "Brightness that maps to pure white; lower values clip \
+                                 highlights sooner, Auto keeps the tone mapper's curve",
+                            )
+                            .pointer()
+                            .changed()
+                        {
+                            actions.render_settings_changed = true;
+                        }
+                        if reset_button(ui, &mut state.white_point, DEFAULT_WHITE_POINT) {
+                            actions.render_settings_changed = true;
+                        }
+                        let scene = state.scene_camera.white_point;
+                        if scene_diff_marker(ui, &mut state.white_point, scene) {
+                            actions.render_settings_changed = true;
+                        }
+                    });
+                });
+
+                let features = &mut state.shader_features;
+                if ui
+                    .checkbox(&mut features.nee, "Light Sampling (NEE)")
+                    .on_hover_text(
+                        "Sample lights directly at each bounce; spot lights need it to show up",
+                    )
+                    .pointer()
+                    .changed()
+                    | ui.checkbox(&mut features.russian_roulette, "Russian Roulette")
+                        .on_hover_text("Randomly end dim paths early to save time per sample")
+                        .pointer()
+                        .changed()
+                {
+                    actions.shader_features_changed = true;
+                }
+
+                ui.checkbox(&mut state.dynamic_resolution, "Dynamic Resolution")
+                    .on_hover_text("Render at half resolution while the camera moves")
+                    .pointer();
+
+                ui.horizontal(|ui| {
+                    ui.label("Max Render Size:");
+                    egui::ComboBox::from_id_salt("max_render_size")
+                        .selected_text(max_render_size_label(state.max_render_size))
+                        .show_ui(ui, |ui| {
+                            for &size in MAX_RENDER_SIZE_OPTIONS {
+                                if ui
+                                    .selectable_value(
+                                        &mut state.max_render_size,
+                                        size,
+                                        max_render_size_label(size),
+                                    )
+                                    .pointer()
+                                    .changed()
+                                {
+                                    actions.render_size_changed = true;
+                                }
+                            }
+                        });
+                })
+                .response
+                .on_hover_text(
+                    "Longest side of the path-traced image; larger windows are upscaled",
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Supersampling:");
+                    egui::ComboBox::from_id_salt("supersampling")
+                        .selected_text(format!("{}x", state.supersampling))
+                        .show_ui(ui, |ui| {
+                            for &factor in SUPERSAMPLING_OPTIONS {
+                                if ui
+                                    .selectable_value(
+                                        &mut state.supersampling,
+                                        factor,
+                                        format!("{factor}x"),
+                                    )
+                                    .pointer()
+                                    .changed()
+                                {
+                                    actions.render_size_changed = true;
+                                }
+                            }
+                        });
+                })
+                .response
+                .on_hover_text(
+                    "Render at a multiple of the window size and average it down for \
+                     smoother edges; screenshots keep the larger size",
+                );
+                if let Some((w, h)) = state.clamped_render_size {
+                    if state.supersampling > 1.0 {
+                        ui.weak(format!("Rendering at {w}x{h}, capped by Max Render Size"));
+                    } else {
+                        ui.weak(format!("Rendering at {w}x{h}, upscaled to the window"));
+                    }
+                }
+                ui.checkbox(&mut state.sharpen_upscale, "Sharpen Upscaling")
+                    .on_hover_text(
+                        "Contrast-adaptive sharpening when a smaller render is stretched to the window",
+                    )
+                    .pointer();
+                ui.checkbox(&mut state.keep_samples_on_resize, "Keep Samples on Resize")
+                    .on_hover_text(
+                        "Stretch the accumulated image to the new window size instead of \
+                         starting over; edges stay blocky until new samples refine them",
+                    )
+                    .pointer();
+
+                ui.horizontal(|ui| {
+                    ui.label("Render Region:");
+                    if state.drawing_render_region {
+                        if ui.button("Cancel").pointer().clicked() {
+                            state.drawing_render_region = false;
+                        }
+                    } else if ui
+                        .button("Draw")
+                        .on_hover_text("Drag a rectangle on the image to path trace only that area")
+                        .pointer()
+                        .clicked()
+                    {
+                        state.drawing_render_region = true;
+                        ui.close_menu();
+                    }
+                    if state.render_region.is_some() && ui.button("Clear").pointer().clicked() {
+                        state.render_region = None;
+                        actions.render_region_changed = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("FPS Cap:");
+                    egui::ComboBox::from_id_salt("fps_cap")
+                        .selected_text(fps_cap_label(state.fps_cap))
+                        .show_ui(ui, |ui| {
+                            for &cap in FPS_CAP_OPTIONS {
+                                ui.selectable_value(&mut state.fps_cap, cap, fps_cap_label(cap))
+                                    .pointer();
+                            }
+                        });
+                })
+                .response
+                .on_hover_text(
+                    "Limit frames per second to save power once the image has converged",
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Samples/Frame:");
+                    ui.add(egui::Slider::new(
+                        &mut state.samples_per_frame,
+                        1..=MAX_SAMPLES_PER_FRAME,
+                    ))
+                    .on_hover_text(
+                        "Trace several samples before each present for faster convergence \
+                         on an idle GPU; fewer run while frames take long",
+                    )
+                    .pointer();
                 });
+                ui.checkbox(&mut state.reopen_last_scene, "Reopen Last Scene")
+                    .on_hover_text("Open the last opened or saved scene on launch")
+                    .pointer();
+
+                ui.separator();
+                ui.strong("Controls");
+                draw_controls_settings(ui, state, actions);
 
                 ui.separator();
                 ui.strong("Skybox");
 
                 ui.horizontal(|ui| {
-                    ui.label("Color:");
-                    let mut color = state.skybox_color;
-                    if ui.color_edit_button_rgb(&mut color).pointer().changed() {
-                        state.skybox_color = color;
+                    ui.label("Mode:");
+                    egui::ComboBox::from_id_salt("skybox_mode")
+                        .selected_text(state.skybox_mode.label())
+                        .show_ui(ui, |ui| {
+                            for &mode in SkyboxMode::ALL {
+                                if ui
+                                    .selectable_value(&mut state.skybox_mode, mode, mode.label())
+                                    .pointer()
+                                    .changed()
+                                {
+                                    actions.render_settings_changed = true;
+                                }
+                            }
+                        });
+                    let scene = state.scene_camera.skybox_mode;
+                    if scene_diff_marker(ui, &mut state.skybox_mode, scene) {
                         actions.render_settings_changed = true;
                     }
                 });
 
-                labeled_slider(
+                let is_solid = state.skybox_mode == SkyboxMode::SolidColor;
+                let is_sun_sky = state.skybox_mode == SkyboxMode::SunSky;
+                if is_sun_sky {
+                    scene_slider(
+                        ui,
+                        "Sun Azimuth:",
+                        &mut state.sun_azimuth,
+                        DEFAULT_SUN_AZIMUTH,
+                        state.scene_camera.sun_azimuth,
+                        0.0..=360.0,
+                        &mut actions.render_settings_changed,
+                    );
+                    scene_slider(
+                        ui,
+                        "Sun Elevation:",
+                        &mut state.sun_elevation,
+                        DEFAULT_SUN_ELEVATION,
+                        state.scene_camera.sun_elevation,
+                        -10.0..=90.0,
+                        &mut actions.render_settings_changed,
+                    );
+                    scene_slider(
+                        ui,
+                        "Turbidity:",
+                        &mut state.sky_turbidity,
+                        DEFAULT_SKY_TURBIDITY,
+                        state.scene_camera.sky_turbidity,
+                        2.0..=10.0,
+                        &mut actions.render_settings_changed,
+                    );
+                } else {
+                    ui.horizontal(|ui| {
+                        ui.label(if is_solid { "Color:" } else { "Zenith:" });
+                        let mut color = state.skybox_color;
+                        if ui.color_edit_button_rgb(&mut color).pointer().changed() {
+                            state.skybox_color = color;
+                            actions.render_settings_changed = true;
+                        }
+                        let scene = state.scene_camera.skybox_color;
+                        if scene_diff_marker(ui, &mut state.skybox_color, scene) {
+                            actions.render_settings_changed = true;
+                        }
+                    });
+                }
+
+                if !is_solid && !is_sun_sky {
+                    ui.horizontal(|ui| {
+                        ui.label("Horizon:");
+                        let mut color = state.skybox_horizon_color;
+                        if ui.color_edit_button_rgb(&mut color).pointer().changed() {
+                            state.skybox_horizon_color = color;
+                            actions.render_settings_changed = true;
+                        }
+                        let scene = state.scene_camera.skybox_horizon_color;
+                        if scene_diff_marker(ui, &mut state.skybox_horizon_color, scene) {
+                            actions.render_settings_changed = true;
+                        }
+                    });
+                }
+
+                scene_slider(
                     ui,
                     "Brightness:",
                     &mut state.skybox_brightness,
+                    DEFAULT_SKYBOX_BRIGHTNESS,
+                    state.scene_camera.skybox_brightness,
                     0.0..=2.0,
                     &mut actions.render_settings_changed,
                 );
 
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(&mut state.transparent_background, "Transparent Background")
+                        .on_hover_text(
+                            "Leave the sky out where nothing is hit; screenshots keep the alpha",
+                        )
+                        .pointer()
+                        .changed()
+                    {
+                        actions.render_settings_changed = true;
+                    }
+                    let scene = state.scene_camera.transparent_background;
+                    if scene_diff_marker(ui, &mut state.transparent_background, scene) {
+                        actions.render_settings_changed = true;
+                    }
+                });
+
                 ui.separator();
 
                 ui.strong("Effects");
-                let mut effects_changed = false;
-                egui::ScrollArea::vertical()
-                    .max_height(200.0)
-                    .show(ui, |ui| {
-                        for &effect in PostEffect::ALL_EFFECTS {
-                            let active = state.active_effects.contains(&effect);
-                            let mut checked = active;
-                            if ui
-                                .checkbox(&mut checked, effect.label())
-                                .pointer()
-                                .clicked()
-                            {
-                                if checked {
-                                    state.active_effects.push(effect);
-                                } else {
-                                    state.active_effects.retain(|&e| e != effect);
-                                }
-                                effects_changed = true;
-                            }
-                            if checked && effect == PostEffect::OilPainting {
-                                indented_slider(
-                                    ui,
-                                    20.0,
-                                    "Radius:",
-                                    &mut state.oil_radius,
-                                    1..=8,
-                                    &mut actions.post_effect_params_changed,
-                                );
-                            }
-                            if checked && effect == PostEffect::Comic {
-                                indented_slider(
-                                    ui,
-                                    20.0,
-                                    "Levels:",
-                                    &mut state.comic_levels,
-                                    2..=16,
-                                    &mut actions.post_effect_params_changed,
-                                );
-                            }
-                        }
-
-                        if state.active_effects.len() >= 2 {
-                            ui.separator();
-                            ui.strong("Order");
-                            let mut swap: Option<(usize, usize)> = None;
-                            for i in 0..state.active_effects.len() {
-                                ui.horizontal(|ui| {
-                                    ui.label(format!(
-                                        "{}. {}",
-                                        i + 1,
-                                        state.active_effects[i].label()
-                                    ));
-                                    ui.with_layout(
-                                        egui::Layout::right_to_left(egui::Align::Center),
-                                        |ui| {
-                                            if i + 1 < state.active_effects.len()
-                                                && ui.small_button("Dn").pointer().clicked()
-                                            {
-                                                swap = Some((i, i + 1));
-                                            }
-                                            if i > 0 && ui.small_button("Up").pointer().clicked() {
-                                                swap = Some((i, i - 1));
-                                            }
-                                        },
-                                    );
-                                });
-                            }
-                            if let Some((a, b)) = swap {
-                                state.active_effects.swap(a, b);
-                                effects_changed = true;
-                            }
-                        }
-                    });
-                if effects_changed {
+                if effect_chain_editor(
+                    ui,
+                    "effects_a",
+                    &mut state.active_effects,
+                    &mut state.oil_radius,
+                    &mut state.comic_levels,
+                    &mut actions.post_effect_params_changed,
+                ) {
                     actions.effects_changed = Some(state.active_effects.clone());
                 }
+
+                ui.separator();
+                if ui
+                    .checkbox(&mut state.compare_enabled, "A/B Compare")
+                    .on_hover_text("Split the view: the right side of the divider runs chain B")
+                    .pointer()
+                    .changed()
+                {
+                    actions.post_effect_params_changed = true;
+                }
+                if state.compare_enabled {
+                    ui.strong("Effects B (right side)");
+                    if effect_chain_editor(
+                        ui,
+                        "effects_b",
+                        &mut state.compare_effects,
+                        &mut state.oil_radius,
+                        &mut state.comic_levels,
+                        &mut actions.post_effect_params_changed,
+                    ) {
+                        actions.post_effect_params_changed = true;
+                    }
+                }
+            })
+            .response
+            .pointer();
+
+            ui.menu_button("💡 Lights", |ui| {
+                draw_lights_menu(ui, state, actions);
+            })
+            .response
+            .pointer();
+
+            ui.menu_button("🐞 Debug", |ui| {
+                draw_debug_menu(ui, state, actions);
             })
             .response
             .pointer();
 
             ui.menu_button("? Help", |ui| {
+                if ui.button("Scene Diagnostics").pointer().clicked() {
+                    state.diagnostics_open = true;
+                    ui.close_menu();
+                }
                 if ui.button("Shortcuts").pointer().clicked() {
                     state.shortcuts_dialog_open = true;
                     ui.close_menu();
@@ -333,24 +1187,269 @@ pub fn draw_toolbar(ctx: &Context, state: &mut UiState, shapes: &[Shape], action
             .response
             .pointer();
 
+            if !state.scene_issues.is_empty()
+                && ui
+                    .button(
+                        egui::RichText::new(format!("⚠ {}", state.scene_issues.len()))
+                            .color(egui::Color32::YELLOW),
+                    )
+                    .on_hover_text("Scene problems detected — click for details")
+                    .pointer()
+                    .clicked()
+            {
+                state.diagnostics_open = !state.diagnostics_open;
+            }
+
             ui.separator();
 
             ui.label(format!("FPS: {:.0}", state.fps));
+            let memory = &state.gpu_memory;
+            ui.label(format!("VRAM: ~{}", format_mib(memory.total())))
+                .on_hover_text(format!(
+                    "Estimated GPU memory\n\
+                     Shapes & lights: {}\n\
+                     Materials: {}\n\
+                     BVH: {}\n\
+                     Textures: {}\n\
+                     Render targets: {}",
+                    format_mib(memory.shapes),
+                    format_mib(memory.materials),
+                    format_mib(memory.bvh),
+                    format_mib(memory.textures),
+                    format_mib(memory.render_targets),
+                ));
+            if !state.view_mode.accumulates() {
+                return;
+            }
             ui.label(format!("Samples: {}", state.sample_count));
             ui.label(format!(
                 "Time: {}",
                 format_elapsed(state.render_elapsed_secs)
             ));
+            if let Some(progress) = RenderProgress::new(
+                state.sample_count,
+                state.target_samples,
+                state.render_elapsed_secs,
+            ) {
+                let text = match progress.eta_secs {
+                    _ if progress.fraction >= 1.0 => "Done".to_string(),
+                    Some(eta) => format!(
+                        "{:.0}%  ETA {}",
+                        progress.fraction * 100.0,
+                        format_elapsed(eta)
+                    ),
+                    None => format!("{:.0}%", progress.fraction * 100.0),
+                };
+                ui.add(
+                    egui::ProgressBar::new(progress.fraction)
+                        .desired_width(140.0)
+                        .text(text),
+                );
+            }
         });
     });
 }
 
+fn format_mib(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / MIB)
+}
+
 fn format_elapsed(secs: f32) -> String {
     let mins = (secs / 60.0) as u32;
     let remaining = secs % 60.0;
     format!("{mins}:{remaining:05.2}")
 }
 
+/// Developer options that affect presentation rather than the rendered image.
+fn draw_debug_menu(ui: &mut egui::Ui, state: &mut UiState, actions: &mut UiActions) {
+    ui.set_min_width(200.0);
+    ui.strong("Present Mode");
+    for &mode in PresentModeChoice::ALL {
+        let supported = state.supported_present_modes.contains(&mode);
+        let response = ui
+            .add_enabled(
+                supported,
+                egui::SelectableLabel::new(state.present_mode == mode, mode.label()),
+            )
+            .on_disabled_hover_text("Not supported by this display surface")
+            .pointer();
+        if response.clicked() && state.present_mode != mode {
+            state.present_mode = mode;
+            actions.present_mode_changed = Some(mode);
+        }
+    }
+
+    ui.separator();
+    ui.strong("BVH");
+    let params = &mut state.bvh_params;
+    // Rebuild once a drag ends rather than on every step of it.
+    let settled = |r: egui::Response| r.drag_stopped() || (r.changed() && !r.dragged());
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Leaf Size:");
+        changed |= settled(
+            ui.add(egui::Slider::new(
+                &mut params.max_leaf_prims,
+                1..=MAX_BVH_LEAF_PRIMS,
+            ))
+            .on_hover_text("Most primitives per leaf: smaller leaves mean more nodes to visit")
+            .pointer(),
+        );
+    });
+    ui.horizontal(|ui| {
+        ui.label("SAH Bins:");
+        changed |= settled(
+            ui.add(egui::Slider::new(
+                &mut params.num_bins,
+                2..=MAX_BVH_NUM_BINS,
+            ))
+            .on_hover_text("Split candidates per axis: more bins find better splits, slower")
+            .pointer(),
+        );
+    });
+    changed |= ui
+        .checkbox(&mut params.brute_force, "Brute Force")
+        .on_hover_text(
+            "Skip the BVH and test every shape for every ray, to tell BVH bugs \
+             apart from others. Can be faster with only a few shapes",
+        )
+        .pointer()
+        .changed();
+    if ui.button("Rebuild BVH").pointer().clicked() {
+        changed = true;
+    }
+    actions.bvh_params_changed |= changed;
+    let stats = state.bvh_stats;
+    ui.weak(format!(
+        "{} nodes, {} leaves, depth {}, {:.1} ms",
+        stats.nodes, stats.leaves, stats.max_depth, state.bvh_build_ms
+    ));
+
+    ui.separator();
+    ui.checkbox(&mut state.crosshair, "Crosshair Inspector")
+        .on_hover_text(
+            "Show what the center of the view points at: shape, position, \
+             material and the rendered color of that pixel",
+        )
+        .pointer();
+    ui.checkbox(&mut state.light_gizmos, "Light Gizmos")
+        .on_hover_text("Mark every light in the view with an icon; click one to select it")
+        .pointer();
+    if state.dev_mode {
+        ui.checkbox(&mut state.shader_console_open, "Shader Console")
+            .on_hover_text("Edit the BSDF module's WGSL and recompile the path tracer live")
+            .pointer();
+    }
+}
+
+/// The editor headlight, then light groups with an on/off toggle and an
+/// emission multiplier each.
+fn draw_lights_menu(ui: &mut egui::Ui, state: &mut UiState, actions: &mut UiActions) {
+    ui.set_min_width(240.0);
+    ui.horizontal(|ui| {
+        if ui
+            .checkbox(&mut state.headlight, "Headlight")
+            .pointer()
+            .on_hover_text("Light the scene from the camera while it has no lights of its own")
+            .changed()
+        {
+            actions.render_settings_changed = true;
+        }
+        if state.headlight {
+            ui.weak(if state.headlight_active {
+                "active"
+            } else {
+                "off: scene is lit"
+            });
+        }
+    });
+    ui.add_enabled_ui(state.headlight, |ui| {
+        indented_slider(
+            ui,
+            20.0,
+            "×",
+            &mut state.headlight_strength,
+            DEFAULT_HEADLIGHT_STRENGTH,
+            0.0..=5.0,
+            &mut actions.render_settings_changed,
+        );
+    });
+    ui.separator();
+    if state.light_groups.is_empty() {
+        ui.label("No emissive shapes in scene");
+        return;
+    }
+    egui::ScrollArea::vertical()
+        .max_height(400.0)
+        .show(ui, |ui| {
+            for group in &mut state.light_groups {
+                ui.horizontal(|ui| {
+                    let label = if group.shape_count > 1 {
+                        format!("{} ({})", group.name, group.shape_count)
+                    } else {
+                        group.name.clone()
+                    };
+                    if ui.checkbox(&mut group.enabled, label).pointer().changed() {
+                        actions.light_groups_changed = true;
+                    }
+                });
+                ui.add_enabled_ui(group.enabled, |ui| {
+                    indented_slider(
+                        ui,
+                        20.0,
+                        "×",
+                        &mut group.multiplier,
+                        1.0,
+                        0.0..=10.0,
+                        &mut actions.light_groups_changed,
+                    );
+                });
+            }
+        });
+}
+
+/// Search box and type dropdown narrowing the shape list below them.
+fn draw_shape_filter(ui: &mut egui::Ui, shapes: &[Shape], state: &mut UiState) {
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut state.shape_search)
+                .hint_text("Search")
+                .desired_width(120.0),
+        )
+        .on_hover_text("Show shapes whose name contains this text");
+        egui::ComboBox::from_id_salt("shape_type_filter")
+            .selected_text(state.shape_type_filter.map_or("All Types", |t| t.label()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut state.shape_type_filter, None, "All Types")
+                    .pointer();
+                // Only types the scene has.
+                for &shape_type in ShapeType::ALL {
+                    if shapes.iter().any(|s| s.shape_type == shape_type) {
+                        ui.selectable_value(
+                            &mut state.shape_type_filter,
+                            Some(shape_type),
+                            shape_type.label(),
+                        )
+                        .pointer();
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Show only shapes of this type")
+            .pointer();
+        if (!state.shape_search.is_empty() || state.shape_type_filter.is_some())
+            && ui
+                .small_button("x")
+                .on_hover_text("Clear the filter")
+                .pointer()
+                .clicked()
+        {
+            state.shape_search.clear();
+            state.shape_type_filter = None;
+        }
+    });
+}
+
 /// Draw the shapes list, collapsing consecutive same-named shapes into groups.
 fn draw_shapes_list(
     ui: &mut egui::Ui,
@@ -358,6 +1457,11 @@ fn draw_shapes_list(
     state: &mut UiState,
     actions: &mut UiActions,
 ) {
+    let search = state.shape_search.clone();
+    let type_filter = state.shape_type_filter;
+    let filtering = !search.trim().is_empty() || type_filter.is_some();
+    let matches = |i: usize| shape_matches_filter(&shapes[i], i, &search, type_filter);
+    let mut shown = 0;
     let mut i = 0;
     while i < shapes.len() {
         // Check if this starts a run of shapes with the same non-empty name.
@@ -371,24 +1475,43 @@ fn draw_shapes_list(
             let count = group_end - group_start;
 
             if count > 1 {
-                // Render as a collapsible group.
-                let header = format!("{name} ({count})");
-                egui::CollapsingHeader::new(&header)
-                    .default_open(false)
-                    .show(ui, |ui| {
-                        for j in group_start..group_end {
-                            draw_group_child_entry(ui, shapes, j, state, actions);
-                        }
-                    });
+                // Render as a collapsible group of its matching members,
+                // opened while a filter narrows it down.
+                let members: Vec<usize> =
+                    (group_start..group_end).filter(|&j| matches(j)).collect();
+                if !members.is_empty() {
+                    let header = if members.len() == count {
+                        format!("{name} ({count})")
+                    } else {
+                        format!("{name} ({} of {count})", members.len())
+                    };
+                    // Keyed by group, not title, so a changing count keeps it open.
+                    egui::CollapsingHeader::new(header)
+                        .id_salt((name, group_start))
+                        .open(filtering.then_some(true))
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            for &j in &members {
+                                draw_group_child_entry(ui, shapes, j, state, actions);
+                            }
+                        });
+                    shown += members.len();
+                }
                 i = group_end;
                 continue;
             }
         }
 
         // Single (ungrouped) shape.
-        draw_shape_entry(ui, shapes, i, state, actions);
+        if matches(i) {
+            draw_shape_entry(ui, shapes, i, state, actions);
+            shown += 1;
+        }
         i += 1;
     }
+    if shown == 0 {
+        ui.label("No matching shapes");
+    }
 }
 
 /// Entry for a child within a collapsible group — shows "Type #idx" instead of the group name.
@@ -410,7 +1533,7 @@ fn draw_shape_entry(
     state: &mut UiState,
     actions: &mut UiActions,
 ) {
-    let label = shape_label(&shapes[i], i);
+    let label = shapes[i].label(i);
     draw_selectable_shape_entry(ui, i, &label, state, actions);
 }
 
@@ -423,14 +1546,21 @@ fn draw_selectable_shape_entry(
 ) {
     let selected = state.selected_shape == Some(i);
     ui.horizontal(|ui| {
-        let response = ui.selectable_label(selected, label).pointer();
+        let response = ui
+            .selectable_label(selected, label)
+            .on_hover_text("Double-click to fly to it")
+            .pointer();
         if ui.small_button("x").pointer().clicked() {
             state.confirm_delete_shape = Some(i);
         }
-        if response.clicked() {
+        // A single click keeps the list open so a second one can fly there.
+        if response.clicked() || response.double_clicked() {
             state.selected_shape = Some(i);
             state.model_scale = 1.0;
             actions.selected_shape = Some(i);
+        }
+        if response.double_clicked() {
+            actions.fly_to_shape = Some(i);
             ui.close_menu();
         }
     });